@@ -0,0 +1,254 @@
+use super::{ActiveChannels, Config, Event, InterchainAccounts, Pallet, PortOwner};
+use crate::{
+	metadata::Metadata, IcaError, CONTROLLER_MODULE_ID, CONTROLLER_PORT_PREFIX, HOST_PORT_ID,
+};
+use alloc::string::ToString;
+use codec::{Decode, Encode};
+use ibc::{
+	core::{
+		ics04_channel::{
+			channel::{Counterparty, Order},
+			error::{ChannelError, PacketError},
+			handler::ModuleExtras,
+			msgs::acknowledgement::Acknowledgement,
+			packet::Packet,
+			Version,
+		},
+		ics24_host::identifier::{ChannelId, ConnectionId, PortId},
+		ics26_routing::context::{Module, ModuleId, ModuleOutputBuilder},
+	},
+	signer::Signer,
+};
+use pallet_ibc_utils::{ack::GenericAcknowledgement, traits::ChannelReaderInterface};
+use sp_std::{marker::PhantomData, str::FromStr};
+
+/// The ibc module id of the controller, to be used by the runtime's `AddModule` implementation.
+pub fn module_id() -> ModuleId {
+	ModuleId::from_str(CONTROLLER_MODULE_ID).expect("never failed")
+}
+
+/// Resolves every `icacontroller-*` port to the controller module.
+pub fn lookup_module_by_port(port_id: &PortId) -> Option<ModuleId> {
+	port_id.as_str().starts_with(CONTROLLER_PORT_PREFIX).then(module_id)
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct IcaControllerModule<T>(pub PhantomData<T>);
+
+impl<T: Config> IcaControllerModule<T> {
+	fn owner(port_id: &PortId) -> Result<T::AccountId, IcaError> {
+		<PortOwner<T>>::get(port_id).ok_or(IcaError::UnknownOwner)
+	}
+
+	fn channel_connection(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<ConnectionId, ChannelError> {
+		let channel_end =
+			<T::IbcContext as ChannelReaderInterface>::channel_end(port_id, channel_id)?;
+		channel_end
+			.connection_hops()
+			.first()
+			.cloned()
+			.ok_or(ChannelError::InvalidConnectionHopsLength { expected: 1, actual: 0 })
+	}
+
+	fn validate_open_init(
+		order: Order,
+		connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		counterparty: &Counterparty,
+		version: &Version,
+	) -> Result<T::AccountId, IcaError> {
+		if order != Order::Ordered {
+			return Err(IcaError::InvalidChannelOrdering)
+		}
+		if !port_id.as_str().starts_with(CONTROLLER_PORT_PREFIX) {
+			return Err(IcaError::InvalidPort { port_id: port_id.to_string() })
+		}
+		if counterparty.port_id().as_str() != HOST_PORT_ID {
+			return Err(IcaError::InvalidPort { port_id: counterparty.port_id().to_string() })
+		}
+		let owner = Self::owner(port_id)?;
+
+		let connection_id = connection_hops
+			.first()
+			.ok_or(IcaError::InvalidConnection { reason: "missing connection hop".to_string() })?;
+		let connection_end =
+			<T::IbcContext as ChannelReaderInterface>::connection_end(connection_id)
+				.map_err(|e| IcaError::InvalidConnection { reason: e.to_string() })?;
+		let host_connection_id =
+			connection_end
+				.counterparty()
+				.connection_id()
+				.ok_or(IcaError::InvalidConnection {
+					reason: "missing counterparty connection".to_string(),
+				})?;
+		Metadata::from_version(version)?.validate(connection_id, host_connection_id)?;
+
+		if let Some(channel_id) = <ActiveChannels<T>>::get(connection_id, port_id) {
+			let is_open =
+				<T::IbcContext as ChannelReaderInterface>::channel_end(port_id, &channel_id)
+					.map(|channel_end| channel_end.is_open())
+					.unwrap_or(false);
+			if is_open {
+				return Err(IcaError::ActiveChannelAlreadySet)
+			}
+		}
+
+		Ok(owner)
+	}
+
+	fn sequence_owner(packet: &Packet) -> Result<T::AccountId, PacketError> {
+		Self::owner(&packet.port_on_a)
+			.map_err(|e| PacketError::AppModule { description: e.to_string() })
+	}
+}
+
+impl<T: Config> Module for IcaControllerModule<T> {
+	fn on_chan_open_init(
+		&mut self,
+		order: Order,
+		connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		counterparty: &Counterparty,
+		version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		let owner =
+			Self::validate_open_init(order, connection_hops, port_id, counterparty, version)
+				.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+
+		let connection_id = connection_hops[0].clone();
+		<ActiveChannels<T>>::insert(&connection_id, port_id, channel_id);
+		Pallet::<T>::deposit_event(Event::<T>::ChannelOpenInitiated {
+			owner,
+			connection_id,
+			port_id: port_id.clone(),
+			channel_id: channel_id.clone(),
+		});
+
+		Ok((ModuleExtras::empty(), version.clone()))
+	}
+
+	fn on_chan_open_try(
+		&mut self,
+		_order: Order,
+		_connection_hops: &[ConnectionId],
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+		_counterparty: &Counterparty,
+		_counterparty_version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		Err(ChannelError::AppModule { description: IcaError::UnsupportedOperation.to_string() })
+	}
+
+	fn on_chan_open_ack(
+		&mut self,
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		counterparty_version: &Version,
+	) -> Result<ModuleExtras, ChannelError> {
+		let map_err = |e: IcaError| ChannelError::AppModule { description: e.to_string() };
+
+		let owner = Self::owner(port_id).map_err(map_err)?;
+		let connection_id = Self::channel_connection(port_id, channel_id)?;
+		let metadata = Metadata::from_version(counterparty_version).map_err(map_err)?;
+		let connection_end =
+			<T::IbcContext as ChannelReaderInterface>::connection_end(&connection_id)?;
+		let host_connection_id = connection_end.counterparty().connection_id().ok_or(map_err(
+			IcaError::InvalidConnection { reason: "missing counterparty connection".to_string() },
+		))?;
+		metadata.validate(&connection_id, host_connection_id).map_err(map_err)?;
+		if metadata.address.is_empty() {
+			return Err(map_err(IcaError::InvalidVersion {
+				reason: "host did not provide an interchain account address".to_string(),
+			}))
+		}
+
+		let address = metadata.address.into_bytes();
+		<ActiveChannels<T>>::insert(&connection_id, port_id, channel_id);
+		<InterchainAccounts<T>>::insert(&connection_id, port_id, &address);
+		Pallet::<T>::deposit_event(Event::<T>::InterchainAccountRegistered {
+			owner,
+			connection_id,
+			address,
+		});
+
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_chan_open_confirm(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Err(ChannelError::AppModule { description: IcaError::UnsupportedOperation.to_string() })
+	}
+
+	fn on_chan_close_init(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		// interchain account channels are only closed by packet timeouts
+		Err(ChannelError::AppModule { description: IcaError::UnsupportedOperation.to_string() })
+	}
+
+	fn on_chan_close_confirm(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_recv_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		_packet: &Packet,
+		_relayer: &Signer,
+	) -> Acknowledgement {
+		GenericAcknowledgement::error(IcaError::UnsupportedOperation).into()
+	}
+
+	fn on_acknowledgement_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		acknowledgement: &Acknowledgement,
+		_relayer: &Signer,
+	) -> Result<(), PacketError> {
+		let owner = Self::sequence_owner(packet)?;
+		let ack = GenericAcknowledgement::from_bytes(acknowledgement.as_ref())
+			.ok_or(PacketError::AppModule { description: "invalid acknowledgement".to_string() })?;
+
+		let event = match &ack {
+			GenericAcknowledgement::Result(_) => Event::<T>::TxAcknowledged {
+				owner,
+				sequence: packet.sequence,
+				result: ack.result().unwrap_or_default(),
+			},
+			GenericAcknowledgement::Error(error) => Event::<T>::TxFailed {
+				owner,
+				sequence: packet.sequence,
+				error: error.as_bytes().to_vec(),
+			},
+		};
+		Pallet::<T>::deposit_event(event);
+
+		Ok(())
+	}
+
+	fn on_timeout_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		_relayer: &Signer,
+	) -> Result<(), PacketError> {
+		let owner = Self::sequence_owner(packet)?;
+		Pallet::<T>::deposit_event(Event::<T>::TxTimedOut { owner, sequence: packet.sequence });
+
+		Ok(())
+	}
+}
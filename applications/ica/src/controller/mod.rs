@@ -0,0 +1,288 @@
+//! ICS-27 controller submodule.
+//!
+//! Every signed account of this chain owns the port `icacontroller-<hex account>`. The owner
+//! registers an interchain account on a connection with [`Pallet::register_interchain_account`],
+//! which opens an ORDERED channel towards the `icahost` port of the counterparty. Once the host
+//! acknowledges the handshake, the address of the remote account is recorded and the owner can
+//! submit protobuf-encoded Cosmos messages with [`Pallet::send_tx`].
+//!
+//! The runtime routes the controller ports to this module through its `AddModule`
+//! implementation, using [`callback::module_id`] for `add_module` and
+//! [`callback::lookup_module_by_port`] for `lookup_module_by_port`.
+pub use pallet::*;
+
+pub mod callback;
+
+use crate::{metadata::Metadata, packet::InterchainAccountPacketData, *};
+use alloc::{format, string::ToString};
+use codec::Encode;
+use frame_support::pallet_prelude::*;
+use frame_system::pallet_prelude::*;
+use ibc::{
+	core::{
+		ics04_channel::{
+			channel::{Order, State},
+			events::SendPacket,
+			msgs::chan_open_init::{MsgChannelOpenInit, TYPE_URL as CHAN_OPEN_INIT_TYPE_URL},
+			packet::Sequence,
+			timeout::TimeoutHeight,
+		},
+		ics24_host::identifier::{ChannelId, ConnectionId, PortId},
+	},
+	signer::Signer,
+	timestamp::Timestamp,
+};
+use ibc_proto::{google::protobuf::Any, protobuf::Protobuf};
+use pallet_ibc_utils::{
+	packet::{build_packet, send_packet},
+	traits::{ChannelKeeperInterface, ChannelReaderInterface},
+};
+use sp_std::{fmt::Debug, str::FromStr, vec, vec::Vec};
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+
+	#[pallet::pallet]
+	#[pallet::without_storage_info]
+	pub struct Pallet<T>(_);
+
+	/// Configure the pallet by specifying the parameters and types on which it depends.
+	#[pallet::config]
+	pub trait Config:
+		frame_system::Config + pallet_timestamp::Config + Sync + Send + Debug
+	{
+		/// The aggregated event type of the runtime.
+		type RuntimeEvent: Parameter
+			+ Member
+			+ From<Event<Self>>
+			+ Debug
+			+ IsType<<Self as frame_system::Config>::RuntimeEvent>;
+
+		type IbcContext: ChannelKeeperInterface + ChannelReaderInterface;
+
+		/// Entry of pallet-ibc, used to submit the channel handshake of new interchain accounts
+		type IbcRouter: pallet_ibc_utils::Router;
+	}
+
+	#[pallet::storage]
+	/// key: controller port id
+	/// value: owner of the port
+	pub type PortOwner<T: Config> = StorageMap<_, Blake2_128Concat, PortId, T::AccountId>;
+
+	#[pallet::storage]
+	/// key1: controller connection id
+	/// key2: controller port id
+	/// value: channel id of the active interchain account channel
+	pub type ActiveChannels<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, ConnectionId, Blake2_128Concat, PortId, ChannelId>;
+
+	#[pallet::storage]
+	/// key1: controller connection id
+	/// key2: controller port id
+	/// value: address of the interchain account on the host chain
+	pub type InterchainAccounts<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, ConnectionId, Blake2_128Concat, PortId, Vec<u8>>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The channel handshake of an interchain account was started
+		ChannelOpenInitiated {
+			owner: T::AccountId,
+			connection_id: ConnectionId,
+			port_id: PortId,
+			channel_id: ChannelId,
+		},
+		/// The host registered the interchain account at `address`
+		InterchainAccountRegistered {
+			owner: T::AccountId,
+			connection_id: ConnectionId,
+			address: Vec<u8>,
+		},
+		/// Send packet event
+		SendPacket(SendPacket),
+		/// The host executed the messages of the packet
+		TxAcknowledged { owner: T::AccountId, sequence: Sequence, result: Vec<u8> },
+		/// The host failed to execute the messages of the packet
+		TxFailed { owner: T::AccountId, sequence: Sequence, error: Vec<u8> },
+		/// The packet timed out before the host received it, closing the channel
+		TxTimedOut { owner: T::AccountId, sequence: Sequence },
+	}
+
+	#[pallet::error]
+	pub enum Error<T> {
+		/// The owner account can not be turned into a controller port
+		InvalidOwnerPort,
+		/// The connection is unknown or has no counterparty
+		InvalidConnection,
+		/// An open interchain account channel already exists
+		ActiveChannelAlreadySet,
+		/// No open interchain account channel exists
+		ActiveChannelNotFound,
+		/// The host did not report an interchain account address yet
+		InterchainAccountNotFound,
+		/// pallet-ibc rejected the channel handshake
+		ChannelOpenInitFailed,
+		/// No messages were supplied
+		EmptyMessages,
+		/// The memo is not valid utf8
+		InvalidMemo,
+		/// The timeout timestamp overflowed
+		InvalidTimeout,
+		/// Sending the packet failed
+		SendPacketFailed,
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T>
+	where
+		u64: From<<T as pallet_timestamp::Config>::Moment>
+			+ From<<T as frame_system::Config>::BlockNumber>,
+	{
+		/// Register an interchain account for the signer on `connection_id`.
+		///
+		/// Starts an ORDERED channel handshake from the signer's controller port to the `icahost`
+		/// port of the counterparty. The account address is known once the handshake is
+		/// acknowledged, see `Event::InterchainAccountRegistered`.
+		#[pallet::call_index(0)]
+		#[pallet::weight(0)]
+		pub fn register_interchain_account(
+			origin: OriginFor<T>,
+			connection_id: ConnectionId,
+		) -> DispatchResultWithPostInfo {
+			let owner = ensure_signed(origin)?;
+			let port_id = Self::owner_port(&owner)?;
+
+			if let Some(channel_id) = <ActiveChannels<T>>::get(&connection_id, &port_id) {
+				let channel_end = T::IbcContext::channel_end(&port_id, &channel_id)
+					.map_err(|_| Error::<T>::ActiveChannelNotFound)?;
+				ensure!(
+					channel_end.state_matches(&State::Closed),
+					Error::<T>::ActiveChannelAlreadySet
+				);
+				<ActiveChannels<T>>::remove(&connection_id, &port_id);
+			}
+
+			let connection_end = T::IbcContext::connection_end(&connection_id)
+				.map_err(|_| Error::<T>::InvalidConnection)?;
+			let host_connection_id = connection_end
+				.counterparty()
+				.connection_id()
+				.cloned()
+				.ok_or(Error::<T>::InvalidConnection)?;
+			let metadata = Metadata::new(&connection_id, &host_connection_id);
+
+			<PortOwner<T>>::insert(&port_id, &owner);
+
+			let msg = MsgChannelOpenInit {
+				port_id_on_a: port_id.clone(),
+				connection_hops_on_a: vec![connection_id.clone()],
+				port_id_on_b: PortId::from_str(HOST_PORT_ID)
+					.map_err(|_| Error::<T>::InvalidOwnerPort)?,
+				ordering: Order::Ordered,
+				signer: Self::owner_signer(&owner)?,
+				version_proposal: metadata.to_version(),
+			};
+			let value = msg.encode_vec().map_err(|_| Error::<T>::ChannelOpenInitFailed)?;
+			T::IbcRouter::dispatch(vec![Any {
+				type_url: CHAN_OPEN_INIT_TYPE_URL.to_string(),
+				value,
+			}])?;
+
+			// pallet-ibc reports handler errors as events, the channel is only recorded by the
+			// `on_chan_open_init` callback when the handshake step succeeded.
+			ensure!(
+				<ActiveChannels<T>>::contains_key(&connection_id, &port_id),
+				Error::<T>::ChannelOpenInitFailed
+			);
+
+			Ok(().into())
+		}
+
+		/// Execute `messages` with the signer's interchain account on the host of
+		/// `connection_id`.
+		///
+		/// Parameters:
+		/// - `messages`: protobuf-encoded Cosmos SDK messages, executed atomically on the host.
+		/// - `memo`: arbitrary memo forwarded with the packet.
+		/// - `relative_timeout`: nanoseconds from now after which the packet times out.
+		#[pallet::call_index(1)]
+		#[pallet::weight(0)]
+		pub fn send_tx(
+			origin: OriginFor<T>,
+			connection_id: ConnectionId,
+			messages: Vec<Any>,
+			memo: Vec<u8>,
+			relative_timeout: u64,
+		) -> DispatchResultWithPostInfo {
+			let owner = ensure_signed(origin)?;
+			let port_id = Self::owner_port(&owner)?;
+			ensure!(!messages.is_empty(), Error::<T>::EmptyMessages);
+
+			let channel_id = <ActiveChannels<T>>::get(&connection_id, &port_id)
+				.ok_or(Error::<T>::ActiveChannelNotFound)?;
+			ensure!(
+				<InterchainAccounts<T>>::contains_key(&connection_id, &port_id),
+				Error::<T>::InterchainAccountNotFound
+			);
+			let memo =
+				alloc::string::String::from_utf8(memo).map_err(|_| Error::<T>::InvalidMemo)?;
+
+			let timeout_timestamp = (Self::host_timestamp() +
+				core::time::Duration::from_nanos(relative_timeout))
+			.map_err(|_| Error::<T>::InvalidTimeout)?;
+			let data = InterchainAccountPacketData::execute_tx(messages, memo).to_bytes();
+
+			let packet = build_packet::<T::IbcContext>(
+				&port_id,
+				&channel_id,
+				data,
+				TimeoutHeight::no_timeout(),
+				timeout_timestamp,
+			)
+			.map_err(|e| {
+				log::error!(target: LOG_TARGET, "❌ [send_tx] build packet: {:?}", e);
+				Error::<T>::ActiveChannelNotFound
+			})?;
+			let event = send_packet::<T::IbcContext>(packet).map_err(|e| {
+				log::error!(target: LOG_TARGET, "❌ [send_tx] send packet: {:?}", e);
+				Error::<T>::SendPacketFailed
+			})?;
+
+			Self::deposit_event(Event::SendPacket(event));
+
+			Ok(().into())
+		}
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// The controller port owned by `owner`: `icacontroller-<hex encoded account>`.
+	pub fn owner_port(owner: &T::AccountId) -> Result<PortId, Error<T>> {
+		PortId::from_str(&format!("{}{}", CONTROLLER_PORT_PREFIX, hex::encode(owner.encode())))
+			.map_err(|_| Error::<T>::InvalidOwnerPort)
+	}
+
+	/// The address of `owner`'s interchain account on the host of `connection_id`.
+	pub fn interchain_account(
+		owner: &T::AccountId,
+		connection_id: &ConnectionId,
+	) -> Option<Vec<u8>> {
+		let port_id = Self::owner_port(owner).ok()?;
+		<InterchainAccounts<T>>::get(connection_id, port_id)
+	}
+
+	fn owner_signer(owner: &T::AccountId) -> Result<Signer, Error<T>> {
+		Signer::from_str(&format!("0x{}", hex::encode(owner.encode())))
+			.map_err(|_| Error::<T>::InvalidOwnerPort)
+	}
+
+	fn host_timestamp() -> Timestamp
+	where
+		u64: From<<T as pallet_timestamp::Config>::Moment>,
+	{
+		let millis = u64::from(<pallet_timestamp::Pallet<T>>::get());
+		Timestamp::from_nanoseconds(millis.saturating_mul(1_000_000)).unwrap_or_default()
+	}
+}
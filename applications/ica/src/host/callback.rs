@@ -0,0 +1,244 @@
+use super::{ActiveChannels, Config, Event, InterchainAccounts, Pallet};
+use crate::{
+	metadata::Metadata, packet::InterchainAccountPacketData, IcaError, CONTROLLER_PORT_PREFIX,
+	HOST_MODULE_ID, HOST_PORT_ID,
+};
+use alloc::{format, string::ToString};
+use codec::{Decode, Encode};
+use ibc::{
+	core::{
+		ics04_channel::{
+			channel::{Counterparty, Order},
+			error::{ChannelError, PacketError},
+			handler::ModuleExtras,
+			msgs::acknowledgement::Acknowledgement,
+			packet::Packet,
+			Version,
+		},
+		ics24_host::identifier::{ChannelId, ConnectionId, PortId},
+		ics26_routing::context::{Module, ModuleId, ModuleOutputBuilder},
+	},
+	signer::Signer,
+};
+use pallet_ibc_utils::{ack::GenericAcknowledgement, traits::ChannelReaderInterface};
+use sp_std::{marker::PhantomData, str::FromStr};
+
+/// The ibc module id of the host, to be used by the runtime's `AddModule` implementation.
+pub fn module_id() -> ModuleId {
+	ModuleId::from_str(HOST_MODULE_ID).expect("never failed")
+}
+
+/// Resolves the `icahost` port to the host module.
+pub fn lookup_module_by_port(port_id: &PortId) -> Option<ModuleId> {
+	(port_id.as_str() == HOST_PORT_ID).then(module_id)
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct IcaHostModule<T>(pub PhantomData<T>);
+
+impl<T: Config> IcaHostModule<T> {
+	fn channel_connection(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<ConnectionId, IcaError> {
+		<T::IbcContext as ChannelReaderInterface>::channel_end(port_id, channel_id)
+			.map_err(|e| IcaError::InvalidConnection { reason: e.to_string() })?
+			.connection_hops()
+			.first()
+			.cloned()
+			.ok_or(IcaError::InvalidConnection { reason: "missing connection hop".to_string() })
+	}
+
+	fn open_try(
+		order: Order,
+		connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		counterparty: &Counterparty,
+		counterparty_version: &Version,
+	) -> Result<Version, IcaError> {
+		if order != Order::Ordered {
+			return Err(IcaError::InvalidChannelOrdering)
+		}
+		if port_id.as_str() != HOST_PORT_ID {
+			return Err(IcaError::InvalidPort { port_id: port_id.to_string() })
+		}
+		let controller_port_id = counterparty.port_id();
+		if !controller_port_id.as_str().starts_with(CONTROLLER_PORT_PREFIX) {
+			return Err(IcaError::InvalidPort { port_id: controller_port_id.to_string() })
+		}
+
+		let connection_id = connection_hops
+			.first()
+			.ok_or(IcaError::InvalidConnection { reason: "missing connection hop".to_string() })?;
+		let connection_end =
+			<T::IbcContext as ChannelReaderInterface>::connection_end(connection_id)
+				.map_err(|e| IcaError::InvalidConnection { reason: e.to_string() })?;
+		let controller_connection_id =
+			connection_end
+				.counterparty()
+				.connection_id()
+				.ok_or(IcaError::InvalidConnection {
+					reason: "missing counterparty connection".to_string(),
+				})?;
+		let mut metadata = Metadata::from_version(counterparty_version)?;
+		metadata.validate(controller_connection_id, connection_id)?;
+
+		if let Some(channel_id) = <ActiveChannels<T>>::get(connection_id, controller_port_id) {
+			let is_open =
+				<T::IbcContext as ChannelReaderInterface>::channel_end(port_id, &channel_id)
+					.map(|channel_end| channel_end.is_open())
+					.unwrap_or(false);
+			if is_open {
+				return Err(IcaError::ActiveChannelAlreadySet)
+			}
+		}
+
+		let account = match <InterchainAccounts<T>>::get(connection_id, controller_port_id) {
+			Some(account) => account,
+			None => {
+				let account = Pallet::<T>::derive_account(connection_id, controller_port_id);
+				<InterchainAccounts<T>>::insert(connection_id, controller_port_id, &account);
+				Pallet::<T>::deposit_event(Event::<T>::InterchainAccountRegistered {
+					connection_id: connection_id.clone(),
+					controller_port_id: controller_port_id.clone(),
+					account: account.clone(),
+				});
+				account
+			},
+		};
+		metadata.address = format!("0x{}", hex::encode(account.encode()));
+
+		Ok(metadata.to_version())
+	}
+
+	fn execute(packet: &Packet) -> Result<(T::AccountId, u32), IcaError> {
+		let connection_id = Self::channel_connection(&packet.port_on_b, &packet.chan_on_b)?;
+		let account = <InterchainAccounts<T>>::get(&connection_id, &packet.port_on_a)
+			.ok_or(IcaError::UnknownOwner)?;
+
+		let messages = InterchainAccountPacketData::from_bytes(&packet.data)?.messages()?;
+		let calls = Pallet::<T>::decode_calls(messages)?;
+		let count = calls.len() as u32;
+		Pallet::<T>::execute_calls(&account, calls)?;
+
+		Ok((account, count))
+	}
+}
+
+impl<T: Config> Module for IcaHostModule<T> {
+	fn on_chan_open_init(
+		&mut self,
+		_order: Order,
+		_connection_hops: &[ConnectionId],
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+		_counterparty: &Counterparty,
+		_version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		Err(ChannelError::AppModule { description: IcaError::UnsupportedOperation.to_string() })
+	}
+
+	fn on_chan_open_try(
+		&mut self,
+		order: Order,
+		connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		_channel_id: &ChannelId,
+		counterparty: &Counterparty,
+		counterparty_version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		let version =
+			Self::open_try(order, connection_hops, port_id, counterparty, counterparty_version)
+				.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+
+		Ok((ModuleExtras::empty(), version))
+	}
+
+	fn on_chan_open_ack(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+		_counterparty_version: &Version,
+	) -> Result<ModuleExtras, ChannelError> {
+		Err(ChannelError::AppModule { description: IcaError::UnsupportedOperation.to_string() })
+	}
+
+	fn on_chan_open_confirm(
+		&mut self,
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		let channel_end =
+			<T::IbcContext as ChannelReaderInterface>::channel_end(port_id, channel_id)?;
+		let connection_id = Self::channel_connection(port_id, channel_id)
+			.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+		<ActiveChannels<T>>::insert(
+			connection_id,
+			channel_end.counterparty().port_id(),
+			channel_id,
+		);
+
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_chan_close_init(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Err(ChannelError::AppModule { description: IcaError::UnsupportedOperation.to_string() })
+	}
+
+	fn on_chan_close_confirm(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_recv_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		_relayer: &Signer,
+	) -> Acknowledgement {
+		match Self::execute(packet) {
+			Ok((account, calls)) => {
+				Pallet::<T>::deposit_event(Event::<T>::PacketExecuted {
+					account,
+					sequence: packet.sequence,
+					calls,
+				});
+				GenericAcknowledgement::success(calls.encode()).into()
+			},
+			Err(e) => {
+				log::error!(target: crate::LOG_TARGET, "❌ [ica host] execute packet: {}", e);
+				Pallet::<T>::deposit_event(Event::<T>::PacketExecutionFailed {
+					sequence: packet.sequence,
+					error: e.to_string().into_bytes(),
+				});
+				GenericAcknowledgement::error(e).into()
+			},
+		}
+	}
+
+	fn on_acknowledgement_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		_packet: &Packet,
+		_acknowledgement: &Acknowledgement,
+		_relayer: &Signer,
+	) -> Result<(), PacketError> {
+		Err(PacketError::AppModule { description: IcaError::UnsupportedOperation.to_string() })
+	}
+
+	fn on_timeout_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		_packet: &Packet,
+		_relayer: &Signer,
+	) -> Result<(), PacketError> {
+		Err(PacketError::AppModule { description: IcaError::UnsupportedOperation.to_string() })
+	}
+}
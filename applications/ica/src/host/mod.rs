@@ -0,0 +1,171 @@
+//! ICS-27 host submodule.
+//!
+//! Controllers on counterparty chains open ORDERED channels from their `icacontroller-*` port to
+//! the `icahost` port of this chain. For every (connection, controller port) pair the host derives
+//! a deterministic local account and reports it back in the handshake metadata.
+//!
+//! Packets carry messages of type [`RUNTIME_CALL_TYPE_URL`] whose value is a SCALE-encoded
+//! `RuntimeCall`. Calls that pass [`Config::CallFilter`] are dispatched atomically from the
+//! interchain account, within [`Config::MaxWeightPerPacket`]. Any failure reverts the whole packet
+//! and is reported to the controller as an error acknowledgement.
+pub use pallet::*;
+
+pub mod callback;
+
+use crate::*;
+use alloc::{format, string::ToString};
+use codec::{Decode, Encode};
+use frame_support::{
+	dispatch::{Dispatchable, GetDispatchInfo, PostDispatchInfo},
+	pallet_prelude::*,
+	storage::{with_transaction, TransactionOutcome},
+	traits::Contains,
+};
+use ibc::core::{
+	ics04_channel::packet::Sequence,
+	ics24_host::identifier::{ChannelId, ConnectionId, PortId},
+};
+use ibc_proto::google::protobuf::Any;
+use pallet_ibc_utils::traits::{ChannelKeeperInterface, ChannelReaderInterface};
+use sp_runtime::traits::TrailingZeroInput;
+use sp_std::{fmt::Debug, vec::Vec};
+
+/// Type url of the messages executed by the host: the value is a SCALE-encoded `RuntimeCall`
+pub const RUNTIME_CALL_TYPE_URL: &str = "/substrate.RuntimeCall";
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+
+	#[pallet::pallet]
+	#[pallet::without_storage_info]
+	pub struct Pallet<T>(_);
+
+	/// Configure the pallet by specifying the parameters and types on which it depends.
+	#[pallet::config]
+	pub trait Config: frame_system::Config + Sync + Send + Debug {
+		/// The aggregated event type of the runtime.
+		type RuntimeEvent: Parameter
+			+ Member
+			+ From<Event<Self>>
+			+ Debug
+			+ IsType<<Self as frame_system::Config>::RuntimeEvent>;
+
+		/// The calls interchain accounts may execute
+		type RuntimeCall: Parameter
+			+ Dispatchable<
+				RuntimeOrigin = <Self as frame_system::Config>::RuntimeOrigin,
+				PostInfo = PostDispatchInfo,
+			> + GetDispatchInfo;
+
+		/// Allowlist of calls interchain accounts may execute
+		type CallFilter: Contains<<Self as Config>::RuntimeCall>;
+
+		/// Maximum number of messages in a single packet
+		#[pallet::constant]
+		type MaxMessagesPerPacket: Get<u32>;
+
+		/// Maximum total weight of the calls of a single packet
+		#[pallet::constant]
+		type MaxWeightPerPacket: Get<Weight>;
+
+		type IbcContext: ChannelKeeperInterface + ChannelReaderInterface;
+	}
+
+	#[pallet::storage]
+	/// key1: host connection id
+	/// key2: controller port id
+	/// value: interchain account
+	pub type InterchainAccounts<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, ConnectionId, Blake2_128Concat, PortId, T::AccountId>;
+
+	#[pallet::storage]
+	/// key1: host connection id
+	/// key2: controller port id
+	/// value: channel id of the active interchain account channel
+	pub type ActiveChannels<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, ConnectionId, Blake2_128Concat, PortId, ChannelId>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// An interchain account was registered for a controller
+		InterchainAccountRegistered {
+			connection_id: ConnectionId,
+			controller_port_id: PortId,
+			account: T::AccountId,
+		},
+		/// All calls of the packet were executed
+		PacketExecuted { account: T::AccountId, sequence: Sequence, calls: u32 },
+		/// The packet was rejected or one of its calls failed, nothing was executed
+		PacketExecutionFailed { sequence: Sequence, error: Vec<u8> },
+	}
+
+	#[pallet::error]
+	pub enum Error<T> {}
+}
+
+impl<T: Config> Pallet<T> {
+	/// The interchain account of `controller_port_id` on `connection_id`.
+	///
+	/// Derived as `blake2_256("ics27/host" ++ connection_id ++ controller_port_id)`, so the
+	/// account is the same every time the controller re-opens its channel.
+	pub fn derive_account(
+		connection_id: &ConnectionId,
+		controller_port_id: &PortId,
+	) -> T::AccountId {
+		let entropy = (b"ics27/host", connection_id.as_str(), controller_port_id.as_str())
+			.using_encoded(sp_io::hashing::blake2_256);
+		Decode::decode(&mut TrailingZeroInput::new(entropy.as_ref()))
+			.expect("infinite length input; no invalid inputs for type; qed")
+	}
+
+	/// Decode the messages of a packet into calls, checking them against the allowlist and the
+	/// per-packet limits.
+	pub fn decode_calls(messages: Vec<Any>) -> Result<Vec<<T as Config>::RuntimeCall>, IcaError> {
+		if messages.is_empty() || messages.len() > T::MaxMessagesPerPacket::get() as usize {
+			return Err(IcaError::InvalidPacketData {
+				reason: format!("unsupported number of messages {}", messages.len()),
+			})
+		}
+
+		let mut total_weight = Weight::zero();
+		let mut calls = Vec::with_capacity(messages.len());
+		for message in messages {
+			if message.type_url != RUNTIME_CALL_TYPE_URL {
+				return Err(IcaError::UnauthorizedMessage { type_url: message.type_url })
+			}
+			let call = <T as Config>::RuntimeCall::decode(&mut message.value.as_slice())
+				.map_err(|e| IcaError::InvalidPacketData { reason: e.to_string() })?;
+			if !T::CallFilter::contains(&call) {
+				return Err(IcaError::UnauthorizedMessage { type_url: message.type_url })
+			}
+			total_weight = total_weight.saturating_add(call.get_dispatch_info().weight);
+			calls.push(call);
+		}
+
+		if total_weight.any_gt(T::MaxWeightPerPacket::get()) {
+			return Err(IcaError::WeightLimitExceeded)
+		}
+
+		Ok(calls)
+	}
+
+	/// Dispatch `calls` from `account`, reverting all of them if one fails.
+	pub fn execute_calls(
+		account: &T::AccountId,
+		calls: Vec<<T as Config>::RuntimeCall>,
+	) -> Result<(), IcaError> {
+		with_transaction(|| {
+			for call in calls {
+				let origin = frame_system::RawOrigin::Signed(account.clone()).into();
+				if let Err(e) = call.dispatch(origin) {
+					return TransactionOutcome::Rollback(Err(IcaError::DispatchFailed {
+						reason: format!("{:?}", e.error),
+					}))
+				}
+			}
+			TransactionOutcome::Commit(Ok(()))
+		})
+	}
+}
@@ -0,0 +1,97 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! # Overview
+//!
+//! Implementation of [ICS-27 interchain accounts](https://github.com/cosmos/ibc/tree/main/spec/app/ics-027-interchain-accounts)
+//! on top of pallet-ibc.
+//!
+//! - [`controller`]: lets accounts of this chain register and drive accounts on a host chain over
+//!   ordered channels bound to `icacontroller-*` ports.
+//! - [`host`]: lets controllers on counterparty chains execute allowlisted runtime calls from
+//!   accounts derived on this chain, over channels bound to the `icahost` port.
+extern crate alloc;
+
+pub mod controller;
+pub mod host;
+pub mod metadata;
+pub mod packet;
+
+#[cfg(test)]
+mod tests;
+
+#[cfg(test)]
+mod mock;
+
+use alloc::{format, string::String};
+use sp_runtime::DispatchError;
+use sp_std::fmt;
+
+pub const LOG_TARGET: &str = "runtime::pallet-ics27-ica";
+
+/// ICS-27 application version
+pub const VERSION: &str = "ics27-1";
+/// Prefix of the per-owner controller ports
+pub const CONTROLLER_PORT_PREFIX: &str = "icacontroller-";
+/// Port bound by the host submodule
+pub const HOST_PORT_ID: &str = "icahost";
+/// Module id of the controller submodule in the ibc router
+pub const CONTROLLER_MODULE_ID: &str = "icacontroller";
+/// Module id of the host submodule in the ibc router
+pub const HOST_MODULE_ID: &str = "icahost";
+/// The only supported message encoding
+pub const ENCODING_PROTOBUF: &str = "proto3";
+/// The only supported transaction type
+pub const TX_TYPE_SDK_MULTI_MSG: &str = "sdk_multi_msg";
+
+/// Errors raised while handling ICS-27 handshakes and packets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IcaError {
+	/// channel ordering must be ORDERED
+	InvalidChannelOrdering,
+	/// invalid port
+	InvalidPort { port_id: String },
+	/// invalid version or metadata
+	InvalidVersion { reason: String },
+	/// connection does not match the metadata
+	InvalidConnection { reason: String },
+	/// an active channel already exists for this owner and connection
+	ActiveChannelAlreadySet,
+	/// the operation is not supported by this submodule
+	UnsupportedOperation,
+	/// packet data could not be decoded
+	InvalidPacketData { reason: String },
+	/// unknown owner for the port
+	UnknownOwner,
+	/// the message is not allowed on this host
+	UnauthorizedMessage { type_url: String },
+	/// the messages exceed the weight allowed for a packet
+	WeightLimitExceeded,
+	/// executing a message failed
+	DispatchFailed { reason: String },
+}
+
+impl fmt::Display for IcaError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidChannelOrdering => write!(f, "channel ordering must be ORDERED"),
+			Self::InvalidPort { port_id } => write!(f, "invalid ics27 port: {}", port_id),
+			Self::InvalidVersion { reason } => write!(f, "invalid ics27 version: {}", reason),
+			Self::InvalidConnection { reason } => write!(f, "invalid connection: {}", reason),
+			Self::ActiveChannelAlreadySet =>
+				write!(f, "an active interchain account channel already exists"),
+			Self::UnsupportedOperation => write!(f, "operation not supported"),
+			Self::InvalidPacketData { reason } => write!(f, "invalid packet data: {}", reason),
+			Self::UnknownOwner => write!(f, "no owner registered for port"),
+			Self::UnauthorizedMessage { type_url } =>
+				write!(f, "message not allowed on host: {}", type_url),
+			Self::WeightLimitExceeded => write!(f, "packet exceeds the maximum weight"),
+			Self::DispatchFailed { reason } => write!(f, "message execution failed: {}", reason),
+		}
+	}
+}
+
+impl From<DispatchError> for IcaError {
+	fn from(e: DispatchError) -> Self {
+		Self::DispatchFailed { reason: format!("{:?}", e) }
+	}
+}
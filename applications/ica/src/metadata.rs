@@ -0,0 +1,97 @@
+use crate::{IcaError, ENCODING_PROTOBUF, TX_TYPE_SDK_MULTI_MSG, VERSION};
+use alloc::{
+	format,
+	string::{String, ToString},
+};
+use ibc::core::{ics04_channel::Version, ics24_host::identifier::ConnectionId};
+use serde::{Deserialize, Serialize};
+
+/// The JSON metadata negotiated as channel version during the ICS-27 handshake.
+///
+/// The host fills in `address` with the interchain account it registered for the controller.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+	pub version: String,
+	pub controller_connection_id: String,
+	pub host_connection_id: String,
+	#[serde(default)]
+	pub address: String,
+	pub encoding: String,
+	pub tx_type: String,
+}
+
+impl Metadata {
+	pub fn new(controller_connection_id: &ConnectionId, host_connection_id: &ConnectionId) -> Self {
+		Self {
+			version: VERSION.to_string(),
+			controller_connection_id: controller_connection_id.to_string(),
+			host_connection_id: host_connection_id.to_string(),
+			address: String::new(),
+			encoding: ENCODING_PROTOBUF.to_string(),
+			tx_type: TX_TYPE_SDK_MULTI_MSG.to_string(),
+		}
+	}
+
+	pub fn from_version(version: &Version) -> Result<Self, IcaError> {
+		serde_json::from_str(version.as_str())
+			.map_err(|e| IcaError::InvalidVersion { reason: e.to_string() })
+	}
+
+	pub fn to_version(&self) -> Version {
+		Version::new(serde_json::to_string(self).expect("Metadata's Serialize impl never fails"))
+	}
+
+	/// Check the version, encoding and tx type, and that the metadata was negotiated over the
+	/// given pair of connections.
+	pub fn validate(
+		&self,
+		controller_connection_id: &ConnectionId,
+		host_connection_id: &ConnectionId,
+	) -> Result<(), IcaError> {
+		if self.version != VERSION {
+			return Err(IcaError::InvalidVersion {
+				reason: format!("expected {}, got {}", VERSION, self.version),
+			})
+		}
+		if self.encoding != ENCODING_PROTOBUF {
+			return Err(IcaError::InvalidVersion {
+				reason: format!("unsupported encoding {}", self.encoding),
+			})
+		}
+		if self.tx_type != TX_TYPE_SDK_MULTI_MSG {
+			return Err(IcaError::InvalidVersion {
+				reason: format!("unsupported tx type {}", self.tx_type),
+			})
+		}
+		if self.controller_connection_id != controller_connection_id.as_str() {
+			return Err(IcaError::InvalidConnection {
+				reason: format!(
+					"expected controller connection {}, got {}",
+					controller_connection_id, self.controller_connection_id
+				),
+			})
+		}
+		if self.host_connection_id != host_connection_id.as_str() {
+			return Err(IcaError::InvalidConnection {
+				reason: format!(
+					"expected host connection {}, got {}",
+					host_connection_id, self.host_connection_id
+				),
+			})
+		}
+
+		Ok(())
+	}
+}
+
+#[test]
+fn test_metadata_round_trip() {
+	let controller = ConnectionId::new(0);
+	let host = ConnectionId::new(7);
+	let metadata = Metadata::new(&controller, &host);
+
+	let decoded = Metadata::from_version(&metadata.to_version()).unwrap();
+	assert_eq!(decoded, metadata);
+	assert!(decoded.validate(&controller, &host).is_ok());
+	assert!(decoded.validate(&host, &controller).is_err());
+}
@@ -0,0 +1,265 @@
+use crate::{self as pallet_ics27_ica, controller, host};
+use frame_support::{
+	construct_runtime, parameter_types,
+	traits::{ConstU128, ConstU16, ConstU32, ConstU64, Contains, GenesisBuild},
+	weights::Weight,
+};
+use ibc::{
+	core::{
+		ics02_client::height::Height,
+		ics03_connection::{
+			connection::{
+				ConnectionEnd, Counterparty as ConnectionCounterparty, State as ConnectionState,
+			},
+			version::get_compatible_versions,
+		},
+		ics04_channel::{
+			channel::{ChannelEnd, Counterparty, Order, State},
+			Version,
+		},
+		ics23_commitment::commitment::CommitmentPrefix,
+		ics24_host::{
+			identifier::{ChannelId, ClientId, ConnectionId, PortId},
+			path::ChannelEndsPath,
+		},
+		ics26_routing::context::ModuleId,
+	},
+	mock::{
+		client_state::{client_type as mock_client_type, MockClientState},
+		consensus_state::MockConsensusState,
+		header::MockHeader,
+	},
+	timestamp::Timestamp,
+};
+use ibc_proto::{
+	google::protobuf::Any, ibc::core::connection::v1::ConnectionEnd as RawConnectionEnd,
+	protobuf::Protobuf,
+};
+use pallet_ibc_utils::module::{AddModule, Router};
+use sp_runtime::{
+	generic,
+	traits::{AccountIdLookup, BlakeTwo256},
+	AccountId32,
+};
+use std::marker::PhantomData;
+
+pub type AccountId = AccountId32;
+pub type Balance = u128;
+pub type BlockNumber = u64;
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+// Configure a mock runtime to test the pallets.
+construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system,
+		Balances: pallet_balances,
+		Timestamp: pallet_timestamp,
+		Ibc: pallet_ibc,
+		IcaController: pallet_ics27_ica::controller,
+		IcaHost: pallet_ics27_ica::host,
+	}
+);
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type AccountId = AccountId;
+	type RuntimeCall = RuntimeCall;
+	type Lookup = AccountIdLookup<AccountId, ()>;
+	type Index = u32;
+	type BlockNumber = BlockNumber;
+	type Hash = sp_core::H256;
+	type Hashing = BlakeTwo256;
+	type Header = generic::Header<BlockNumber, BlakeTwo256>;
+	type RuntimeEvent = RuntimeEvent;
+	type RuntimeOrigin = RuntimeOrigin;
+	type BlockHashCount = ();
+	type DbWeight = ();
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type AccountData = pallet_balances::AccountData<Balance>;
+	type SystemWeightInfo = ();
+	type SS58Prefix = ConstU16<42>;
+	type OnSetCode = ();
+	type MaxConsumers = ConstU32<16>;
+}
+
+pub const DOLLARS: Balance = 1_000_000_000_000;
+
+impl pallet_balances::Config for Test {
+	type MaxLocks = ConstU32<50>;
+	type MaxReserves = ConstU32<50>;
+	type ReserveIdentifier = [u8; 8];
+	type Balance = Balance;
+	type RuntimeEvent = RuntimeEvent;
+	type DustRemoval = ();
+	type ExistentialDeposit = ConstU128<DOLLARS>;
+	type AccountStore = frame_system::Pallet<Test>;
+	type WeightInfo = pallet_balances::weights::SubstrateWeight<Test>;
+}
+
+impl pallet_timestamp::Config for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = ConstU64<3_000>;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const ExpectedBlockTime: u64 = 6;
+	pub const ChainVersion: u64 = 0;
+}
+
+impl pallet_ibc::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type TimeProvider = pallet_timestamp::Pallet<Test>;
+	type ExpectedBlockTime = ExpectedBlockTime;
+	const IBC_COMMITMENT_PREFIX: &'static [u8] = b"Ibc";
+	type ChainVersion = ChainVersion;
+	type IbcModule = IcaModule;
+	type MaxClientStateSize = ConstU32<{ 64 * 1024 }>;
+	type MaxChannelsPerConnection = ConstU32<64>;
+	type MaxEventSize = ConstU32<{ 8 * 1024 }>;
+	type WeightInfo = ();
+}
+
+/// Routes the `icacontroller-*` ports to the controller and the `icahost` port to the host.
+pub struct IcaModule;
+
+impl AddModule for IcaModule {
+	fn add_module(router: Router) -> Router {
+		router
+			.add_route(
+				controller::callback::module_id(),
+				controller::callback::IcaControllerModule::<Test>(PhantomData),
+			)
+			.and_then(|router| {
+				router.add_route(
+					host::callback::module_id(),
+					host::callback::IcaHostModule::<Test>(PhantomData),
+				)
+			})
+			.unwrap_or_else(|e| panic!("add module failed by {}", e))
+	}
+
+	fn lookup_module_by_port(port_id: &PortId) -> Option<ModuleId> {
+		controller::callback::lookup_module_by_port(port_id)
+			.or_else(|| host::callback::lookup_module_by_port(port_id))
+	}
+}
+
+impl pallet_ics27_ica::controller::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type IbcContext = pallet_ibc::context::Context<Test>;
+	type IbcRouter = Ibc;
+}
+
+/// Interchain accounts may only move balances.
+pub struct BalancesOnly;
+impl Contains<RuntimeCall> for BalancesOnly {
+	fn contains(call: &RuntimeCall) -> bool {
+		matches!(call, RuntimeCall::Balances(_))
+	}
+}
+
+parameter_types! {
+	pub static MaxWeightPerPacket: Weight = Weight::from_parts(u64::MAX, u64::MAX);
+}
+
+impl pallet_ics27_ica::host::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type RuntimeCall = RuntimeCall;
+	type CallFilter = BalancesOnly;
+	type MaxMessagesPerPacket = ConstU32<4>;
+	type MaxWeightPerPacket = MaxWeightPerPacket;
+	type IbcContext = pallet_ibc::context::Context<Test>;
+}
+
+/// The connection of this chain and the one of the counterparty it leads to.
+pub fn connection_ids() -> (ConnectionId, ConnectionId) {
+	(ConnectionId::new(0), ConnectionId::new(7))
+}
+
+/// The time of the mock chain and of the mock client, in milliseconds.
+pub const NOW: u64 = 1_700_000_000_000;
+
+/// Store an open connection towards the counterparty over a mock client at height 5, and set the
+/// time of the mock chain and of the client to [`NOW`].
+pub fn store_connection() {
+	let (connection_id, counterparty_connection_id) = connection_ids();
+	let client_id = ClientId::new(mock_client_type(), 0).unwrap();
+	let header = MockHeader {
+		height: Height::new(0, 5).unwrap(),
+		timestamp: Timestamp::from_nanoseconds(NOW * 1_000_000).unwrap(),
+	};
+	let connection_end = ConnectionEnd::new(
+		ConnectionState::Open,
+		client_id.clone(),
+		ConnectionCounterparty::new(
+			client_id.clone(),
+			Some(counterparty_connection_id),
+			CommitmentPrefix::try_from(b"ibc".to_vec()).unwrap(),
+		),
+		get_compatible_versions(),
+		core::time::Duration::ZERO,
+	);
+
+	let config = pallet_ibc::GenesisConfig {
+		clients: vec![(
+			client_id.to_string(),
+			pallet_ibc::MOCK_CLIENT_TYPE.to_string(),
+			Protobuf::<Any>::encode_vec(&MockClientState::new(header)).unwrap(),
+		)],
+		consensus_states: vec![(
+			client_id.to_string(),
+			0,
+			5,
+			Protobuf::<Any>::encode_vec(&MockConsensusState::new(header)).unwrap(),
+		)],
+		connections: vec![(
+			connection_id.to_string(),
+			Protobuf::<RawConnectionEnd>::encode_vec(&connection_end).unwrap(),
+		)],
+		channels: vec![],
+		sequences: vec![],
+	};
+	<pallet_ibc::GenesisConfig as GenesisBuild<Test>>::build(&config);
+	pallet_timestamp::Pallet::<Test>::set_timestamp(NOW);
+}
+
+/// Store an ordered channel in `state` between `port_id` and `counterparty_port_id`.
+pub fn store_channel(
+	port_id: &PortId,
+	channel_id: &ChannelId,
+	counterparty_port_id: &PortId,
+	state: State,
+	version: Version,
+) {
+	let channel_end = ChannelEnd::new(
+		state,
+		Order::Ordered,
+		Counterparty::new(counterparty_port_id.clone(), Some(ChannelId::new(3))),
+		vec![connection_ids().0],
+		version,
+	);
+	<pallet_ibc::Channels<Test>>::insert(
+		ChannelEndsPath(port_id.clone(), channel_id.clone()),
+		channel_end,
+	);
+}
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut ext: sp_io::TestExternalities =
+		frame_system::GenesisConfig::default().build_storage::<Test>().unwrap().into();
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
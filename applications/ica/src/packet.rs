@@ -0,0 +1,71 @@
+use crate::IcaError;
+use alloc::string::{String, ToString};
+use base64::Engine;
+use ibc_proto::{google::protobuf::Any, ibc::applications::interchain_accounts::v1::CosmosTx};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use sp_std::vec::Vec;
+
+/// Packet type executing a `CosmosTx` on the host chain
+pub const TYPE_EXECUTE_TX: &str = "TYPE_EXECUTE_TX";
+
+/// `InterchainAccountPacketData` in the JSON form exchanged with ibc-go.
+///
+/// `data` is the base64 of a protobuf-encoded `CosmosTx`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterchainAccountPacketData {
+	#[serde(rename = "type")]
+	pub packet_type: String,
+	pub data: String,
+	#[serde(default)]
+	pub memo: String,
+}
+
+impl InterchainAccountPacketData {
+	/// Packet data executing `messages` atomically on the host chain.
+	pub fn execute_tx(messages: Vec<Any>, memo: String) -> Self {
+		let tx = CosmosTx { messages };
+		Self {
+			packet_type: TYPE_EXECUTE_TX.to_string(),
+			data: base64::engine::general_purpose::STANDARD.encode(tx.encode_to_vec()),
+			memo,
+		}
+	}
+
+	/// Decode the messages carried by an `TYPE_EXECUTE_TX` packet.
+	pub fn messages(&self) -> Result<Vec<Any>, IcaError> {
+		if self.packet_type != TYPE_EXECUTE_TX {
+			return Err(IcaError::InvalidPacketData {
+				reason: alloc::format!("unsupported packet type {}", self.packet_type),
+			})
+		}
+		let data = base64::engine::general_purpose::STANDARD
+			.decode(&self.data)
+			.map_err(|e| IcaError::InvalidPacketData { reason: e.to_string() })?;
+		let tx = CosmosTx::decode(data.as_slice())
+			.map_err(|e| IcaError::InvalidPacketData { reason: e.to_string() })?;
+
+		Ok(tx.messages)
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, IcaError> {
+		serde_json::from_slice(bytes)
+			.map_err(|e| IcaError::InvalidPacketData { reason: e.to_string() })
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		serde_json::to_vec(self).expect("InterchainAccountPacketData's Serialize impl never fails")
+	}
+}
+
+#[test]
+fn test_execute_tx_packet_data_round_trip() {
+	let message =
+		Any { type_url: "/cosmos.bank.v1beta1.MsgSend".to_string(), value: alloc::vec![1, 2, 3] };
+	let packet_data =
+		InterchainAccountPacketData::execute_tx(alloc::vec![message.clone()], "memo".to_string());
+
+	let decoded = InterchainAccountPacketData::from_bytes(&packet_data.to_bytes()).unwrap();
+	assert_eq!(decoded, packet_data);
+	assert_eq!(decoded.messages().unwrap(), alloc::vec![message]);
+}
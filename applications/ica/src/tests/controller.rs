@@ -0,0 +1,292 @@
+//! Channel handshake and acknowledgements of the controller.
+use crate::{
+	controller::{
+		callback::IcaControllerModule, ActiveChannels, Error, Event, InterchainAccounts, Pallet,
+		PortOwner,
+	},
+	metadata::Metadata,
+	mock::{
+		connection_ids, new_test_ext, store_channel, store_connection, AccountId, IcaController,
+		RuntimeEvent, RuntimeOrigin, System, Test,
+	},
+	HOST_PORT_ID,
+};
+use core::marker::PhantomData;
+use frame_support::{assert_noop, assert_ok};
+use ibc::{
+	core::{
+		ics04_channel::{
+			channel::{Counterparty, Order, State},
+			packet::{Packet, Sequence},
+			timeout::TimeoutHeight,
+		},
+		ics24_host::{
+			identifier::{ChannelId, PortId},
+			path::{ChannelEndsPath, CommitmentsPath, SeqSendsPath},
+		},
+		ics26_routing::context::{Module, ModuleOutputBuilder},
+	},
+	signer::Signer,
+	timestamp::Timestamp,
+};
+use ibc_proto::google::protobuf::Any;
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use std::str::FromStr;
+
+fn host_port() -> PortId {
+	PortId::from_str(HOST_PORT_ID).unwrap()
+}
+
+/// The controller port of `owner`, with the owner recorded.
+fn owner_port(owner: &AccountId) -> PortId {
+	let port_id = Pallet::<Test>::owner_port(owner).unwrap();
+	<PortOwner<Test>>::insert(&port_id, owner);
+	port_id
+}
+
+fn metadata() -> Metadata {
+	let (connection_id, host_connection_id) = connection_ids();
+	Metadata::new(&connection_id, &host_connection_id)
+}
+
+fn open_init(
+	module: &mut IcaControllerModule<Test>,
+	order: Order,
+	port_id: &PortId,
+	counterparty_port_id: PortId,
+) -> bool {
+	module
+		.on_chan_open_init(
+			order,
+			&[connection_ids().0],
+			port_id,
+			&ChannelId::new(1),
+			&Counterparty::new(counterparty_port_id, None),
+			&metadata().to_version(),
+		)
+		.is_ok()
+}
+
+#[test]
+fn test_channel_handshake_registers_the_account() {
+	new_test_ext().execute_with(|| {
+		let mut module = IcaControllerModule::<Test>(PhantomData);
+		let alice = AccountId::from([1; 32]);
+		let port_id = owner_port(&alice);
+		let (connection_id, _) = connection_ids();
+		store_connection();
+
+		assert!(open_init(&mut module, Order::Ordered, &port_id, host_port()));
+		assert_eq!(<ActiveChannels<Test>>::get(&connection_id, &port_id), Some(ChannelId::new(1)));
+
+		// the host reports the address of the account it registered
+		let mut version = metadata();
+		version.address = "0x0101".to_string();
+		store_channel(
+			&port_id,
+			&ChannelId::new(1),
+			&host_port(),
+			State::TryOpen,
+			metadata().to_version(),
+		);
+		module
+			.on_chan_open_ack(&port_id, &ChannelId::new(1), &version.to_version())
+			.unwrap();
+
+		assert_eq!(
+			Pallet::<Test>::interchain_account(&alice, &connection_id),
+			Some(b"0x0101".to_vec())
+		);
+		assert!(System::events().iter().any(|record| record.event ==
+			RuntimeEvent::IcaController(Event::InterchainAccountRegistered {
+				owner: alice.clone(),
+				connection_id: connection_id.clone(),
+				address: b"0x0101".to_vec(),
+			})));
+
+		// the channel is open, a second one is refused
+		store_channel(
+			&port_id,
+			&ChannelId::new(1),
+			&host_port(),
+			State::Open,
+			version.to_version(),
+		);
+		assert!(!open_init(&mut module, Order::Ordered, &port_id, host_port()));
+	})
+}
+
+#[test]
+fn test_channel_handshake_rejects_invalid_channels() {
+	new_test_ext().execute_with(|| {
+		let mut module = IcaControllerModule::<Test>(PhantomData);
+		let alice = AccountId::from([1; 32]);
+		store_connection();
+
+		// the port of an account that never registered has no owner
+		let port_id = Pallet::<Test>::owner_port(&alice).unwrap();
+		assert!(!open_init(&mut module, Order::Ordered, &port_id, host_port()));
+
+		let port_id = owner_port(&alice);
+		assert!(!open_init(&mut module, Order::Unordered, &port_id, host_port()));
+		assert!(!open_init(&mut module, Order::Ordered, &port_id, PortId::transfer()));
+		assert!(!<ActiveChannels<Test>>::contains_key(connection_ids().0, &port_id));
+
+		// the host must report an address
+		assert!(open_init(&mut module, Order::Ordered, &port_id, host_port()));
+		store_channel(
+			&port_id,
+			&ChannelId::new(1),
+			&host_port(),
+			State::TryOpen,
+			metadata().to_version(),
+		);
+		assert!(module
+			.on_chan_open_ack(&port_id, &ChannelId::new(1), &metadata().to_version())
+			.is_err());
+		assert!(!<InterchainAccounts<Test>>::contains_key(connection_ids().0, &port_id));
+	})
+}
+
+#[test]
+fn test_acknowledgements_are_reported_to_the_owner() {
+	new_test_ext().execute_with(|| {
+		let mut module = IcaControllerModule::<Test>(PhantomData);
+		let alice = AccountId::from([1; 32]);
+		let port_id = owner_port(&alice);
+		let packet = Packet {
+			sequence: 1u64.into(),
+			port_on_a: port_id,
+			chan_on_a: ChannelId::new(1),
+			port_on_b: host_port(),
+			chan_on_b: ChannelId::new(3),
+			data: vec![],
+			timeout_height_on_b: TimeoutHeight::no_timeout(),
+			timeout_timestamp_on_b: Timestamp::none(),
+		};
+		let relayer = Signer::from_str("relayer").unwrap();
+		let mut output = ModuleOutputBuilder::new();
+
+		let ack = GenericAcknowledgement::success(b"ok");
+		module
+			.on_acknowledgement_packet(&mut output, &packet, &ack.into(), &relayer)
+			.unwrap();
+		let ack = GenericAcknowledgement::error("out of funds");
+		module
+			.on_acknowledgement_packet(&mut output, &packet, &ack.into(), &relayer)
+			.unwrap();
+		module.on_timeout_packet(&mut output, &packet, &relayer).unwrap();
+
+		let events: Vec<_> = System::events().into_iter().map(|record| record.event).collect();
+		assert_eq!(
+			events,
+			vec![
+				RuntimeEvent::IcaController(Event::TxAcknowledged {
+					owner: alice.clone(),
+					sequence: 1u64.into(),
+					result: b"ok".to_vec(),
+				}),
+				RuntimeEvent::IcaController(Event::TxFailed {
+					owner: alice.clone(),
+					sequence: 1u64.into(),
+					error: b"out of funds".to_vec(),
+				}),
+				RuntimeEvent::IcaController(Event::TxTimedOut {
+					owner: alice,
+					sequence: 1u64.into()
+				}),
+			]
+		);
+
+		// packets of ports without an owner are refused
+		let mut packet = packet;
+		packet.port_on_a = Pallet::<Test>::owner_port(&AccountId::from([2; 32])).unwrap();
+		assert!(module.on_timeout_packet(&mut output, &packet, &relayer).is_err());
+	})
+}
+
+#[test]
+fn test_register_and_send_tx_through_pallet_ibc() {
+	new_test_ext().execute_with(|| {
+		let mut module = IcaControllerModule::<Test>(PhantomData);
+		let alice = AccountId::from([1; 32]);
+		let port_id = Pallet::<Test>::owner_port(&alice).unwrap();
+		let (connection_id, _) = connection_ids();
+		let channel_id = ChannelId::new(0);
+		let messages = vec![Any { type_url: "/cosmos.bank.v1beta1.MsgSend".into(), value: vec![] }];
+		store_connection();
+
+		// the account can not send before it is registered
+		assert_noop!(
+			IcaController::send_tx(
+				RuntimeOrigin::signed(alice.clone()),
+				connection_id.clone(),
+				messages.clone(),
+				vec![],
+				1_000_000_000,
+			),
+			Error::<Test>::ActiveChannelNotFound
+		);
+
+		// pallet-ibc opens the channel and routes the handshake to the controller
+		assert_ok!(IcaController::register_interchain_account(
+			RuntimeOrigin::signed(alice.clone()),
+			connection_id.clone(),
+		));
+		let channel_end =
+			<pallet_ibc::Channels<Test>>::get(ChannelEndsPath(port_id.clone(), channel_id.clone()))
+				.unwrap();
+		assert!(channel_end.state_matches(&State::Init));
+		assert_eq!(channel_end.counterparty().port_id(), &host_port());
+		assert_eq!(<PortOwner<Test>>::get(&port_id), Some(alice.clone()));
+		assert_eq!(<ActiveChannels<Test>>::get(&connection_id, &port_id), Some(channel_id.clone()));
+		assert!(System::events().iter().any(|record| record.event ==
+			RuntimeEvent::IcaController(Event::ChannelOpenInitiated {
+				owner: alice.clone(),
+				connection_id: connection_id.clone(),
+				port_id: port_id.clone(),
+				channel_id: channel_id.clone(),
+			})));
+
+		// a second handshake is refused while the first one is pending
+		assert_noop!(
+			IcaController::register_interchain_account(
+				RuntimeOrigin::signed(alice.clone()),
+				connection_id.clone(),
+			),
+			Error::<Test>::ActiveChannelAlreadySet
+		);
+
+		// the host acknowledges the handshake with the address of the account
+		let mut version = metadata();
+		version.address = "0x0101".to_string();
+		module.on_chan_open_ack(&port_id, &channel_id, &version.to_version()).unwrap();
+		store_channel(&port_id, &channel_id, &host_port(), State::Open, version.to_version());
+		assert_eq!(
+			Pallet::<Test>::interchain_account(&alice, &connection_id),
+			Some(b"0x0101".to_vec())
+		);
+
+		// the packet is committed by pallet-ibc
+		assert_ok!(IcaController::send_tx(
+			RuntimeOrigin::signed(alice.clone()),
+			connection_id,
+			messages,
+			b"memo".to_vec(),
+			1_000_000_000,
+		));
+		assert!(<pallet_ibc::PacketCommitment<Test>>::contains_key(CommitmentsPath {
+			port_id: port_id.clone(),
+			channel_id: channel_id.clone(),
+			sequence: Sequence::from(1),
+		}));
+		assert_eq!(
+			<pallet_ibc::NextSequenceSend<Test>>::get(SeqSendsPath(port_id, channel_id)),
+			Some(Sequence::from(2))
+		);
+		assert!(System::events().iter().any(|record| matches!(
+			record.event,
+			RuntimeEvent::IcaController(Event::SendPacket(_))
+		)));
+	})
+}
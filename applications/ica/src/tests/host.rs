@@ -0,0 +1,207 @@
+//! Channel handshake of the host and execution of the calls of interchain accounts.
+use crate::{
+	host::{
+		callback::IcaHostModule, ActiveChannels, Event, InterchainAccounts, Pallet,
+		RUNTIME_CALL_TYPE_URL,
+	},
+	metadata::Metadata,
+	mock::{
+		connection_ids, new_test_ext, store_channel, store_connection, AccountId, Balances,
+		MaxWeightPerPacket, RuntimeCall, RuntimeEvent, System, Test, DOLLARS,
+	},
+	packet::InterchainAccountPacketData,
+	CONTROLLER_PORT_PREFIX, HOST_PORT_ID,
+};
+use codec::Encode;
+use core::marker::PhantomData;
+use frame_support::{dispatch::GetDispatchInfo, traits::Currency};
+use ibc::{
+	core::{
+		ics04_channel::{
+			channel::{Counterparty, Order, State},
+			packet::Packet,
+			timeout::TimeoutHeight,
+		},
+		ics24_host::identifier::{ChannelId, PortId},
+		ics26_routing::context::{Module, ModuleOutputBuilder},
+	},
+	signer::Signer,
+	timestamp::Timestamp,
+};
+use ibc_proto::google::protobuf::Any;
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use std::str::FromStr;
+
+fn host_port() -> PortId {
+	PortId::from_str(HOST_PORT_ID).unwrap()
+}
+
+fn controller_port() -> PortId {
+	PortId::from_str(&format!("{}owner", CONTROLLER_PORT_PREFIX)).unwrap()
+}
+
+/// The metadata proposed by the controller.
+fn metadata() -> Metadata {
+	let (connection_id, counterparty_connection_id) = connection_ids();
+	Metadata::new(&counterparty_connection_id, &connection_id)
+}
+
+fn open_try(module: &mut IcaHostModule<Test>, order: Order) -> Result<Metadata, String> {
+	module
+		.on_chan_open_try(
+			order,
+			&[connection_ids().0],
+			&host_port(),
+			&ChannelId::new(0),
+			&Counterparty::new(controller_port(), Some(ChannelId::new(3))),
+			&metadata().to_version(),
+		)
+		.map(|(_, version)| Metadata::from_version(&version).unwrap())
+		.map_err(|e| e.to_string())
+}
+
+/// Open the channel of the controller, returning its funded interchain account.
+fn open_channel() -> AccountId {
+	let mut module = IcaHostModule::<Test>(PhantomData);
+	store_connection();
+	let version = open_try(&mut module, Order::Ordered).unwrap().to_version();
+	store_channel(&host_port(), &ChannelId::new(0), &controller_port(), State::Open, version);
+	module.on_chan_open_confirm(&host_port(), &ChannelId::new(0)).unwrap();
+
+	let account = Pallet::<Test>::derive_account(&connection_ids().0, &controller_port());
+	Balances::make_free_balance_be(&account, 10 * DOLLARS);
+	account
+}
+
+fn transfer(to: &AccountId, value: u128) -> RuntimeCall {
+	RuntimeCall::Balances(pallet_balances::Call::transfer { dest: to.clone().into(), value })
+}
+
+/// Deliver a packet executing `calls`, returning its acknowledgement.
+fn execute(calls: &[RuntimeCall]) -> GenericAcknowledgement {
+	let messages = calls
+		.iter()
+		.map(|call| Any { type_url: RUNTIME_CALL_TYPE_URL.to_string(), value: call.encode() })
+		.collect();
+	let packet = Packet {
+		sequence: 1u64.into(),
+		port_on_a: controller_port(),
+		chan_on_a: ChannelId::new(3),
+		port_on_b: host_port(),
+		chan_on_b: ChannelId::new(0),
+		data: InterchainAccountPacketData::execute_tx(messages, String::new()).to_bytes(),
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	};
+	let ack = IcaHostModule::<Test>(PhantomData).on_recv_packet(
+		&mut ModuleOutputBuilder::new(),
+		&packet,
+		&Signer::from_str("relayer").unwrap(),
+	);
+	GenericAcknowledgement::from_bytes(ack.as_ref()).unwrap()
+}
+
+#[test]
+fn test_channel_handshake_registers_the_account() {
+	new_test_ext().execute_with(|| {
+		let mut module = IcaHostModule::<Test>(PhantomData);
+		store_connection();
+
+		assert!(open_try(&mut module, Order::Unordered).is_err());
+
+		let metadata = open_try(&mut module, Order::Ordered).unwrap();
+		let account = Pallet::<Test>::derive_account(&connection_ids().0, &controller_port());
+		assert_eq!(metadata.address, format!("0x{}", hex::encode(account.encode())));
+		assert_eq!(
+			<InterchainAccounts<Test>>::get(connection_ids().0, controller_port()),
+			Some(account)
+		);
+
+		store_channel(
+			&host_port(),
+			&ChannelId::new(0),
+			&controller_port(),
+			State::Open,
+			metadata.to_version(),
+		);
+		module.on_chan_open_confirm(&host_port(), &ChannelId::new(0)).unwrap();
+		assert_eq!(
+			<ActiveChannels<Test>>::get(connection_ids().0, controller_port()),
+			Some(ChannelId::new(0))
+		);
+
+		// a second channel is refused while the first one is open
+		assert!(open_try(&mut module, Order::Ordered).is_err());
+	})
+}
+
+#[test]
+fn test_allowlisted_calls_are_executed() {
+	new_test_ext().execute_with(|| {
+		let account = open_channel();
+		let bob = AccountId::from([2; 32]);
+
+		let ack = execute(&[transfer(&bob, 2 * DOLLARS), transfer(&bob, 3 * DOLLARS)]);
+
+		assert!(ack.is_success());
+		assert_eq!(Balances::free_balance(&bob), 5 * DOLLARS);
+		assert_eq!(Balances::free_balance(&account), 5 * DOLLARS);
+		assert!(System::events().iter().any(|record| record.event ==
+			RuntimeEvent::IcaHost(Event::PacketExecuted {
+				account: account.clone(),
+				sequence: 1u64.into(),
+				calls: 2,
+			})));
+	})
+}
+
+#[test]
+fn test_calls_outside_the_allowlist_are_rejected() {
+	new_test_ext().execute_with(|| {
+		let account = open_channel();
+		let bob = AccountId::from([2; 32]);
+		let remark = RuntimeCall::System(frame_system::Call::remark { remark: vec![1] });
+
+		let ack = execute(&[transfer(&bob, 2 * DOLLARS), remark]);
+
+		assert!(!ack.is_success());
+		assert_eq!(Balances::free_balance(&bob), 0);
+		assert_eq!(Balances::free_balance(&account), 10 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_calls_over_the_weight_limit_are_rejected() {
+	new_test_ext().execute_with(|| {
+		let account = open_channel();
+		let bob = AccountId::from([2; 32]);
+		let call = transfer(&bob, 2 * DOLLARS);
+		MaxWeightPerPacket::set(call.get_dispatch_info().weight);
+
+		assert!(execute(&[call.clone()]).is_success());
+		let ack = execute(&[call.clone(), call]);
+
+		assert_eq!(ack, GenericAcknowledgement::error(crate::IcaError::WeightLimitExceeded));
+		assert_eq!(Balances::free_balance(&bob), 2 * DOLLARS);
+		assert_eq!(Balances::free_balance(&account), 8 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_failed_call_reverts_the_packet() {
+	new_test_ext().execute_with(|| {
+		let account = open_channel();
+		let bob = AccountId::from([2; 32]);
+
+		// the second transfer exceeds the balance left
+		let ack = execute(&[transfer(&bob, 6 * DOLLARS), transfer(&bob, 6 * DOLLARS)]);
+
+		assert!(!ack.is_success());
+		assert_eq!(Balances::free_balance(&bob), 0);
+		assert_eq!(Balances::free_balance(&account), 10 * DOLLARS);
+		assert!(System::events().iter().any(|record| matches!(
+			record.event,
+			RuntimeEvent::IcaHost(Event::PacketExecutionFailed { .. })
+		)));
+	})
+}
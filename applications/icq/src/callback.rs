@@ -0,0 +1,230 @@
+use crate::{
+	packet::{InterchainQueryPacketAck, InterchainQueryPacketData},
+	*,
+};
+use codec::{Decode, Encode};
+use ibc::{
+	core::{
+		ics04_channel::{
+			channel::{Counterparty, Order},
+			error::{ChannelError, PacketError},
+			handler::ModuleExtras,
+			msgs::acknowledgement::Acknowledgement,
+			packet::Packet,
+			Version,
+		},
+		ics24_host::identifier::ConnectionId,
+		ics26_routing::context::{Module, ModuleId, ModuleOutputBuilder},
+	},
+	signer::Signer,
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use sp_std::marker::PhantomData;
+
+/// The ibc module id of the interchain query module, to be used by the runtime's `AddModule`
+/// implementation.
+pub fn module_id() -> ModuleId {
+	ModuleId::from_str(MODULE_ID).expect("never failed")
+}
+
+/// Resolves the `icqcontroller` and `icqhost` ports to the interchain query module.
+pub fn lookup_module_by_port(port_id: &PortId) -> Option<ModuleId> {
+	matches!(port_id.as_str(), CONTROLLER_PORT_ID | HOST_PORT_ID).then(module_id)
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct IcqModule<T>(pub PhantomData<T>);
+
+impl<T: Config> IcqModule<T> {
+	fn validate_channel(
+		order: Order,
+		port_id: &PortId,
+		counterparty_port_id: &PortId,
+		version: &Version,
+	) -> Result<(), IcqError> {
+		if order != Order::Unordered {
+			return Err(IcqError::InvalidChannelOrdering)
+		}
+		let expected_counterparty = match port_id.as_str() {
+			CONTROLLER_PORT_ID => HOST_PORT_ID,
+			HOST_PORT_ID => CONTROLLER_PORT_ID,
+			_ => return Err(IcqError::InvalidPort { port_id: port_id.to_string() }),
+		};
+		if counterparty_port_id.as_str() != expected_counterparty {
+			return Err(IcqError::InvalidPort { port_id: counterparty_port_id.to_string() })
+		}
+		Self::validate_version(version)
+	}
+
+	fn validate_version(version: &Version) -> Result<(), IcqError> {
+		if version.to_string() != VERSION {
+			return Err(IcqError::InvalidVersion { version: version.to_string() })
+		}
+		Ok(())
+	}
+
+	fn answer(packet: &Packet) -> Result<(Vec<u8>, u32), IcqError> {
+		if packet.port_on_b.as_str() != HOST_PORT_ID {
+			return Err(IcqError::UnsupportedOperation)
+		}
+		let requests = InterchainQueryPacketData::from_bytes(&packet.data)?.requests()?;
+		let responses = Pallet::<T>::answer_queries(requests)?;
+		let count = responses.len() as u32;
+
+		Ok((InterchainQueryPacketAck::new(responses).to_bytes(), count))
+	}
+
+	fn responses(ack: &GenericAcknowledgement) -> Result<Vec<QueryResponse>, IcqError> {
+		let result = ack.result().ok_or(IcqError::InvalidPacketData {
+			reason: "invalid acknowledgement result".to_string(),
+		})?;
+		let responses = InterchainQueryPacketAck::from_bytes(&result)?.responses()?;
+		Ok(responses.into_iter().map(Into::into).collect())
+	}
+}
+
+impl<T: Config> Module for IcqModule<T> {
+	fn on_chan_open_init(
+		&mut self,
+		order: Order,
+		_connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		_channel_id: &ChannelId,
+		counterparty: &Counterparty,
+		version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		let version =
+			if version.is_empty() { Version::new(VERSION.to_string()) } else { version.clone() };
+		Self::validate_channel(order, port_id, counterparty.port_id(), &version)
+			.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+
+		Ok((ModuleExtras::empty(), version))
+	}
+
+	fn on_chan_open_try(
+		&mut self,
+		order: Order,
+		_connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		_channel_id: &ChannelId,
+		counterparty: &Counterparty,
+		counterparty_version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		Self::validate_channel(order, port_id, counterparty.port_id(), counterparty_version)
+			.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+
+		Ok((ModuleExtras::empty(), counterparty_version.clone()))
+	}
+
+	fn on_chan_open_ack(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+		counterparty_version: &Version,
+	) -> Result<ModuleExtras, ChannelError> {
+		Self::validate_version(counterparty_version)
+			.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_chan_open_confirm(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_chan_close_init(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Err(ChannelError::AppModule { description: IcqError::UnsupportedOperation.to_string() })
+	}
+
+	fn on_chan_close_confirm(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_recv_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		_relayer: &Signer,
+	) -> Acknowledgement {
+		match Self::answer(packet) {
+			Ok((ack, queries)) => {
+				Pallet::<T>::deposit_event(Event::<T>::QueriesAnswered {
+					channel_id: packet.chan_on_b.clone(),
+					sequence: packet.sequence,
+					queries,
+				});
+				GenericAcknowledgement::success(ack).into()
+			},
+			Err(e) => {
+				log::error!(target: LOG_TARGET, "❌ [icq host] answer queries: {}", e);
+				GenericAcknowledgement::error(e).into()
+			},
+		}
+	}
+
+	fn on_acknowledgement_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		acknowledgement: &Acknowledgement,
+		_relayer: &Signer,
+	) -> Result<(), PacketError> {
+		let map_err = |e: IcqError| PacketError::AppModule { description: e.to_string() };
+		let ack = GenericAcknowledgement::from_bytes(acknowledgement.as_ref())
+			.ok_or(PacketError::AppModule { description: "invalid acknowledgement".to_string() })?;
+
+		match &ack {
+			GenericAcknowledgement::Result(_) => {
+				let responses = Self::responses(&ack).map_err(map_err)?;
+				let (query_id, origin) = Pallet::<T>::settle_query(
+					&packet.chan_on_a,
+					packet.sequence,
+					QueryStatus::Answered,
+				)
+				.map_err(map_err)?;
+				T::QueryHandler::on_response(query_id, &origin, responses.clone());
+				Pallet::<T>::deposit_event(Event::<T>::QueryAnswered { query_id, responses });
+			},
+			GenericAcknowledgement::Error(error) => {
+				let (query_id, origin) = Pallet::<T>::settle_query(
+					&packet.chan_on_a,
+					packet.sequence,
+					QueryStatus::Failed,
+				)
+				.map_err(map_err)?;
+				let error = error.as_bytes().to_vec();
+				T::QueryHandler::on_error(query_id, &origin, error.clone());
+				Pallet::<T>::deposit_event(Event::<T>::QueryFailed { query_id, error });
+			},
+		}
+
+		Ok(())
+	}
+
+	fn on_timeout_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		_relayer: &Signer,
+	) -> Result<(), PacketError> {
+		let (query_id, origin) =
+			Pallet::<T>::settle_query(&packet.chan_on_a, packet.sequence, QueryStatus::TimedOut)
+				.map_err(|e| PacketError::AppModule { description: e.to_string() })?;
+		T::QueryHandler::on_timeout(query_id, &origin);
+		Pallet::<T>::deposit_event(Event::<T>::QueryTimedOut { query_id });
+
+		Ok(())
+	}
+}
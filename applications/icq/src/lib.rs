@@ -0,0 +1,419 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! # Overview
+//!
+//! Implementation of [ICS-31 cross-chain queries](https://github.com/cosmos/ibc/tree/main/spec/app/ics-031-crosschain-queries)
+//! on top of pallet-ibc, compatible with the `async-icq` module of ibc-go chains.
+//!
+//! - Controller: accounts send queries with [`Pallet::send_query`] and pallets of the runtime
+//!   through [`InterchainQuerier`], over channels bound to the `icqcontroller` port. Each query
+//!   gets a [`QueryId`] whose [`QueryStatus`] is kept in storage, and answers, failures and
+//!   timeouts are handed to [`Config::QueryHandler`].
+//! - Host: counterparties query the `icqhost` port of this chain for raw storage values under
+//!   [`STORAGE_QUERY_PATH`], restricted to the keys allowed by [`Config::StorageQueryFilter`].
+//!
+//! The runtime routes both ports to this module through its `AddModule` implementation, using
+//! [`callback::module_id`] and [`callback::lookup_module_by_port`].
+extern crate alloc;
+
+pub use pallet::*;
+
+pub mod callback;
+pub mod packet;
+
+#[cfg(test)]
+mod tests;
+
+#[cfg(test)]
+mod mock;
+
+use crate::packet::{InterchainQueryPacketData, RequestQuery, ResponseQuery};
+use alloc::{
+	format,
+	string::{String, ToString},
+};
+use frame_support::{pallet_prelude::*, traits::Contains};
+use frame_system::pallet_prelude::*;
+use ibc::{
+	core::{
+		ics04_channel::{events::SendPacket, packet::Sequence, timeout::TimeoutHeight},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	timestamp::Timestamp,
+};
+use pallet_ibc_utils::{
+	packet::{build_packet, send_packet},
+	traits::{ChannelKeeperInterface, ChannelReaderInterface},
+};
+use sp_runtime::traits::UniqueSaturatedInto;
+use sp_std::{fmt, fmt::Debug, str::FromStr, vec::Vec};
+
+pub const LOG_TARGET: &str = "runtime::pallet-ics31-icq";
+
+/// ICS-31 application version
+pub const VERSION: &str = "icq-1";
+/// Port sending queries
+pub const CONTROLLER_PORT_ID: &str = "icqcontroller";
+/// Port answering queries
+pub const HOST_PORT_ID: &str = "icqhost";
+/// Module id of the interchain query module in the ibc router
+pub const MODULE_ID: &str = "icq";
+/// Query path answered by the host: `data` is a raw storage key, the response its value
+pub const STORAGE_QUERY_PATH: &str = "/substrate/storage";
+
+/// Identifier of a query sent by this chain
+pub type QueryId = u64;
+
+/// Errors raised while handling ICS-31 handshakes and packets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IcqError {
+	/// channel ordering must be UNORDERED
+	InvalidChannelOrdering,
+	/// invalid port
+	InvalidPort { port_id: String },
+	/// invalid version
+	InvalidVersion { version: String },
+	/// the operation is not supported on this port
+	UnsupportedOperation,
+	/// packet or acknowledgement data could not be decoded
+	InvalidPacketData { reason: String },
+	/// the query is not allowed on this host
+	UnauthorizedQuery { path: String },
+	/// no query was sent with this packet
+	UnknownQuery,
+}
+
+impl fmt::Display for IcqError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidChannelOrdering => write!(f, "channel ordering must be UNORDERED"),
+			Self::InvalidPort { port_id } => write!(f, "invalid ics31 port: {}", port_id),
+			Self::InvalidVersion { version } => write!(f, "invalid ics31 version: {}", version),
+			Self::UnsupportedOperation => write!(f, "operation not supported"),
+			Self::InvalidPacketData { reason } => write!(f, "invalid packet data: {}", reason),
+			Self::UnauthorizedQuery { path } => write!(f, "query not allowed on host: {}", path),
+			Self::UnknownQuery => write!(f, "unknown query"),
+		}
+	}
+}
+
+/// A query to send to a counterparty chain
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct QueryRequest {
+	/// Query path on the host, e.g. `/store/bank/key`
+	pub path: Vec<u8>,
+	/// Query data, e.g. the store key
+	pub data: Vec<u8>,
+	/// Height to query at, 0 for the latest height
+	pub height: u64,
+}
+
+impl From<QueryRequest> for RequestQuery {
+	fn from(request: QueryRequest) -> Self {
+		Self {
+			data: request.data,
+			path: String::from_utf8_lossy(&request.path).to_string(),
+			height: request.height as i64,
+			prove: false,
+		}
+	}
+}
+
+/// The answer of a counterparty chain to a query
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct QueryResponse {
+	/// 0 on success, the host's error code otherwise
+	pub code: u32,
+	pub value: Vec<u8>,
+	/// Height the query was answered at
+	pub height: u64,
+}
+
+impl From<ResponseQuery> for QueryResponse {
+	fn from(response: ResponseQuery) -> Self {
+		Self { code: response.code, value: response.value, height: response.height as u64 }
+	}
+}
+
+/// Progress of a query
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum QueryStatus {
+	/// The packet was sent and is waiting for an acknowledgement
+	Pending,
+	/// The host answered, the responses were handed to the handler
+	Answered,
+	/// The host rejected the queries
+	Failed,
+	/// The packet timed out
+	TimedOut,
+}
+
+/// A query sent by this chain
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct QueryInfo<AccountId> {
+	/// Account or pallet account that sent the query
+	pub origin: AccountId,
+	pub channel_id: ChannelId,
+	pub sequence: Sequence,
+	pub status: QueryStatus,
+}
+
+/// Receives the outcome of the queries sent by this chain.
+pub trait OnQueryResponse<AccountId> {
+	/// The host answered the queries, `responses` are in the order of the requests.
+	fn on_response(query_id: QueryId, origin: &AccountId, responses: Vec<QueryResponse>);
+	/// The host rejected the queries.
+	fn on_error(query_id: QueryId, origin: &AccountId, error: Vec<u8>);
+	/// The queries did not reach the host in time.
+	fn on_timeout(query_id: QueryId, origin: &AccountId);
+}
+
+impl<AccountId> OnQueryResponse<AccountId> for () {
+	fn on_response(_query_id: QueryId, _origin: &AccountId, _responses: Vec<QueryResponse>) {}
+	fn on_error(_query_id: QueryId, _origin: &AccountId, _error: Vec<u8>) {}
+	fn on_timeout(_query_id: QueryId, _origin: &AccountId) {}
+}
+
+/// Lets other pallets send interchain queries.
+pub trait InterchainQuerier<AccountId> {
+	/// Send `requests` over `channel_id` of the `icqcontroller` port on behalf of `origin`,
+	/// timing out `relative_timeout` nanoseconds after the current block time.
+	fn send_query(
+		origin: AccountId,
+		channel_id: ChannelId,
+		requests: Vec<QueryRequest>,
+		relative_timeout: u64,
+	) -> Result<QueryId, DispatchError>;
+}
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+
+	#[pallet::pallet]
+	#[pallet::without_storage_info]
+	pub struct Pallet<T>(_);
+
+	/// Configure the pallet by specifying the parameters and types on which it depends.
+	#[pallet::config]
+	pub trait Config:
+		frame_system::Config + pallet_timestamp::Config + Sync + Send + Debug
+	{
+		/// The aggregated event type of the runtime.
+		type RuntimeEvent: Parameter
+			+ Member
+			+ From<Event<Self>>
+			+ Debug
+			+ IsType<<Self as frame_system::Config>::RuntimeEvent>;
+
+		/// Receives the answers, failures and timeouts of the queries sent by this chain
+		type QueryHandler: OnQueryResponse<Self::AccountId>;
+
+		/// Storage keys counterparties may query on this chain
+		type StorageQueryFilter: Contains<Vec<u8>>;
+
+		/// Maximum number of queries in a single packet, sent or answered
+		#[pallet::constant]
+		type MaxQueriesPerPacket: Get<u32>;
+
+		type IbcContext: ChannelKeeperInterface + ChannelReaderInterface;
+	}
+
+	#[pallet::storage]
+	/// value: id of the next query
+	pub type NextQueryId<T: Config> = StorageValue<_, QueryId, ValueQuery>;
+
+	#[pallet::storage]
+	/// key: query id
+	/// value: query info
+	pub type Queries<T: Config> = StorageMap<_, Twox64Concat, QueryId, QueryInfo<T::AccountId>>;
+
+	#[pallet::storage]
+	/// key1: controller channel id
+	/// key2: packet sequence
+	/// value: query id
+	pub type QueryIdBySequence<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, ChannelId, Twox64Concat, Sequence, QueryId>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// Send packet event
+		SendPacket(SendPacket),
+		/// Queries were sent to a counterparty
+		QuerySent { query_id: QueryId, origin: T::AccountId, channel_id: ChannelId },
+		/// The counterparty answered the queries
+		QueryAnswered { query_id: QueryId, responses: Vec<QueryResponse> },
+		/// The counterparty rejected the queries
+		QueryFailed { query_id: QueryId, error: Vec<u8> },
+		/// The queries timed out
+		QueryTimedOut { query_id: QueryId },
+		/// Queries of a counterparty were answered
+		QueriesAnswered { channel_id: ChannelId, sequence: Sequence, queries: u32 },
+	}
+
+	#[pallet::error]
+	pub enum Error<T> {
+		/// No query to send, or too many
+		InvalidQueryCount,
+		/// The timeout overflows
+		InvalidTimeout,
+		/// The channel cannot be used
+		InvalidChannel,
+		/// Sending the packet failed
+		SendPacketFailed,
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T>
+	where
+		u64: From<<T as pallet_timestamp::Config>::Moment>
+			+ From<<T as frame_system::Config>::BlockNumber>,
+	{
+		/// Send `requests` to the host at the other end of `channel_id`.
+		///
+		/// Parameters:
+		/// - `relative_timeout`: nanoseconds after the current block time the packet times out.
+		///
+		/// The answer is reported through `QueryHandler` and the `QueryAnswered` event.
+		#[pallet::call_index(0)]
+		#[pallet::weight(0)]
+		pub fn send_query(
+			origin: OriginFor<T>,
+			channel_id: ChannelId,
+			requests: Vec<QueryRequest>,
+			relative_timeout: u64,
+		) -> DispatchResultWithPostInfo {
+			let origin = ensure_signed(origin)?;
+
+			<Self as InterchainQuerier<T::AccountId>>::send_query(
+				origin,
+				channel_id,
+				requests,
+				relative_timeout,
+			)?;
+
+			Ok(().into())
+		}
+	}
+}
+
+impl<T: Config> InterchainQuerier<T::AccountId> for Pallet<T>
+where
+	u64: From<<T as pallet_timestamp::Config>::Moment>,
+{
+	fn send_query(
+		origin: T::AccountId,
+		channel_id: ChannelId,
+		requests: Vec<QueryRequest>,
+		relative_timeout: u64,
+	) -> Result<QueryId, DispatchError> {
+		ensure!(
+			!requests.is_empty() && requests.len() <= T::MaxQueriesPerPacket::get() as usize,
+			Error::<T>::InvalidQueryCount
+		);
+
+		let port_id = PortId::from_str(CONTROLLER_PORT_ID).expect("never failed");
+		let timeout_timestamp = (Self::host_timestamp() +
+			core::time::Duration::from_nanos(relative_timeout))
+		.map_err(|_| Error::<T>::InvalidTimeout)?;
+		let data = InterchainQueryPacketData::new(
+			requests.into_iter().map(Into::into).collect(),
+			String::new(),
+		)
+		.to_bytes();
+
+		let packet = build_packet::<T::IbcContext>(
+			&port_id,
+			&channel_id,
+			data,
+			TimeoutHeight::no_timeout(),
+			timeout_timestamp,
+		)
+		.map_err(|e| {
+			log::error!(target: LOG_TARGET, "❌ [send_query] build packet: {:?}", e);
+			Error::<T>::InvalidChannel
+		})?;
+		let sequence = packet.sequence;
+		let event = send_packet::<T::IbcContext>(packet).map_err(|e| {
+			log::error!(target: LOG_TARGET, "❌ [send_query] send packet: {:?}", e);
+			Error::<T>::SendPacketFailed
+		})?;
+
+		let query_id = <NextQueryId<T>>::mutate(|id| {
+			let query_id = *id;
+			*id = id.wrapping_add(1);
+			query_id
+		});
+		<Queries<T>>::insert(
+			query_id,
+			QueryInfo {
+				origin: origin.clone(),
+				channel_id: channel_id.clone(),
+				sequence,
+				status: QueryStatus::Pending,
+			},
+		);
+		<QueryIdBySequence<T>>::insert(&channel_id, sequence, query_id);
+
+		Self::deposit_event(Event::SendPacket(event));
+		Self::deposit_event(Event::QuerySent { query_id, origin, channel_id });
+
+		Ok(query_id)
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// Answer the queries of a counterparty, all of them or none.
+	pub fn answer_queries(requests: Vec<RequestQuery>) -> Result<Vec<ResponseQuery>, IcqError> {
+		if requests.is_empty() || requests.len() > T::MaxQueriesPerPacket::get() as usize {
+			return Err(IcqError::InvalidPacketData {
+				reason: format!("unsupported number of queries {}", requests.len()),
+			})
+		}
+
+		let height: u64 = <frame_system::Pallet<T>>::block_number().unique_saturated_into();
+		requests
+			.into_iter()
+			.map(|request| {
+				if request.path != STORAGE_QUERY_PATH ||
+					!T::StorageQueryFilter::contains(&request.data)
+				{
+					return Err(IcqError::UnauthorizedQuery { path: request.path })
+				}
+				Ok(ResponseQuery {
+					key: request.data.clone(),
+					value: sp_io::storage::get(&request.data)
+						.map(|v| v.to_vec())
+						.unwrap_or_default(),
+					height: height as i64,
+					..Default::default()
+				})
+			})
+			.collect()
+	}
+
+	/// Record the outcome of the query sent with `sequence` on `channel_id`.
+	pub(crate) fn settle_query(
+		channel_id: &ChannelId,
+		sequence: Sequence,
+		status: QueryStatus,
+	) -> Result<(QueryId, T::AccountId), IcqError> {
+		let query_id =
+			<QueryIdBySequence<T>>::take(channel_id, sequence).ok_or(IcqError::UnknownQuery)?;
+		let origin = <Queries<T>>::try_mutate(query_id, |query| {
+			let query = query.as_mut().ok_or(IcqError::UnknownQuery)?;
+			query.status = status;
+			Ok::<_, IcqError>(query.origin.clone())
+		})?;
+		Ok((query_id, origin))
+	}
+
+	fn host_timestamp() -> Timestamp
+	where
+		u64: From<<T as pallet_timestamp::Config>::Moment>,
+	{
+		let millis = u64::from(<pallet_timestamp::Pallet<T>>::get());
+		Timestamp::from_nanoseconds(millis.saturating_mul(1_000_000)).unwrap_or_default()
+	}
+}
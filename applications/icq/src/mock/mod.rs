@@ -0,0 +1,107 @@
+use crate as pallet_ics31_icq;
+use frame_support::{
+	construct_runtime, parameter_types,
+	traits::{ConstU16, ConstU32, ConstU64, Contains},
+};
+use sp_runtime::{
+	generic,
+	traits::{AccountIdLookup, BlakeTwo256},
+	AccountId32,
+};
+
+pub type AccountId = AccountId32;
+pub type BlockNumber = u64;
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+// Configure a mock runtime to test the pallet.
+construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system,
+		Timestamp: pallet_timestamp,
+		Ibc: pallet_ibc,
+		Icq: pallet_ics31_icq,
+	}
+);
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type AccountId = AccountId;
+	type RuntimeCall = RuntimeCall;
+	type Lookup = AccountIdLookup<AccountId, ()>;
+	type Index = u32;
+	type BlockNumber = BlockNumber;
+	type Hash = sp_core::H256;
+	type Hashing = BlakeTwo256;
+	type Header = generic::Header<BlockNumber, BlakeTwo256>;
+	type RuntimeEvent = RuntimeEvent;
+	type RuntimeOrigin = RuntimeOrigin;
+	type BlockHashCount = ();
+	type DbWeight = ();
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type AccountData = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ConstU16<42>;
+	type OnSetCode = ();
+	type MaxConsumers = ConstU32<16>;
+}
+
+impl pallet_timestamp::Config for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = ConstU64<3_000>;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const ExpectedBlockTime: u64 = 6;
+	pub const ChainVersion: u64 = 0;
+}
+
+impl pallet_ibc::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type TimeProvider = pallet_timestamp::Pallet<Test>;
+	type ExpectedBlockTime = ExpectedBlockTime;
+	const IBC_COMMITMENT_PREFIX: &'static [u8] = b"Ibc";
+	type ChainVersion = ChainVersion;
+	type IbcModule = pallet_ibc_utils::module::DefaultRouter;
+	type MaxClientStateSize = ConstU32<{ 64 * 1024 }>;
+	type MaxChannelsPerConnection = ConstU32<64>;
+	type MaxEventSize = ConstU32<{ 8 * 1024 }>;
+	type WeightInfo = ();
+}
+
+/// Prefix of the storage keys counterparties may query
+pub const QUERYABLE_PREFIX: &[u8] = b":queryable:";
+
+pub struct QueryablePrefix;
+impl Contains<Vec<u8>> for QueryablePrefix {
+	fn contains(key: &Vec<u8>) -> bool {
+		key.starts_with(QUERYABLE_PREFIX)
+	}
+}
+
+impl pallet_ics31_icq::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type QueryHandler = ();
+	type StorageQueryFilter = QueryablePrefix;
+	type MaxQueriesPerPacket = ConstU32<2>;
+	type IbcContext = pallet_ibc::context::Context<Test>;
+}
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut ext: sp_io::TestExternalities =
+		frame_system::GenesisConfig::default().build_storage::<Test>().unwrap().into();
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
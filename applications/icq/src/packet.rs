@@ -0,0 +1,134 @@
+use crate::IcqError;
+use alloc::string::{String, ToString};
+use base64::Engine;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use sp_std::vec::Vec;
+
+/// A single query, the ABCI `RequestQuery` of tendermint.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct RequestQuery {
+	#[prost(bytes = "vec", tag = "1")]
+	pub data: Vec<u8>,
+	#[prost(string, tag = "2")]
+	pub path: String,
+	#[prost(int64, tag = "3")]
+	pub height: i64,
+	#[prost(bool, tag = "4")]
+	pub prove: bool,
+}
+
+/// The answer to a single query, the fields of the ABCI `ResponseQuery` used by ICS-31.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct ResponseQuery {
+	#[prost(uint32, tag = "1")]
+	pub code: u32,
+	#[prost(string, tag = "3")]
+	pub log: String,
+	#[prost(bytes = "vec", tag = "6")]
+	pub key: Vec<u8>,
+	#[prost(bytes = "vec", tag = "7")]
+	pub value: Vec<u8>,
+	#[prost(int64, tag = "9")]
+	pub height: i64,
+}
+
+/// The queries of a packet
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct CosmosQuery {
+	#[prost(message, repeated, tag = "1")]
+	pub requests: Vec<RequestQuery>,
+}
+
+/// The answers of a packet, in the order of the queries
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct CosmosResponse {
+	#[prost(message, repeated, tag = "1")]
+	pub responses: Vec<ResponseQuery>,
+}
+
+fn invalid_packet_data(e: impl ToString) -> IcqError {
+	IcqError::InvalidPacketData { reason: e.to_string() }
+}
+
+/// `InterchainQueryPacketData` in the JSON form exchanged with ibc-go.
+///
+/// `data` is the base64 of a protobuf-encoded [`CosmosQuery`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterchainQueryPacketData {
+	pub data: String,
+	#[serde(default)]
+	pub memo: String,
+}
+
+impl InterchainQueryPacketData {
+	pub fn new(requests: Vec<RequestQuery>, memo: String) -> Self {
+		let query = CosmosQuery { requests };
+		Self { data: base64::engine::general_purpose::STANDARD.encode(query.encode_to_vec()), memo }
+	}
+
+	/// Decode the queries carried by the packet.
+	pub fn requests(&self) -> Result<Vec<RequestQuery>, IcqError> {
+		let data = base64::engine::general_purpose::STANDARD
+			.decode(&self.data)
+			.map_err(invalid_packet_data)?;
+		Ok(CosmosQuery::decode(data.as_slice()).map_err(invalid_packet_data)?.requests)
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, IcqError> {
+		serde_json::from_slice(bytes).map_err(invalid_packet_data)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		serde_json::to_vec(self).expect("InterchainQueryPacketData's Serialize impl never fails")
+	}
+}
+
+/// `InterchainQueryPacketAck` in its JSON form, the result of a successful acknowledgement.
+///
+/// `data` is the base64 of a protobuf-encoded [`CosmosResponse`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterchainQueryPacketAck {
+	pub data: String,
+}
+
+impl InterchainQueryPacketAck {
+	pub fn new(responses: Vec<ResponseQuery>) -> Self {
+		let response = CosmosResponse { responses };
+		Self { data: base64::engine::general_purpose::STANDARD.encode(response.encode_to_vec()) }
+	}
+
+	/// Decode the answers carried by the acknowledgement.
+	pub fn responses(&self) -> Result<Vec<ResponseQuery>, IcqError> {
+		let data = base64::engine::general_purpose::STANDARD
+			.decode(&self.data)
+			.map_err(invalid_packet_data)?;
+		Ok(CosmosResponse::decode(data.as_slice()).map_err(invalid_packet_data)?.responses)
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, IcqError> {
+		serde_json::from_slice(bytes).map_err(invalid_packet_data)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		serde_json::to_vec(self).expect("InterchainQueryPacketAck's Serialize impl never fails")
+	}
+}
+
+#[test]
+fn test_query_packet_round_trip() {
+	let request = RequestQuery {
+		data: alloc::vec![1, 2, 3],
+		path: "/store/bank/key".to_string(),
+		height: 0,
+		prove: false,
+	};
+	let packet_data = InterchainQueryPacketData::new(alloc::vec![request.clone()], String::new());
+	let decoded = InterchainQueryPacketData::from_bytes(&packet_data.to_bytes()).unwrap();
+	assert_eq!(decoded.requests().unwrap(), alloc::vec![request]);
+
+	let response = ResponseQuery { value: alloc::vec![4, 5], height: 10, ..Default::default() };
+	let ack = InterchainQueryPacketAck::new(alloc::vec![response.clone()]);
+	let decoded = InterchainQueryPacketAck::from_bytes(&ack.to_bytes()).unwrap();
+	assert_eq!(decoded.responses().unwrap(), alloc::vec![response]);
+}
@@ -0,0 +1,212 @@
+//! Answering the queries of counterparties and handling the answers to ours.
+use crate::{
+	callback::IcqModule,
+	mock::{new_test_ext, AccountId, RuntimeEvent, System, Test, QUERYABLE_PREFIX},
+	packet::{InterchainQueryPacketAck, InterchainQueryPacketData, RequestQuery, ResponseQuery},
+	Event, Queries, QueryIdBySequence, QueryInfo, QueryResponse, QueryStatus, CONTROLLER_PORT_ID,
+	HOST_PORT_ID, STORAGE_QUERY_PATH,
+};
+use core::marker::PhantomData;
+use ibc::{
+	core::{
+		ics04_channel::{
+			packet::{Packet, Sequence},
+			timeout::TimeoutHeight,
+		},
+		ics24_host::identifier::{ChannelId, PortId},
+		ics26_routing::context::{Module, ModuleOutputBuilder},
+	},
+	signer::Signer,
+	timestamp::Timestamp,
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use std::str::FromStr;
+
+fn relayer() -> Signer {
+	Signer::from_str("relayer").unwrap()
+}
+
+/// A packet from the `icqcontroller` port of the counterparty to the `icqhost` port of this
+/// chain, or the reverse for queries of this chain.
+fn packet(to_host: bool, data: Vec<u8>) -> Packet {
+	let (port_on_a, port_on_b) = if to_host {
+		(CONTROLLER_PORT_ID, HOST_PORT_ID)
+	} else {
+		(HOST_PORT_ID, CONTROLLER_PORT_ID)
+	};
+	Packet {
+		sequence: 1u64.into(),
+		port_on_a: PortId::from_str(port_on_a).unwrap(),
+		chan_on_a: ChannelId::new(0),
+		port_on_b: PortId::from_str(port_on_b).unwrap(),
+		chan_on_b: ChannelId::new(1),
+		data,
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	}
+}
+
+fn queryable(key: &[u8]) -> Vec<u8> {
+	[QUERYABLE_PREFIX, key].concat()
+}
+
+fn storage_query(key: &[u8]) -> RequestQuery {
+	RequestQuery {
+		data: key.to_vec(),
+		path: STORAGE_QUERY_PATH.to_string(),
+		height: 0,
+		prove: false,
+	}
+}
+
+/// Deliver the queries `requests` of a counterparty, returning the acknowledgement.
+fn receive(requests: Vec<RequestQuery>) -> GenericAcknowledgement {
+	let data = InterchainQueryPacketData::new(requests, String::new()).to_bytes();
+	let ack = IcqModule::<Test>(PhantomData).on_recv_packet(
+		&mut ModuleOutputBuilder::new(),
+		&packet(true, data),
+		&relayer(),
+	);
+	GenericAcknowledgement::from_bytes(ack.as_ref()).unwrap()
+}
+
+/// Record a query of this chain sent with sequence 1 over channel 0.
+fn store_query(query_id: u64) -> AccountId {
+	let origin = AccountId::from([1; 32]);
+	<Queries<Test>>::insert(
+		query_id,
+		QueryInfo {
+			origin: origin.clone(),
+			channel_id: ChannelId::new(0),
+			sequence: 1u64.into(),
+			status: QueryStatus::Pending,
+		},
+	);
+	<QueryIdBySequence<Test>>::insert(ChannelId::new(0), Sequence::from(1), query_id);
+	origin
+}
+
+#[test]
+fn test_queries_are_answered_from_storage() {
+	new_test_ext().execute_with(|| {
+		let key = queryable(b"key");
+		sp_io::storage::set(&key, b"value");
+
+		let ack = receive(vec![storage_query(&key), storage_query(&queryable(b"missing"))]);
+
+		let result = InterchainQueryPacketAck::from_bytes(&ack.result().unwrap()).unwrap();
+		let responses = result.responses().unwrap();
+		assert_eq!(responses.len(), 2);
+		assert_eq!(responses[0].key, key);
+		assert_eq!(responses[0].value, b"value".to_vec());
+		assert_eq!(responses[0].height, 1);
+		assert!(responses[1].value.is_empty());
+		assert!(System::events().iter().any(|record| record.event ==
+			RuntimeEvent::Icq(Event::QueriesAnswered {
+				channel_id: ChannelId::new(1),
+				sequence: 1u64.into(),
+				queries: 2,
+			})));
+	})
+}
+
+#[test]
+fn test_disallowed_queries_are_rejected() {
+	new_test_ext().execute_with(|| {
+		let key = queryable(b"key");
+		sp_io::storage::set(&key, b"value");
+		sp_io::storage::set(b":secret", b"value");
+
+		// a key outside the filter fails the whole packet
+		assert!(!receive(vec![storage_query(&key), storage_query(b":secret")]).is_success());
+		// as do other paths
+		let mut query = storage_query(&key);
+		query.path = "/store/bank/key".to_string();
+		assert!(!receive(vec![query]).is_success());
+		// and too many queries
+		assert!(!receive(vec![storage_query(&key); 3]).is_success());
+		assert!(!receive(vec![]).is_success());
+
+		// queries are only answered on the host port
+		let data = InterchainQueryPacketData::new(vec![storage_query(&key)], String::new());
+		let ack = IcqModule::<Test>(PhantomData).on_recv_packet(
+			&mut ModuleOutputBuilder::new(),
+			&packet(false, data.to_bytes()),
+			&relayer(),
+		);
+		assert!(!GenericAcknowledgement::from_bytes(ack.as_ref()).unwrap().is_success());
+		assert!(System::events().is_empty());
+	})
+}
+
+#[test]
+fn test_answers_settle_the_query() {
+	new_test_ext().execute_with(|| {
+		let mut module = IcqModule::<Test>(PhantomData);
+		store_query(3);
+		let response = ResponseQuery { value: vec![4, 5], height: 10, ..Default::default() };
+		let ack = GenericAcknowledgement::success(
+			InterchainQueryPacketAck::new(vec![response]).to_bytes(),
+		);
+
+		module
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&packet(false, vec![]),
+				&ack.into(),
+				&relayer(),
+			)
+			.unwrap();
+
+		assert_eq!(<Queries<Test>>::get(3).unwrap().status, QueryStatus::Answered);
+		assert!(!<QueryIdBySequence<Test>>::contains_key(ChannelId::new(0), Sequence::from(1)));
+		assert!(System::events().iter().any(|record| record.event ==
+			RuntimeEvent::Icq(Event::QueryAnswered {
+				query_id: 3,
+				responses: vec![QueryResponse { code: 0, value: vec![4, 5], height: 10 }],
+			})));
+
+		// the query was settled, a second acknowledgement is refused
+		let ack = GenericAcknowledgement::error("late");
+		assert!(module
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&packet(false, vec![]),
+				&ack.into(),
+				&relayer()
+			)
+			.is_err());
+	})
+}
+
+#[test]
+fn test_errors_and_timeouts_settle_the_query() {
+	new_test_ext().execute_with(|| {
+		let mut module = IcqModule::<Test>(PhantomData);
+		store_query(3);
+		let ack = GenericAcknowledgement::error("not allowed");
+		module
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&packet(false, vec![]),
+				&ack.into(),
+				&relayer(),
+			)
+			.unwrap();
+		assert_eq!(<Queries<Test>>::get(3).unwrap().status, QueryStatus::Failed);
+		assert!(System::events().iter().any(|record| record.event ==
+			RuntimeEvent::Icq(Event::QueryFailed {
+				query_id: 3,
+				error: b"not allowed".to_vec()
+			})));
+
+		store_query(4);
+		module
+			.on_timeout_packet(&mut ModuleOutputBuilder::new(), &packet(false, vec![]), &relayer())
+			.unwrap();
+		assert_eq!(<Queries<Test>>::get(4).unwrap().status, QueryStatus::TimedOut);
+		assert!(System::events()
+			.iter()
+			.any(|record| record.event == RuntimeEvent::Icq(Event::QueryTimedOut { query_id: 4 })));
+	})
+}
@@ -0,0 +1,244 @@
+use crate::{impls::NftKeeper, packet::NonFungibleTokenPacketData, *};
+use codec::{Decode, Encode};
+use frame_support::storage::{with_transaction, TransactionOutcome};
+use ibc::core::{
+	ics04_channel::{
+		channel::{Counterparty, Order},
+		error::{ChannelError, PacketError},
+		handler::ModuleExtras,
+		msgs::acknowledgement::Acknowledgement,
+		packet::Packet,
+		Version,
+	},
+	ics24_host::identifier::ConnectionId,
+	ics26_routing::context::{Module, ModuleId, ModuleOutputBuilder},
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use sp_std::marker::PhantomData;
+
+/// The ibc module id of the nft transfer module, to be used by the runtime's `AddModule`
+/// implementation.
+pub fn module_id() -> ModuleId {
+	ModuleId::from_str(MODULE_ID_STR).expect("never failed")
+}
+
+/// Resolves the `nft-transfer` port to the nft transfer module.
+pub fn lookup_module_by_port(port_id: &PortId) -> Option<ModuleId> {
+	(port_id.as_str() == PORT_ID_STR).then(module_id)
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct IbcNftTransferModule<T>(pub PhantomData<T>);
+
+impl<T: Config> IbcNftTransferModule<T> {
+	fn validate_channel(order: Order, port_id: &PortId) -> Result<(), NftTransferError> {
+		if order != Order::Unordered {
+			return Err(NftTransferError::InvalidChannelOrdering)
+		}
+		if port_id.as_str() != PORT_ID_STR {
+			return Err(NftTransferError::InvalidPort { port_id: port_id.to_string() })
+		}
+		Ok(())
+	}
+
+	fn validate_version(version: &Version) -> Result<(), NftTransferError> {
+		if version.to_string() != VERSION {
+			return Err(NftTransferError::InvalidVersion { version: version.to_string() })
+		}
+		Ok(())
+	}
+
+	fn account(address: &str) -> Result<T::AccountId, NftTransferError> {
+		let invalid = || NftTransferError::InvalidAccount { account: address.to_string() };
+		let signer = Signer::from_str(address).map_err(|_| invalid())?;
+		T::AccountIdConversion::try_from(signer)
+			.map(|a| a.into_account())
+			.map_err(|_| invalid())
+	}
+
+	/// Receive the tokens of a packet: unescrow the items of classes returning to this chain,
+	/// mint vouchers otherwise.
+	fn process_recv_packet(&mut self, packet: &Packet) -> Result<(), NftTransferError> {
+		let data = NonFungibleTokenPacketData::from_bytes(&packet.data)?;
+		let receiver = Self::account(&data.receiver)?;
+		let mut class = PrefixedClassId::from_full_path(&data.class_id);
+
+		if class.has_prefix(&packet.port_on_a, &packet.chan_on_a) {
+			class.remove_prefix(&packet.port_on_a, &packet.chan_on_a);
+			let escrow = Pallet::<T>::escrow_account(&packet.port_on_b, &packet.chan_on_b);
+			for token_id in data.token_ids.iter() {
+				let (collection, item) = self.resolve_token(&class, token_id)?;
+				self.transfer_nft(&escrow, &receiver, &collection, &item)?;
+			}
+		} else {
+			class.add_prefix(&packet.port_on_b, &packet.chan_on_b);
+			let class_metadata = NftMetadata {
+				uri: data.class_uri.as_bytes().to_vec(),
+				data: data.class_data.as_bytes().to_vec(),
+			};
+			let collection = self.voucher_collection(&class, class_metadata)?;
+			for (index, token_id) in data.token_ids.iter().enumerate() {
+				let item = self.voucher_item(&collection, token_id);
+				let metadata = NftMetadata {
+					uri: data.token_uri(index).as_bytes().to_vec(),
+					data: data.token_data(index).as_bytes().to_vec(),
+				};
+				self.mint_nft(&receiver, &collection, &item, metadata)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Give the tokens of a failed or timed out packet back to the sender: unescrow native items,
+	/// mint the burnt vouchers again.
+	fn refund_packet(&mut self, packet: &Packet) -> Result<(), NftTransferError> {
+		let data = NonFungibleTokenPacketData::from_bytes(&packet.data)?;
+		let sender = Self::account(&data.sender)?;
+		let class = PrefixedClassId::from_full_path(&data.class_id);
+		let is_source = !class.has_prefix(&packet.port_on_a, &packet.chan_on_a);
+		let escrow = Pallet::<T>::escrow_account(&packet.port_on_a, &packet.chan_on_a);
+
+		with_transaction(|| {
+			for token_id in data.token_ids.iter() {
+				let result = self.resolve_token(&class, token_id).and_then(|(collection, item)| {
+					if is_source {
+						self.transfer_nft(&escrow, &sender, &collection, &item)
+					} else {
+						let metadata = self.token_metadata(&collection, &item);
+						self.mint_nft(&sender, &collection, &item, metadata)
+					}
+				});
+				if let Err(e) = result {
+					return TransactionOutcome::Rollback(Err(e))
+				}
+			}
+			TransactionOutcome::Commit(Ok(()))
+		})?;
+
+		Pallet::<T>::deposit_event(Event::<T>::Refunded {
+			sender,
+			class_id: data.class_id.into_bytes(),
+			token_ids: data.token_ids.into_iter().map(String::into_bytes).collect(),
+		});
+		Ok(())
+	}
+}
+
+impl<T: Config> Module for IbcNftTransferModule<T> {
+	fn on_chan_open_init(
+		&mut self,
+		order: Order,
+		_connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		_channel_id: &ChannelId,
+		_counterparty: &Counterparty,
+		version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		Self::validate_channel(order, port_id)
+			.and_then(|_| if version.is_empty() { Ok(()) } else { Self::validate_version(version) })
+			.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+
+		Ok((ModuleExtras::empty(), Version::new(VERSION.to_string())))
+	}
+
+	fn on_chan_open_try(
+		&mut self,
+		order: Order,
+		_connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		_channel_id: &ChannelId,
+		_counterparty: &Counterparty,
+		counterparty_version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		Self::validate_channel(order, port_id)
+			.and_then(|_| Self::validate_version(counterparty_version))
+			.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+
+		Ok((ModuleExtras::empty(), Version::new(VERSION.to_string())))
+	}
+
+	fn on_chan_open_ack(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+		counterparty_version: &Version,
+	) -> Result<ModuleExtras, ChannelError> {
+		Self::validate_version(counterparty_version)
+			.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_chan_open_confirm(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_chan_close_init(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		// escrowed items could never be returned once the channel is closed
+		Err(ChannelError::AppModule { description: "channel cannot be closed".to_string() })
+	}
+
+	fn on_chan_close_confirm(
+		&mut self,
+		_port_id: &PortId,
+		_channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Ok(ModuleExtras::empty())
+	}
+
+	fn on_recv_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		_relayer: &Signer,
+	) -> Acknowledgement {
+		let result = with_transaction(|| match self.process_recv_packet(packet) {
+			Ok(()) => TransactionOutcome::Commit(Ok(())),
+			Err(e) => TransactionOutcome::Rollback(Err(e)),
+		});
+
+		match result {
+			Ok(()) => GenericAcknowledgement::success(alloc::vec![1u8]).into(),
+			Err(e) => {
+				log::error!(target: LOG_TARGET, "❌ [on_recv_packet] {}", e);
+				GenericAcknowledgement::error(e).into()
+			},
+		}
+	}
+
+	fn on_acknowledgement_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		acknowledgement: &Acknowledgement,
+		_relayer: &Signer,
+	) -> Result<(), PacketError> {
+		let ack = GenericAcknowledgement::from_bytes(acknowledgement.as_ref())
+			.ok_or(PacketError::AppModule { description: "invalid acknowledgement".to_string() })?;
+		if ack.is_success() {
+			return Ok(())
+		}
+
+		self.refund_packet(packet)
+			.map_err(|e| PacketError::AppModule { description: e.to_string() })
+	}
+
+	fn on_timeout_packet(
+		&mut self,
+		_output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		_relayer: &Signer,
+	) -> Result<(), PacketError> {
+		self.refund_packet(packet)
+			.map_err(|e| PacketError::AppModule { description: e.to_string() })
+	}
+}
@@ -0,0 +1,128 @@
+use alloc::{
+	format,
+	string::{String, ToString},
+	vec::Vec,
+};
+use codec::{Decode, Encode};
+use ibc::core::ics24_host::identifier::{ChannelId, PortId};
+use sha2::{Digest, Sha256};
+use sp_std::str::FromStr;
+
+/// A non-fungible token class id along with the trace of the channels it was sent through.
+#[derive(Clone, Debug, PartialEq, Eq, Decode, Encode, scale_info::TypeInfo)]
+pub struct PrefixedClassId {
+	/// A series of `{port-id}/{channel-id}`s for tracing the source of the class.
+	pub trace_path: Vec<u8>,
+	/// Class id on the chain the class originates from.
+	pub base_class_id: Vec<u8>,
+}
+
+impl PrefixedClassId {
+	/// A class that originates from this chain.
+	pub fn native(base_class_id: &str) -> Self {
+		Self { trace_path: Vec::new(), base_class_id: base_class_id.as_bytes().to_vec() }
+	}
+
+	/// Parse a full class path such as `nft-transfer/channel-0/kitties`.
+	///
+	/// Leading `{port-id}/{channel-id}` pairs make up the trace path, everything after the first
+	/// segment pair that is not a valid port and channel is the base class id.
+	pub fn from_full_path(path: &str) -> Self {
+		let segments: Vec<&str> = path.split('/').collect();
+		let mut trace_len = 0;
+		// keep at least one segment for the base class id
+		while trace_len + 2 < segments.len() &&
+			PortId::from_str(segments[trace_len]).is_ok() &&
+			ChannelId::from_str(segments[trace_len + 1]).is_ok()
+		{
+			trace_len += 2;
+		}
+
+		Self {
+			trace_path: segments[..trace_len].join("/").into_bytes(),
+			base_class_id: segments[trace_len..].join("/").into_bytes(),
+		}
+	}
+
+	/// Whether the class originates from this chain.
+	pub fn is_native(&self) -> bool {
+		self.trace_path.is_empty()
+	}
+
+	/// Whether the first hop of the trace is `{port_id}/{channel_id}`.
+	pub fn has_prefix(&self, port_id: &PortId, channel_id: &ChannelId) -> bool {
+		let prefix = format!("{}/{}", port_id, channel_id);
+		let trace_path = String::from_utf8_lossy(&self.trace_path);
+		trace_path == prefix || trace_path.starts_with(&format!("{}/", prefix))
+	}
+
+	/// Remove the first hop `{port_id}/{channel_id}` of the trace, if present.
+	pub fn remove_prefix(&mut self, port_id: &PortId, channel_id: &ChannelId) {
+		if !self.has_prefix(port_id, channel_id) {
+			return
+		}
+		let prefix_len = format!("{}/{}", port_id, channel_id).len();
+		let rest = self.trace_path.split_off(prefix_len);
+		self.trace_path = rest.strip_prefix(b"/").map(<[u8]>::to_vec).unwrap_or(rest);
+	}
+
+	/// Prepend the hop `{port_id}/{channel_id}` to the trace.
+	pub fn add_prefix(&mut self, port_id: &PortId, channel_id: &ChannelId) {
+		let mut trace_path = format!("{}/{}", port_id, channel_id).into_bytes();
+		if !self.trace_path.is_empty() {
+			trace_path.push(b'/');
+			trace_path.extend_from_slice(&self.trace_path);
+		}
+		self.trace_path = trace_path;
+	}
+
+	/// The full path `{trace_path}/{base_class_id}`, as carried in packet data.
+	pub fn full_path(&self) -> String {
+		let base_class_id = String::from_utf8_lossy(&self.base_class_id);
+		if self.trace_path.is_empty() {
+			base_class_id.to_string()
+		} else {
+			format!("{}/{}", String::from_utf8_lossy(&self.trace_path), base_class_id)
+		}
+	}
+
+	/// The local class id: the base class id for native classes, `ibc/{hash}` for vouchers.
+	pub fn ibc_class_id(&self) -> String {
+		if self.is_native() {
+			self.full_path()
+		} else {
+			derive_ibc_class_id_with_path(&self.full_path())
+		}
+	}
+}
+
+/// Derive the voucher class id of a class path, as `ibc/{hex(sha256(path))}`, mirroring
+/// ICS-20 denomination tracing.
+pub fn derive_ibc_class_id_with_path(class_path: &str) -> String {
+	let mut hasher = Sha256::new();
+	hasher.update(class_path.as_bytes());
+
+	format!("ibc/{}", hex::encode_upper(hasher.finalize()))
+}
+
+#[test]
+fn test_prefixed_class_id() {
+	let port_id = PortId::from_str("nft-transfer").unwrap();
+	let channel_0 = ChannelId::new(0);
+	let channel_1 = ChannelId::new(1);
+
+	let mut class = PrefixedClassId::from_full_path("nft-transfer/channel-1/kitties/gen0");
+	assert_eq!(class.trace_path, b"nft-transfer/channel-1".to_vec());
+	assert_eq!(class.base_class_id, b"kitties/gen0".to_vec());
+	assert!(class.has_prefix(&port_id, &channel_1));
+	assert!(!class.has_prefix(&port_id, &channel_0));
+
+	class.add_prefix(&port_id, &channel_0);
+	assert_eq!(class.full_path(), "nft-transfer/channel-0/nft-transfer/channel-1/kitties/gen0");
+	assert!(class.ibc_class_id().starts_with("ibc/"));
+
+	class.remove_prefix(&port_id, &channel_0);
+	class.remove_prefix(&port_id, &channel_1);
+	assert!(class.is_native());
+	assert_eq!(class.ibc_class_id(), "kitties/gen0");
+}
@@ -0,0 +1,224 @@
+use crate::{callback::IbcNftTransferModule, *};
+use sp_runtime::traits::{One, UniqueSaturatedInto};
+
+/// Moves, mints and burns the items backing ICS-721 transfers, the counterpart of ICS-20's
+/// `BankKeeper`.
+pub trait NftKeeper {
+	type AccountId;
+	type CollectionId;
+	type ItemId;
+
+	/// Transfer `item` of `collection` from `from` to `to`, used to escrow and unescrow native
+	/// items.
+	fn transfer_nft(
+		&mut self,
+		from: &Self::AccountId,
+		to: &Self::AccountId,
+		collection: &Self::CollectionId,
+		item: &Self::ItemId,
+	) -> Result<(), NftTransferError>;
+
+	/// Mint voucher `item` of `collection` to `to`, recording its uri and data.
+	fn mint_nft(
+		&mut self,
+		to: &Self::AccountId,
+		collection: &Self::CollectionId,
+		item: &Self::ItemId,
+		metadata: NftMetadata,
+	) -> Result<(), NftTransferError>;
+
+	/// Burn voucher `item` of `collection` owned by `owner`.
+	///
+	/// The token id mapping and metadata are kept so the same item is minted again if the token
+	/// comes back.
+	fn burn_nft(
+		&mut self,
+		owner: &Self::AccountId,
+		collection: &Self::CollectionId,
+		item: &Self::ItemId,
+	) -> Result<(), NftTransferError>;
+}
+
+impl<T: Config> NftKeeper for IbcNftTransferModule<T> {
+	type AccountId = T::AccountId;
+	type CollectionId = T::CollectionId;
+	type ItemId = T::ItemId;
+
+	fn transfer_nft(
+		&mut self,
+		from: &Self::AccountId,
+		to: &Self::AccountId,
+		collection: &Self::CollectionId,
+		item: &Self::ItemId,
+	) -> Result<(), NftTransferError> {
+		if <T::Nonfungibles as Inspect<T::AccountId>>::owner(collection, item).as_ref() !=
+			Some(from)
+		{
+			return Err(NftTransferError::NotOwner { token_id: Self::item_to_string(item) })
+		}
+		<T::Nonfungibles as Transfer<T::AccountId>>::transfer(collection, item, to)?;
+
+		Pallet::<T>::deposit_event(Event::<T>::Transferred {
+			collection: *collection,
+			item: *item,
+			from: from.clone(),
+			to: to.clone(),
+		});
+		Ok(())
+	}
+
+	fn mint_nft(
+		&mut self,
+		to: &Self::AccountId,
+		collection: &Self::CollectionId,
+		item: &Self::ItemId,
+		metadata: NftMetadata,
+	) -> Result<(), NftTransferError> {
+		<T::Nonfungibles as Mutate<T::AccountId>>::mint_into(collection, item, to)?;
+		<TokenMetadata<T>>::insert(collection, item, metadata);
+
+		Pallet::<T>::deposit_event(Event::<T>::Minted {
+			collection: *collection,
+			item: *item,
+			owner: to.clone(),
+		});
+		Ok(())
+	}
+
+	fn burn_nft(
+		&mut self,
+		owner: &Self::AccountId,
+		collection: &Self::CollectionId,
+		item: &Self::ItemId,
+	) -> Result<(), NftTransferError> {
+		<T::Nonfungibles as Mutate<T::AccountId>>::burn(collection, item, Some(owner))?;
+
+		Pallet::<T>::deposit_event(Event::<T>::Burned {
+			collection: *collection,
+			item: *item,
+			owner: owner.clone(),
+		});
+		Ok(())
+	}
+}
+
+impl<T: Config> IbcNftTransferModule<T> {
+	fn collection_to_string(collection: &T::CollectionId) -> String {
+		UniqueSaturatedInto::<u128>::unique_saturated_into(*collection).to_string()
+	}
+
+	fn item_to_string(item: &T::ItemId) -> String {
+		UniqueSaturatedInto::<u128>::unique_saturated_into(*item).to_string()
+	}
+
+	/// The class of a local collection: its trace for vouchers, its decimal id otherwise.
+	pub fn class_of(&self, collection: &T::CollectionId) -> PrefixedClassId {
+		<CollectionClass<T>>::get(collection)
+			.and_then(<ClassTrace<T>>::get)
+			.unwrap_or_else(|| PrefixedClassId::native(&Self::collection_to_string(collection)))
+	}
+
+	/// The ics721 token id of a local item, `None` if the item does not exist.
+	pub fn token_id_of(&self, collection: &T::CollectionId, item: &T::ItemId) -> Option<String> {
+		if <CollectionClass<T>>::contains_key(collection) {
+			return <VoucherTokenIds<T>>::get(collection, item)
+				.map(|token_id| String::from_utf8_lossy(&token_id).to_string())
+		}
+		<T::Nonfungibles as Inspect<T::AccountId>>::owner(collection, item)
+			.map(|_| Self::item_to_string(item))
+	}
+
+	/// Uri and data of a class, falling back to the `uri` and `data` collection attributes for
+	/// native collections.
+	pub fn class_metadata(&self, collection: &T::CollectionId) -> NftMetadata {
+		<ClassMetadata<T>>::get(collection).unwrap_or_else(|| NftMetadata {
+			uri: <T::Nonfungibles as Inspect<T::AccountId>>::collection_attribute(
+				collection, b"uri",
+			)
+			.unwrap_or_default(),
+			data: <T::Nonfungibles as Inspect<T::AccountId>>::collection_attribute(
+				collection, b"data",
+			)
+			.unwrap_or_default(),
+		})
+	}
+
+	/// Uri and data of a token, falling back to the `uri` and `data` item attributes for native
+	/// collections.
+	pub fn token_metadata(&self, collection: &T::CollectionId, item: &T::ItemId) -> NftMetadata {
+		<TokenMetadata<T>>::get(collection, item).unwrap_or_else(|| NftMetadata {
+			uri: <T::Nonfungibles as Inspect<T::AccountId>>::attribute(collection, item, b"uri")
+				.unwrap_or_default(),
+			data: <T::Nonfungibles as Inspect<T::AccountId>>::attribute(collection, item, b"data")
+				.unwrap_or_default(),
+		})
+	}
+
+	/// Resolve a token of a class, as traced from this chain, to the local collection and item.
+	pub fn resolve_token(
+		&self,
+		class: &PrefixedClassId,
+		token_id: &str,
+	) -> Result<(T::CollectionId, T::ItemId), NftTransferError> {
+		let unknown_token = || NftTransferError::UnknownToken { token_id: token_id.to_string() };
+
+		if class.is_native() {
+			let class_id = class.full_path();
+			let collection = u128::from_str(&class_id)
+				.ok()
+				.and_then(|id| T::CollectionId::try_from(id).ok())
+				.ok_or(NftTransferError::UnknownClass { class_id })?;
+			let item = u128::from_str(token_id)
+				.ok()
+				.and_then(|id| T::ItemId::try_from(id).ok())
+				.ok_or_else(unknown_token)?;
+			return Ok((collection, item))
+		}
+
+		let class_id = class.ibc_class_id();
+		let collection = <VoucherCollections<T>>::get(class_id.as_bytes())
+			.ok_or(NftTransferError::UnknownClass { class_id })?;
+		let item =
+			<VoucherItems<T>>::get(collection, token_id.as_bytes()).ok_or_else(unknown_token)?;
+		Ok((collection, item))
+	}
+
+	/// The voucher collection of `class`, created on first use.
+	pub fn voucher_collection(
+		&mut self,
+		class: &PrefixedClassId,
+		metadata: NftMetadata,
+	) -> Result<T::CollectionId, NftTransferError> {
+		let class_id = class.ibc_class_id().into_bytes();
+		if let Some(collection) = <VoucherCollections<T>>::get(&class_id) {
+			return Ok(collection)
+		}
+
+		let collection =
+			<NextVoucherCollectionId<T>>::get().unwrap_or_else(T::VoucherCollectionIdStart::get);
+		let owner = Pallet::<T>::account_id();
+		<T::Nonfungibles as Create<T::AccountId>>::create_collection(&collection, &owner, &owner)?;
+
+		<NextVoucherCollectionId<T>>::put(collection.saturating_add(One::one()));
+		<VoucherCollections<T>>::insert(&class_id, collection);
+		<CollectionClass<T>>::insert(collection, &class_id);
+		<ClassTrace<T>>::insert(&class_id, class);
+		<ClassMetadata<T>>::insert(collection, metadata);
+
+		Pallet::<T>::deposit_event(Event::<T>::VoucherClassCreated { class_id, collection });
+		Ok(collection)
+	}
+
+	/// The voucher item of `token_id` in a voucher collection, allocated on first use.
+	pub fn voucher_item(&mut self, collection: &T::CollectionId, token_id: &str) -> T::ItemId {
+		if let Some(item) = <VoucherItems<T>>::get(collection, token_id.as_bytes()) {
+			return item
+		}
+
+		let item = <NextVoucherItemId<T>>::get(collection);
+		<NextVoucherItemId<T>>::insert(collection, item.saturating_add(One::one()));
+		<VoucherItems<T>>::insert(collection, token_id.as_bytes(), item);
+		<VoucherTokenIds<T>>::insert(collection, item, token_id.as_bytes());
+		item
+	}
+}
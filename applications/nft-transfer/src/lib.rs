@@ -0,0 +1,444 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! # Overview
+//!
+//! Implementation of [ICS-721 non-fungible token transfer](https://github.com/cosmos/ibc/tree/main/spec/app/ics-721-nft-transfer)
+//! on top of pallet-ibc, backed by any `nonfungibles` implementation such as `pallet-nfts`.
+//!
+//! Classes native to this chain are identified by their collection id. When sent out, their items
+//! are escrowed in a per-channel account. Classes received from a counterparty are represented by
+//! voucher collections created by this pallet, identified as `ibc/{hash}` of their class path;
+//! their items are burnt when sent back towards the source.
+extern crate alloc;
+
+pub use pallet::*;
+
+pub mod callback;
+pub mod class;
+pub mod impls;
+pub mod packet;
+
+use crate::{callback::IbcNftTransferModule, class::PrefixedClassId, impls::NftKeeper};
+use alloc::{
+	format,
+	string::{String, ToString},
+};
+use frame_support::{
+	pallet_prelude::*,
+	traits::tokens::nonfungibles::{Create, Inspect, Mutate, Transfer},
+	PalletId,
+};
+use frame_system::pallet_prelude::*;
+use ibc::{
+	core::{
+		ics04_channel::{events::SendPacket, timeout::TimeoutHeight},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	signer::Signer,
+	timestamp::Timestamp,
+};
+use pallet_ibc_utils::packet::{build_packet, send_packet};
+use sp_runtime::traits::{AccountIdConversion, AtLeast32BitUnsigned, IdentifyAccount};
+use sp_std::{fmt, fmt::Debug, marker::PhantomData, str::FromStr, vec::Vec};
+
+pub const LOG_TARGET: &str = "runtime::pallet-ics721-nft-transfer";
+
+/// ICS-721 application version
+pub const VERSION: &str = "ics721-1";
+/// Port bound by the nft transfer module
+pub const PORT_ID_STR: &str = "nft-transfer";
+/// Module id of the nft transfer module in the ibc router
+pub const MODULE_ID_STR: &str = "nft-transfer";
+
+/// Errors raised while handling ICS-721 handshakes and packets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NftTransferError {
+	/// channel ordering must be UNORDERED
+	InvalidChannelOrdering,
+	/// invalid port
+	InvalidPort { port_id: String },
+	/// invalid version
+	InvalidVersion { version: String },
+	/// packet data could not be decoded or is malformed
+	InvalidPacketData { reason: &'static str },
+	/// sender or receiver could not be parsed into an account
+	InvalidAccount { account: String },
+	/// no local collection for the class
+	UnknownClass { class_id: String },
+	/// no local item for the token
+	UnknownToken { token_id: String },
+	/// the token is not owned by the expected account
+	NotOwner { token_id: String },
+	/// the nonfungibles implementation failed
+	Nonfungibles { reason: String },
+}
+
+impl fmt::Display for NftTransferError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidChannelOrdering => write!(f, "channel ordering must be UNORDERED"),
+			Self::InvalidPort { port_id } => write!(f, "invalid ics721 port: {}", port_id),
+			Self::InvalidVersion { version } => write!(f, "invalid ics721 version: {}", version),
+			Self::InvalidPacketData { reason } => write!(f, "invalid packet data: {}", reason),
+			Self::InvalidAccount { account } => write!(f, "invalid account: {}", account),
+			Self::UnknownClass { class_id } => write!(f, "unknown class: {}", class_id),
+			Self::UnknownToken { token_id } => write!(f, "unknown token: {}", token_id),
+			Self::NotOwner { token_id } => write!(f, "token {} not owned by sender", token_id),
+			Self::Nonfungibles { reason } => write!(f, "nonfungibles error: {}", reason),
+		}
+	}
+}
+
+impl From<DispatchError> for NftTransferError {
+	fn from(e: DispatchError) -> Self {
+		Self::Nonfungibles { reason: format!("{:?}", e) }
+	}
+}
+
+/// Uri and data of a class or a token, as carried in ICS-721 packets.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Encode, Decode, scale_info::TypeInfo)]
+pub struct NftMetadata {
+	pub uri: Vec<u8>,
+	pub data: Vec<u8>,
+}
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+
+	#[pallet::pallet]
+	#[pallet::without_storage_info]
+	pub struct Pallet<T>(_);
+
+	/// Configure the pallet by specifying the parameters and types on which it depends.
+	#[pallet::config]
+	pub trait Config:
+		frame_system::Config + pallet_timestamp::Config + Sync + Send + Debug
+	{
+		/// The aggregated event type of the runtime.
+		type RuntimeEvent: Parameter
+			+ Member
+			+ From<Event<Self>>
+			+ Debug
+			+ IsType<<Self as frame_system::Config>::RuntimeEvent>;
+
+		/// Identifier of a collection, its decimal representation is the ics721 class id of
+		/// native collections
+		type CollectionId: Parameter
+			+ Member
+			+ Copy
+			+ MaybeSerializeDeserialize
+			+ AtLeast32BitUnsigned;
+
+		/// Identifier of an item, its decimal representation is the ics721 token id of items of
+		/// native collections
+		type ItemId: Parameter + Member + Copy + MaybeSerializeDeserialize + AtLeast32BitUnsigned;
+
+		/// Expose customizable non-fungible token escrow, mint and burn
+		type Nonfungibles: Inspect<Self::AccountId, CollectionId = Self::CollectionId, ItemId = Self::ItemId>
+			+ Transfer<Self::AccountId>
+			+ Mutate<Self::AccountId>
+			+ Create<Self::AccountId>;
+
+		/// Account Id Conversion from SS58 string or hex string
+		type AccountIdConversion: TryFrom<Signer>
+			+ IdentifyAccount<AccountId = Self::AccountId>
+			+ Clone
+			+ PartialEq
+			+ Debug;
+
+		/// Owner of the voucher collections and root of the escrow accounts
+		#[pallet::constant]
+		type PalletId: Get<PalletId>;
+
+		/// First collection id used for voucher collections, ids below are left to native
+		/// collections
+		#[pallet::constant]
+		type VoucherCollectionIdStart: Get<Self::CollectionId>;
+
+		/// Maximum number of tokens sent in a single transfer
+		#[pallet::constant]
+		type MaxTokensPerTransfer: Get<u32>;
+
+		type IbcContext: pallet_ibc_utils::traits::ChannelKeeperInterface
+			+ pallet_ibc_utils::traits::ChannelReaderInterface;
+	}
+
+	#[pallet::storage]
+	/// key: voucher class id, `ibc/{hash}`
+	/// value: class trace
+	pub type ClassTrace<T: Config> = StorageMap<_, Blake2_128Concat, Vec<u8>, PrefixedClassId>;
+
+	#[pallet::storage]
+	/// key: voucher class id, `ibc/{hash}`
+	/// value: local collection of the vouchers
+	pub type VoucherCollections<T: Config> =
+		StorageMap<_, Blake2_128Concat, Vec<u8>, T::CollectionId>;
+
+	#[pallet::storage]
+	/// key: voucher collection
+	/// value: voucher class id, `ibc/{hash}`
+	pub type CollectionClass<T: Config> = StorageMap<_, Blake2_128Concat, T::CollectionId, Vec<u8>>;
+
+	#[pallet::storage]
+	/// value: next collection id to allocate to a voucher class
+	pub type NextVoucherCollectionId<T: Config> = StorageValue<_, T::CollectionId>;
+
+	#[pallet::storage]
+	/// key1: voucher collection
+	/// key2: token id on the counterparty
+	/// value: local item
+	pub type VoucherItems<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		T::CollectionId,
+		Blake2_128Concat,
+		Vec<u8>,
+		T::ItemId,
+	>;
+
+	#[pallet::storage]
+	/// key1: voucher collection
+	/// key2: local item
+	/// value: token id on the counterparty
+	pub type VoucherTokenIds<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		T::CollectionId,
+		Blake2_128Concat,
+		T::ItemId,
+		Vec<u8>,
+	>;
+
+	#[pallet::storage]
+	/// key: voucher collection
+	/// value: next item id to allocate in the collection
+	pub type NextVoucherItemId<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::CollectionId, T::ItemId, ValueQuery>;
+
+	#[pallet::storage]
+	/// key: collection
+	/// value: class uri and data
+	pub type ClassMetadata<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::CollectionId, NftMetadata>;
+
+	#[pallet::storage]
+	/// key1: collection
+	/// key2: item
+	/// value: token uri and data
+	pub type TokenMetadata<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		T::CollectionId,
+		Blake2_128Concat,
+		T::ItemId,
+		NftMetadata,
+	>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// Send packet event
+		SendPacket(SendPacket),
+		/// A voucher collection was created for a class received from a counterparty
+		VoucherClassCreated { class_id: Vec<u8>, collection: T::CollectionId },
+		/// An item was moved into or out of escrow
+		Transferred {
+			collection: T::CollectionId,
+			item: T::ItemId,
+			from: T::AccountId,
+			to: T::AccountId,
+		},
+		/// A voucher was minted
+		Minted { collection: T::CollectionId, item: T::ItemId, owner: T::AccountId },
+		/// A voucher was burnt
+		Burned { collection: T::CollectionId, item: T::ItemId, owner: T::AccountId },
+		/// A transfer failed on the counterparty or timed out, the tokens were refunded
+		Refunded { sender: T::AccountId, class_id: Vec<u8>, token_ids: Vec<Vec<u8>> },
+	}
+
+	#[pallet::error]
+	pub enum Error<T> {
+		/// No token to transfer, or too many
+		InvalidTokenCount,
+		/// The collection or one of the items is unknown
+		UnknownToken,
+		/// The sender does not own one of the items
+		NotOwner,
+		/// The receiver or memo is not valid utf8
+		InvalidUtf8,
+		/// The packet data built from the items is not valid
+		InvalidPacketData,
+		/// The timeout overflows
+		InvalidTimeout,
+		/// The channel cannot be used
+		InvalidChannel,
+		/// Escrowing or burning the items failed
+		EscrowFailed,
+		/// Sending the packet failed
+		SendPacketFailed,
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T>
+	where
+		u64: From<<T as pallet_timestamp::Config>::Moment>
+			+ From<<T as frame_system::Config>::BlockNumber>,
+	{
+		/// ICS721 non-fungible token transfer.
+		///
+		/// Escrows the items of a native collection, or burns the vouchers of a class that is
+		/// sent back towards its source, and sends them over `source_channel` of the
+		/// `nft-transfer` port.
+		///
+		/// Parameters:
+		/// - `relative_timeout`: nanoseconds after the current block time the packet times out.
+		#[pallet::call_index(0)]
+		#[pallet::weight(0)]
+		pub fn transfer(
+			origin: OriginFor<T>,
+			source_channel: ChannelId,
+			collection: T::CollectionId,
+			items: Vec<T::ItemId>,
+			receiver: Vec<u8>,
+			relative_timeout: u64,
+			memo: Vec<u8>,
+		) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			ensure!(
+				!items.is_empty() && items.len() <= T::MaxTokensPerTransfer::get() as usize,
+				Error::<T>::InvalidTokenCount
+			);
+			let receiver = String::from_utf8(receiver).map_err(|_| Error::<T>::InvalidUtf8)?;
+			let memo = String::from_utf8(memo).map_err(|_| Error::<T>::InvalidUtf8)?;
+
+			let port_id = PortId::from_str(PORT_ID_STR).expect("never failed");
+			let timeout_timestamp = (Self::host_timestamp() +
+				core::time::Duration::from_nanos(relative_timeout))
+			.map_err(|_| Error::<T>::InvalidTimeout)?;
+
+			let mut keeper = IbcNftTransferModule(PhantomData::<T>);
+			let class = keeper.class_of(&collection);
+			let is_source = !class.has_prefix(&port_id, &source_channel);
+			let escrow = Self::escrow_account(&port_id, &source_channel);
+
+			let mut token_ids = Vec::with_capacity(items.len());
+			let mut token_uris = Vec::with_capacity(items.len());
+			let mut token_data = Vec::with_capacity(items.len());
+			for item in items.iter() {
+				let token_id =
+					keeper.token_id_of(&collection, item).ok_or(Error::<T>::UnknownToken)?;
+				ensure!(
+					<T::Nonfungibles as Inspect<T::AccountId>>::owner(&collection, item).as_ref() ==
+						Some(&sender),
+					Error::<T>::NotOwner
+				);
+				let metadata = keeper.token_metadata(&collection, item);
+				token_uris.push(String::from_utf8_lossy(&metadata.uri).to_string());
+				token_data.push(String::from_utf8_lossy(&metadata.data).to_string());
+				token_ids.push(token_id);
+
+				let result = if is_source {
+					keeper.transfer_nft(&sender, &escrow, &collection, item)
+				} else {
+					keeper.burn_nft(&sender, &collection, item)
+				};
+				result.map_err(|e| {
+					log::error!(target: LOG_TARGET, "❌ [transfer] escrow or burn: {}", e);
+					Error::<T>::EscrowFailed
+				})?;
+			}
+
+			let class_metadata = keeper.class_metadata(&collection);
+			let data = packet::NonFungibleTokenPacketData {
+				class_id: class.full_path(),
+				class_uri: String::from_utf8_lossy(&class_metadata.uri).to_string(),
+				class_data: String::from_utf8_lossy(&class_metadata.data).to_string(),
+				token_uris: if token_uris.iter().all(String::is_empty) {
+					Vec::new()
+				} else {
+					token_uris
+				},
+				token_data: if token_data.iter().all(String::is_empty) {
+					Vec::new()
+				} else {
+					token_data
+				},
+				token_ids,
+				sender: format!("0x{}", hex::encode(sender.encode())),
+				receiver,
+				memo,
+			};
+			data.validate().map_err(|_| Error::<T>::InvalidPacketData)?;
+
+			let packet = build_packet::<T::IbcContext>(
+				&port_id,
+				&source_channel,
+				data.to_bytes(),
+				TimeoutHeight::no_timeout(),
+				timeout_timestamp,
+			)
+			.map_err(|e| {
+				log::error!(target: LOG_TARGET, "❌ [transfer] build packet: {:?}", e);
+				Error::<T>::InvalidChannel
+			})?;
+			let event = send_packet::<T::IbcContext>(packet).map_err(|e| {
+				log::error!(target: LOG_TARGET, "❌ [transfer] send packet: {:?}", e);
+				Error::<T>::SendPacketFailed
+			})?;
+
+			Self::deposit_event(Event::SendPacket(event));
+
+			Ok(().into())
+		}
+	}
+}
+
+/// The hash identifying the escrow of `(port_id, channel_id)`,
+/// `sha256("ics721-1\0{port_id}/{channel_id}")`.
+///
+/// Sub-accounts only keep the first 20 bytes of their seed, so the channel is hashed rather than
+/// encoded as is, which would leave every channel with the same account.
+pub fn escrow_account_hash(port_id: &PortId, channel_id: &ChannelId) -> [u8; 32] {
+	let mut data = VERSION.as_bytes().to_vec();
+	data.push(0);
+	data.extend_from_slice(format!("{}/{}", port_id, channel_id).as_bytes());
+	sp_io::hashing::sha2_256(&data)
+}
+
+impl<T: Config> Pallet<T> {
+	/// The account holding the native items sent out over `(port_id, channel_id)`: the
+	/// sub-account of the pallet id for [`escrow_account_hash`], distinct for every channel.
+	pub fn escrow_account(port_id: &PortId, channel_id: &ChannelId) -> T::AccountId {
+		T::PalletId::get().into_sub_account_truncating(escrow_account_hash(port_id, channel_id))
+	}
+
+	/// The account owning the voucher collections.
+	pub fn account_id() -> T::AccountId {
+		T::PalletId::get().into_account_truncating()
+	}
+
+	fn host_timestamp() -> Timestamp
+	where
+		u64: From<<T as pallet_timestamp::Config>::Moment>,
+	{
+		let millis = u64::from(<pallet_timestamp::Pallet<T>>::get());
+		Timestamp::from_nanoseconds(millis.saturating_mul(1_000_000)).unwrap_or_default()
+	}
+}
+
+#[test]
+fn test_escrow_accounts_of_channels_differ() {
+	use sp_runtime::AccountId32;
+
+	let pallet_id = PalletId(*b"ibc/nftt");
+	let port_id = PortId::from_str(PORT_ID_STR).unwrap();
+	let escrow = |channel_id: u64| -> AccountId32 {
+		pallet_id
+			.into_sub_account_truncating(escrow_account_hash(&port_id, &ChannelId::new(channel_id)))
+	};
+	assert_ne!(escrow(0), escrow(1));
+	assert_ne!(escrow(1), escrow(10));
+	let owner: AccountId32 = pallet_id.into_account_truncating();
+	assert_ne!(escrow(0), owner);
+}
@@ -0,0 +1,92 @@
+use crate::NftTransferError;
+use alloc::{string::String, vec::Vec};
+use serde::{Deserialize, Serialize};
+
+/// ICS-721 packet data, JSON encoded on the wire.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NonFungibleTokenPacketData {
+	/// Full class path, `{trace_path}/{base_class_id}`
+	pub class_id: String,
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub class_uri: String,
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub class_data: String,
+	pub token_ids: Vec<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub token_uris: Vec<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub token_data: Vec<String>,
+	pub sender: String,
+	pub receiver: String,
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub memo: String,
+}
+
+impl NonFungibleTokenPacketData {
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, NftTransferError> {
+		let data: Self = serde_json::from_slice(bytes)
+			.map_err(|_| NftTransferError::InvalidPacketData { reason: "invalid json" })?;
+		data.validate()?;
+		Ok(data)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		serde_json::to_vec(self).expect("never failed")
+	}
+
+	/// Stateless checks of ICS-721 `ValidateBasic`.
+	pub fn validate(&self) -> Result<(), NftTransferError> {
+		let invalid = |reason| Err(NftTransferError::InvalidPacketData { reason });
+		if self.class_id.trim().is_empty() {
+			return invalid("empty class id")
+		}
+		if self.token_ids.is_empty() || self.token_ids.iter().any(|id| id.trim().is_empty()) {
+			return invalid("empty token id")
+		}
+		if !self.token_uris.is_empty() && self.token_uris.len() != self.token_ids.len() {
+			return invalid("token uris do not match token ids")
+		}
+		if !self.token_data.is_empty() && self.token_data.len() != self.token_ids.len() {
+			return invalid("token data do not match token ids")
+		}
+		if self.sender.trim().is_empty() || self.receiver.trim().is_empty() {
+			return invalid("empty sender or receiver")
+		}
+		Ok(())
+	}
+
+	/// Uri of the `index`-th token, empty when not provided.
+	pub fn token_uri(&self, index: usize) -> &str {
+		self.token_uris.get(index).map(String::as_str).unwrap_or_default()
+	}
+
+	/// Data of the `index`-th token, empty when not provided.
+	pub fn token_data(&self, index: usize) -> &str {
+		self.token_data.get(index).map(String::as_str).unwrap_or_default()
+	}
+}
+
+#[test]
+fn test_packet_data_json() {
+	use alloc::string::ToString;
+
+	let data = NonFungibleTokenPacketData {
+		class_id: "nft-transfer/channel-0/kitties".to_string(),
+		token_ids: alloc::vec!["kitty-1".to_string()],
+		token_uris: alloc::vec!["ipfs://kitty-1".to_string()],
+		sender: "alice".to_string(),
+		receiver: "bob".to_string(),
+		..Default::default()
+	};
+	let json = data.to_bytes();
+	assert_eq!(
+		json,
+		br#"{"classId":"nft-transfer/channel-0/kitties","tokenIds":["kitty-1"],"tokenUris":["ipfs://kitty-1"],"sender":"alice","receiver":"bob"}"#.to_vec()
+	);
+	assert_eq!(NonFungibleTokenPacketData::from_bytes(&json), Ok(data));
+	assert!(NonFungibleTokenPacketData::from_bytes(
+		br#"{"classId":"c","tokenIds":[],"sender":"a","receiver":"b"}"#
+	)
+	.is_err());
+}
@@ -0,0 +1,35 @@
+//! Runtime API of the ICS20 transfer pallet.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+	/// Queries of the ICS20 transfer pallet
+	pub trait Ics20TransferApi<AccountId, AssetId, PendingTransfer>
+	where
+		AccountId: Codec,
+		AssetId: Codec,
+		PendingTransfer: Codec,
+	{
+		/// The outgoing transfers of `account` that are not settled yet, with the port, channel
+		/// and sequence of their packet.
+		fn pending_transfers(account: AccountId) -> Vec<PendingTransfer>;
+
+		/// The amounts held by the escrow accounts, by channel id and denomination.
+		fn escrow_totals() -> Vec<(Vec<u8>, Vec<u8>, u128)>;
+
+		/// The supplies of the vouchers minted on this chain, by `ibc/{hash}` denomination.
+		fn voucher_supplies() -> Vec<(Vec<u8>, u128)>;
+
+		/// The trace path and base denomination of the `ibc/{hash}` denomination `hash`.
+		fn denom_trace(hash: Vec<u8>) -> Option<(Vec<u8>, Vec<u8>)>;
+
+		/// The trace path and base denomination of the voucher registered as the asset `asset_id`.
+		fn denom_trace_by_asset(asset_id: AssetId) -> Option<(Vec<u8>, Vec<u8>)>;
+
+		/// At most `limit` denomination traces following the one of `start_after`, as
+		/// `ibc/{hash}` denomination, trace path and base denomination.
+		fn denom_traces(start_after: Option<Vec<u8>>, limit: u32) -> Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>;
+	}
+}
@@ -0,0 +1,304 @@
+//! Codecs of ICS20 addresses.
+//!
+//! The sender of an outgoing transfer and the receiver of an incoming one are strings, turned into
+//! accounts by [`Config::AccountIdConversion`]. [`CodecAccount`] makes that conversion pluggable
+//! with an [`AddressCodec`]: hex with [`HexCodec`], SS58 of a given network with [`Ss58Codec`],
+//! EVM accounts with [`H160Codec`], or several of them with a tuple, e.g.
+//!
+//! ```ignore
+//! type AccountIdConversion = CodecAccount<AccountId, (Ss58Codec<SS58Prefix>, H160Codec)>;
+//! ```
+//!
+//! Receivers of outgoing transfers are addresses on the counterparty chain, which
+//! [`Config::ReceiverValidator`] checks before the funds are escrowed or burnt, e.g. with
+//! [`Bech32Address`] for Cosmos chains.
+use crate::*;
+use alloc::vec;
+use codec::{Decode, Encode};
+use core::marker::PhantomData;
+use scale_info::TypeInfo;
+
+/// Decodes the ICS20 addresses of accounts of this chain.
+pub trait AddressCodec<AccountId> {
+	/// The account of `address`, `None` if it is not an address of the codec.
+	fn decode(address: &str) -> Option<AccountId>;
+}
+
+/// The first of two codecs decoding an address.
+impl<AccountId, A, B> AddressCodec<AccountId> for (A, B)
+where
+	A: AddressCodec<AccountId>,
+	B: AddressCodec<AccountId>,
+{
+	fn decode(address: &str) -> Option<AccountId> {
+		A::decode(address).or_else(|| B::decode(address))
+	}
+}
+
+/// `0x`-prefixed hex of 32-byte accounts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HexCodec;
+
+impl<AccountId: From<[u8; 32]>> AddressCodec<AccountId> for HexCodec {
+	fn decode(address: &str) -> Option<AccountId> {
+		decode_hex::<32>(address).map(Into::into)
+	}
+}
+
+/// SS58 addresses of 32-byte accounts with the network prefix `Prefix`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ss58Codec<Prefix>(PhantomData<Prefix>);
+
+impl<AccountId: From<[u8; 32]>, Prefix: Get<u16>> AddressCodec<AccountId> for Ss58Codec<Prefix> {
+	fn decode(address: &str) -> Option<AccountId> {
+		let (prefix, account) = decode_ss58(address)?;
+		(prefix == Prefix::get()).then(|| account.into())
+	}
+}
+
+/// `0x`-prefixed hex of 20-byte EVM accounts, mapped to the account
+/// `blake2_256("evm:" ++ address)` as Frontier's `HashedAddressMapping` does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct H160Codec;
+
+impl<AccountId: From<[u8; 32]>> AddressCodec<AccountId> for H160Codec {
+	fn decode(address: &str) -> Option<AccountId> {
+		let address = decode_hex::<20>(address)?;
+		let mut data = b"evm:".to_vec();
+		data.extend_from_slice(&address);
+		Some(sp_io::hashing::blake2_256(&data).into())
+	}
+}
+
+/// An account decoded from an ICS20 address by `Codec`.
+///
+/// Module accounts, e.g. escrow accounts, are addressed in hex and always decoded.
+#[derive(Encode, Decode, TypeInfo)]
+#[scale_info(skip_type_params(Codec))]
+pub struct CodecAccount<AccountId, Codec>(pub AccountId, PhantomData<Codec>);
+
+impl<AccountId: Clone, Codec> Clone for CodecAccount<AccountId, Codec> {
+	fn clone(&self) -> Self {
+		Self(self.0.clone(), PhantomData)
+	}
+}
+
+impl<AccountId: PartialEq, Codec> PartialEq for CodecAccount<AccountId, Codec> {
+	fn eq(&self, other: &Self) -> bool {
+		self.0 == other.0
+	}
+}
+
+impl<AccountId: Debug, Codec> Debug for CodecAccount<AccountId, Codec> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_tuple("CodecAccount").field(&self.0).finish()
+	}
+}
+
+impl<AccountId, Codec> From<AccountId> for CodecAccount<AccountId, Codec> {
+	fn from(account: AccountId) -> Self {
+		Self(account, PhantomData)
+	}
+}
+
+impl<AccountId, Codec> IdentifyAccount for CodecAccount<AccountId, Codec> {
+	type AccountId = AccountId;
+	fn into_account(self) -> Self::AccountId {
+		self.0
+	}
+}
+
+impl<AccountId, Codec> TryFrom<Signer> for CodecAccount<AccountId, Codec>
+where
+	AccountId: From<[u8; 32]>,
+	Codec: AddressCodec<AccountId>,
+{
+	type Error = &'static str;
+
+	fn try_from(signer: Signer) -> Result<Self, Self::Error> {
+		<(HexCodec, Codec)>::decode(signer.as_ref())
+			.map(|account| Self(account, PhantomData))
+			.ok_or("invalid ibc address")
+	}
+}
+
+/// Checks the addresses of accounts of counterparty chains.
+pub trait AddressValidator {
+	fn is_valid(address: &str) -> bool;
+}
+
+/// Accepts every address.
+impl AddressValidator for () {
+	fn is_valid(_address: &str) -> bool {
+		true
+	}
+}
+
+/// Addresses valid for either validator.
+impl<A: AddressValidator, B: AddressValidator> AddressValidator for (A, B) {
+	fn is_valid(address: &str) -> bool {
+		A::is_valid(address) || B::is_valid(address)
+	}
+}
+
+/// Accepts `0x`-prefixed hex of 20 or 32-byte accounts.
+impl AddressValidator for HexCodec {
+	fn is_valid(address: &str) -> bool {
+		decode_hex::<32>(address).is_some() || decode_hex::<20>(address).is_some()
+	}
+}
+
+/// Accepts SS58 addresses of 32-byte accounts, whatever their network.
+impl<Prefix> AddressValidator for Ss58Codec<Prefix> {
+	fn is_valid(address: &str) -> bool {
+		decode_ss58(address).is_some()
+	}
+}
+
+/// Accepts bech32 and bech32m addresses with a valid checksum, as used by Cosmos chains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bech32Address;
+
+impl AddressValidator for Bech32Address {
+	fn is_valid(address: &str) -> bool {
+		is_valid_bech32(address)
+	}
+}
+
+fn decode_hex<const N: usize>(address: &str) -> Option<[u8; N]> {
+	let bytes = hex::decode(address.strip_prefix("0x")?).ok()?;
+	bytes.try_into().ok()
+}
+
+/// The network prefix and account of an SS58 address of a 32-byte account.
+fn decode_ss58(address: &str) -> Option<(u16, [u8; 32])> {
+	const CHECKSUM_LEN: usize = 2;
+
+	let data = bs58::decode(address).into_vec().ok()?;
+	let (prefix_len, prefix) = match data.first()? {
+		0..=63 => (1, data[0] as u16),
+		64..=127 => {
+			let lower = (data[0] << 2) | (data.get(1)? >> 6);
+			let upper = data[1] & 0b0011_1111;
+			(2, (lower as u16) | ((upper as u16) << 8))
+		},
+		_ => return None,
+	};
+	if data.len() != prefix_len + 32 + CHECKSUM_LEN {
+		return None
+	}
+
+	let (body, checksum) = data.split_at(data.len() - CHECKSUM_LEN);
+	let mut preimage = b"SS58PRE".to_vec();
+	preimage.extend_from_slice(body);
+	if sp_io::hashing::blake2_512(&preimage)[..CHECKSUM_LEN] != *checksum {
+		return None
+	}
+
+	let account = body[prefix_len..].try_into().ok()?;
+	Some((prefix, account))
+}
+
+/// Whether `address` is a bech32 or bech32m string with a valid checksum, see BIP-173 and BIP-350.
+pub fn is_valid_bech32(address: &str) -> bool {
+	const CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+	const BECH32_CONST: u32 = 1;
+	const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+	if !address.is_ascii() ||
+		(address.chars().any(|c| c.is_ascii_lowercase()) &&
+			address.chars().any(|c| c.is_ascii_uppercase()))
+	{
+		return false
+	}
+	let address = address.to_ascii_lowercase();
+	let separator = match address.rfind('1') {
+		Some(separator) if separator > 0 && separator + 7 <= address.len() => separator,
+		_ => return false,
+	};
+	let (hrp, data) = (&address.as_bytes()[..separator], &address.as_bytes()[separator + 1..]);
+	if hrp.iter().any(|c| !(33..=126).contains(c)) {
+		return false
+	}
+
+	let mut values = vec![];
+	values.extend(hrp.iter().map(|c| c >> 5));
+	values.push(0);
+	values.extend(hrp.iter().map(|c| c & 31));
+	for c in data {
+		match CHARSET.iter().position(|x| x == c) {
+			Some(value) => values.push(value as u8),
+			None => return false,
+		}
+	}
+
+	let checksum = bech32_polymod(&values);
+	checksum == BECH32_CONST || checksum == BECH32M_CONST
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+	const GENERATOR: [u32; 5] = [0x3b6a_57b2, 0x2650_8e6d, 0x1ea1_19fa, 0x3d42_33dd, 0x2a14_62b3];
+
+	let mut checksum = 1u32;
+	for value in values {
+		let top = checksum >> 25;
+		checksum = ((checksum & 0x1ff_ffff) << 5) ^ (*value as u32);
+		for (i, generator) in GENERATOR.iter().enumerate() {
+			if (top >> i) & 1 == 1 {
+				checksum ^= generator;
+			}
+		}
+	}
+	checksum
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::traits::ConstU16;
+
+	const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+	const ALICE_HEX: &str = "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+
+	#[test]
+	fn test_address_codecs() {
+		let alice = decode_hex::<32>(ALICE_HEX).unwrap();
+		assert_eq!(<Ss58Codec<ConstU16<42>> as AddressCodec<[u8; 32]>>::decode(ALICE), Some(alice));
+		assert_eq!(<Ss58Codec<ConstU16<0>> as AddressCodec<[u8; 32]>>::decode(ALICE), None);
+		assert_eq!(<HexCodec as AddressCodec<[u8; 32]>>::decode(ALICE_HEX), Some(alice));
+		assert_eq!(<HexCodec as AddressCodec<[u8; 32]>>::decode(ALICE), None);
+
+		let evm = "0x6be02d1d3665660d22ff9624b7be0551ee1ac91b";
+		let mapped = <H160Codec as AddressCodec<[u8; 32]>>::decode(evm).unwrap();
+		assert_ne!(mapped, [0u8; 32]);
+		assert_eq!(<H160Codec as AddressCodec<[u8; 32]>>::decode(ALICE_HEX), None);
+
+		type Codec = (HexCodec, (Ss58Codec<ConstU16<42>>, H160Codec));
+		assert_eq!(<Codec as AddressCodec<[u8; 32]>>::decode(ALICE), Some(alice));
+		assert_eq!(<Codec as AddressCodec<[u8; 32]>>::decode(evm), Some(mapped));
+
+		let account = CodecAccount::<[u8; 32], Ss58Codec<ConstU16<42>>>::try_from(
+			ALICE_HEX.parse::<Signer>().unwrap(),
+		)
+		.unwrap();
+		assert_eq!(account.into_account(), alice);
+	}
+
+	#[test]
+	fn test_bech32_validation() {
+		// BIP-173 and BIP-350 test vectors
+		assert!(Bech32Address::is_valid("A12UEL5L"));
+		assert!(Bech32Address::is_valid("a12uel5l"));
+		assert!(Bech32Address::is_valid("abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw"));
+		assert!(Bech32Address::is_valid("a1lqfn3a"));
+
+		assert!(!Bech32Address::is_valid("a12UEL5L"));
+		assert!(!Bech32Address::is_valid("abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxx"));
+		assert!(!Bech32Address::is_valid("1pzry9x0s0muk"));
+		assert!(!Bech32Address::is_valid("li1dgmt3"));
+		assert!(!Bech32Address::is_valid(ALICE));
+
+		assert!(<(Bech32Address, HexCodec)>::is_valid(ALICE_HEX));
+		assert!(<Ss58Codec<()> as AddressValidator>::is_valid(ALICE));
+	}
+}
@@ -0,0 +1,124 @@
+//! Conversion of ICS20 amounts into local balances.
+//!
+//! Packet amounts are 256-bit and expressed in the units of the token on the counterparty chain.
+//! They are converted with overflow checks, and scaled by the [`DecimalConversion`] set for the
+//! full path of the denomination, if any. Received amounts are rounded down, so the dust of a
+//! receive is never minted. Amounts leaving an account must convert exactly, so the counterparty
+//! never gets more than what was escrowed or burnt. The conversion of a denomination cannot change
+//! while tokens of it are escrowed, minted as vouchers or in flight, as they would come back at
+//! other decimals than they left at.
+use crate::*;
+use codec::{Decode, Encode};
+use ibc::applications::transfer::{error::TokenTransferError, Amount, PrefixedDenom};
+use primitive_types::U256;
+use scale_info::TypeInfo;
+
+/// Decimals of an asset on this chain and in ICS20 packets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct DecimalConversion {
+	pub local_decimals: u8,
+	pub remote_decimals: u8,
+}
+
+impl DecimalConversion {
+	/// The local amount of `amount` packet units, `None` on overflow or, if `exact`, when
+	/// `amount` has dust.
+	pub fn to_local(&self, amount: U256, exact: bool) -> Option<U256> {
+		if self.local_decimals >= self.remote_decimals {
+			let factor = U256::from(10u8)
+				.checked_pow(U256::from(self.local_decimals - self.remote_decimals))?;
+			return amount.checked_mul(factor)
+		}
+		let factor =
+			U256::from(10u8).checked_pow(U256::from(self.remote_decimals - self.local_decimals))?;
+		if exact && !(amount % factor).is_zero() {
+			return None
+		}
+		Some(amount / factor)
+	}
+
+	/// The packet units of `amount` of the local asset, `None` on overflow or when `amount` has
+	/// dust in packet units.
+	pub fn to_remote(&self, amount: U256) -> Option<U256> {
+		DecimalConversion {
+			local_decimals: self.remote_decimals,
+			remote_decimals: self.local_decimals,
+		}
+		.to_local(amount, true)
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// The balance of the local asset of `denom` corresponding to `amount` packet units, see
+	/// [`DecimalConversion::to_local`].
+	pub fn local_amount(
+		denom: &PrefixedDenom,
+		amount: Amount,
+		exact: bool,
+	) -> Result<u128, TokenTransferError> {
+		let amount = U256::from(amount);
+		let local = match <DecimalConversions<T>>::get(denom.to_string().into_bytes()) {
+			Some(conversion) => conversion.to_local(amount, exact),
+			None => Some(amount),
+		};
+		local.and_then(|local| u128::try_from(local).ok()).ok_or_else(|| {
+			log::error!(target: LOG_TARGET, "❌ [local_amount] invalid amount {} of {}", amount, denom);
+			TokenTransferError::InvalidToken
+		})
+	}
+
+	/// The packet units of `amount` of the local asset of `denom`, see
+	/// [`DecimalConversion::to_remote`].
+	pub fn packet_amount(
+		denom: &PrefixedDenom,
+		amount: u128,
+	) -> Result<Amount, TokenTransferError> {
+		let amount = U256::from(amount);
+		let remote = match <DecimalConversions<T>>::get(denom.to_string().into_bytes()) {
+			Some(conversion) => conversion.to_remote(amount),
+			None => Some(amount),
+		};
+		remote.map(Amount::from).ok_or_else(|| {
+			log::error!(target: LOG_TARGET, "❌ [packet_amount] invalid amount {} of {}", amount, denom);
+			TokenTransferError::InvalidToken
+		})
+	}
+
+	/// Whether tokens of `denom` are escrowed, minted as vouchers, or in flight in a pending or
+	/// held transfer.
+	pub(crate) fn has_outstanding_supply(denom: &PrefixedDenom) -> bool {
+		let path = denom.to_string().into_bytes();
+		<EscrowTotals<T>>::iter().any(|(_, escrowed, total)| escrowed == path && total > 0) ||
+			<VoucherSupply<T>>::get(Self::asset_name(denom)) > 0 ||
+			<PendingTransfers<T>>::iter_values().any(|transfer| transfer.denom == path) ||
+			<HeldTransfers<T>>::iter_values().any(|transfer| transfer.denom == path)
+	}
+
+	/// Whether tokens of the denomination registered as the asset name `name`, the `ibc/{hash}`
+	/// denomination of a voucher or a base denomination, are outstanding.
+	pub(crate) fn has_outstanding_supply_of_asset(name: &[u8]) -> bool {
+		let path = match <DenomTrace<T>>::get(name) {
+			Some(trace) => trace.full_path(),
+			None => name.to_vec(),
+		};
+		core::str::from_utf8(&path)
+			.ok()
+			.and_then(|path| path.parse::<PrefixedDenom>().ok())
+			.map_or(false, |denom| Self::has_outstanding_supply(&denom))
+	}
+}
+
+#[test]
+fn test_decimal_conversion() {
+	let down = DecimalConversion { local_decimals: 12, remote_decimals: 18 };
+	assert_eq!(down.to_local(U256::from(3_000_000u64), true), Some(U256::from(3u8)));
+	assert_eq!(down.to_local(U256::from(3_500_000u64), true), None);
+	assert_eq!(down.to_local(U256::from(3_500_000u64), false), Some(U256::from(3u8)));
+
+	let up = DecimalConversion { local_decimals: 18, remote_decimals: 6 };
+	assert_eq!(up.to_local(U256::from(2u8), true), Some(U256::from(2_000_000_000_000u64)));
+	assert_eq!(up.to_local(U256::MAX, false), None);
+	assert_eq!(up.to_remote(U256::from(2_000_000_000_000u64)), Some(U256::from(2u8)));
+	assert_eq!(up.to_remote(U256::from(2_000_000_000_001u64)), None);
+	assert_eq!(down.to_remote(U256::from(3u8)), Some(U256::from(3_000_000u64)));
+}
@@ -0,0 +1,165 @@
+//! Registration of the local assets of received denominations.
+//!
+//! The first time a voucher of a denomination without a local asset is minted, an asset id is
+//! taken from [`NextAssetId`], skipping the ids already in use, e.g. by assets created through
+//! `pallet-assets` directly, and [`Config::AssetRegistrar`] creates the asset, with metadata
+//! derived from the denomination trace unless [`Config::AdminOrigin`] set some for it. Derived
+//! metadata does not guess the decimals of the denomination: they are zero until governance sets
+//! them. The mapping is recorded in the pallet's [`AssetIdByName`], which the runtime is expected
+//! to use as [`Config::AssetIdByName`].
+//!
+//! [`Config::AdminOrigin`] may also block denominations from being received, map them to existing
+//! assets and change the sufficiency of their assets.
+//!
+//! Besides the native token, assets issued on this chain can be sent out once
+//! [`Config::AdminOrigin`] maps a denomination to them in [`NativeAssets`]. They are escrowed when
+//! sent and unescrowed when they return.
+use crate::*;
+use alloc::string::ToString;
+use codec::{Decode, Encode};
+use frame_support::traits::fungibles::Inspect;
+use ibc::applications::transfer::PrefixedDenom;
+use scale_info::TypeInfo;
+use sp_runtime::{
+	traits::{CheckedAdd, One},
+	DispatchError,
+};
+
+/// Maximum number of asset ids in use skipped when registering an asset
+pub const MAX_TAKEN_ASSET_IDS: u32 = 32;
+
+/// Metadata of the local asset of a denomination
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct AssetMetadata<Balance> {
+	pub name: Vec<u8>,
+	pub symbol: Vec<u8>,
+	pub decimals: u8,
+	pub is_sufficient: bool,
+	pub min_balance: Balance,
+}
+
+/// The local asset behind a denomination
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalAsset<AssetId> {
+	/// The native token, held in [`Config::Currency`]
+	Native,
+	/// An asset of [`Config::Fungibles`]
+	Asset(AssetId),
+}
+
+/// Creates the local assets of received denominations, e.g. in `pallet-assets`.
+pub trait AssetRegistrar<AssetId, Balance> {
+	/// Create the asset `asset_id` with `metadata`.
+	fn create_asset(asset_id: AssetId, metadata: &AssetMetadata<Balance>) -> DispatchResult;
+
+	/// Set whether the asset `asset_id` is sufficient for an account to exist.
+	fn set_sufficient(asset_id: AssetId, is_sufficient: bool) -> DispatchResult;
+}
+
+/// Rejects every registration, so only denominations mapped by governance can be received.
+impl<AssetId, Balance> AssetRegistrar<AssetId, Balance> for () {
+	fn create_asset(_asset_id: AssetId, _metadata: &AssetMetadata<Balance>) -> DispatchResult {
+		Err(DispatchError::Other("asset registration disabled"))
+	}
+
+	fn set_sufficient(_asset_id: AssetId, _is_sufficient: bool) -> DispatchResult {
+		Err(DispatchError::Other("asset registration disabled"))
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// Key of the local asset of `denom` in [`AssetIdByName`]: the `ibc/{hash}` denomination of
+	/// vouchers, the base denomination of native tokens.
+	pub fn asset_name(denom: &PrefixedDenom) -> Vec<u8> {
+		if denom.trace_path.is_empty() {
+			return denom.base_denom.as_str().as_bytes().to_vec()
+		}
+		utils::derive_ibc_denom(denom)
+			.map(String::into_bytes)
+			.unwrap_or_else(|_| denom.to_string().into_bytes())
+	}
+
+	/// The local asset of `denom`: for tokens sourced on this chain, the native token or the asset
+	/// mapped in [`NativeAssets`], for vouchers the asset of their `ibc/{hash}` denomination.
+	pub fn local_asset(denom: &PrefixedDenom) -> Option<LocalAsset<T::AssetId>> {
+		if denom.trace_path.is_empty() {
+			let base_denom = denom.base_denom.as_str().as_bytes();
+			if base_denom == T::NATIVE_TOKEN_NAME {
+				return Some(LocalAsset::Native)
+			}
+			return <NativeAssets<T>>::get(base_denom).map(LocalAsset::Asset)
+		}
+		T::AssetIdByName::try_get_asset_id(Self::asset_name(denom))
+			.ok()
+			.map(LocalAsset::Asset)
+	}
+
+	/// Metadata of the local asset of `denom`: named after its full path, with the base denom as
+	/// symbol and no decimals, since they cannot be told from the denom.
+	pub fn derive_asset_metadata(denom: &PrefixedDenom) -> AssetMetadata<T::AssetBalance> {
+		AssetMetadata {
+			name: denom.to_string().into_bytes(),
+			symbol: denom.base_denom.as_str().to_uppercase().into_bytes(),
+			decimals: 0,
+			is_sufficient: false,
+			min_balance: T::AutoAssetMinBalance::get(),
+		}
+	}
+
+	/// The local asset of `denom`, registered first if it has none.
+	pub fn ensure_asset(denom: &PrefixedDenom) -> Result<T::AssetId, DispatchError> {
+		let asset_name = Self::asset_name(denom);
+		ensure!(!<BlockedDenoms<T>>::contains_key(&asset_name), Error::<T>::DenomBlocked);
+		if let Ok(asset_id) = T::AssetIdByName::try_get_asset_id(&asset_name) {
+			return Ok(asset_id)
+		}
+
+		let metadata = <DenomMetadata<T>>::get(&asset_name)
+			.unwrap_or_else(|| Self::derive_asset_metadata(denom));
+		let asset_id = Self::next_free_asset_id()?;
+		T::AssetRegistrar::create_asset(asset_id, &metadata)?;
+		<NextAssetId<T>>::put(asset_id.saturating_add(One::one()));
+		<AssetIdByName<T>>::insert(&asset_name, asset_id);
+		<AssetNameById<T>>::insert(asset_id, &asset_name);
+
+		Self::deposit_event(Event::<T>::AssetRegistered { denom: asset_name, asset_id });
+		Ok(asset_id)
+	}
+
+	/// The first asset id from [`NextAssetId`] that is neither an existing asset nor mapped to a
+	/// denomination.
+	fn next_free_asset_id() -> Result<T::AssetId, DispatchError> {
+		let mut asset_id = <NextAssetId<T>>::get().unwrap_or_else(T::AutoAssetIdStart::get);
+		for _ in 0..MAX_TAKEN_ASSET_IDS {
+			if !<T::Fungibles as Inspect<T::AccountId>>::asset_exists(asset_id) &&
+				!<AssetNameById<T>>::contains_key(asset_id)
+			{
+				return Ok(asset_id)
+			}
+			asset_id = asset_id.checked_add(&One::one()).ok_or(Error::<T>::InvalidTokenId)?;
+		}
+		Err(Error::<T>::InvalidTokenId.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::mock::Test;
+	use ibc::applications::transfer::PrefixedDenom;
+	use sp_std::str::FromStr;
+
+	#[test]
+	fn test_derive_asset_metadata() {
+		let denom = PrefixedDenom::from_str("transfer/channel-0/uatom").unwrap();
+		let metadata = crate::Pallet::<Test>::derive_asset_metadata(&denom);
+		assert_eq!(metadata.name, b"transfer/channel-0/uatom".to_vec());
+		assert_eq!(metadata.symbol, b"UATOM".to_vec());
+		assert_eq!(metadata.decimals, 0);
+
+		// a leading u is not taken for a micro prefix
+		let denom = PrefixedDenom::from_str("transfer/channel-0/unicorn").unwrap();
+		let metadata = crate::Pallet::<Test>::derive_asset_metadata(&denom);
+		assert_eq!(metadata.symbol, b"UNICORN".to_vec());
+		assert_eq!(metadata.decimals, 0);
+	}
+}
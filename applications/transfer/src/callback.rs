@@ -1,5 +1,11 @@
-use crate::Config;
-use alloc::string::ToString;
+use crate::{
+	hooks::HookMetadata, packet::FungibleTokenPacketData, pending::TransferOutcome,
+	policy::PolicyDecision, utils::transactional, Config, Pallet, LOG_TARGET,
+};
+use alloc::{
+	format,
+	string::{String, ToString},
+};
 use codec::{Decode, Encode};
 use ibc::{
 	core::{
@@ -16,12 +22,36 @@ use ibc::{
 	},
 	signer::Signer,
 };
+use pallet_ibc_utils::ack::GenericAcknowledgement;
 
 use sp_std::marker::PhantomData;
 
 #[derive(Debug, Encode, Decode)]
 pub struct IbcTransferModule<T>(pub PhantomData<T>);
 
+impl<T: Config> IbcTransferModule<T> {
+	fn recv_packet(
+		&mut self,
+		output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		relayer: &Signer,
+	) -> Acknowledgement {
+		let data = match FungibleTokenPacketData::from_bytes(&packet.data) {
+			Ok(data) => data,
+			Err(_) =>
+				return ibc::applications::transfer::context::on_recv_packet(
+					self, output, packet, relayer,
+				),
+		};
+		match HookMetadata::from_memo(&data.memo) {
+			Ok(Some(hook)) => self.recv_packet_with_hook(output, packet, relayer, data, hook),
+			Ok(None) =>
+				ibc::applications::transfer::context::on_recv_packet(self, output, packet, relayer),
+			Err(e) => GenericAcknowledgement::error(e).into(),
+		}
+	}
+}
+
 impl<T: Config> Module for IbcTransferModule<T> {
 	fn on_chan_open_init(
 		&mut self,
@@ -32,7 +62,7 @@ impl<T: Config> Module for IbcTransferModule<T> {
 		counterparty: &Counterparty,
 		version: &Version,
 	) -> Result<(ModuleExtras, Version), ChannelError> {
-		ibc::applications::transfer::context::on_chan_open_init(
+		let result = ibc::applications::transfer::context::on_chan_open_init(
 			self,
 			order,
 			connection_hops,
@@ -41,7 +71,10 @@ impl<T: Config> Module for IbcTransferModule<T> {
 			counterparty,
 			version,
 		)
-		.map_err(|e| ChannelError::AppModule { description: e.to_string() })
+		.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+		Pallet::<T>::register_escrow_account(port_id, channel_id)
+			.map_err(|e| ChannelError::AppModule { description: format!("{:?}", e) })?;
+		Ok(result)
 	}
 
 	fn on_chan_open_try(
@@ -53,7 +86,7 @@ impl<T: Config> Module for IbcTransferModule<T> {
 		counterparty: &Counterparty,
 		counterparty_version: &Version,
 	) -> Result<(ModuleExtras, Version), ChannelError> {
-		ibc::applications::transfer::context::on_chan_open_try(
+		let result = ibc::applications::transfer::context::on_chan_open_try(
 			self,
 			order,
 			connection_hops,
@@ -62,7 +95,10 @@ impl<T: Config> Module for IbcTransferModule<T> {
 			counterparty,
 			counterparty_version,
 		)
-		.map_err(|e| ChannelError::AppModule { description: e.to_string() })
+		.map_err(|e| ChannelError::AppModule { description: e.to_string() })?;
+		Pallet::<T>::register_escrow_account(port_id, channel_id)
+			.map_err(|e| ChannelError::AppModule { description: format!("{:?}", e) })?;
+		Ok(result)
 	}
 
 	fn on_chan_open_ack(
@@ -112,7 +148,25 @@ impl<T: Config> Module for IbcTransferModule<T> {
 		packet: &Packet,
 		relayer: &Signer,
 	) -> Acknowledgement {
-		ibc::applications::transfer::context::on_recv_packet(self, output, packet, relayer)
+		if let Err(e) = Pallet::<T>::ensure_receive_enabled(packet) {
+			return GenericAcknowledgement::error(format!("{:?}", e)).into()
+		}
+		let decision = Pallet::<T>::recv_decision(packet);
+		if let PolicyDecision::Deny(reason) = &decision {
+			return GenericAcknowledgement::error(format!("transfer denied: {}", reason)).into()
+		}
+		if let Err(e) = Pallet::<T>::record_recv_packet(packet) {
+			return GenericAcknowledgement::error(format!("{:?}", e)).into()
+		}
+		let ack = if decision == PolicyDecision::Hold {
+			self.recv_packet_on_hold(output, packet, relayer)
+		} else {
+			self.recv_packet(output, packet, relayer)
+		};
+		if !GenericAcknowledgement::from_bytes(ack.as_ref()).map_or(false, |ack| ack.is_success()) {
+			Pallet::<T>::undo_recv_packet(packet);
+		}
+		ack
 	}
 
 	fn on_acknowledgement_packet(
@@ -122,14 +176,28 @@ impl<T: Config> Module for IbcTransferModule<T> {
 		acknowledgement: &Acknowledgement,
 		relayer: &Signer,
 	) -> Result<(), PacketError> {
-		ibc::applications::transfer::context::on_acknowledgement_packet(
-			self,
-			output,
-			packet,
-			acknowledgement,
-			relayer,
-		)
-		.map_err(|e| PacketError::AppModule { description: e.to_string() })
+		let result = transactional(|| {
+			ibc::applications::transfer::context::on_acknowledgement_packet(
+				self,
+				output,
+				packet,
+				acknowledgement,
+				relayer,
+			)
+			.map_err(|e| e.to_string())
+		});
+
+		let success = GenericAcknowledgement::from_bytes(acknowledgement.as_ref())
+			.map_or(false, |ack| ack.is_success());
+		let outcome = match (&result, success) {
+			(Ok(_), true) => TransferOutcome::Succeeded,
+			(Ok(_), false) => TransferOutcome::Refunded,
+			(Err(_), _) => TransferOutcome::RefundFailed,
+		};
+		settle_transfer::<T>(packet, outcome, result)?;
+		Pallet::<T>::settle_send_packet(packet, success);
+
+		Ok(())
 	}
 
 	fn on_timeout_packet(
@@ -138,7 +206,41 @@ impl<T: Config> Module for IbcTransferModule<T> {
 		packet: &Packet,
 		relayer: &Signer,
 	) -> Result<(), PacketError> {
-		ibc::applications::transfer::context::on_timeout_packet(self, output, packet, relayer)
-			.map_err(|e| PacketError::AppModule { description: e.to_string() })
+		let result = transactional(|| {
+			ibc::applications::transfer::context::on_timeout_packet(self, output, packet, relayer)
+				.map_err(|e| e.to_string())
+		});
+
+		let outcome = match result {
+			Ok(_) => TransferOutcome::Refunded,
+			Err(_) => TransferOutcome::RefundFailed,
+		};
+		settle_transfer::<T>(packet, outcome, result)?;
+		Pallet::<T>::settle_send_packet(packet, false);
+
+		Ok(())
+	}
+}
+
+/// Settle the tracked transfer of `packet`. A failed refund of a tracked transfer is recorded on
+/// it and does not fail the packet, so [`Pallet::refund_failed_transfer`] can pay it out later;
+/// other failed refunds fail the packet.
+fn settle_transfer<T: Config>(
+	packet: &Packet,
+	outcome: TransferOutcome,
+	result: Result<(), String>,
+) -> Result<(), PacketError> {
+	if let Err(e) = &result {
+		log::error!(
+			target: LOG_TARGET,
+			"❌ [settle_transfer] refund of packet {} on {}: {}",
+			packet.sequence,
+			packet.chan_on_a,
+			e
+		);
+	}
+	if Pallet::<T>::settle_transfer(packet, outcome) {
+		return Ok(())
 	}
+	result.map_err(|description| PacketError::AppModule { description })
 }
@@ -1,21 +1,150 @@
+use crate::*;
 use alloc::vec::Vec;
-use codec::{Decode, Encode};
-use ibc::applications::transfer::denom::PrefixedDenom as IbcPrefixedDenom;
+use codec::{Decode, Encode, EncodeLike, MaxEncodedLen};
+use frame_support::{CloneNoBound, EqNoBound, PartialEqNoBound, RuntimeDebugNoBound};
+use ibc::applications::transfer::{
+	denom::PrefixedDenom as IbcPrefixedDenom, error::TokenTransferError,
+};
+use log::error;
 
-/// A type that contains the base denomination for ICS20 and the source tracing information path.
-#[derive(Clone, Debug, Decode, Encode, scale_info::TypeInfo)]
-pub struct PrefixedDenom {
+/// A type that contains the base denomination for ICS20 and the source tracing information path,
+/// each at most `S` bytes long.
+#[derive(
+	CloneNoBound,
+	RuntimeDebugNoBound,
+	PartialEqNoBound,
+	EqNoBound,
+	Decode,
+	Encode,
+	MaxEncodedLen,
+	scale_info::TypeInfo,
+)]
+#[scale_info(skip_type_params(S))]
+#[codec(mel_bound())]
+pub struct PrefixedDenom<S: Get<u32>> {
 	/// A series of `{port-id}/{channel-id}`s for tracing the source of the token.
-	pub trace_path: Vec<u8>,
+	pub trace_path: BoundedVec<u8, S>,
 	/// Base denomination of the relayed fungible token.
-	pub base_denom: Vec<u8>,
+	pub base_denom: BoundedVec<u8, S>,
 }
 
-impl From<IbcPrefixedDenom> for PrefixedDenom {
-	fn from(value: IbcPrefixedDenom) -> Self {
-		Self {
-			trace_path: alloc::format!("{}", value.trace_path).as_bytes().to_vec(),
-			base_denom: value.base_denom.as_str().as_bytes().to_vec(),
+impl<S: Get<u32>> PrefixedDenom<S> {
+	/// `{trace_path}/{base_denom}`, or the base denomination alone for native tokens.
+	pub fn full_path(&self) -> Vec<u8> {
+		if self.trace_path.is_empty() {
+			return self.base_denom.to_vec()
 		}
+		let mut path = self.trace_path.to_vec();
+		path.push(b'/');
+		path.extend_from_slice(&self.base_denom);
+		path
 	}
 }
+
+impl<S: Get<u32>> TryFrom<IbcPrefixedDenom> for PrefixedDenom<S> {
+	type Error = TokenTransferError;
+
+	/// Fails with [`TokenTransferError::InvalidToken`] if the trace path or the base denomination
+	/// is longer than `S`.
+	fn try_from(value: IbcPrefixedDenom) -> Result<Self, Self::Error> {
+		let trace_path = alloc::format!("{}", value.trace_path).into_bytes();
+		let base_denom = value.base_denom.as_str().as_bytes().to_vec();
+		match (trace_path.try_into(), base_denom.try_into()) {
+			(Ok(trace_path), Ok(base_denom)) => Ok(Self { trace_path, base_denom }),
+			_ => {
+				error!("❌ [denom_trace]: trace of ({}) too long", value);
+				Err(TokenTransferError::InvalidToken)
+			},
+		}
+	}
+}
+
+/// The length of an `ibc/{hash}` denomination, the hex encoding of a SHA-256 hash behind `ibc/`.
+pub type MaxIbcDenomLen = ConstU32<68>;
+
+/// An `ibc/{hash}` denomination keying [`DenomTrace`], encoded as the bytes it holds so that the
+/// storage item is passed and was formerly keyed by plain bytes.
+#[derive(
+	Clone, RuntimeDebug, PartialEq, Eq, Decode, Encode, MaxEncodedLen, scale_info::TypeInfo,
+)]
+pub struct IbcDenom(BoundedVec<u8, MaxIbcDenomLen>);
+
+impl IbcDenom {
+	pub fn into_inner(self) -> Vec<u8> {
+		self.0.into_inner()
+	}
+}
+
+impl core::ops::Deref for IbcDenom {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		&self.0
+	}
+}
+
+impl EncodeLike<IbcDenom> for Vec<u8> {}
+impl EncodeLike<IbcDenom> for &Vec<u8> {}
+impl EncodeLike<IbcDenom> for &[u8] {}
+
+impl<T: Config> Pallet<T> {
+	/// Record the trace of `denom` under its `ibc/{hash}` denomination.
+	pub fn store_denom_trace(denom: &IbcPrefixedDenom) -> Result<(), TokenTransferError> {
+		if denom.trace_path.is_empty() {
+			return Ok(())
+		}
+		let hash = utils::derive_ibc_denom(denom)?.into_bytes();
+		if !<DenomTrace<T>>::contains_key(&hash) {
+			<DenomTrace<T>>::insert(hash, PrefixedDenom::try_from(denom.clone())?);
+		}
+		Ok(())
+	}
+
+	/// The trace of the `ibc/{hash}` denomination `hash`.
+	pub fn denom_trace(hash: &[u8]) -> Option<PrefixedDenom<T::MaxTraceLength>> {
+		<DenomTrace<T>>::get(hash)
+	}
+
+	/// The trace of the voucher denomination of the local asset `asset_id`.
+	pub fn denom_trace_by_asset(asset_id: T::AssetId) -> Option<PrefixedDenom<T::MaxTraceLength>> {
+		<AssetNameById<T>>::get(asset_id).and_then(|name| <DenomTrace<T>>::get(name))
+	}
+
+	/// At most `limit` denomination traces, with their `ibc/{hash}` denomination, following the
+	/// one of `start_after`.
+	pub fn denom_traces(
+		start_after: Option<Vec<u8>>,
+		limit: u32,
+	) -> Vec<(Vec<u8>, PrefixedDenom<T::MaxTraceLength>)> {
+		match start_after {
+			Some(hash) => <DenomTrace<T>>::iter_from(<DenomTrace<T>>::hashed_key_for(hash))
+				.take(limit as usize)
+				.map(|(hash, trace)| (hash.into_inner(), trace))
+				.collect(),
+			None => <DenomTrace<T>>::iter()
+				.take(limit as usize)
+				.map(|(hash, trace)| (hash.into_inner(), trace))
+				.collect(),
+		}
+	}
+}
+
+#[test]
+fn test_full_path() {
+	use sp_std::str::FromStr;
+
+	let denom = IbcPrefixedDenom::from_str("transfer/channel-0/uatom").unwrap();
+	let trace = PrefixedDenom::<ConstU32<64>>::try_from(denom).unwrap();
+	assert_eq!(trace.full_path(), b"transfer/channel-0/uatom".to_vec());
+	let denom = IbcPrefixedDenom::from_str("uatom").unwrap();
+	let trace = PrefixedDenom::<ConstU32<64>>::try_from(denom).unwrap();
+	assert_eq!(trace.full_path(), b"uatom".to_vec());
+}
+
+#[test]
+fn test_trace_too_long() {
+	use sp_std::str::FromStr;
+
+	let denom = IbcPrefixedDenom::from_str("transfer/channel-0/uatom").unwrap();
+	assert!(PrefixedDenom::<ConstU32<8>>::try_from(denom).is_err());
+}
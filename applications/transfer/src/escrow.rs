@@ -0,0 +1,103 @@
+//! Escrow accounts of ICS20 channels.
+//!
+//! The tokens sourced on this chain are escrowed, when sent over a channel, in a sub-account of
+//! [`Config::PalletId`] derived from the port and channel: `modl`, the pallet id and the first 20
+//! bytes of the hash ICS20 derives the escrow address of the channel from, see
+//! [`get_channel_escrow_address_hash`]. It is not the escrow address a Cosmos chain computes for
+//! the channel, which is those 20 bytes alone.
+//!
+//! Escrow accounts are registered in [`EscrowAccounts`] when their channel is opened, or when
+//! tokens are first sent over it. Registration gives the account a provider reference, so that it
+//! can escrow assets that are not sufficient, and transfers it the existential deposit of the
+//! native token from the account of the pallet, which is kept out of the escrowed totals. Native
+//! tokens are then always unescrowed keeping the account alive, so no escrowed balance is ever
+//! reaped as dust. Assets are unescrowed keeping the account alive unless it is emptied.
+use crate::{assets::LocalAsset, utils::get_channel_escrow_address_hash, *};
+use frame_support::{traits::ExistenceRequirement, PalletId};
+use sp_runtime::{traits::AccountIdConversion, DispatchError};
+
+impl<T: Config> Pallet<T> {
+	/// The escrow account of `channel_id` on `port_id`.
+	pub fn channel_escrow_account(port_id: &PortId, channel_id: &ChannelId) -> T::AccountId {
+		let pallet_id: PalletId = T::PalletId::get();
+		pallet_id.into_sub_account_truncating(get_channel_escrow_address_hash(port_id, channel_id))
+	}
+
+	/// The registered escrow account of `channel_id` on `port_id`, or the one it would be
+	/// registered with.
+	pub fn escrow_account(port_id: &PortId, channel_id: &ChannelId) -> T::AccountId {
+		<EscrowAccounts<T>>::get(port_id, channel_id)
+			.unwrap_or_else(|| Self::channel_escrow_account(port_id, channel_id))
+	}
+
+	/// The account of the pallet, paying the existential deposit of the escrow accounts.
+	pub fn pallet_account() -> T::AccountId {
+		T::PalletId::get().into_account_truncating()
+	}
+
+	/// Register the escrow account of `channel_id` on `port_id`, if it is not, and return it.
+	///
+	/// Fails if the account of the pallet cannot pay the existential deposit of the escrow account.
+	pub fn register_escrow_account(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<T::AccountId, DispatchError> {
+		if let Some(account) = <EscrowAccounts<T>>::get(port_id, channel_id) {
+			return Ok(account)
+		}
+
+		let account = Self::channel_escrow_account(port_id, channel_id);
+		T::Currency::transfer(
+			&Self::pallet_account(),
+			&account,
+			T::Currency::minimum_balance(),
+			ExistenceRequirement::AllowDeath,
+		)?;
+		frame_system::Pallet::<T>::inc_providers(&account);
+		<EscrowAccounts<T>>::insert(port_id, channel_id, &account);
+		<EscrowAccountChannels<T>>::insert(&account, (port_id.clone(), channel_id.clone()));
+
+		Self::deposit_event(Event::<T>::EscrowAccountRegistered {
+			port_id: port_id.clone(),
+			channel_id: channel_id.clone(),
+			account: account.clone(),
+		});
+		Ok(account)
+	}
+
+	/// The registered escrow accounts, by port and channel.
+	pub fn escrow_accounts() -> Vec<(PortId, ChannelId, T::AccountId)> {
+		<EscrowAccounts<T>>::iter().collect()
+	}
+
+	/// Whether `account` is the escrow account of a channel.
+	pub fn is_escrow_account(account: &T::AccountId) -> bool {
+		<EscrowAccountChannels<T>>::contains_key(account)
+	}
+
+	/// Whether the transfer of `amount` of `asset` out of `from` must keep `from` alive, `None` to
+	/// keep the default of the asset. Escrow accounts are kept alive unless they are emptied of
+	/// an asset.
+	pub(crate) fn escrow_keep_alive(
+		from: &T::AccountId,
+		asset: LocalAsset<T::AssetId>,
+		amount: u128,
+	) -> Option<bool> {
+		if !Self::is_escrow_account(from) {
+			return None
+		}
+		Some(asset == LocalAsset::Native || Self::asset_balance(from, asset) != amount)
+	}
+
+	/// The existence requirement of a transfer of the native token out of `from`, keeping escrow
+	/// accounts alive on their existential deposit.
+	pub(crate) fn native_existence_requirement(
+		from: &T::AccountId,
+		amount: u128,
+	) -> ExistenceRequirement {
+		match Self::escrow_keep_alive(from, LocalAsset::Native, amount) {
+			Some(true) => ExistenceRequirement::KeepAlive,
+			_ => ExistenceRequirement::AllowDeath,
+		}
+	}
+}
@@ -0,0 +1,451 @@
+//! Packet-forward middleware.
+//!
+//! Wraps [`IbcTransferModule`] so that a transfer received with a memo such as
+//!
+//! ```json
+//! {"forward":{"receiver":"cosmos1...","port":"transfer","channel":"channel-1","timeout":600000000000,"retries":2}}
+//! ```
+//!
+//! lands on an intermediate account derived from the receiving channel and the original sender,
+//! and is sent on to `receiver` over `port`/`channel`. An optional `next` field is passed as the
+//! memo of the forwarded transfer, enabling further hops.
+//!
+//! The acknowledgement of the received packet is deferred until the forwarded packet is
+//! acknowledged, and carries the acknowledgement of the next hop. If the next hop fails, or the
+//! forwarded packet times out more than `retries` times, the received funds are unwound and the
+//! previous hop gets an error acknowledgement so it refunds the original sender. If that fails,
+//! e.g. because the original channel was closed, the acknowledgement or timeout of the forwarded
+//! packet fails as a whole and the forward stays in flight, so it can be relayed again.
+//!
+//! The runtime routes the transfer port to [`PacketForwardMiddleware`] instead of
+//! [`IbcTransferModule`] to enable forwarding.
+use crate::{
+	callback::IbcTransferModule,
+	packet::FungibleTokenPacketData,
+	transfer::OutgoingTransfer,
+	utils::{get_forward_intermediate_address, get_received_denom, transactional},
+	*,
+};
+use alloc::{format, string::ToString};
+use codec::{Decode, Encode};
+use ibc::{
+	applications::transfer::{
+		context::{BankKeeper, TokenTransferReader},
+		Amount, PrefixedCoin, PrefixedDenom, PORT_ID_STR,
+	},
+	core::{
+		ics04_channel::{
+			channel::{Counterparty, Order},
+			error::{ChannelError, PacketError},
+			handler::ModuleExtras,
+			msgs::acknowledgement::Acknowledgement,
+			packet::Packet,
+			timeout::TimeoutHeight,
+			Version,
+		},
+		ics24_host::identifier::{ChannelId, ConnectionId, PortId},
+		ics26_routing::context::{Module, ModuleOutputBuilder},
+	},
+	signer::Signer,
+	timestamp::Timestamp,
+};
+use ibc_proto::{ibc::core::channel::v1::Packet as RawPacket, protobuf::Protobuf};
+use pallet_ibc_utils::{
+	ack::GenericAcknowledgement, packet::write_acknowledgement, traits::ChannelKeeperInterface,
+};
+use serde::{Deserialize, Serialize};
+use sp_runtime::traits::UniqueSaturatedInto;
+use sp_std::{marker::PhantomData, str::FromStr};
+
+/// Timeout of forwarded packets when the instruction does not set one, in nanoseconds
+pub const DEFAULT_FORWARD_TIMEOUT: u64 = 10 * 60 * 1_000_000_000;
+/// Number of times a timed out forward is retried when the instruction does not set it
+pub const DEFAULT_FORWARD_RETRIES: u8 = 3;
+
+/// The `forward` instruction of an ICS20 memo.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardMetadata {
+	/// Receiver on the next hop
+	pub receiver: String,
+	pub port: String,
+	pub channel: String,
+	/// Timeout of the forwarded packet, relative to the block time and in nanoseconds
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub timeout: Option<u64>,
+	/// Number of times the forward is retried on timeout
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub retries: Option<u8>,
+	/// Memo of the forwarded transfer, either a JSON object or a string
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub next: Option<serde_json::Value>,
+}
+
+impl ForwardMetadata {
+	/// The forward instruction of `memo`, `None` for memos without one or that are not JSON.
+	pub fn from_memo(memo: &str) -> Result<Option<Self>, String> {
+		let memo: serde_json::Value = match serde_json::from_str(memo) {
+			Ok(memo) => memo,
+			Err(_) => return Ok(None),
+		};
+		match memo.get("forward") {
+			Some(forward) => serde_json::from_value(forward.clone())
+				.map(Some)
+				.map_err(|e| format!("invalid forward memo: {}", e)),
+			None => Ok(None),
+		}
+	}
+
+	/// The memo of the forwarded transfer.
+	pub fn next_memo(&self) -> String {
+		match &self.next {
+			None => String::new(),
+			Some(serde_json::Value::String(memo)) => memo.clone(),
+			Some(next) => next.to_string(),
+		}
+	}
+}
+
+/// A forwarded transfer waiting for its acknowledgement.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, scale_info::TypeInfo)]
+pub struct InFlightForward {
+	/// Protobuf-encoded packet received from the previous hop, acknowledged once the forward
+	/// settles
+	pub original_packet: Vec<u8>,
+	/// Denomination of the funds on this chain
+	pub denom: Vec<u8>,
+	pub amount: Vec<u8>,
+	/// Account holding the funds between the hops
+	pub intermediate: Vec<u8>,
+	/// Whether the funds were unescrowed when received, rather than minted
+	pub unescrowed: bool,
+	/// JSON-encoded [`ForwardMetadata`]
+	pub forward: Vec<u8>,
+	pub retries_left: u8,
+}
+
+#[derive(Debug, Encode, Decode)]
+pub struct PacketForwardMiddleware<T>(pub PhantomData<T>);
+
+impl<T: Config> PacketForwardMiddleware<T> {
+	fn inner() -> IbcTransferModule<T> {
+		IbcTransferModule(PhantomData::<T>)
+	}
+
+	fn host_timestamp() -> Timestamp {
+		let millis: u64 = <pallet_timestamp::Pallet<T>>::get().unique_saturated_into();
+		Timestamp::from_nanoseconds(millis.saturating_mul(1_000_000)).unwrap_or_default()
+	}
+
+	fn account(address: &[u8]) -> Result<T::AccountIdConversion, String> {
+		let address = String::from_utf8_lossy(address);
+		Signer::from_str(&address)
+			.ok()
+			.and_then(|signer| T::AccountIdConversion::try_from(signer).ok())
+			.ok_or(format!("invalid account {}", address))
+	}
+
+	fn coin(in_flight: &InFlightForward) -> Result<PrefixedCoin, String> {
+		let denom = PrefixedDenom::from_str(&String::from_utf8_lossy(&in_flight.denom))
+			.map_err(|e| e.to_string())?;
+		let amount = Amount::from_str(&String::from_utf8_lossy(&in_flight.amount))
+			.map_err(|e| e.to_string())?;
+		Ok(PrefixedCoin { denom, amount })
+	}
+
+	/// Receive the funds of `packet` on the intermediate account and send them over the next hop.
+	fn receive_and_forward(
+		output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		relayer: &Signer,
+		data: FungibleTokenPacketData,
+		forward: ForwardMetadata,
+	) -> Result<(), String> {
+		if forward.port != PORT_ID_STR {
+			return Err(format!("cannot forward to port {}", forward.port))
+		}
+		ChannelId::from_str(&forward.channel).map_err(|e| e.to_string())?;
+		let intermediate = get_forward_intermediate_address(&packet.chan_on_b, &data.sender)
+			.map_err(|e| e.to_string())?;
+
+		let recv_data = FungibleTokenPacketData {
+			receiver: intermediate.to_string(),
+			memo: String::new(),
+			..data.clone()
+		};
+		let recv_packet = Packet { data: recv_data.to_bytes(), ..packet.clone() };
+		let ack = Self::inner().on_recv_packet(output, &recv_packet, relayer);
+		match GenericAcknowledgement::from_bytes(ack.as_ref()) {
+			Some(GenericAcknowledgement::Error(error)) => return Err(error),
+			None => return Err("invalid acknowledgement".to_string()),
+			Some(GenericAcknowledgement::Result(_)) => {},
+		}
+
+		let (denom, unescrowed) =
+			get_received_denom(packet, &data.denom).map_err(|e| e.to_string())?;
+
+		<T::IbcContext as ChannelKeeperInterface>::defer_packet_acknowledgement(
+			&packet.port_on_b,
+			&packet.chan_on_b,
+			&packet.sequence,
+		)
+		.map_err(|e| e.to_string())?;
+
+		let in_flight = InFlightForward {
+			original_packet: packet.encode_vec().map_err(|e| e.to_string())?,
+			denom: denom.to_string().into_bytes(),
+			amount: data.amount.into_bytes(),
+			intermediate: intermediate.to_string().into_bytes(),
+			unescrowed,
+			forward: serde_json::to_vec(&forward).map_err(|e| e.to_string())?,
+			retries_left: forward.retries.unwrap_or(DEFAULT_FORWARD_RETRIES),
+		};
+		Self::send_forward(in_flight)
+	}
+
+	/// Send the funds held by the intermediate account over the next hop.
+	fn send_forward(in_flight: InFlightForward) -> Result<(), String> {
+		let forward: ForwardMetadata =
+			serde_json::from_slice(&in_flight.forward).map_err(|e| e.to_string())?;
+		let port_id = PortId::from_str(&forward.port).map_err(|e| e.to_string())?;
+		let channel_id = ChannelId::from_str(&forward.channel).map_err(|e| e.to_string())?;
+		let coin = Self::coin(&in_flight)?;
+		let sender = Self::account(&in_flight.intermediate)?.into_account();
+		let timeout_timestamp = (Self::host_timestamp() +
+			core::time::Duration::from_nanos(forward.timeout.unwrap_or(DEFAULT_FORWARD_TIMEOUT)))
+		.map_err(|e| e.to_string())?;
+
+		let sequence = Pallet::<T>::send_outgoing(OutgoingTransfer {
+			sender,
+			port_id,
+			channel_id: channel_id.clone(),
+			coin,
+			receiver: forward.receiver.clone(),
+			memo: forward.next_memo(),
+			timeout_height: TimeoutHeight::no_timeout(),
+			timeout_timestamp,
+		})
+		.map_err(|e| format!("forward to {}: {:?}", forward.receiver, e))?;
+
+		<InFlightForwards<T>>::insert(&channel_id, sequence, in_flight);
+		Pallet::<T>::deposit_event(Event::<T>::PacketForwarded { channel_id, sequence });
+
+		Ok(())
+	}
+
+	/// Undo the receive of a failed forward: the intermediate account gives the funds back to
+	/// the escrow they came from, or burns the vouchers minted for them.
+	fn unwind(in_flight: &InFlightForward, original_packet: &Packet) -> Result<(), String> {
+		let coin = Self::coin(in_flight)?;
+		let intermediate = Self::account(&in_flight.intermediate)?;
+
+		let mut inner = Self::inner();
+		if in_flight.unescrowed {
+			let escrow = inner
+				.get_channel_escrow_address(&original_packet.port_on_b, &original_packet.chan_on_b)
+				.map_err(|e| e.to_string())?;
+			inner.send_coins(&intermediate, &escrow, &coin).map_err(|e| e.to_string())
+		} else {
+			inner.burn_coins(&intermediate, &coin).map_err(|e| e.to_string())
+		}
+	}
+
+	/// Acknowledge the packet received from the previous hop, unwinding the receive first if the
+	/// forward failed.
+	fn settle(in_flight: InFlightForward, ack: GenericAcknowledgement) -> Result<(), String> {
+		let original_packet =
+			<Packet as Protobuf<RawPacket>>::decode_vec(&in_flight.original_packet)
+				.map_err(|e| e.to_string())?;
+		let success = ack.is_success();
+		if !success {
+			Self::unwind(&in_flight, &original_packet)?;
+		}
+
+		let channel_id = original_packet.chan_on_b.clone();
+		let sequence = original_packet.sequence;
+		let event = write_acknowledgement::<T::IbcContext>(original_packet, ack.into())
+			.map_err(|e| e.to_string())?;
+		Pallet::<T>::deposit_event(Event::<T>::WriteAcknowledgement(event));
+		Pallet::<T>::deposit_event(Event::<T>::ForwardSettled { channel_id, sequence, success });
+
+		Ok(())
+	}
+}
+
+impl<T: Config> Module for PacketForwardMiddleware<T> {
+	fn on_chan_open_init(
+		&mut self,
+		order: Order,
+		connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		counterparty: &Counterparty,
+		version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		Self::inner().on_chan_open_init(
+			order,
+			connection_hops,
+			port_id,
+			channel_id,
+			counterparty,
+			version,
+		)
+	}
+
+	fn on_chan_open_try(
+		&mut self,
+		order: Order,
+		connection_hops: &[ConnectionId],
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		counterparty: &Counterparty,
+		counterparty_version: &Version,
+	) -> Result<(ModuleExtras, Version), ChannelError> {
+		Self::inner().on_chan_open_try(
+			order,
+			connection_hops,
+			port_id,
+			channel_id,
+			counterparty,
+			counterparty_version,
+		)
+	}
+
+	fn on_chan_open_ack(
+		&mut self,
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		counterparty_version: &Version,
+	) -> Result<ModuleExtras, ChannelError> {
+		Self::inner().on_chan_open_ack(port_id, channel_id, counterparty_version)
+	}
+
+	fn on_chan_open_confirm(
+		&mut self,
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Self::inner().on_chan_open_confirm(port_id, channel_id)
+	}
+
+	fn on_chan_close_init(
+		&mut self,
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Self::inner().on_chan_close_init(port_id, channel_id)
+	}
+
+	fn on_chan_close_confirm(
+		&mut self,
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<ModuleExtras, ChannelError> {
+		Self::inner().on_chan_close_confirm(port_id, channel_id)
+	}
+
+	fn on_recv_packet(
+		&mut self,
+		output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		relayer: &Signer,
+	) -> Acknowledgement {
+		let data = match FungibleTokenPacketData::from_bytes(&packet.data) {
+			Ok(data) => data,
+			Err(_) => return Self::inner().on_recv_packet(output, packet, relayer),
+		};
+		let forward = match ForwardMetadata::from_memo(&data.memo) {
+			Ok(Some(forward)) => forward,
+			Ok(None) => return Self::inner().on_recv_packet(output, packet, relayer),
+			Err(e) => return GenericAcknowledgement::error(e).into(),
+		};
+
+		match transactional(|| Self::receive_and_forward(output, packet, relayer, data, forward)) {
+			// ibc-rs requires an acknowledgement, but pallet-ibc neither stores nor reports this
+			// one since it was deferred: the acknowledgement is written once the forwarded packet
+			// settles
+			Ok(()) => GenericAcknowledgement::success([1u8]).into(),
+			Err(e) => {
+				log::error!(target: LOG_TARGET, "❌ [forward] forward packet: {}", e);
+				GenericAcknowledgement::error(e).into()
+			},
+		}
+	}
+
+	fn on_acknowledgement_packet(
+		&mut self,
+		output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		acknowledgement: &Acknowledgement,
+		relayer: &Signer,
+	) -> Result<(), PacketError> {
+		transactional(|| {
+			Self::inner()
+				.on_acknowledgement_packet(output, packet, acknowledgement, relayer)
+				.map_err(|e| e.to_string())?;
+
+			if let Some(in_flight) = <InFlightForwards<T>>::take(&packet.chan_on_a, packet.sequence)
+			{
+				let ack = GenericAcknowledgement::from_bytes(acknowledgement.as_ref())
+					.unwrap_or_else(|| GenericAcknowledgement::error("invalid acknowledgement"));
+				Self::settle(in_flight, ack)?;
+			}
+
+			Ok(())
+		})
+		.map_err(|description| {
+			log::error!(target: LOG_TARGET, "❌ [forward] settle forwarded packet: {}", description);
+			PacketError::AppModule { description }
+		})
+	}
+
+	fn on_timeout_packet(
+		&mut self,
+		output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		relayer: &Signer,
+	) -> Result<(), PacketError> {
+		transactional(|| {
+			Self::inner()
+				.on_timeout_packet(output, packet, relayer)
+				.map_err(|e| e.to_string())?;
+
+			if let Some(mut in_flight) =
+				<InFlightForwards<T>>::take(&packet.chan_on_a, packet.sequence)
+			{
+				if in_flight.retries_left > 0 {
+					in_flight.retries_left -= 1;
+					match transactional(|| Self::send_forward(in_flight.clone())) {
+						Ok(()) => return Ok(()),
+						Err(e) =>
+							log::error!(target: LOG_TARGET, "❌ [forward] retry forward: {}", e),
+					}
+				}
+				Self::settle(in_flight, GenericAcknowledgement::error("forward timed out"))?;
+			}
+
+			Ok(())
+		})
+		.map_err(|description| {
+			log::error!(target: LOG_TARGET, "❌ [forward] settle forwarded packet: {}", description);
+			PacketError::AppModule { description }
+		})
+	}
+}
+
+#[test]
+fn test_forward_metadata_from_memo() {
+	let memo = r#"{"forward":{"receiver":"cosmos1","port":"transfer","channel":"channel-1","retries":2,"next":{"forward":{"receiver":"osmo1","port":"transfer","channel":"channel-7"}}}}"#;
+	let forward = ForwardMetadata::from_memo(memo).unwrap().unwrap();
+	assert_eq!(forward.channel, "channel-1");
+	assert_eq!(forward.retries, Some(2));
+	assert_eq!(forward.timeout, None);
+
+	let next = ForwardMetadata::from_memo(&forward.next_memo()).unwrap().unwrap();
+	assert_eq!(next.receiver, "osmo1");
+	assert_eq!(next.next_memo(), "");
+
+	assert_eq!(ForwardMetadata::from_memo("").unwrap(), None);
+	assert_eq!(ForwardMetadata::from_memo("thanks").unwrap(), None);
+	assert_eq!(ForwardMetadata::from_memo(r#"{"wasm":{}}"#).unwrap(), None);
+	assert!(ForwardMetadata::from_memo(r#"{"forward":{"receiver":"cosmos1"}}"#).is_err());
+}
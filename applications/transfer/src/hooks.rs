@@ -0,0 +1,158 @@
+//! Receive hooks.
+//!
+//! A transfer received with a memo such as
+//!
+//! ```json
+//! {"hooks":{"calls":["0x0a07..."]}}
+//! ```
+//!
+//! lands on an account derived from the receiving channel and the original sender, see
+//! [`get_hook_account_address`], instead of the receiver of the packet. Each entry of `calls` is
+//! the hex of a SCALE-encoded `RuntimeCall`, which is then dispatched from that account, e.g. to
+//! stake or swap the received funds.
+//!
+//! Calls must pass [`Config::HookCallFilter`] and fit in [`Config::MaxHookWeight`]. If the memo is
+//! invalid or one of the calls fails, the receive is reverted and the packet gets an error
+//! acknowledgement, so the sender is refunded.
+use crate::{
+	callback::IbcTransferModule,
+	packet::FungibleTokenPacketData,
+	utils::{get_hook_account_address, transactional},
+	*,
+};
+use alloc::{format, string::ToString};
+use codec::DecodeLimit;
+use frame_support::{
+	dispatch::{Dispatchable, GetDispatchInfo},
+	traits::Contains,
+};
+use ibc::core::{
+	ics04_channel::{msgs::acknowledgement::Acknowledgement, packet::Packet},
+	ics26_routing::context::ModuleOutputBuilder,
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use serde::{Deserialize, Serialize};
+
+/// The `hooks` instruction of an ICS20 memo.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookMetadata {
+	/// Hex of the SCALE-encoded calls, `0x` prefix optional
+	pub calls: Vec<String>,
+}
+
+impl HookMetadata {
+	/// The hooks instruction of `memo`, `None` for memos without one or that are not JSON.
+	pub fn from_memo(memo: &str) -> Result<Option<Self>, String> {
+		let memo: serde_json::Value = match serde_json::from_str(memo) {
+			Ok(memo) => memo,
+			Err(_) => return Ok(None),
+		};
+		match memo.get("hooks") {
+			Some(hooks) => serde_json::from_value(hooks.clone())
+				.map(Some)
+				.map_err(|e| format!("invalid hooks memo: {}", e)),
+			None => Ok(None),
+		}
+	}
+
+	/// Decode the calls of the hook, checking them against the allowlist and the weight limit.
+	pub fn decode_calls<T: Config>(&self) -> Result<Vec<<T as Config>::RuntimeCall>, String> {
+		if self.calls.is_empty() {
+			return Err("hooks memo without calls".to_string())
+		}
+
+		let mut total_weight = Weight::zero();
+		let mut calls = Vec::with_capacity(self.calls.len());
+		for call in self.calls.iter() {
+			let bytes = hex::decode(call.trim_start_matches("0x"))
+				.map_err(|e| format!("invalid hook call {}: {}", call, e))?;
+			let call = <T as Config>::RuntimeCall::decode_with_depth_limit(
+				sp_api::MAX_EXTRINSIC_DEPTH,
+				&mut bytes.as_slice(),
+			)
+			.map_err(|e| format!("invalid hook call {}: {}", call, e))?;
+			if !T::HookCallFilter::contains(&call) {
+				return Err("hook call not allowed".to_string())
+			}
+			total_weight = total_weight.saturating_add(call.get_dispatch_info().weight);
+			calls.push(call);
+		}
+
+		if total_weight.any_gt(T::MaxHookWeight::get()) {
+			return Err("hook calls exceed the weight limit".to_string())
+		}
+
+		Ok(calls)
+	}
+}
+
+impl<T: Config> IbcTransferModule<T> {
+	/// Receive the funds of `packet` on the hook account of its sender, then dispatch the calls of
+	/// the hook from it, reverting everything if a step fails.
+	pub(crate) fn recv_packet_with_hook(
+		&mut self,
+		output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		relayer: &Signer,
+		data: FungibleTokenPacketData,
+		hook: HookMetadata,
+	) -> Acknowledgement {
+		let result = transactional(|| {
+			let calls = hook.decode_calls::<T>()?;
+			let signer = get_hook_account_address(&packet.chan_on_b, &data.sender)
+				.map_err(|e| e.to_string())?;
+			let account = T::AccountIdConversion::try_from(signer.clone())
+				.map_err(|_| format!("invalid hook account {}", signer))?
+				.into_account();
+
+			let recv_data = FungibleTokenPacketData {
+				receiver: signer.to_string(),
+				memo: String::new(),
+				..data.clone()
+			};
+			let recv_packet = Packet { data: recv_data.to_bytes(), ..packet.clone() };
+			let ack = ibc::applications::transfer::context::on_recv_packet(
+				self,
+				output,
+				&recv_packet,
+				relayer,
+			);
+			match GenericAcknowledgement::from_bytes(ack.as_ref()) {
+				Some(GenericAcknowledgement::Result(_)) => {},
+				Some(GenericAcknowledgement::Error(error)) => return Err(error),
+				None => return Err("invalid acknowledgement".to_string()),
+			}
+
+			let count = calls.len() as u32;
+			for call in calls {
+				let origin = frame_system::RawOrigin::Signed(account.clone()).into();
+				call.dispatch(origin).map_err(|e| format!("hook call failed: {:?}", e.error))?;
+			}
+			Pallet::<T>::deposit_event(Event::<T>::HookExecuted {
+				channel_id: packet.chan_on_b.clone(),
+				sequence: packet.sequence,
+				account,
+				calls: count,
+			});
+
+			Ok(ack)
+		});
+
+		result.unwrap_or_else(|e| {
+			log::error!(target: LOG_TARGET, "❌ [hooks] execute receive hook: {}", e);
+			GenericAcknowledgement::error(e).into()
+		})
+	}
+}
+
+#[test]
+fn test_hook_metadata_from_memo() {
+	let hook = HookMetadata::from_memo(r#"{"hooks":{"calls":["0x0a07","0b00"]}}"#)
+		.unwrap()
+		.unwrap();
+	assert_eq!(hook.calls, ["0x0a07", "0b00"]);
+
+	assert_eq!(HookMetadata::from_memo("").unwrap(), None);
+	assert_eq!(HookMetadata::from_memo(r#"{"forward":{}}"#).unwrap(), None);
+	assert!(HookMetadata::from_memo(r#"{"hooks":{"calls":"0x0a07"}}"#).is_err());
+}
@@ -1,10 +1,12 @@
-use crate::{callback::IbcTransferModule, utils::get_channel_escrow_address, *};
-use alloc::{format, string::ToString};
-use codec::{Decode, Encode};
-use frame_support::traits::{
-	fungibles::{Mutate, Transfer},
-	ExistenceRequirement::AllowDeath,
+use crate::{
+	address::{AddressCodec, HexCodec},
+	assets::LocalAsset,
+	callback::IbcTransferModule,
+	*,
 };
+use alloc::string::ToString;
+use codec::{Decode, Encode};
+use frame_support::traits::fungibles::{Mutate, Transfer};
 use ibc::{
 	applications::transfer::{
 		context::{BankKeeper, TokenTransferContext, TokenTransferReader},
@@ -16,7 +18,6 @@ use ibc::{
 };
 use log::error;
 use pallet_ibc_utils::AssetIdAndNameProvider;
-use primitive_types::U256;
 use scale_info::TypeInfo;
 use sp_runtime::{
 	traits::{CheckedConversion, IdentifyAccount, Verify},
@@ -33,25 +34,22 @@ impl<T: Config> BankKeeper for IbcTransferModule<T> {
 		to: &Self::AccountId,
 		amt: &PrefixedCoin,
 	) -> Result<(), TokenTransferError> {
-		let is_native_asset = amt.denom.trace_path.is_empty();
-		match is_native_asset {
+		let local_asset = Pallet::<T>::local_asset(&amt.denom).ok_or_else(|| {
+			error!("❌ [send_coins]: denom without local asset: ({})", amt.denom);
+			TokenTransferError::InvalidToken
+		})?;
+		let amount = Pallet::<T>::local_amount(&amt.denom, amt.amount, true)?;
+		match local_asset {
 			// transfer native token
-			true => {
-				let amount = U256::from(amt.amount).low_u128().checked_into().unwrap(); // TODO: FIX IN THE FUTURE
-				let native_token_name = T::NATIVE_TOKEN_NAME;
-				let ibc_token_name = amt.denom.base_denom.as_str().as_bytes();
-
-				// assert native token name equal want to send ibc token name
-				assert_eq!(
-					native_token_name, ibc_token_name,
-					"send ibc token name is not native token name"
-				);
-
+			LocalAsset::Native => {
+				let existence =
+					Pallet::<T>::native_existence_requirement(&from.clone().into_account(), amount);
+				let amount = amount.checked_into().ok_or(TokenTransferError::InvalidToken)?;
 				<T::Currency as Currency<T::AccountId>>::transfer(
 					&from.clone().into_account(),
 					&to.clone().into_account(),
 					amount,
-					AllowDeath,
+					existence,
 				)
 				.map_err(|error| {
 					error!(
@@ -69,38 +67,40 @@ impl<T: Config> BankKeeper for IbcTransferModule<T> {
 				))
 			},
 			// transfer non-native token
-			false => {
-				let amount = U256::from(amt.amount).low_u128().into();
-				let denom = amt.denom.base_denom.as_str();
-				// look cross chain asset have register in host chain
-				match T::AssetIdByName::try_get_asset_id(denom) {
-					Ok(token_id) => {
-						<T::Fungibles as Transfer<T::AccountId>>::transfer(
-							token_id,
-							&from.clone().into_account(),
-							&to.clone().into_account(),
-							amount,
-							true,
-						)
-						.map_err(|error| {
-							error!("❌ [send_coins] : Error: ({:?})", error);
-							TokenTransferError::InvalidToken
-						})?;
+			LocalAsset::Asset(token_id) => {
+				let keep_alive = Pallet::<T>::escrow_keep_alive(
+					&from.clone().into_account(),
+					local_asset,
+					amount,
+				)
+				.unwrap_or(true);
+				let amount = amount.into();
+				<T::Fungibles as Transfer<T::AccountId>>::transfer(
+					token_id,
+					&from.clone().into_account(),
+					&to.clone().into_account(),
+					amount,
+					keep_alive,
+				)
+				.map_err(|error| {
+					error!("❌ [send_coins] : Error: ({:?})", error);
+					TokenTransferError::InvalidToken
+				})?;
 
-						// add emit transfer no native token event
-						Pallet::<T>::deposit_event(Event::<T>::TransferNoNativeToken(
-							from.clone(),
-							to.clone(),
-							amount,
-						));
-					},
-					Err(_error) => {
-						error!("❌ [send_coins]: denom: ({:?})", denom);
-						return Err(TokenTransferError::InvalidToken)
-					},
-				}
+				// add emit transfer no native token event
+				Pallet::<T>::deposit_event(Event::<T>::TransferNoNativeToken(
+					from.clone(),
+					to.clone(),
+					amount,
+				));
 			},
 		}
+		Pallet::<T>::record_escrow_transfer(
+			&from.clone().into_account(),
+			&to.clone().into_account(),
+			&amt.denom,
+			amount,
+		)?;
 
 		Ok(())
 	}
@@ -110,17 +110,18 @@ impl<T: Config> BankKeeper for IbcTransferModule<T> {
 		account: &Self::AccountId,
 		amt: &PrefixedCoin,
 	) -> Result<(), TokenTransferError> {
-		let amount = U256::from(amt.amount).low_u128().into();
-		let denom = amt.denom.base_denom.as_str();
-		let denom_trace_hash =
-			crate::utils::derive_ibc_denom_with_path(&format!("{}", amt.denom.trace_path.clone()))?
-				.as_bytes()
-				.to_vec();
-		let denom_trace = crate::denom::PrefixedDenom::from(amt.denom.clone());
-		// insert denom trace hash, and demo_trace
-		<DenomTrace<T>>::insert(denom_trace_hash, denom_trace);
-		// look cross chain asset have register in host chain
-		match T::AssetIdByName::try_get_asset_id(denom) {
+		// the dust below one local unit is not minted
+		let amount = Pallet::<T>::local_amount(&amt.denom, amt.amount, false)?;
+		if amount == 0 {
+			error!("❌ [mint_coins]: amount below one unit of ({})", amt.denom);
+			return Err(TokenTransferError::InvalidToken)
+		}
+		let amount = amount.into();
+		let denom = amt.denom.to_string();
+		// record the trace of the voucher under its ibc denom
+		Pallet::<T>::store_denom_trace(&amt.denom)?;
+		// look cross chain asset have register in host chain, registering it otherwise
+		match Pallet::<T>::ensure_asset(&amt.denom) {
 			Ok(token_id) => {
 				<T::Fungibles as Mutate<T::AccountId>>::mint_into(
 					token_id,
@@ -132,6 +133,8 @@ impl<T: Config> BankKeeper for IbcTransferModule<T> {
 					TokenTransferError::InvalidToken
 				})?;
 
+				Pallet::<T>::record_mint(&amt.denom, amount.into())?;
+
 				// add mint token event
 				Pallet::<T>::deposit_event(Event::<T>::MintToken(
 					token_id,
@@ -139,8 +142,8 @@ impl<T: Config> BankKeeper for IbcTransferModule<T> {
 					amount,
 				));
 			},
-			Err(_error) => {
-				error!("❌ [mint_coins]: denom: ({:?})", denom);
+			Err(error) => {
+				error!("❌ [mint_coins]: denom: ({:?}), Error: ({:?})", denom, error);
 				return Err(TokenTransferError::InvalidToken)
 			},
 		}
@@ -152,10 +155,15 @@ impl<T: Config> BankKeeper for IbcTransferModule<T> {
 		account: &Self::AccountId,
 		amt: &PrefixedCoin,
 	) -> Result<(), TokenTransferError> {
-		let amount = U256::from(amt.amount).low_u128().into();
-		let denom = amt.denom.base_denom.as_str();
+		let amount = Pallet::<T>::local_amount(&amt.denom, amt.amount, true)?.into();
+		// only vouchers are burnt, tokens sourced on this chain are escrowed instead
+		if amt.denom.trace_path.is_empty() {
+			error!("❌ [burn_coins]: burn unprefixed denom: ({})", amt.denom);
+			return Err(TokenTransferError::InvalidToken)
+		}
+		let denom = Pallet::<T>::asset_name(&amt.denom);
 		// look cross chain asset have register in host chain
-		match T::AssetIdByName::try_get_asset_id(denom) {
+		match T::AssetIdByName::try_get_asset_id(&denom) {
 			Ok(token_id) => {
 				<T::Fungibles as Mutate<T::AccountId>>::burn_from(
 					token_id,
@@ -167,6 +175,8 @@ impl<T: Config> BankKeeper for IbcTransferModule<T> {
 					TokenTransferError::InvalidToken
 				})?;
 
+				Pallet::<T>::record_burn(&amt.denom, amount.into())?;
+
 				// add burn token event
 				Pallet::<T>::deposit_event(Event::<T>::BurnToken(
 					token_id,
@@ -198,19 +208,15 @@ impl<T: Config> TokenTransferReader for IbcTransferModule<T> {
 		port_id: &PortId,
 		channel_id: &ChannelId,
 	) -> Result<Self::AccountId, TokenTransferError> {
-		get_channel_escrow_address(port_id, channel_id)?
-			.try_into()
-			.map_err(|_| TokenTransferError::ParseAccountFailure)
+		Ok(Pallet::<T>::escrow_account(port_id, channel_id).into())
 	}
 
 	fn is_send_enabled(&self) -> bool {
-		// TODO(davirain), need according channelEnd def
-		true
+		<GlobalSwitch<T>>::get().send
 	}
 
 	fn is_receive_enabled(&self) -> bool {
-		// TODO(davirain), need according channelEnd def
-		true
+		<GlobalSwitch<T>>::get().receive
 	}
 }
 
@@ -228,6 +234,12 @@ pub type AccountId = <<Signature as Verify>::Signer as IdentifyAccount>::Account
 #[derive(Clone, Debug, PartialEq, TypeInfo, Encode, Decode)]
 pub struct IbcAccount(AccountId);
 
+impl From<AccountId> for IbcAccount {
+	fn from(account: AccountId) -> Self {
+		Self(account)
+	}
+}
+
 impl IdentifyAccount for IbcAccount {
 	type AccountId = AccountId;
 	fn into_account(self) -> Self::AccountId {
@@ -242,23 +254,13 @@ where
 	type Error = &'static str;
 
 	/// Convert a signer to an IBC account.
-	/// Only valid hex strings are supported for now.
+	/// Only hex strings are supported, see [`crate::address::CodecAccount`] for other formats.
 	fn try_from(signer: Signer) -> Result<Self, Self::Error> {
-		let acc_str = signer.as_ref();
-		if acc_str.starts_with("0x") {
-			match acc_str.strip_prefix("0x") {
-				Some(hex_string) => TryInto::<[u8; 32]>::try_into(
-					hex::decode(hex_string).map_err(|_| "Error decoding invalid hex string")?,
-				)
-				.map_err(|_| "Invalid account id hex string")
-				.map(|acc| Self(acc.into())),
-				_ => Err("Signer does not hold a valid hex string"),
-			}
-		}
-		// Do SS58 decoding instead
-		else {
-			error!("Convert Signer ❌ : Failed! ");
-			Err("invalid ibc address or substrate address")
-		}
+		<HexCodec as AddressCodec<AccountId>>::decode(signer.as_ref())
+			.map(Self)
+			.ok_or_else(|| {
+				error!("Convert Signer ❌ : Failed! ");
+				"invalid ibc address or substrate address"
+			})
 	}
 }
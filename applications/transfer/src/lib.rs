@@ -7,10 +7,25 @@ extern crate alloc;
 /// <https://docs.substrate.io/reference/frame-pallets/>
 pub use pallet::*;
 
+pub mod address;
+pub mod amount;
+pub mod assets;
 pub mod callback;
 pub mod context_channel;
 pub mod denom;
+pub mod escrow;
+pub mod forward;
+pub mod hooks;
 pub mod impls;
+pub mod migrations;
+pub mod packet;
+pub mod pending;
+pub mod policy;
+pub mod ratelimit;
+pub mod supply;
+pub mod switches;
+pub mod timeout;
+pub mod transfer;
 pub mod utils;
 
 #[cfg(test)]
@@ -19,28 +34,35 @@ mod tests;
 #[cfg(test)]
 mod mock;
 
-use crate::callback::IbcTransferModule;
-use alloc::string::String;
+use crate::{address::AddressValidator, callback::IbcTransferModule};
+use alloc::string::{String, ToString};
 use frame_support::{
+	dispatch::{Dispatchable, GetDispatchInfo, PostDispatchInfo},
 	pallet_prelude::*,
 	traits::{
 		fungibles::{Mutate, Transfer},
 		tokens::{AssetId, Balance as AssetBalance},
-		Currency,
+		Contains, Currency,
 	},
 };
 use frame_system::pallet_prelude::*;
 use ibc::{
-	applications::transfer::msgs::transfer::MsgTransfer,
-	core::ics04_channel::events::SendPacket,
+	applications::transfer::{is_sender_chain_source, msgs::transfer::MsgTransfer, PrefixedCoin},
+	core::{
+		ics04_channel::{
+			events::{SendPacket, WriteAcknowledgement},
+			packet::Sequence,
+		},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
 	events::IbcEvent,
 	handler::{HandlerOutput, HandlerOutputBuilder},
 	signer::Signer,
 };
 use ibc_proto::google::protobuf::Any;
-use pallet_ibc_utils::{AssetIdAndNameProvider, Router};
-use sp_runtime::traits::IdentifyAccount;
-use sp_std::{fmt::Debug, vec::Vec};
+use pallet_ibc_utils::{migration::Cursor, traits::ChannelReaderInterface, AssetIdAndNameProvider};
+use sp_runtime::traits::{AtLeast32BitUnsigned, IdentifyAccount, Zero};
+use sp_std::{fmt::Debug, str::FromStr, vec::Vec};
 
 pub const LOG_TARGET: &str = "runtime::pallet-ics20-transfer";
 
@@ -51,8 +73,14 @@ type BalanceOf<T> =
 pub mod pallet {
 	use super::*;
 
+	/// The current storage version.
+	const STORAGE_VERSION: StorageVersion = StorageVersion::new(4);
+
+	/// The storage items keyed or valued by ibc-rs types or names, which have no `MaxEncodedLen`,
+	/// are `unbounded`; their identifiers are bounded by ibc-rs validation. The denomination
+	/// traces and the migration cursor are bounded.
 	#[pallet::pallet]
-	#[pallet::without_storage_info]
+	#[pallet::storage_version(STORAGE_VERSION)]
 	pub struct Pallet<T>(_);
 
 	/// Configure the pallet by specifying the parameters and types on which it depends.
@@ -71,7 +99,7 @@ pub mod pallet {
 		type Currency: Currency<Self::AccountId>;
 
 		/// Identifier for the class of asset.
-		type AssetId: AssetId + MaybeSerializeDeserialize + Default;
+		type AssetId: AssetId + MaybeSerializeDeserialize + Default + AtLeast32BitUnsigned;
 
 		/// The units in which we record balances.
 		type AssetBalance: AssetBalance + From<u128> + Into<u128>;
@@ -83,13 +111,68 @@ pub mod pallet {
 		/// Map of cross-chain asset ID & name
 		type AssetIdByName: AssetIdAndNameProvider<Self::AssetId>;
 
-		/// Account Id Conversion from SS58 string or hex string
+		/// Creates the local assets of denominations received for the first time
+		type AssetRegistrar: assets::AssetRegistrar<Self::AssetId, Self::AssetBalance>;
+
+		/// First asset id given to automatically registered assets
+		#[pallet::constant]
+		type AutoAssetIdStart: Get<Self::AssetId>;
+
+		/// Minimum balance of automatically registered assets
+		#[pallet::constant]
+		type AutoAssetMinBalance: Get<Self::AssetBalance>;
+
+		/// Account Id Conversion from SS58 string or hex string, e.g. an
+		/// [`address::CodecAccount`] with the address codecs of the chain
 		type AccountIdConversion: TryFrom<Signer>
+			+ From<Self::AccountId>
 			+ IdentifyAccount<AccountId = Self::AccountId>
 			+ Clone
 			+ PartialEq
 			+ Debug;
 
+		/// Checks the receivers of outgoing transfers before their funds are escrowed or burnt
+		type ReceiverValidator: address::AddressValidator;
+
+		/// Decides whether transfers are allowed, before the funds of sends are escrowed or burnt
+		/// and the funds of receives minted or unescrowed, e.g. `()` to allow all
+		type TransferPolicy: policy::TransferPolicy<Self::AccountId>;
+
+		/// The calls receive hooks may dispatch
+		type RuntimeCall: Parameter
+			+ Dispatchable<
+				RuntimeOrigin = <Self as frame_system::Config>::RuntimeOrigin,
+				PostInfo = PostDispatchInfo,
+			> + GetDispatchInfo;
+
+		/// Allowlist of calls receive hooks may dispatch
+		type HookCallFilter: Contains<<Self as Config>::RuntimeCall>;
+
+		/// Maximum total weight of the calls of a single receive hook
+		#[pallet::constant]
+		type MaxHookWeight: Get<Weight>;
+
+		/// Blocks of the counterparty chain after its latest known height that transfers setting no
+		/// timeout time out at, zero for no timeout height
+		#[pallet::constant]
+		type DefaultTimeoutHeightOffset: Get<u64>;
+
+		/// Nanoseconds after the latest known timestamp of the counterparty chain that transfers
+		/// setting no timeout time out at, zero for no timeout timestamp
+		#[pallet::constant]
+		type DefaultTimeoutTimestampOffset: Get<u64>;
+
+		/// Maximum length of the trace path and of the base denomination of a voucher
+		#[pallet::constant]
+		type MaxTraceLength: Get<u32>;
+
+		/// Id of the pallet, whose sub-accounts escrow the tokens sent over each channel
+		#[pallet::constant]
+		type PalletId: Get<frame_support::PalletId>;
+
+		/// Origin managing the transfer module, e.g. its rate limits
+		type AdminOrigin: EnsureOrigin<<Self as frame_system::Config>::RuntimeOrigin>;
+
 		type IbcContext: pallet_ibc_utils::traits::ChannelKeeperInterface
 			+ pallet_ibc_utils::traits::ChannelReaderInterface;
 
@@ -100,14 +183,180 @@ pub mod pallet {
 	type AssetName = Vec<u8>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// (asset name) => asset id
 	pub type AssetIdByName<T: Config> =
 		StorageMap<_, Twox64Concat, AssetName, T::AssetId, ValueQuery>;
 
 	#[pallet::storage]
-	// key: denom trace hash
+	#[pallet::unbounded]
+	/// (denom) => asset issued on this chain that is sent out under the denom
+	pub type NativeAssets<T: Config> = StorageMap<_, Twox64Concat, AssetName, T::AssetId>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (full path of the denom) => decimals of its asset locally and in ICS20 packets, when they
+	/// differ
+	pub type DecimalConversions<T: Config> =
+		StorageMap<_, Twox64Concat, AssetName, amount::DecimalConversion>;
+
+	#[pallet::storage]
+	/// next asset id given to an automatically registered asset
+	pub type NextAssetId<T: Config> = StorageValue<_, T::AssetId>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (asset name) => metadata of the asset registered for it, overriding the derived one
+	pub type DenomMetadata<T: Config> =
+		StorageMap<_, Twox64Concat, AssetName, assets::AssetMetadata<T::AssetBalance>>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (asset name) => () if the denomination may not be received
+	pub type BlockedDenoms<T: Config> = StorageMap<_, Twox64Concat, AssetName, ()>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (asset id) => asset name, the reverse of `AssetIdByName`
+	pub type AssetNameById<T: Config> = StorageMap<_, Twox64Concat, T::AssetId, AssetName>;
+
+	#[pallet::storage]
+	// key: `ibc/{hash}` denom of a voucher, hash of its full path
 	// value: denom trace
-	pub type DenomTrace<T: Config> = StorageMap<_, Blake2_128Concat, Vec<u8>, denom::PrefixedDenom>;
+	pub type DenomTrace<T: Config> =
+		StorageMap<_, Blake2_128Concat, denom::IbcDenom, denom::PrefixedDenom<T::MaxTraceLength>>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (port id, channel id) => escrow account of the channel
+	pub type EscrowAccounts<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, PortId, Blake2_128Concat, ChannelId, T::AccountId>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (escrow account) => (port id, channel id) it escrows for, the reverse of `EscrowAccounts`
+	pub type EscrowAccountChannels<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, (PortId, ChannelId)>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (channel id, sequence) of a forwarded packet => the transfer it forwards
+	pub type InFlightForwards<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		ChannelId,
+		Blake2_128Concat,
+		Sequence,
+		forward::InFlightForward,
+	>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (channel id, denom) => rate limit of the flows of the denom over the channel
+	pub type RateLimits<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		ChannelId,
+		Blake2_128Concat,
+		Vec<u8>,
+		ratelimit::RateLimit<T::BlockNumber>,
+	>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (channel id, sequence) of a rate limited send => end of the window it was sent in
+	pub type RateLimitedSends<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		ChannelId,
+		Blake2_128Concat,
+		Sequence,
+		T::BlockNumber,
+	>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// ((port id, channel id), sequence) of a sent packet => the transfer it sends, until it is
+	/// settled
+	pub type PendingTransfers<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		(PortId, ChannelId),
+		Blake2_128Concat,
+		Sequence,
+		pending::PendingTransfer<T::AccountId, T::BlockNumber>,
+	>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (sender, ((port id, channel id), sequence)) => () for the transfers in `PendingTransfers`
+	pub type PendingTransfersBySender<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		T::AccountId,
+		Blake2_128Concat,
+		((PortId, ChannelId), Sequence),
+		(),
+	>;
+
+	#[pallet::storage]
+	/// whether transfers may be sent and received at all
+	pub type GlobalSwitch<T: Config> = StorageValue<_, switches::Switch, ValueQuery>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (channel id) => whether transfers may be sent and received over the channel, if not both
+	pub type ChannelSwitches<T: Config> =
+		StorageMap<_, Blake2_128Concat, ChannelId, switches::Switch>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (denom) => whether the denom may be sent and received, if not both
+	pub type DenomSwitches<T: Config> = StorageMap<_, Blake2_128Concat, Vec<u8>, switches::Switch>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (target) => rule of the list transfer policy on the target
+	pub type PolicyRules<T: Config> =
+		StorageMap<_, Blake2_128Concat, policy::PolicyTarget<T::AccountId>, policy::PolicyRule>;
+
+	#[pallet::storage]
+	/// (kind) => () if the list transfer policy only allows the allowlisted targets of the kind
+	pub type StrictPolicyKinds<T: Config> = StorageMap<_, Twox64Concat, policy::PolicyKind, ()>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (channel id, sequence) of a received packet => the transfer it received, held for review
+	pub type HeldTransfers<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		ChannelId,
+		Blake2_128Concat,
+		Sequence,
+		policy::HeldTransfer,
+	>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (channel id, denom) => amount of the denom held by the escrow account of the channel
+	pub type EscrowTotals<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		ChannelId,
+		Blake2_128Concat,
+		Vec<u8>,
+		u128,
+		ValueQuery,
+	>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// (`ibc/{hash}` denom) => amount of vouchers of the denom minted and not burnt
+	pub type VoucherSupply<T: Config> = StorageMap<_, Blake2_128Concat, Vec<u8>, u128, ValueQuery>;
+
+	#[pallet::storage]
+	/// Cursor of the stepped migration in progress, see [`migrations`]
+	pub type MigrationCursor<T: Config> = StorageValue<_, Cursor>;
 
 	#[pallet::genesis_config]
 	pub struct GenesisConfig<T: Config> {
@@ -126,6 +375,7 @@ pub mod pallet {
 		fn build(&self) {
 			for (token_id, id) in self.asset_id_by_name.iter() {
 				<AssetIdByName<T>>::insert(token_id.as_bytes(), id);
+				<AssetNameById<T>>::insert(id, token_id.as_bytes());
 			}
 		}
 	}
@@ -151,6 +401,139 @@ pub mod pallet {
 		BurnToken(T::AssetId, T::AccountIdConversion, T::AssetBalance),
 		/// Mint chairperson token event
 		MintToken(T::AssetId, T::AccountIdConversion, T::AssetBalance),
+		/// Write acknowledgement event, for the deferred acknowledgement of a forwarded packet
+		WriteAcknowledgement(WriteAcknowledgement),
+		/// A received transfer was forwarded over the next hop
+		PacketForwarded {
+			channel_id: ChannelId,
+			sequence: Sequence,
+		},
+		/// The acknowledgement of a forwarded transfer was written
+		ForwardSettled {
+			channel_id: ChannelId,
+			sequence: Sequence,
+			success: bool,
+		},
+		/// The calls of the receive hook of a transfer were dispatched from `account`
+		HookExecuted {
+			channel_id: ChannelId,
+			sequence: Sequence,
+			account: T::AccountId,
+			calls: u32,
+		},
+		/// A rate limit was set on the flows of `denom` over `channel_id`
+		RateLimitSet {
+			channel_id: ChannelId,
+			denom: Vec<u8>,
+			quota: ratelimit::Quota<T::BlockNumber>,
+		},
+		/// The rate limit of `denom` over `channel_id` was removed
+		RateLimitRemoved {
+			channel_id: ChannelId,
+			denom: Vec<u8>,
+		},
+		/// The flows of the current window of a rate limit were cleared
+		RateLimitReset {
+			channel_id: ChannelId,
+			denom: Vec<u8>,
+		},
+		/// A local asset was registered for a received denomination
+		AssetRegistered {
+			denom: Vec<u8>,
+			asset_id: T::AssetId,
+		},
+		/// The metadata of the asset of `denom` was set
+		DenomMetadataSet {
+			denom: Vec<u8>,
+		},
+		/// Receiving `denom` was blocked or unblocked
+		DenomBlockedSet {
+			denom: Vec<u8>,
+			blocked: bool,
+		},
+		/// `denom` was mapped to an existing asset
+		DenomMapped {
+			denom: Vec<u8>,
+			asset_id: T::AssetId,
+		},
+		/// The sufficiency of an asset was set
+		AssetSufficiencySet {
+			asset_id: T::AssetId,
+			is_sufficient: bool,
+		},
+		/// The asset sent out under `denom` was set
+		NativeAssetSet {
+			denom: Vec<u8>,
+			asset_id: Option<T::AssetId>,
+		},
+		/// The decimal conversion of `denom` was set
+		DecimalConversionSet {
+			denom: Vec<u8>,
+			conversion: Option<amount::DecimalConversion>,
+		},
+		/// The escrow account of a channel was registered
+		EscrowAccountRegistered {
+			port_id: PortId,
+			channel_id: ChannelId,
+			account: T::AccountId,
+		},
+		/// The switch of the transfers of `scope` was set
+		SwitchSet {
+			scope: switches::SwitchScope,
+			switch: switches::Switch,
+		},
+		/// The rule of the list transfer policy on `target` was set
+		PolicyRuleSet {
+			target: policy::PolicyTarget<T::AccountId>,
+			rule: Option<policy::PolicyRule>,
+		},
+		/// Whether the list transfer policy only allows the allowlisted targets of `kind` was set
+		PolicyStrictSet {
+			kind: policy::PolicyKind,
+			strict: bool,
+		},
+		/// The transfer received with packet `sequence` over `channel_id` was held for review
+		TransferHeld {
+			channel_id: ChannelId,
+			sequence: Sequence,
+		},
+		/// The held transfer received with packet `sequence` over `channel_id` was released to
+		/// `receiver`
+		HeldTransferReleased {
+			channel_id: ChannelId,
+			sequence: Sequence,
+			receiver: T::AccountId,
+		},
+		/// The held transfer received with packet `sequence` over `channel_id` was returned to
+		/// its sender with packet `return_sequence`
+		HeldTransferReturned {
+			channel_id: ChannelId,
+			sequence: Sequence,
+			return_sequence: Sequence,
+		},
+		/// The transfer sent with packet `sequence` over `port_id/channel_id` was acknowledged
+		TransferSucceeded {
+			port_id: PortId,
+			channel_id: ChannelId,
+			sequence: Sequence,
+			sender: T::AccountId,
+		},
+		/// The transfer sent with packet `sequence` over `port_id/channel_id` failed and was
+		/// refunded
+		TransferRefunded {
+			port_id: PortId,
+			channel_id: ChannelId,
+			sequence: Sequence,
+			sender: T::AccountId,
+		},
+		/// The transfer sent with packet `sequence` over `port_id/channel_id` failed and could
+		/// not be refunded
+		TransferFailed {
+			port_id: PortId,
+			channel_id: ChannelId,
+			sequence: Sequence,
+			sender: T::AccountId,
+		},
 	}
 
 	// Errors inform users that something went wrong.
@@ -164,6 +547,56 @@ pub mod pallet {
 		WrongAssetId,
 		///
 		DecodeStringFailed,
+		/// The transfer exceeds the rate limit of its channel and denom
+		RateLimitExceeded,
+		/// No rate limit is set for the channel and denom
+		RateLimitNotFound,
+		/// The window of a rate limit is zero
+		InvalidQuota,
+		/// The source channel of the transfer does not exist
+		InvalidChannel,
+		/// The denomination is blocked
+		DenomBlocked,
+		/// The amount overflows or has dust in local units
+		InvalidAmount,
+		/// The receiver is not a valid address of the counterparty chain
+		InvalidReceiver,
+		/// The sender of the transfer is not the signer
+		InvalidSender,
+		/// The transfer has no valid timeout
+		InvalidTimeout,
+		/// The timeout of the transfer has already passed on the counterparty chain
+		TimeoutExpired,
+		/// The funds of the transfer could not be escrowed or burnt, or its packet sent
+		TransferFailed,
+		/// Sending the denomination over the channel is disabled
+		SendDisabled,
+		/// Receiving the denomination over the channel is disabled
+		ReceiveDisabled,
+		/// The transfer policy denies the transfer
+		TransferDenied,
+		/// The transfer policy holds the transfer, which cannot be sent
+		TransferOnHold,
+		/// No transfer is held for the channel and sequence
+		HeldTransferNotFound,
+		/// Tokens of the denomination are escrowed, minted or in flight
+		DenomInUse,
+		/// No transfer is pending for the port, channel and sequence
+		PendingTransferNotFound,
+		/// The refund of the pending transfer did not fail
+		TransferNotRefundable,
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_idle(_n: BlockNumberFor<T>, remaining_weight: Weight) -> Weight {
+			Self::resume_migration(remaining_weight)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn try_state(_n: BlockNumberFor<T>) -> Result<(), &'static str> {
+			Self::do_try_state()
+		}
 	}
 
 	// Dispatchable functions allows users to interact with the pallet and invoke state changes.
@@ -179,7 +612,8 @@ pub mod pallet {
 		/// Handling transfer request as sending chain or receiving chain.
 		///
 		/// Parameters:
-		/// - `messages`: A serialized protocol buffer message containing the transfer request.
+		/// - `messages`: A serialized protocol buffer message containing the transfer request,
+		///   whose sender must be the signer.
 		///
 		/// The relevant events are emitted when successful.
 		#[pallet::call_index(0)]
@@ -188,12 +622,380 @@ pub mod pallet {
 			origin: OriginFor<T>,
 			messages: Vec<Any>,
 		) -> DispatchResultWithPostInfo {
-			let _sender = ensure_signed(origin)?;
+			let sender = ensure_signed(origin)?;
 
-			let _ = Self::dispatch(messages)?;
+			Self::send_raw_transfers(&sender, messages)?;
 
 			Ok(().into())
 		}
+
+		/// ICS20 fungible token transfer of a local asset from the signer.
+		///
+		/// Parameters:
+		/// - `asset_id`: The local asset to send, the native token for `None`.
+		/// - `amount`: The amount to send, in local units.
+		/// - `receiver`: The receiver on the counterparty chain.
+		/// - `source_channel`: The channel of the transfer port to send over.
+		/// - `timeout`: When the transfer times out on the counterparty chain, by default a while
+		///   after the latest height and timestamp known of it.
+		/// - `memo`: The memo of the transfer, e.g. forward or hook instructions.
+		#[pallet::call_index(10)]
+		#[pallet::weight(0)]
+		pub fn transfer(
+			origin: OriginFor<T>,
+			asset_id: Option<T::AssetId>,
+			amount: u128,
+			receiver: Vec<u8>,
+			source_channel: ChannelId,
+			timeout: transfer::TransferTimeout,
+			memo: Vec<u8>,
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+			let denom = Self::asset_denom(asset_id).ok_or(Error::<T>::InvalidTokenId)?;
+			let packet_amount =
+				Self::packet_amount(&denom, amount).map_err(|_| Error::<T>::InvalidAmount)?;
+			let receiver = String::from_utf8(receiver).map_err(|_| Error::<T>::InvalidReceiver)?;
+			let memo = String::from_utf8(memo).map_err(|_| Error::<T>::DecodeStringFailed)?;
+			let (timeout_height, timeout_timestamp) =
+				timeout.to_packet_timeout().ok_or(Error::<T>::InvalidTimeout)?;
+
+			let sequence = Self::send_outgoing(transfer::OutgoingTransfer {
+				sender: sender.clone(),
+				port_id: PortId::transfer(),
+				channel_id: source_channel.clone(),
+				coin: PrefixedCoin { denom: denom.clone(), amount: packet_amount },
+				receiver: receiver.clone(),
+				memo,
+				timeout_height,
+				timeout_timestamp,
+			})?;
+			Self::track_transfer(
+				&PortId::transfer(),
+				&source_channel,
+				sequence,
+				sender,
+				receiver.into_bytes(),
+				denom.to_string().into_bytes(),
+				amount,
+			);
+
+			Ok(())
+		}
+
+		/// Set the rate limit of `denom` over `channel_id`, keeping the flows of the current
+		/// window if one is already set.
+		#[pallet::call_index(1)]
+		#[pallet::weight(0)]
+		pub fn set_rate_limit(
+			origin: OriginFor<T>,
+			channel_id: ChannelId,
+			denom: Vec<u8>,
+			quota: ratelimit::Quota<T::BlockNumber>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(!quota.window.is_zero(), Error::<T>::InvalidQuota);
+
+			<RateLimits<T>>::mutate(&channel_id, &denom, |limit| match limit {
+				Some(limit) => limit.quota = quota,
+				None => *limit = Some(ratelimit::RateLimit::new(quota)),
+			});
+			Self::deposit_event(Event::<T>::RateLimitSet { channel_id, denom, quota });
+
+			Ok(())
+		}
+
+		/// Remove the rate limit of `denom` over `channel_id`.
+		#[pallet::call_index(2)]
+		#[pallet::weight(0)]
+		pub fn remove_rate_limit(
+			origin: OriginFor<T>,
+			channel_id: ChannelId,
+			denom: Vec<u8>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(
+				<RateLimits<T>>::contains_key(&channel_id, &denom),
+				Error::<T>::RateLimitNotFound
+			);
+
+			<RateLimits<T>>::remove(&channel_id, &denom);
+			Self::deposit_event(Event::<T>::RateLimitRemoved { channel_id, denom });
+
+			Ok(())
+		}
+
+		/// Clear the flows of the current window of the rate limit of `denom` over `channel_id`.
+		#[pallet::call_index(3)]
+		#[pallet::weight(0)]
+		pub fn reset_rate_limit(
+			origin: OriginFor<T>,
+			channel_id: ChannelId,
+			denom: Vec<u8>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			<RateLimits<T>>::try_mutate(&channel_id, &denom, |limit| {
+				let limit = limit.as_mut().ok_or(Error::<T>::RateLimitNotFound)?;
+				limit.flow.inflow = 0;
+				limit.flow.outflow = 0;
+				Ok::<_, Error<T>>(())
+			})?;
+			Self::deposit_event(Event::<T>::RateLimitReset { channel_id, denom });
+
+			Ok(())
+		}
+
+		/// Set the metadata of the asset created for `denom` when it is first received, instead of
+		/// the one derived from its trace. `None` restores the derived metadata.
+		#[pallet::call_index(4)]
+		#[pallet::weight(0)]
+		pub fn set_denom_metadata(
+			origin: OriginFor<T>,
+			denom: Vec<u8>,
+			metadata: Option<assets::AssetMetadata<T::AssetBalance>>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			<DenomMetadata<T>>::set(&denom, metadata);
+			Self::deposit_event(Event::<T>::DenomMetadataSet { denom });
+
+			Ok(())
+		}
+
+		/// Block or unblock receiving `denom`.
+		#[pallet::call_index(5)]
+		#[pallet::weight(0)]
+		pub fn set_denom_blocked(
+			origin: OriginFor<T>,
+			denom: Vec<u8>,
+			blocked: bool,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			if blocked {
+				<BlockedDenoms<T>>::insert(&denom, ());
+			} else {
+				<BlockedDenoms<T>>::remove(&denom);
+			}
+			Self::deposit_event(Event::<T>::DenomBlockedSet { denom, blocked });
+
+			Ok(())
+		}
+
+		/// Map `denom` to the existing asset `asset_id`, replacing any registered asset.
+		///
+		/// Both the asset `denom` was mapped to and the denomination `asset_id` was mapped to are
+		/// unmapped, and neither may have tokens outstanding.
+		#[pallet::call_index(6)]
+		#[pallet::weight(0)]
+		pub fn map_denom(
+			origin: OriginFor<T>,
+			denom: Vec<u8>,
+			asset_id: T::AssetId,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			let old_name = <AssetNameById<T>>::get(asset_id);
+			let in_use = Self::has_outstanding_supply_of_asset;
+			ensure!(
+				!in_use(&denom) && !old_name.as_deref().map_or(false, in_use),
+				Error::<T>::DenomInUse
+			);
+
+			if let Ok(old_id) = <AssetIdByName<T>>::try_get(&denom) {
+				<AssetNameById<T>>::remove(old_id);
+			}
+			if let Some(old_name) = old_name {
+				<AssetIdByName<T>>::remove(old_name);
+			}
+			<AssetIdByName<T>>::insert(&denom, asset_id);
+			<AssetNameById<T>>::insert(asset_id, &denom);
+			Self::deposit_event(Event::<T>::DenomMapped { denom, asset_id });
+
+			Ok(())
+		}
+
+		/// Set whether the asset of `denom` is sufficient for an account to exist.
+		#[pallet::call_index(7)]
+		#[pallet::weight(0)]
+		pub fn set_asset_sufficient(
+			origin: OriginFor<T>,
+			denom: Vec<u8>,
+			is_sufficient: bool,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			let asset_id = T::AssetIdByName::try_get_asset_id(&denom)
+				.map_err(|_| Error::<T>::InvalidTokenId)?;
+			<T::AssetRegistrar as assets::AssetRegistrar<_, _>>::set_sufficient(
+				asset_id,
+				is_sufficient,
+			)?;
+			Self::deposit_event(Event::<T>::AssetSufficiencySet { asset_id, is_sufficient });
+
+			Ok(())
+		}
+
+		/// Let the asset `asset_id`, issued on this chain, be sent out under `denom`. `None`
+		/// removes the mapping of `denom`.
+		#[pallet::call_index(8)]
+		#[pallet::weight(0)]
+		pub fn set_native_asset(
+			origin: OriginFor<T>,
+			denom: Vec<u8>,
+			asset_id: Option<T::AssetId>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(
+				denom.as_slice() != T::NATIVE_TOKEN_NAME && !denom.contains(&b'/'),
+				Error::<T>::InvalidTokenId
+			);
+
+			<NativeAssets<T>>::set(&denom, asset_id);
+			Self::deposit_event(Event::<T>::NativeAssetSet { denom, asset_id });
+
+			Ok(())
+		}
+
+		/// Set the decimals of the asset of `denom` on this chain and in ICS20 packets. `None`
+		/// removes the conversion, packet amounts then being local balances. It cannot change
+		/// while tokens of `denom` are escrowed, minted or in flight.
+		#[pallet::call_index(9)]
+		#[pallet::weight(0)]
+		pub fn set_decimal_conversion(
+			origin: OriginFor<T>,
+			denom: Vec<u8>,
+			conversion: Option<amount::DecimalConversion>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+			let prefixed = String::from_utf8(denom)
+				.ok()
+				.and_then(|denom| ibc::applications::transfer::PrefixedDenom::from_str(&denom).ok())
+				.ok_or(Error::<T>::InvalidTokenId)?;
+			ensure!(!Self::has_outstanding_supply(&prefixed), Error::<T>::DenomInUse);
+
+			let denom = prefixed.to_string().into_bytes();
+			<DecimalConversions<T>>::set(&denom, conversion);
+			Self::deposit_event(Event::<T>::DecimalConversionSet { denom, conversion });
+
+			Ok(())
+		}
+
+		/// Enable or disable sending and receiving the transfers of `scope`.
+		#[pallet::call_index(11)]
+		#[pallet::weight(0)]
+		pub fn set_switch(
+			origin: OriginFor<T>,
+			scope: switches::SwitchScope,
+			switch: switches::Switch,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			Self::store_switch(&scope, switch);
+			Self::deposit_event(Event::<T>::SwitchSet { scope, switch });
+
+			Ok(())
+		}
+
+		/// Set the rule of the list transfer policy on `target`, removing it for `None`.
+		#[pallet::call_index(12)]
+		#[pallet::weight(0)]
+		pub fn set_policy_rule(
+			origin: OriginFor<T>,
+			target: policy::PolicyTarget<T::AccountId>,
+			rule: Option<policy::PolicyRule>,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			<PolicyRules<T>>::set(&target, rule);
+			Self::deposit_event(Event::<T>::PolicyRuleSet { target, rule });
+
+			Ok(())
+		}
+
+		/// Set whether the list transfer policy only allows the allowlisted targets of `kind`.
+		#[pallet::call_index(13)]
+		#[pallet::weight(0)]
+		pub fn set_policy_strict(
+			origin: OriginFor<T>,
+			kind: policy::PolicyKind,
+			strict: bool,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			if strict {
+				<StrictPolicyKinds<T>>::insert(kind, ());
+			} else {
+				<StrictPolicyKinds<T>>::remove(kind);
+			}
+			Self::deposit_event(Event::<T>::PolicyStrictSet { kind, strict });
+
+			Ok(())
+		}
+
+		/// Release the held transfer received with packet `sequence` over `channel_id` to its
+		/// receiver.
+		#[pallet::call_index(14)]
+		#[pallet::weight(0)]
+		pub fn release_held_transfer(
+			origin: OriginFor<T>,
+			channel_id: ChannelId,
+			sequence: Sequence,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			let receiver = Self::release_held(&channel_id, sequence)?;
+			Self::deposit_event(Event::<T>::HeldTransferReleased {
+				channel_id,
+				sequence,
+				receiver,
+			});
+
+			Ok(())
+		}
+
+		/// Pay out the refund of the transfer sent with packet `sequence` over
+		/// `port_id/channel_id`, whose refund failed when the packet was settled.
+		#[pallet::call_index(15)]
+		#[pallet::weight(0)]
+		pub fn refund_failed_transfer(
+			origin: OriginFor<T>,
+			port_id: PortId,
+			channel_id: ChannelId,
+			sequence: Sequence,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			let sender = Self::refund_failed(&port_id, &channel_id, sequence)?;
+			Self::deposit_event(Event::<T>::TransferRefunded {
+				port_id,
+				channel_id,
+				sequence,
+				sender,
+			});
+
+			Ok(())
+		}
+
+		/// Return the held transfer received with packet `sequence` over `channel_id` to its
+		/// sender on the counterparty chain.
+		#[pallet::call_index(16)]
+		#[pallet::weight(0)]
+		pub fn return_held_transfer(
+			origin: OriginFor<T>,
+			channel_id: ChannelId,
+			sequence: Sequence,
+		) -> DispatchResult {
+			T::AdminOrigin::ensure_origin(origin)?;
+
+			let return_sequence = Self::return_held(&channel_id, sequence)?;
+			Self::deposit_event(Event::<T>::HeldTransferReturned {
+				channel_id,
+				sequence,
+				return_sequence,
+			});
+
+			Ok(())
+		}
 	}
 }
 
@@ -209,7 +1011,7 @@ impl<T: Config> AssetIdAndNameProvider<T::AssetId> for Pallet<T> {
 	}
 
 	fn try_get_asset_name(asset_id: T::AssetId) -> Result<Vec<u8>, Self::Err> {
-		let token_id = <AssetIdByName<T>>::iter().find(|p| p.1 == asset_id).map(|p| p.0);
+		let token_id = <AssetNameById<T>>::get(asset_id);
 		match token_id {
 			Some(id) => Ok(id),
 			_ => Err(Error::<T>::WrongAssetId),
@@ -217,12 +1019,24 @@ impl<T: Config> AssetIdAndNameProvider<T::AssetId> for Pallet<T> {
 	}
 }
 
-impl<T: Config> pallet_ibc_utils::Router for Pallet<T>
+impl<T: Config> pallet_ibc_utils::SignedRouter<T::AccountId> for Pallet<T>
 where
 	u64: From<<T as pallet_timestamp::Config>::Moment>
 		+ From<<T as frame_system::Config>::BlockNumber>,
 {
-	fn dispatch(messages: Vec<Any>) -> DispatchResult {
+	/// Send the `MsgTransfer`s of `messages`, which must all be sent by `signer`.
+	fn dispatch(signer: &T::AccountId, messages: Vec<Any>) -> DispatchResult {
+		Self::send_raw_transfers(signer, messages)
+	}
+}
+
+impl<T: Config> Pallet<T>
+where
+	u64: From<<T as pallet_timestamp::Config>::Moment>
+		+ From<<T as frame_system::Config>::BlockNumber>,
+{
+	/// Send the `MsgTransfer`s of `messages`, which must all be sent by `sender`.
+	fn send_raw_transfers(sender: &T::AccountId, messages: Vec<Any>) -> DispatchResult {
 		let mut ctx = IbcTransferModule(PhantomData::<T>);
 
 		log::trace!(
@@ -233,8 +1047,47 @@ where
 
 		for message in messages {
 			let mut handle_out = HandlerOutputBuilder::new();
-			let msg_transfer =
+			let mut msg_transfer =
 				MsgTransfer::try_from(message).map_err(|_| Error::<T>::ParserMsgTransferError)?;
+			let msg_sender = T::AccountIdConversion::try_from(msg_transfer.sender.clone())
+				.map_err(|_| Error::<T>::InvalidSender)?
+				.into_account();
+			ensure!(&msg_sender == sender, Error::<T>::InvalidSender);
+			let (timeout_height, timeout_timestamp) = Self::resolve_timeout(
+				&msg_transfer.port_on_a,
+				&msg_transfer.chan_on_a,
+				msg_transfer.timeout_height_on_b,
+				msg_transfer.timeout_timestamp_on_b,
+			)?;
+			msg_transfer.timeout_height_on_b = timeout_height;
+			msg_transfer.timeout_timestamp_on_b = timeout_timestamp;
+			ensure!(
+				T::ReceiverValidator::is_valid(msg_transfer.receiver.as_ref()),
+				Error::<T>::InvalidReceiver
+			);
+			let (port_id, channel_id) =
+				(msg_transfer.port_on_a.clone(), msg_transfer.chan_on_a.clone());
+			let sequence = <T::IbcContext as ChannelReaderInterface>::get_next_sequence_send(
+				&port_id,
+				&channel_id,
+			)
+			.map_err(|_| Error::<T>::InvalidChannel)?;
+			let denom = msg_transfer.token.denom.clone();
+			ensure!(Self::is_send_enabled_for(&channel_id, &denom), Error::<T>::SendDisabled);
+			let amount = Self::local_amount(&denom, msg_transfer.token.amount, true)
+				.map_err(|_| Error::<T>::InvalidAmount)?;
+			Self::ensure_send_allowed(
+				&msg_sender,
+				msg_transfer.receiver.as_ref(),
+				&port_id,
+				&channel_id,
+				&denom,
+				amount,
+			)?;
+			Self::record_send(&channel_id, sequence, &denom, amount)?;
+			let escrowed = is_sender_chain_source(port_id.clone(), channel_id.clone(), &denom);
+
+			let receiver = msg_transfer.receiver.as_ref().as_bytes().to_vec();
 			let result = ibc::applications::transfer::relay::send_transfer::send_transfer(
 				&mut ctx,
 				&mut handle_out,
@@ -243,9 +1096,22 @@ where
 			match result {
 				Ok(_value) => {
 					log::trace!(target: LOG_TARGET, "raw_transfer Successful!");
+					if escrowed {
+						Self::register_escrow_account(&port_id, &channel_id)?;
+					}
+					Self::track_transfer(
+						&port_id,
+						&channel_id,
+						sequence,
+						msg_sender,
+						receiver,
+						denom.to_string().into_bytes(),
+						amount,
+					);
 				},
 				Err(error) => {
 					log::trace!(target: LOG_TARGET, "raw_transfer Error : {:?} ", error);
+					Self::undo_send(&channel_id, sequence, &denom, amount);
 				},
 			}
 
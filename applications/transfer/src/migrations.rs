@@ -0,0 +1,785 @@
+//! Storage migrations of the transfer pallet.
+//!
+//! As in pallet-ibc, the migrations are [`SteppedMigration`]s: the runtime upgrade runs their
+//! first step and `on_idle` the following ones, as the weight left in the block allows, keeping
+//! the cursor in [`MigrationCursor`]. The storage version is set once the last step is done, and
+//! the next migration, if any, is started from `on_idle` as well. A migration whose step fails is
+//! stopped at the version it upgrades from, to be started again by a later upgrade.
+use crate::*;
+use frame_support::traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion};
+use pallet_ibc_utils::migration::{run_steps, Cursor, StepFailed, StepResult, SteppedMigration};
+
+/// The items migrated per step.
+pub const MIGRATION_STEP: u32 = 64;
+
+impl<T: Config> Pallet<T> {
+	/// Record the outcome of a step of `M`: its cursor, or its storage version once it is done,
+	/// with an empty cursor to start the next migration. A failed migration, or one whose cursor
+	/// is too long to be kept, is stopped.
+	fn end_step<M: SteppedMigration>(result: StepResult) {
+		let result = result
+			.and_then(|cursor| cursor.map(Cursor::try_from).transpose().map_err(|_| StepFailed));
+		match result {
+			Ok(Some(cursor)) => <MigrationCursor<T>>::put(cursor),
+			Err(_) => {
+				log::error!(
+					target: LOG_TARGET,
+					"❌ [migration] migration to version {} failed",
+					M::TO_VERSION
+				);
+				<MigrationCursor<T>>::kill();
+			},
+			Ok(None) => {
+				StorageVersion::new(M::TO_VERSION).put::<Pallet<T>>();
+				log::info!(target: LOG_TARGET, "migrated storage to version {}", M::TO_VERSION);
+				if Self::current_storage_version() > M::TO_VERSION {
+					<MigrationCursor<T>>::put(Cursor::default());
+				} else {
+					<MigrationCursor<T>>::kill();
+				}
+			},
+		}
+	}
+
+	/// Run the steps of the migration in progress, if any, that fit in `remaining_weight`.
+	pub(crate) fn resume_migration(remaining_weight: Weight) -> Weight {
+		let cursor = match <MigrationCursor<T>>::get() {
+			Some(cursor) => cursor.into_inner(),
+			None => return T::DbWeight::get().reads(1),
+		};
+		let weight = match Self::on_chain_storage_version() {
+			version if version < 1 => {
+				let (result, weight) =
+					run_steps::<v1::MigrateToV1<T>>(cursor, MIGRATION_STEP, remaining_weight);
+				Self::end_step::<v1::MigrateToV1<T>>(result);
+				weight
+			},
+			version if version < 2 => {
+				let (result, weight) =
+					run_steps::<v2::MigrateToV2<T, ()>>(cursor, MIGRATION_STEP, remaining_weight);
+				Self::end_step::<v2::MigrateToV2<T, ()>>(result);
+				weight
+			},
+			version if version < 3 => {
+				let (result, weight) =
+					run_steps::<v3::MigrateToV3<T>>(cursor, MIGRATION_STEP, remaining_weight);
+				Self::end_step::<v3::MigrateToV3<T>>(result);
+				weight
+			},
+			version if version < 4 => {
+				let (result, weight) =
+					run_steps::<v4::MigrateToV4<T>>(cursor, MIGRATION_STEP, remaining_weight);
+				Self::end_step::<v4::MigrateToV4<T>>(result);
+				weight
+			},
+			_ => {
+				log::error!(target: LOG_TARGET, "❌ [migration] cursor without migration");
+				<MigrationCursor<T>>::kill();
+				Weight::zero()
+			},
+		};
+		weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+	}
+}
+
+/// Split a cursor into its phase, `first_phase` for the first step, and the raw key following it.
+fn split_cursor(cursor: Option<Vec<u8>>, first_phase: u8) -> (u8, Vec<u8>) {
+	let cursor = cursor.unwrap_or_default();
+	match cursor.split_first() {
+		Some((phase, last_key)) => (*phase, last_key.to_vec()),
+		None => (first_phase, Vec::new()),
+	}
+}
+
+/// Version 1 keys vouchers by their `ibc/{hash}` denomination, the hash of their full path.
+pub mod v1 {
+	use super::*;
+
+	/// The phases of the migration, leading its cursor.
+	const TRACES: u8 = 0;
+	const BASE_DENOMS: u8 = 1;
+	const NAMES: u8 = 2;
+
+	/// [`crate::DenomTrace`] before version 4, keyed by unbounded bytes.
+	#[frame_support::storage_alias]
+	pub(crate) type DenomTrace<T: Config> = StorageMap<
+		Pallet<T>,
+		Blake2_128Concat,
+		Vec<u8>,
+		denom::PrefixedDenom<<T as Config>::MaxTraceLength>,
+	>;
+
+	/// Re-keys [`DenomTrace`], formerly keyed by the hash of the trace path alone, and moves the
+	/// vouchers of [`AssetIdByName`] from their base denomination to their `ibc/{hash}`
+	/// denomination. Fills [`AssetNameById`].
+	pub struct MigrateToV1<T>(PhantomData<T>);
+
+	impl<T: Config> SteppedMigration for MigrateToV1<T> {
+		const TO_VERSION: u16 = 1;
+
+		/// The cursor is the phase followed by the raw key of the last item migrated, empty to
+		/// start the phase from its first item. The traces are re-keyed and their vouchers named
+		/// by both denominations, then the base denominations of the vouchers are removed, then
+		/// the assets are named by id.
+		fn step(cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight) {
+			let (phase, last_key) = split_cursor(cursor, TRACES);
+			let mut reads = 1u64;
+			let mut writes = 0u64;
+
+			let (last_key, migrated) = if phase == TRACES {
+				let mut traces = if last_key.is_empty() {
+					<DenomTrace<T>>::iter()
+				} else {
+					<DenomTrace<T>>::iter_from(last_key)
+				};
+				let batch = (&mut traces).take(limit as usize).collect::<Vec<_>>();
+				let last_key = traces.last_raw_key().to_vec();
+				for (key, trace) in batch.iter() {
+					reads += 1;
+					let path = String::from_utf8_lossy(&trace.full_path()).into_owned();
+					let hash = match utils::derive_ibc_denom_with_path(&path) {
+						Ok(hash) => hash.into_bytes(),
+						Err(e) => {
+							log::error!(target: LOG_TARGET, "❌ [migration] denom {}: {:?}", path, e);
+							return (Err(StepFailed), T::DbWeight::get().reads_writes(reads, writes))
+						},
+					};
+					// traces keyed by their hash, re-keyed or stored since, are left alone
+					if *key == hash {
+						continue
+					}
+					<DenomTrace<T>>::remove(key);
+					<DenomTrace<T>>::insert(&hash, trace);
+					writes += 2;
+					reads += 1;
+					if let Ok(asset_id) = <AssetIdByName<T>>::try_get(trace.base_denom.to_vec()) {
+						<AssetIdByName<T>>::insert(&hash, asset_id);
+						writes += 1;
+					}
+				}
+				(last_key, batch.len() as u64)
+			} else if phase == BASE_DENOMS {
+				let mut traces = if last_key.is_empty() {
+					<DenomTrace<T>>::iter()
+				} else {
+					<DenomTrace<T>>::iter_from(last_key)
+				};
+				let mut migrated = 0;
+				while migrated < limit as u64 {
+					let (hash, trace) = match traces.next() {
+						Some(item) => item,
+						None => break,
+					};
+					migrated += 1;
+					reads += 3;
+					let base_denom = trace.base_denom.to_vec();
+					// only the voucher named by the hash of the trace is removed
+					if let Ok(asset_id) = <AssetIdByName<T>>::try_get(&base_denom) {
+						if <AssetIdByName<T>>::try_get(&hash) == Ok(asset_id) {
+							<AssetIdByName<T>>::remove(base_denom);
+							writes += 1;
+						}
+					}
+				}
+				(traces.last_raw_key().to_vec(), migrated)
+			} else {
+				let mut names = if last_key.is_empty() {
+					<AssetIdByName<T>>::iter()
+				} else {
+					<AssetIdByName<T>>::iter_from(last_key)
+				};
+				let mut migrated = 0;
+				while migrated < limit as u64 {
+					let (name, asset_id) = match names.next() {
+						Some(item) => item,
+						None => break,
+					};
+					migrated += 1;
+					reads += 1;
+					<AssetNameById<T>>::insert(asset_id, name);
+					writes += 1;
+				}
+				(names.last_raw_key().to_vec(), migrated)
+			};
+
+			let weight = T::DbWeight::get().reads_writes(reads, writes);
+			let cursor = match (migrated < limit as u64, phase) {
+				(false, phase) => Some([&[phase][..], &last_key].concat()),
+				(true, TRACES) => Some([BASE_DENOMS].to_vec()),
+				(true, BASE_DENOMS) => Some([NAMES].to_vec()),
+				(true, _) => None,
+			};
+			(Ok(cursor), weight)
+		}
+
+		fn max_step_weight(limit: u32) -> Weight {
+			T::DbWeight::get().reads_writes(limit as u64 * 3 + 1, limit as u64 * 3)
+		}
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV1<T> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T>::on_chain_storage_version() >= 1 || <MigrationCursor<T>>::exists() {
+				return T::DbWeight::get().reads(2)
+			}
+
+			let (result, weight) = Self::step(None, MIGRATION_STEP);
+			Pallet::<T>::end_step::<Self>(result);
+			weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+			Ok((<DenomTrace<T>>::iter_keys().count() as u64).encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(state: Vec<u8>) -> Result<(), &'static str> {
+			let traces = u64::decode(&mut &state[..]).map_err(|_| "invalid pre-upgrade state")?;
+			if <MigrationCursor<T>>::exists() {
+				return Ok(())
+			}
+			ensure!(Pallet::<T>::on_chain_storage_version() >= 1, "storage version not set");
+			ensure!(
+				<DenomTrace<T>>::iter_keys().count() as u64 <= traces,
+				"denom traces added by the migration"
+			);
+			for (hash, trace) in <DenomTrace<T>>::iter() {
+				let path = String::from_utf8_lossy(&trace.full_path()).into_owned();
+				ensure!(
+					utils::derive_ibc_denom_with_path(&path).ok().map(String::into_bytes) ==
+						Some(hash),
+					"denom trace not keyed by its ibc denom"
+				);
+			}
+			for (name, asset_id) in <AssetIdByName<T>>::iter() {
+				ensure!(
+					<AssetNameById<T>>::get(asset_id) == Some(name),
+					"asset without name by id"
+				);
+			}
+			Ok(())
+		}
+	}
+}
+
+/// Version 2 escrows the tokens sent over a channel in a sub-account of [`Config::PalletId`].
+pub mod v2 {
+	use super::*;
+	use crate::utils::get_channel_escrow_address_hash;
+	use frame_support::traits::{
+		fungibles::{Inspect, Transfer},
+		ExistenceRequirement::AllowDeath,
+	};
+	use ibc::core::ics24_host::identifier::PortId;
+	use sp_runtime::traits::TrailingZeroInput;
+
+	/// The phases of the migration of a channel, leading the cursor.
+	const NATIVE: u8 = 0;
+	const ASSETS: u8 = 1;
+	const NATIVE_ASSETS: u8 = 2;
+
+	/// The channels whose escrowed tokens are left to move, the first one being moved. Set by the
+	/// runtime upgrade, so that the steps run from `on_idle` know them.
+	#[frame_support::storage_alias]
+	pub(crate) type LegacyEscrowChannels<T: Config> =
+		StorageValue<Pallet<T>, Vec<(PortId, ChannelId)>>;
+
+	/// Moves the native tokens and assets escrowed for each channel of `Channels` from the account
+	/// of the whole escrow address hash to the registered sub-account of [`Config::PalletId`].
+	pub struct MigrateToV2<T, Channels>(PhantomData<(T, Channels)>);
+
+	/// The escrow account of `channel_id` before version 2.
+	fn legacy_escrow_account<T: Config>(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Option<T::AccountId> {
+		let hash = get_channel_escrow_address_hash(port_id, channel_id);
+		T::AccountId::decode(&mut TrailingZeroInput::new(&hash)).ok()
+	}
+
+	/// Move the balance of `asset_id` held by `legacy` to `escrow`.
+	fn move_asset<T: Config>(
+		asset_id: T::AssetId,
+		legacy: &T::AccountId,
+		escrow: &T::AccountId,
+		channel_id: &ChannelId,
+	) -> Result<(), StepFailed> {
+		let balance = <T::Fungibles as Inspect<T::AccountId>>::balance(asset_id, legacy);
+		if balance.is_zero() {
+			return Ok(())
+		}
+		<T::Fungibles as Transfer<T::AccountId>>::transfer(asset_id, legacy, escrow, balance, false)
+			.map(|_| ())
+			.map_err(|e| {
+				log::error!(target: LOG_TARGET, "❌ [migration] escrow of {}: {:?}", channel_id, e);
+				StepFailed
+			})
+	}
+
+	/// Move the balances of the assets of `assets` held by `legacy` to `escrow`, at most `limit`.
+	/// Returns whether all were moved, and how many.
+	fn move_assets<T: Config>(
+		assets: &mut impl Iterator<Item = T::AssetId>,
+		limit: u64,
+		legacy: &T::AccountId,
+		escrow: &T::AccountId,
+		channel_id: &ChannelId,
+	) -> (Result<bool, StepFailed>, u64) {
+		let mut moved = 0;
+		while moved < limit {
+			let asset_id = match assets.next() {
+				Some(asset_id) => asset_id,
+				None => return (Ok(true), moved),
+			};
+			moved += 1;
+			if let Err(e) = move_asset::<T>(asset_id, legacy, escrow, channel_id) {
+				return (Err(e), moved)
+			}
+		}
+		(Ok(false), moved)
+	}
+
+	impl<T: Config, Channels> SteppedMigration for MigrateToV2<T, Channels> {
+		const TO_VERSION: u16 = 2;
+
+		/// The cursor is the phase of the first channel of [`LegacyEscrowChannels`] followed, for
+		/// its assets, by the raw key of the last asset moved. The native token of the channel is
+		/// moved first, then the assets named in [`AssetNameById`], then the [`NativeAssets`].
+		fn step(cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight) {
+			let mut channels = match <LegacyEscrowChannels<T>>::get() {
+				Some(channels) => channels,
+				None => {
+					log::error!(target: LOG_TARGET, "❌ [migration] escrowed channels not set");
+					return (Err(StepFailed), T::DbWeight::get().reads(1))
+				},
+			};
+			let (mut phase, mut last_key) = split_cursor(cursor, NATIVE);
+			let limit = limit as u64;
+			let mut moved = 0u64;
+
+			let result = loop {
+				let (port_id, channel_id) = match channels.first() {
+					Some(channel) => channel.clone(),
+					None => break Ok(None),
+				};
+				if moved >= limit {
+					break Ok(Some([&[phase][..], &last_key].concat()))
+				}
+				let legacy = match legacy_escrow_account::<T>(&port_id, &channel_id) {
+					Some(legacy) => legacy,
+					None => {
+						channels.remove(0);
+						continue
+					},
+				};
+				let escrow = match Pallet::<T>::register_escrow_account(&port_id, &channel_id) {
+					Ok(escrow) => escrow,
+					Err(e) => {
+						log::error!(target: LOG_TARGET, "❌ [migration] register escrow of {}: {:?}", channel_id, e);
+						break Err(StepFailed)
+					},
+				};
+
+				let (done, count, next_key) = if phase == NATIVE {
+					let balance = T::Currency::free_balance(&legacy);
+					if !balance.is_zero() {
+						if let Err(e) = T::Currency::transfer(&legacy, &escrow, balance, AllowDeath)
+						{
+							log::error!(target: LOG_TARGET, "❌ [migration] escrow of {}: {:?}", channel_id, e);
+							break Err(StepFailed)
+						}
+					}
+					(Ok(true), 1, Vec::new())
+				} else if phase == ASSETS {
+					let mut assets = if last_key.is_empty() {
+						<AssetNameById<T>>::iter_keys()
+					} else {
+						<AssetNameById<T>>::iter_keys_from(last_key.clone())
+					};
+					let (done, count) =
+						move_assets::<T>(&mut assets, limit - moved, &legacy, &escrow, &channel_id);
+					(done, count, assets.last_raw_key().to_vec())
+				} else {
+					let mut assets = if last_key.is_empty() {
+						<NativeAssets<T>>::iter()
+					} else {
+						<NativeAssets<T>>::iter_from(last_key.clone())
+					};
+					let (done, count) = move_assets::<T>(
+						&mut (&mut assets).map(|(_, asset_id)| asset_id),
+						limit - moved,
+						&legacy,
+						&escrow,
+						&channel_id,
+					);
+					(done, count, assets.last_raw_key().to_vec())
+				};
+				moved += count;
+
+				match done {
+					Err(e) => break Err(e),
+					Ok(false) => last_key = next_key,
+					Ok(true) => {
+						last_key = Vec::new();
+						phase = match phase {
+							NATIVE => ASSETS,
+							ASSETS => NATIVE_ASSETS,
+							_ => {
+								channels.remove(0);
+								NATIVE
+							},
+						};
+					},
+				}
+			};
+
+			// the channel that failed is kept first, for a later upgrade to start from
+			if channels.is_empty() {
+				<LegacyEscrowChannels<T>>::kill();
+			} else {
+				<LegacyEscrowChannels<T>>::put(channels);
+			}
+			(result, T::DbWeight::get().reads_writes(moved * 3 + 2, moved * 4 + 1))
+		}
+
+		fn max_step_weight(limit: u32) -> Weight {
+			T::DbWeight::get().reads_writes(limit as u64 * 3 + 2, limit as u64 * 4 + 1)
+		}
+	}
+
+	impl<T, Channels> OnRuntimeUpgrade for MigrateToV2<T, Channels>
+	where
+		T: Config,
+		Channels: Get<Vec<(PortId, ChannelId)>>,
+	{
+		/// Started from `on_idle` instead if the migration to version 1 is still in progress.
+		fn on_runtime_upgrade() -> Weight {
+			let version = Pallet::<T>::on_chain_storage_version();
+			if version >= 2 {
+				return T::DbWeight::get().reads(1)
+			}
+			// the channels left by a migration in progress or stopped are kept
+			if !<LegacyEscrowChannels<T>>::exists() {
+				<LegacyEscrowChannels<T>>::put(Channels::get());
+			}
+			if version != 1 || <MigrationCursor<T>>::exists() {
+				if version < 1 && !<MigrationCursor<T>>::exists() {
+					log::error!(target: LOG_TARGET, "❌ [migration] version 2 before version 1");
+				}
+				return T::DbWeight::get().reads_writes(3, 1)
+			}
+
+			let (result, weight) = Self::step(None, MIGRATION_STEP);
+			Pallet::<T>::end_step::<Self>(result);
+			weight.saturating_add(T::DbWeight::get().reads_writes(3, 3))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+			let escrowed = Channels::get()
+				.into_iter()
+				.filter_map(|(port_id, channel_id)| {
+					let legacy = legacy_escrow_account::<T>(&port_id, &channel_id)?;
+					Some((port_id, channel_id, T::Currency::free_balance(&legacy)))
+				})
+				.collect::<Vec<_>>();
+			Ok(escrowed.encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(state: Vec<u8>) -> Result<(), &'static str> {
+			let escrowed = Vec::<(PortId, ChannelId, BalanceOf<T>)>::decode(&mut &state[..])
+				.map_err(|_| "invalid pre-upgrade state")?;
+			if <MigrationCursor<T>>::exists() {
+				return Ok(())
+			}
+			ensure!(Pallet::<T>::on_chain_storage_version() >= 2, "storage version not set");
+			ensure!(!<LegacyEscrowChannels<T>>::exists(), "escrowed channels left");
+			for (port_id, channel_id, balance) in escrowed {
+				let escrow = <EscrowAccounts<T>>::get(&port_id, &channel_id)
+					.ok_or("channel without escrow account")?;
+				ensure!(
+					T::Currency::free_balance(&escrow) >= balance,
+					"escrowed tokens lost by the migration"
+				);
+			}
+			Ok(())
+		}
+	}
+}
+
+/// Version 3 accounts the escrowed tokens and the minted vouchers, see [`crate::supply`].
+pub mod v3 {
+	use super::*;
+	use crate::assets::LocalAsset;
+	use frame_support::{storage::StoragePrefixedMap, traits::fungibles::Inspect};
+
+	/// The phases of the migration, leading its cursor.
+	const ESCROWS: u8 = 0;
+	const VOUCHERS: u8 = 1;
+
+	/// The escrowable denominations, see [`Pallet::escrowable_denoms`], following the one of raw
+	/// key `last_key`, empty to start from the native token, at most `limit`. Each comes with its
+	/// raw key and its local asset, if it has one. The native token is keyed by the prefix of
+	/// [`NativeAssets`], which precedes the keys of the assets.
+	fn denoms_after<T: Config>(
+		last_key: &[u8],
+		limit: usize,
+	) -> Vec<(Vec<u8>, Vec<u8>, Option<LocalAsset<T::AssetId>>)> {
+		let assets_prefix = <NativeAssets<T>>::final_prefix().to_vec();
+		let traces_prefix = <DenomTrace<T>>::final_prefix().to_vec();
+		let mut denoms = Vec::new();
+		if last_key.is_empty() && limit > 0 {
+			denoms.push((
+				assets_prefix.clone(),
+				T::NATIVE_TOKEN_NAME.to_vec(),
+				Some(LocalAsset::Native),
+			));
+		}
+		if !last_key.starts_with(&traces_prefix) {
+			let from = if last_key.is_empty() { assets_prefix } else { last_key.to_vec() };
+			let remaining = limit.saturating_sub(denoms.len());
+			denoms.extend(<NativeAssets<T>>::iter_from(from).take(remaining).map(
+				|(denom, asset_id)| {
+					let key = <NativeAssets<T>>::hashed_key_for(&denom);
+					(key, denom, Some(LocalAsset::Asset(asset_id)))
+				},
+			));
+		}
+		let from =
+			if last_key.starts_with(&traces_prefix) { last_key.to_vec() } else { traces_prefix };
+		let remaining = limit.saturating_sub(denoms.len());
+		denoms.extend(<DenomTrace<T>>::iter_from(from).take(remaining).map(|(hash, trace)| {
+			let asset = <AssetIdByName<T>>::try_get(&*hash).ok().map(LocalAsset::Asset);
+			(<DenomTrace<T>>::hashed_key_for(&hash), trace.full_path(), asset)
+		}));
+		denoms
+	}
+
+	/// Seeds [`EscrowTotals`] with the balances held by the escrow accounts, and
+	/// [`VoucherSupply`] with the issuances of the voucher assets.
+	pub struct MigrateToV3<T>(PhantomData<T>);
+
+	impl<T: Config> SteppedMigration for MigrateToV3<T> {
+		const TO_VERSION: u16 = 3;
+
+		/// The cursor is the phase followed, for the escrow accounts, by the raw keys of the last
+		/// account seeded and of the last denomination seeded of the next one, and for the
+		/// vouchers by the raw key of the last trace seeded, all empty to start from the first
+		/// item. The escrowed totals are seeded for every escrowable denomination of every escrow
+		/// account, then the voucher supplies. The totals and supplies are not checked until the
+		/// migration is done, see [`Pallet::supply_seeded`], and are overwritten as seeded.
+		fn step(cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight) {
+			let (phase, last_key) = split_cursor(cursor, ESCROWS);
+			let mut reads = 1u64;
+			let mut writes = 0u64;
+
+			if phase == ESCROWS {
+				let (mut last_escrow, mut last_denom) = if last_key.is_empty() {
+					(Vec::new(), Vec::new())
+				} else {
+					match <(Vec<u8>, Vec<u8>)>::decode(&mut &last_key[..]) {
+						Ok(keys) => keys,
+						Err(_) => {
+							log::error!(target: LOG_TARGET, "❌ [migration] invalid cursor");
+							return (Err(StepFailed), T::DbWeight::get().reads(reads))
+						},
+					}
+				};
+				let mut accounts = if last_escrow.is_empty() {
+					<EscrowAccounts<T>>::iter()
+				} else {
+					<EscrowAccounts<T>>::iter_from(last_escrow.clone())
+				};
+				let mut seeded = 0usize;
+				while seeded < limit as usize {
+					let (_, channel_id, escrow) = match accounts.next() {
+						Some(item) => item,
+						None =>
+							return (
+								Ok(Some([VOUCHERS].to_vec())),
+								T::DbWeight::get().reads_writes(reads, writes),
+							),
+					};
+					reads += 1;
+					let remaining = limit as usize - seeded;
+					let denoms = denoms_after::<T>(&last_denom, remaining);
+					for (key, denom, asset) in denoms.iter() {
+						reads += 2;
+						if let Some(asset) = asset {
+							let balance = Pallet::<T>::escrowed_balance(&escrow, *asset);
+							reads += 1;
+							if balance > 0 {
+								<EscrowTotals<T>>::insert(&channel_id, denom, balance);
+							} else {
+								<EscrowTotals<T>>::remove(&channel_id, denom);
+							}
+							writes += 1;
+						}
+						last_denom = key.clone();
+					}
+					seeded += denoms.len();
+					// the account is seeded once its denominations run out before the limit
+					if denoms.len() < remaining {
+						last_escrow = accounts.last_raw_key().to_vec();
+						last_denom = Vec::new();
+					}
+				}
+				let cursor = [&[ESCROWS][..], &(last_escrow, last_denom).encode()].concat();
+				return (Ok(Some(cursor)), T::DbWeight::get().reads_writes(reads, writes))
+			}
+
+			let mut traces = if last_key.is_empty() {
+				<DenomTrace<T>>::iter()
+			} else {
+				<DenomTrace<T>>::iter_from(last_key)
+			};
+			let mut seeded = 0;
+			while seeded < limit {
+				let (hash, _) = match traces.next() {
+					Some(item) => item,
+					None => return (Ok(None), T::DbWeight::get().reads_writes(reads, writes)),
+				};
+				seeded += 1;
+				reads += 2;
+				if let Ok(asset_id) = <AssetIdByName<T>>::try_get(&*hash) {
+					let issuance: u128 =
+						<T::Fungibles as Inspect<T::AccountId>>::total_issuance(asset_id).into();
+					reads += 1;
+					<VoucherSupply<T>>::insert(&*hash, issuance);
+					writes += 1;
+				}
+			}
+			let cursor = [&[VOUCHERS][..], traces.last_raw_key()].concat();
+			(Ok(Some(cursor)), T::DbWeight::get().reads_writes(reads, writes))
+		}
+
+		fn max_step_weight(limit: u32) -> Weight {
+			T::DbWeight::get().reads_writes(limit as u64 * 4 + 2, limit as u64)
+		}
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV3<T> {
+		/// Started from `on_idle` instead if an earlier migration is still in progress.
+		fn on_runtime_upgrade() -> Weight {
+			let version = Pallet::<T>::on_chain_storage_version();
+			if version != 2 || <MigrationCursor<T>>::exists() {
+				if version < 2 && !<MigrationCursor<T>>::exists() {
+					log::error!(target: LOG_TARGET, "❌ [migration] version 3 before version 2");
+				}
+				return T::DbWeight::get().reads(2)
+			}
+
+			let (result, weight) = Self::step(None, MIGRATION_STEP);
+			Pallet::<T>::end_step::<Self>(result);
+			weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(_state: Vec<u8>) -> Result<(), &'static str> {
+			if <MigrationCursor<T>>::exists() {
+				return Ok(())
+			}
+			ensure!(Pallet::<T>::on_chain_storage_version() >= 3, "storage version not set");
+			Pallet::<T>::do_try_state()
+		}
+	}
+}
+
+/// Version 4 bounds the keys of [`DenomTrace`] by [`denom::MaxIbcDenomLen`].
+pub mod v4 {
+	use super::*;
+	use codec::DecodeAll;
+	use frame_support::storage::StoragePrefixedMap;
+	use pallet_ibc_utils::migration::remove_undecodable;
+
+	/// The length of a `Blake2_128Concat` hash, before the key it hashes
+	const HASH_LEN: usize = 16;
+
+	/// Whether the raw key of a trace, without its prefix, and its value decode
+	fn decodes<T: Config>(key: &[u8], value: &[u8]) -> bool {
+		key.get(HASH_LEN..)
+			.map_or(false, |key| denom::IbcDenom::decode_all(&mut &key[..]).is_ok()) &&
+			denom::PrefixedDenom::<T::MaxTraceLength>::decode_all(&mut &value[..]).is_ok()
+	}
+
+	/// The number of traces that no longer decode.
+	#[cfg(any(feature = "try-runtime", test))]
+	pub fn undecodable_entries<T: Config>() -> u64 {
+		pallet_ibc_utils::migration::count_undecodable(
+			&<DenomTrace<T>>::final_prefix(),
+			decodes::<T>,
+		)
+	}
+
+	/// Removes the traces whose key is longer than an `ibc/{hash}` denomination, which no longer
+	/// decode.
+	pub struct MigrateToV4<T>(PhantomData<T>);
+
+	impl<T: Config> SteppedMigration for MigrateToV4<T> {
+		const TO_VERSION: u16 = 4;
+
+		/// The cursor is the raw key of the last trace looked at, empty to start from the first.
+		fn step(cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight) {
+			let (last_key, seen, removed) = remove_undecodable(
+				&<DenomTrace<T>>::final_prefix(),
+				cursor.unwrap_or_default(),
+				limit,
+				decodes::<T>,
+			);
+			if removed > 0 {
+				log::warn!(
+					target: LOG_TARGET,
+					"removed {} denom traces that no longer decode",
+					removed
+				);
+			}
+			(Ok(last_key), T::DbWeight::get().reads_writes(seen + 1, removed))
+		}
+
+		fn max_step_weight(limit: u32) -> Weight {
+			T::DbWeight::get().reads_writes(limit as u64 + 1, limit as u64)
+		}
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV4<T> {
+		/// Started from `on_idle` instead if an earlier migration is still in progress.
+		fn on_runtime_upgrade() -> Weight {
+			let version = Pallet::<T>::on_chain_storage_version();
+			if version != 3 || <MigrationCursor<T>>::exists() {
+				if version < 3 && !<MigrationCursor<T>>::exists() {
+					log::error!(target: LOG_TARGET, "❌ [migration] version 4 before version 3");
+				}
+				return T::DbWeight::get().reads(2)
+			}
+
+			let (result, weight) = Self::step(None, MIGRATION_STEP);
+			Pallet::<T>::end_step::<Self>(result);
+			weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+			let undecodable = undecodable_entries::<T>();
+			if undecodable > 0 {
+				log::warn!(
+					target: LOG_TARGET,
+					"{} denom traces no longer decode and will be removed",
+					undecodable
+				);
+			}
+			Ok(undecodable.encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(_state: Vec<u8>) -> Result<(), &'static str> {
+			if <MigrationCursor<T>>::exists() {
+				return Ok(())
+			}
+			ensure!(Pallet::<T>::on_chain_storage_version() >= 4, "storage version not set");
+			ensure!(undecodable_entries::<T>() == 0, "denom traces that no longer decode left");
+			Pallet::<T>::do_try_state()
+		}
+	}
+}
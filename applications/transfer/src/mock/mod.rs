@@ -4,8 +4,8 @@ use codec::Encode;
 pub use frame_support::{
 	construct_runtime, parameter_types,
 	traits::{
-		AsEnsureOriginWithArg, ConstU128, ConstU16, ConstU32, ConstU8, KeyOwnerProofSystem,
-		Randomness, StorageInfo,
+		AsEnsureOriginWithArg, ConstU128, ConstU16, ConstU32, ConstU64, ConstU8,
+		KeyOwnerProofSystem, Randomness, StorageInfo,
 	},
 	weights::{
 		constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight},
@@ -17,6 +17,7 @@ use frame_system as system;
 use frame_system::EnsureRoot;
 use pallet_assets::AssetsCallback;
 use pallet_ibc_utils::module::Router;
+use pallet_ics20_transfer::assets::{AssetMetadata, AssetRegistrar};
 use sp_io::storage;
 use sp_runtime::{
 	generic,
@@ -41,6 +42,7 @@ construct_runtime!(
 		Assets: pallet_assets::<Instance1>,
 		Balances: pallet_balances,
 		Timestamp: pallet_timestamp,
+		Utility: pallet_utility,
 		Ibc: pallet_ibc,
 		Ics20Transfer: pallet_ics20_transfer,
 	}
@@ -195,6 +197,13 @@ impl pallet_timestamp::Config for Test {
 	type WeightInfo = ();
 }
 
+impl pallet_utility::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type RuntimeCall = RuntimeCall;
+	type PalletsOrigin = OriginCaller;
+	type WeightInfo = ();
+}
+
 parameter_types! {
 	pub const MaxAuthorities: u32 = 100;
 	pub const MaxKeys: u32 = 10_000;
@@ -216,7 +225,7 @@ impl pallet_ibc_utils::module::AddModule for IbcModule {
 	fn add_module(router: Router) -> Router {
 		match router.clone().add_route(
 			MODULE_ID_STR.parse().expect("never failed"),
-			pallet_ics20_transfer::callback::IbcTransferModule::<Test>(
+			pallet_ics20_transfer::forward::PacketForwardMiddleware::<Test>(
 				std::marker::PhantomData::<Test>,
 			),
 		) {
@@ -235,9 +244,67 @@ impl pallet_ics20_transfer::Config for Test {
 	type AssetIdByName = Ics20Transfer;
 	type IbcContext = pallet_ibc::context::Context<Test>;
 	type AccountIdConversion = pallet_ics20_transfer::impls::IbcAccount;
+	type RuntimeCall = RuntimeCall;
+	type HookCallFilter = frame_support::traits::Everything;
+	type ReceiverValidator = ();
+	type TransferPolicy = pallet_ics20_transfer::policy::ListPolicy<Test>;
+	type MaxHookWeight = MaxHookWeight;
+	type AdminOrigin = EnsureRoot<AccountId>;
+	type PalletId = TransferPalletId;
+	type DefaultTimeoutHeightOffset = ConstU64<1_000>;
+	type DefaultTimeoutTimestampOffset = ConstU64<600_000_000_000>;
+	type MaxTraceLength = ConstU32<512>;
+	type AssetRegistrar = AssetsRegistrar;
+	type AutoAssetIdStart = ConstU32<1_000>;
+	type AutoAssetMinBalance = ConstU128<1>;
 	const NATIVE_TOKEN_NAME: &'static [u8] = b"DEMO";
 }
 
+/// Owner of the assets registered for received denominations
+pub const IBC_ASSETS_OWNER: [u8; 32] = [0u8; 32];
+
+pub struct AssetsRegistrar;
+impl AssetRegistrar<AssetId, AssetBalance> for AssetsRegistrar {
+	fn create_asset(asset_id: AssetId, metadata: &AssetMetadata<AssetBalance>) -> DispatchResult {
+		let owner = AccountId::from(IBC_ASSETS_OWNER);
+		Assets::force_create(
+			RuntimeOrigin::root(),
+			asset_id,
+			owner.into(),
+			metadata.is_sufficient,
+			metadata.min_balance,
+		)?;
+		Assets::force_set_metadata(
+			RuntimeOrigin::root(),
+			asset_id,
+			metadata.name.clone(),
+			metadata.symbol.clone(),
+			metadata.decimals,
+			false,
+		)
+	}
+
+	fn set_sufficient(asset_id: AssetId, is_sufficient: bool) -> DispatchResult {
+		let owner = AccountId::from(IBC_ASSETS_OWNER);
+		Assets::force_asset_status(
+			RuntimeOrigin::root(),
+			asset_id,
+			owner.clone().into(),
+			owner.clone().into(),
+			owner.clone().into(),
+			owner.into(),
+			1,
+			is_sufficient,
+			false,
+		)
+	}
+}
+
+parameter_types! {
+	pub MaxHookWeight: Weight = Weight::from_parts(1_000_000_000, 0);
+	pub const TransferPalletId: frame_support::PalletId = frame_support::PalletId(*b"ibc/ics2");
+}
+
 pub type AssetBalance = u128;
 pub type AssetId = u32;
 
@@ -253,11 +320,21 @@ impl pallet_ibc::Config for Test {
 	const IBC_COMMITMENT_PREFIX: &'static [u8] = b"Ibc";
 	type ChainVersion = ChainVersion;
 	type IbcModule = IbcModule;
+	type MaxClientStateSize = ConstU32<{ 64 * 1024 }>;
+	type MaxChannelsPerConnection = ConstU32<64>;
+	type MaxEventSize = ConstU32<{ 8 * 1024 }>;
 	type WeightInfo = ();
 }
 
 #[allow(dead_code)]
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-	system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+	let mut storage = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	// the pallet account pays the existential deposit of the escrow accounts
+	pallet_balances::GenesisConfig::<Test> {
+		balances: vec![(Pallet::<Test>::pallet_account(), 100 * DOLLARS)],
+	}
+	.assimilate_storage(&mut storage)
+	.unwrap();
+	storage.into()
 }
@@ -0,0 +1,27 @@
+use alloc::{string::String, vec::Vec};
+use ibc::applications::transfer::error::TokenTransferError;
+use serde::{Deserialize, Serialize};
+
+/// ICS20 packet data in the JSON form exchanged with ibc-go, memo included.
+///
+/// The ibc-rs `PacketData` drops the memo, so the middlewares reading it decode the raw packet
+/// data with this type instead.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FungibleTokenPacketData {
+	pub denom: String,
+	pub amount: String,
+	pub sender: String,
+	pub receiver: String,
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub memo: String,
+}
+
+impl FungibleTokenPacketData {
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, TokenTransferError> {
+		serde_json::from_slice(bytes).map_err(|_| TokenTransferError::PacketDataDeserialization)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		serde_json::to_vec(self).expect("FungibleTokenPacketData's Serialize impl never fails")
+	}
+}
@@ -0,0 +1,165 @@
+//! Tracking of outgoing transfers until they are settled.
+//!
+//! Every transfer sent by an account of this chain, with [`Pallet::transfer`] or
+//! [`Pallet::raw_transfer`], is recorded in [`PendingTransfers`] until its packet is acknowledged
+//! or times out. Forwarded transfers are settled by the forward middleware instead. A successful
+//! acknowledgement emits [`Event::TransferSucceeded`], an error acknowledgement or a timeout
+//! refunds the sender and emits [`Event::TransferRefunded`]. If the refund fails,
+//! [`Event::TransferFailed`] is emitted and the transfer is kept with the
+//! [`TransferStatus::RefundFailed`] status, until [`Config::AdminOrigin`] pays out its refund with
+//! [`Pallet::refund_failed_transfer`].
+use crate::{callback::IbcTransferModule, *};
+use codec::{Decode, Encode};
+use ibc::{
+	applications::transfer::{
+		context::BankKeeper, is_sender_chain_source, PrefixedCoin, PrefixedDenom,
+	},
+	core::ics04_channel::packet::Packet,
+};
+use scale_info::TypeInfo;
+use sp_std::str::FromStr;
+
+/// Status of an outgoing transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum TransferStatus {
+	/// Waiting for the acknowledgement or timeout of its packet
+	Pending,
+	/// The transfer failed and its sender could not be refunded
+	RefundFailed,
+}
+
+/// An outgoing transfer
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PendingTransfer<AccountId, BlockNumber> {
+	pub sender: AccountId,
+	/// Receiver on the counterparty chain
+	pub receiver: Vec<u8>,
+	/// Denomination of the funds, as sent in the packet
+	pub denom: Vec<u8>,
+	/// Amount of the funds, in local units
+	pub amount: u128,
+	/// Block the transfer was sent at
+	pub sent_at: BlockNumber,
+	pub status: TransferStatus,
+}
+
+/// How the packet of an outgoing transfer was settled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferOutcome {
+	/// The packet was acknowledged successfully
+	Succeeded,
+	/// The packet failed and the sender was refunded
+	Refunded,
+	/// The packet failed and the sender could not be refunded
+	RefundFailed,
+}
+
+impl<T: Config> Pallet<T> {
+	/// Record the transfer sent over `port_id/channel_id` with packet `sequence`.
+	pub(crate) fn track_transfer(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		sequence: Sequence,
+		sender: T::AccountId,
+		receiver: Vec<u8>,
+		denom: Vec<u8>,
+		amount: u128,
+	) {
+		let key = (port_id.clone(), channel_id.clone());
+		<PendingTransfersBySender<T>>::insert(&sender, (key.clone(), sequence), ());
+		<PendingTransfers<T>>::insert(
+			key,
+			sequence,
+			PendingTransfer {
+				sender,
+				receiver,
+				denom,
+				amount,
+				sent_at: frame_system::Pallet::<T>::block_number(),
+				status: TransferStatus::Pending,
+			},
+		);
+	}
+
+	/// Settle the transfer sent with `packet`, returning whether it was tracked.
+	pub(crate) fn settle_transfer(packet: &Packet, outcome: TransferOutcome) -> bool {
+		let (port_id, channel_id, sequence) =
+			(packet.port_on_a.clone(), packet.chan_on_a.clone(), packet.sequence);
+		let key = (port_id.clone(), channel_id.clone());
+		let mut transfer = match <PendingTransfers<T>>::get(&key, sequence) {
+			Some(transfer) => transfer,
+			None => return false,
+		};
+		let sender = transfer.sender.clone();
+
+		match outcome {
+			TransferOutcome::Succeeded | TransferOutcome::Refunded => {
+				<PendingTransfers<T>>::remove(&key, sequence);
+				<PendingTransfersBySender<T>>::remove(&sender, (key, sequence));
+			},
+			TransferOutcome::RefundFailed => {
+				transfer.status = TransferStatus::RefundFailed;
+				<PendingTransfers<T>>::insert(&key, sequence, transfer);
+			},
+		}
+
+		Self::deposit_event(match outcome {
+			TransferOutcome::Succeeded =>
+				Event::<T>::TransferSucceeded { port_id, channel_id, sequence, sender },
+			TransferOutcome::Refunded =>
+				Event::<T>::TransferRefunded { port_id, channel_id, sequence, sender },
+			TransferOutcome::RefundFailed =>
+				Event::<T>::TransferFailed { port_id, channel_id, sequence, sender },
+		});
+		true
+	}
+
+	/// Refund the sender of the transfer sent over `port_id/channel_id` with packet `sequence`,
+	/// whose refund failed when its packet was settled, and stop tracking it.
+	pub(crate) fn refund_failed(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		sequence: Sequence,
+	) -> Result<T::AccountId, Error<T>> {
+		let key = (port_id.clone(), channel_id.clone());
+		let transfer = <PendingTransfers<T>>::get(&key, sequence)
+			.ok_or(Error::<T>::PendingTransferNotFound)?;
+		ensure!(transfer.status == TransferStatus::RefundFailed, Error::<T>::TransferNotRefundable);
+		let denom = PrefixedDenom::from_str(&String::from_utf8_lossy(&transfer.denom))
+			.map_err(|_| Error::<T>::InvalidTokenId)?;
+		let amount =
+			Self::packet_amount(&denom, transfer.amount).map_err(|_| Error::<T>::InvalidAmount)?;
+
+		// the same refund as the one of the failed packet
+		let sender = T::AccountIdConversion::from(transfer.sender.clone());
+		let coin = PrefixedCoin { denom, amount };
+		let mut ctx = IbcTransferModule(PhantomData::<T>);
+		let result = if is_sender_chain_source(port_id.clone(), channel_id.clone(), &coin.denom) {
+			let escrow = T::AccountIdConversion::from(Self::escrow_account(port_id, channel_id));
+			ctx.send_coins(&escrow, &sender, &coin)
+		} else {
+			ctx.mint_coins(&sender, &coin)
+		};
+		result.map_err(|e| {
+			log::error!(target: LOG_TARGET, "❌ [refund_failed] {} on {}: {}", sequence, channel_id, e);
+			Error::<T>::TransferFailed
+		})?;
+
+		<PendingTransfers<T>>::remove(&key, sequence);
+		<PendingTransfersBySender<T>>::remove(&transfer.sender, (key, sequence));
+		Ok(transfer.sender)
+	}
+
+	/// The outgoing transfers of `account` that are not settled, with the port, channel and
+	/// sequence of their packet.
+	pub fn pending_transfers(
+		account: &T::AccountId,
+	) -> Vec<(PortId, ChannelId, Sequence, PendingTransfer<T::AccountId, T::BlockNumber>)> {
+		<PendingTransfersBySender<T>>::iter_key_prefix(account)
+			.filter_map(|((port_id, channel_id), sequence)| {
+				<PendingTransfers<T>>::get((port_id.clone(), channel_id.clone()), sequence)
+					.map(|transfer| (port_id, channel_id, sequence, transfer))
+			})
+			.collect()
+	}
+}
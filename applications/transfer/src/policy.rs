@@ -0,0 +1,399 @@
+//! Policies deciding which transfers are allowed.
+//!
+//! [`Config::TransferPolicy`] is consulted with the full context of a transfer, before the funds
+//! of a send are escrowed or burnt and before the funds of a receive are minted or unescrowed. It
+//! may allow the transfer, deny it with a reason, or hold it:
+//! - a denied send fails, and a denied receive gets an error acknowledgement carrying the reason;
+//! - a held send fails, as its funds would have to leave the sender before it is reviewed;
+//! - a held receive is credited to the hold account of the pallet and recorded in
+//!   [`HeldTransfers`], until [`Config::AdminOrigin`] releases it to its receiver or returns it to
+//!   its sender with a new transfer over the channel it was received over.
+//!
+//! A received transfer whose denomination or amount cannot be converted to this chain is denied.
+//!
+//! [`ListPolicy`] is a reference policy deciding from lists of accounts, addresses, counterparty
+//! chains and denominations managed by [`Config::AdminOrigin`].
+use crate::{
+	callback::IbcTransferModule, packet::FungibleTokenPacketData, transfer::OutgoingTransfer,
+	utils::transactional, *,
+};
+use alloc::{
+	format,
+	string::{String, ToString},
+};
+use codec::{Decode, Encode, MaxEncodedLen};
+use ibc::{
+	applications::transfer::{context::BankKeeper, PrefixedCoin, PrefixedDenom},
+	core::{
+		ics04_channel::{
+			msgs::acknowledgement::Acknowledgement, packet::Packet, timeout::TimeoutHeight,
+		},
+		ics24_host::identifier::ChainId,
+		ics26_routing::context::ModuleOutputBuilder,
+	},
+	timestamp::Timestamp,
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use scale_info::TypeInfo;
+use sp_runtime::traits::AccountIdConversion;
+use sp_std::str::FromStr;
+
+/// Direction of a transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+	/// Sent from this chain
+	Send,
+	/// Received by this chain
+	Receive,
+}
+
+/// Context of a transfer checked by a [`TransferPolicy`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferInfo<AccountId> {
+	pub direction: Direction,
+	/// Account of this chain sending or receiving the funds, `None` if the receiver of a received
+	/// transfer is not an account of this chain
+	pub account: Option<AccountId>,
+	pub sender: String,
+	pub receiver: String,
+	/// Denomination of the funds on this chain, with its trace
+	pub denom: PrefixedDenom,
+	/// Amount of the funds, in local units
+	pub amount: u128,
+	pub port_id: PortId,
+	/// Channel of this chain the transfer is sent or received over
+	pub channel_id: ChannelId,
+	/// Chain id of the counterparty client of the channel, if it is known
+	pub counterparty_chain_id: Option<ChainId>,
+}
+
+/// Decision of a [`TransferPolicy`] on a transfer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolicyDecision {
+	Allow,
+	/// Reject the transfer for the given reason
+	Deny(String),
+	/// Keep the funds until governance reviews the transfer
+	Hold,
+}
+
+/// Decides whether transfers are allowed.
+pub trait TransferPolicy<AccountId> {
+	/// The decision on `transfer`.
+	fn check(transfer: &TransferInfo<AccountId>) -> PolicyDecision;
+}
+
+/// Allows every transfer.
+impl<AccountId> TransferPolicy<AccountId> for () {
+	fn check(_transfer: &TransferInfo<AccountId>) -> PolicyDecision {
+		PolicyDecision::Allow
+	}
+}
+
+/// What a [`ListPolicy`] rule applies to
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum PolicyTarget<AccountId> {
+	/// An account of this chain sending or receiving transfers
+	Account(AccountId),
+	/// An address of a counterparty chain sending or receiving transfers
+	Address(Vec<u8>),
+	/// A counterparty chain, by chain id
+	Chain(Vec<u8>),
+	/// A denomination, as it is named on this chain
+	Denom(Vec<u8>),
+}
+
+impl<AccountId> PolicyTarget<AccountId> {
+	fn kind(&self) -> Option<PolicyKind> {
+		match self {
+			Self::Account(_) => Some(PolicyKind::Account),
+			Self::Address(_) => None,
+			Self::Chain(_) => Some(PolicyKind::Chain),
+			Self::Denom(_) => Some(PolicyKind::Denom),
+		}
+	}
+}
+
+/// Rule of a [`ListPolicy`] on a target
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo, MaxEncodedLen)]
+pub enum PolicyRule {
+	Allow,
+	Deny,
+	Hold,
+}
+
+/// Kinds of targets a [`ListPolicy`] may only allow when they are allowlisted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo, MaxEncodedLen)]
+pub enum PolicyKind {
+	Account,
+	Chain,
+	Denom,
+}
+
+/// A received transfer held for review
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct HeldTransfer {
+	/// Sender on the counterparty chain
+	pub sender: Vec<u8>,
+	pub receiver: Vec<u8>,
+	/// Denomination of the funds on this chain
+	pub denom: Vec<u8>,
+	/// Amount of the funds credited to the hold account, in local units
+	pub amount: u128,
+}
+
+/// Decides from the rules set in [`PolicyRules`]: a transfer is denied if one of its targets is
+/// denied or, for a kind set in [`StrictPolicyKinds`], its target of the kind is not allowed. It
+/// is otherwise held if one of its targets is held.
+pub struct ListPolicy<T>(PhantomData<T>);
+
+impl<T: Config> TransferPolicy<T::AccountId> for ListPolicy<T> {
+	fn check(transfer: &TransferInfo<T::AccountId>) -> PolicyDecision {
+		let remote = match transfer.direction {
+			Direction::Send => &transfer.receiver,
+			Direction::Receive => &transfer.sender,
+		};
+		let mut targets = Vec::new();
+		targets.extend(transfer.account.clone().map(PolicyTarget::Account));
+		targets.push(PolicyTarget::Address(remote.as_bytes().to_vec()));
+		targets.extend(
+			transfer
+				.counterparty_chain_id
+				.as_ref()
+				.map(|chain_id| PolicyTarget::Chain(chain_id.as_str().as_bytes().to_vec())),
+		);
+		targets.push(PolicyTarget::Denom(transfer.denom.to_string().into_bytes()));
+		let rules: Vec<_> = targets
+			.into_iter()
+			.map(|target| {
+				let rule = <PolicyRules<T>>::get(&target);
+				(target, rule)
+			})
+			.collect();
+
+		if let Some((target, _)) = rules.iter().find(|(_, rule)| *rule == Some(PolicyRule::Deny)) {
+			return PolicyDecision::Deny(format!("{:?} is denied", target))
+		}
+		for (kind, _) in <StrictPolicyKinds<T>>::iter() {
+			let allowed = rules.iter().any(|(target, rule)| {
+				target.kind() == Some(kind) && *rule == Some(PolicyRule::Allow)
+			});
+			if !allowed {
+				return PolicyDecision::Deny(format!("{:?} is not allowed", kind))
+			}
+		}
+		if rules.iter().any(|(_, rule)| *rule == Some(PolicyRule::Hold)) {
+			return PolicyDecision::Hold
+		}
+		PolicyDecision::Allow
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// The chain id of the counterparty client of `channel_id`.
+	pub fn counterparty_chain_id(port_id: &PortId, channel_id: &ChannelId) -> Option<ChainId> {
+		let channel_end = T::IbcContext::channel_end(port_id, channel_id).ok()?;
+		let connection_end =
+			T::IbcContext::connection_end(channel_end.connection_hops().first()?).ok()?;
+		Some(T::IbcContext::client_state(connection_end.client_id()).ok()?.chain_id())
+	}
+
+	/// Check that the policy allows `sender` to send `amount` of `denom` to `receiver`.
+	pub(crate) fn ensure_send_allowed(
+		sender: &T::AccountId,
+		receiver: &str,
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		denom: &PrefixedDenom,
+		amount: u128,
+	) -> Result<(), Error<T>> {
+		let transfer = TransferInfo {
+			direction: Direction::Send,
+			account: Some(sender.clone()),
+			sender: Self::account_address(sender),
+			receiver: receiver.to_string(),
+			denom: denom.clone(),
+			amount,
+			port_id: port_id.clone(),
+			channel_id: channel_id.clone(),
+			counterparty_chain_id: Self::counterparty_chain_id(port_id, channel_id),
+		};
+		match T::TransferPolicy::check(&transfer) {
+			PolicyDecision::Allow => Ok(()),
+			PolicyDecision::Deny(reason) => {
+				log::error!(target: LOG_TARGET, "❌ [ensure_send_allowed] {}", reason);
+				Err(Error::<T>::TransferDenied)
+			},
+			PolicyDecision::Hold => Err(Error::<T>::TransferOnHold),
+		}
+	}
+
+	/// The decision of the policy on the transfer received with `packet`, `Allow` if the packet
+	/// is no valid transfer, which the transfer module then rejects, and `Deny` if its funds have
+	/// no amount on this chain.
+	pub(crate) fn recv_decision(packet: &Packet) -> PolicyDecision {
+		let data = match FungibleTokenPacketData::from_bytes(&packet.data) {
+			Ok(data) => data,
+			Err(_) => return PolicyDecision::Allow,
+		};
+		let (denom, amount) = match Self::received_coin(packet) {
+			Some(coin) => coin,
+			None =>
+				return PolicyDecision::Deny(format!(
+					"invalid amount {} of {}",
+					data.amount, data.denom
+				)),
+		};
+		let account = Signer::from_str(&data.receiver)
+			.ok()
+			.and_then(|signer| T::AccountIdConversion::try_from(signer).ok())
+			.map(|account| account.into_account());
+		T::TransferPolicy::check(&TransferInfo {
+			direction: Direction::Receive,
+			account,
+			sender: data.sender,
+			receiver: data.receiver,
+			denom,
+			amount,
+			port_id: packet.port_on_b.clone(),
+			channel_id: packet.chan_on_b.clone(),
+			counterparty_chain_id: Self::counterparty_chain_id(
+				&packet.port_on_b,
+				&packet.chan_on_b,
+			),
+		})
+	}
+
+	/// The account holding the received transfers held for review.
+	pub fn hold_account() -> T::AccountId {
+		T::PalletId::get().into_sub_account_truncating(b"hold")
+	}
+
+	/// The funds of `held`, in packet units.
+	fn held_coin(held: &HeldTransfer) -> Result<PrefixedCoin, Error<T>> {
+		let denom = PrefixedDenom::from_str(&String::from_utf8_lossy(&held.denom))
+			.map_err(|_| Error::<T>::InvalidTokenId)?;
+		let amount =
+			Self::packet_amount(&denom, held.amount).map_err(|_| Error::<T>::InvalidAmount)?;
+		Ok(PrefixedCoin { denom, amount })
+	}
+
+	/// Release the transfer received with packet `sequence` over `channel_id` to its receiver.
+	pub(crate) fn release_held(
+		channel_id: &ChannelId,
+		sequence: Sequence,
+	) -> Result<T::AccountId, Error<T>> {
+		let held = <HeldTransfers<T>>::take(channel_id, sequence)
+			.ok_or(Error::<T>::HeldTransferNotFound)?;
+		let receiver = Signer::from_str(&String::from_utf8_lossy(&held.receiver))
+			.ok()
+			.and_then(|signer| T::AccountIdConversion::try_from(signer).ok())
+			.ok_or(Error::<T>::InvalidReceiver)?;
+		let coin = Self::held_coin(&held)?;
+
+		let hold = T::AccountIdConversion::from(Self::hold_account());
+		IbcTransferModule(PhantomData::<T>)
+			.send_coins(&hold, &receiver, &coin)
+			.map_err(|e| {
+				log::error!(target: LOG_TARGET, "❌ [release_held] {}: {}", sequence, e);
+				Error::<T>::TransferFailed
+			})?;
+		Ok(receiver.into_account())
+	}
+
+	/// Return the transfer received with packet `sequence` over `channel_id` to its sender, with
+	/// a transfer from the hold account over the same channel, returning the sequence of its
+	/// packet. The policy is not consulted, and a failed return refunds the hold account.
+	pub(crate) fn return_held(
+		channel_id: &ChannelId,
+		sequence: Sequence,
+	) -> Result<Sequence, Error<T>> {
+		let held = <HeldTransfers<T>>::take(channel_id, sequence)
+			.ok_or(Error::<T>::HeldTransferNotFound)?;
+		let coin = Self::held_coin(&held)?;
+		let hold = Self::hold_account();
+		let receiver = String::from_utf8(held.sender).map_err(|_| Error::<T>::InvalidReceiver)?;
+
+		let sequence = Self::send_outgoing_unchecked(OutgoingTransfer {
+			sender: hold.clone(),
+			port_id: PortId::transfer(),
+			channel_id: channel_id.clone(),
+			coin,
+			receiver: receiver.clone(),
+			memo: String::new(),
+			timeout_height: TimeoutHeight::no_timeout(),
+			timeout_timestamp: Timestamp::none(),
+		})?;
+		Self::track_transfer(
+			&PortId::transfer(),
+			channel_id,
+			sequence,
+			hold,
+			receiver.into_bytes(),
+			held.denom,
+			held.amount,
+		);
+		Ok(sequence)
+	}
+}
+
+impl<T: Config> IbcTransferModule<T> {
+	/// Receive the funds of `packet` on the hold account and record the transfer for review.
+	pub(crate) fn recv_packet_on_hold(
+		&mut self,
+		output: &mut ModuleOutputBuilder,
+		packet: &Packet,
+		relayer: &Signer,
+	) -> Acknowledgement {
+		let result = transactional(|| {
+			let data = FungibleTokenPacketData::from_bytes(&packet.data)
+				.map_err(|e| format!("invalid packet data: {}", e))?;
+			let hold = Pallet::<T>::hold_account();
+			if !frame_system::Pallet::<T>::account_exists(&hold) {
+				frame_system::Pallet::<T>::inc_providers(&hold);
+			}
+
+			let recv_data = FungibleTokenPacketData {
+				receiver: Pallet::<T>::account_address(&hold),
+				memo: String::new(),
+				..data.clone()
+			};
+			let recv_packet = Packet { data: recv_data.to_bytes(), ..packet.clone() };
+			let ack = ibc::applications::transfer::context::on_recv_packet(
+				self,
+				output,
+				&recv_packet,
+				relayer,
+			);
+			match GenericAcknowledgement::from_bytes(ack.as_ref()) {
+				Some(GenericAcknowledgement::Result(_)) => {},
+				Some(GenericAcknowledgement::Error(error)) => return Err(error),
+				None => return Err("invalid acknowledgement".to_string()),
+			}
+
+			let (denom, amount) = Pallet::<T>::received_coin(packet)
+				.ok_or_else(|| "invalid received denomination".to_string())?;
+			<HeldTransfers<T>>::insert(
+				&packet.chan_on_b,
+				packet.sequence,
+				HeldTransfer {
+					sender: data.sender.into_bytes(),
+					receiver: data.receiver.into_bytes(),
+					denom: denom.to_string().into_bytes(),
+					amount,
+				},
+			);
+			Pallet::<T>::deposit_event(Event::<T>::TransferHeld {
+				channel_id: packet.chan_on_b.clone(),
+				sequence: packet.sequence,
+			});
+
+			Ok(ack)
+		});
+
+		result.unwrap_or_else(|e| {
+			log::error!(target: LOG_TARGET, "❌ [policy] hold received transfer: {}", e);
+			GenericAcknowledgement::error(e).into()
+		})
+	}
+}
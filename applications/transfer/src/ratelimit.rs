@@ -0,0 +1,236 @@
+//! Rate limits of ICS20 flows.
+//!
+//! A [`RateLimit`] set by [`Config::AdminOrigin`] on a (channel, denom) pair caps the net flow of
+//! the denom over the channel in each window of [`Quota::window`] blocks, as a percentage of the
+//! supply of the denom on this chain when the window started. Sends over the quota are rejected and
+//! receives over it get an error acknowledgement.
+//!
+//! Sends that fail, by error acknowledgement or timeout, are taken off the outflow of the window
+//! they were sent in.
+use crate::{assets::LocalAsset, packet::FungibleTokenPacketData, utils::get_received_denom, *};
+use alloc::string::ToString;
+use codec::{Decode, Encode, MaxEncodedLen};
+use frame_support::traits::fungibles::Inspect;
+use ibc::{
+	applications::transfer::{Amount, PrefixedDenom},
+	core::{
+		ics04_channel::packet::{Packet, Sequence},
+		ics24_host::identifier::ChannelId,
+	},
+};
+use scale_info::TypeInfo;
+use sp_runtime::{
+	traits::{AtLeast32BitUnsigned, UniqueSaturatedInto},
+	Percent,
+};
+use sp_std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo, MaxEncodedLen)]
+pub struct Quota<BlockNumber> {
+	/// Maximum net outflow in a window, as a percentage of the channel value
+	pub max_percent_send: Percent,
+	/// Maximum net inflow in a window, as a percentage of the channel value
+	pub max_percent_recv: Percent,
+	/// Length of a window, in blocks
+	pub window: BlockNumber,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Encode, Decode, TypeInfo, MaxEncodedLen)]
+pub struct Flow<BlockNumber> {
+	pub inflow: u128,
+	pub outflow: u128,
+	/// Supply of the denom when the window started
+	pub channel_value: u128,
+	/// Block the window ends at
+	pub window_end: BlockNumber,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo, MaxEncodedLen)]
+pub struct RateLimit<BlockNumber> {
+	pub quota: Quota<BlockNumber>,
+	pub flow: Flow<BlockNumber>,
+}
+
+impl<BlockNumber: AtLeast32BitUnsigned + Copy> RateLimit<BlockNumber> {
+	pub fn new(quota: Quota<BlockNumber>) -> Self {
+		Self { quota, flow: Default::default() }
+	}
+
+	/// Start a new window if the current one is over.
+	fn refresh(&mut self, now: BlockNumber, channel_value: impl FnOnce() -> u128) {
+		if now >= self.flow.window_end {
+			self.flow = Flow {
+				inflow: 0,
+				outflow: 0,
+				channel_value: channel_value(),
+				window_end: now.saturating_add(self.quota.window),
+			};
+		}
+	}
+
+	fn add_outflow(&mut self, amount: u128) -> bool {
+		let outflow = self.flow.outflow.saturating_add(amount);
+		if outflow.saturating_sub(self.flow.inflow) >
+			self.quota.max_percent_send.mul_floor(self.flow.channel_value)
+		{
+			return false
+		}
+		self.flow.outflow = outflow;
+		true
+	}
+
+	fn add_inflow(&mut self, amount: u128) -> bool {
+		let inflow = self.flow.inflow.saturating_add(amount);
+		if inflow.saturating_sub(self.flow.outflow) >
+			self.quota.max_percent_recv.mul_floor(self.flow.channel_value)
+		{
+			return false
+		}
+		self.flow.inflow = inflow;
+		true
+	}
+}
+
+/// The denomination and amount of an ICS20 packet, the denomination as it is on the sending
+/// chain.
+fn packet_coin(packet: &Packet) -> Option<(String, Amount)> {
+	let data = FungibleTokenPacketData::from_bytes(&packet.data).ok()?;
+	let amount = Amount::from_str(&data.amount).ok()?;
+	Some((data.denom, amount))
+}
+
+impl<T: Config> Pallet<T> {
+	/// Supply of `denom` on this chain.
+	pub fn channel_value(denom: &PrefixedDenom) -> u128 {
+		match Self::local_asset(denom) {
+			Some(LocalAsset::Native) => T::Currency::total_issuance().unique_saturated_into(),
+			Some(LocalAsset::Asset(asset_id)) =>
+				<T::Fungibles as Inspect<T::AccountId>>::total_issuance(asset_id).into(),
+			None => 0,
+		}
+	}
+
+	/// Account `amount` of `denom` sent over `channel_id` with packet `sequence`.
+	pub fn record_send(
+		channel_id: &ChannelId,
+		sequence: Sequence,
+		denom: &PrefixedDenom,
+		amount: u128,
+	) -> Result<(), Error<T>> {
+		let now = frame_system::Pallet::<T>::block_number();
+		<RateLimits<T>>::try_mutate(channel_id, denom.to_string().as_bytes(), |limit| {
+			if let Some(limit) = limit {
+				limit.refresh(now, || Self::channel_value(denom));
+				ensure!(limit.add_outflow(amount), Error::<T>::RateLimitExceeded);
+				<RateLimitedSends<T>>::insert(channel_id, sequence, limit.flow.window_end);
+			}
+			Ok(())
+		})
+	}
+
+	/// Account `amount` of `denom` received over `channel_id`.
+	pub fn record_recv(
+		channel_id: &ChannelId,
+		denom: &PrefixedDenom,
+		amount: u128,
+	) -> Result<(), Error<T>> {
+		let now = frame_system::Pallet::<T>::block_number();
+		<RateLimits<T>>::try_mutate(channel_id, denom.to_string().as_bytes(), |limit| {
+			if let Some(limit) = limit {
+				limit.refresh(now, || Self::channel_value(denom));
+				ensure!(limit.add_inflow(amount), Error::<T>::RateLimitExceeded);
+			}
+			Ok(())
+		})
+	}
+
+	/// Take the send of packet `sequence` off the outflow of `channel_id`, if it is still in the
+	/// window it was sent in.
+	pub fn undo_send(
+		channel_id: &ChannelId,
+		sequence: Sequence,
+		denom: &PrefixedDenom,
+		amount: u128,
+	) {
+		if let Some(window_end) = <RateLimitedSends<T>>::take(channel_id, sequence) {
+			<RateLimits<T>>::mutate(channel_id, denom.to_string().as_bytes(), |limit| {
+				if let Some(limit) = limit.as_mut().filter(|l| l.flow.window_end == window_end) {
+					limit.flow.outflow = limit.flow.outflow.saturating_sub(amount);
+				}
+			});
+		}
+	}
+
+	/// Take a receive that failed off the inflow of `channel_id`.
+	pub fn undo_recv(channel_id: &ChannelId, denom: &PrefixedDenom, amount: u128) {
+		<RateLimits<T>>::mutate(channel_id, denom.to_string().as_bytes(), |limit| {
+			if let Some(limit) = limit {
+				limit.flow.inflow = limit.flow.inflow.saturating_sub(amount);
+			}
+		});
+	}
+
+	/// The denomination on this chain and local amount of the funds received with `packet`.
+	pub(crate) fn received_coin(packet: &Packet) -> Option<(PrefixedDenom, u128)> {
+		let (denom, amount) = packet_coin(packet)?;
+		let (denom, _) = get_received_denom(packet, &denom).ok()?;
+		let amount = Self::local_amount(&denom, amount, false).ok()?;
+		Some((denom, amount))
+	}
+
+	/// The denomination and local amount of the funds sent with `packet`.
+	fn sent_coin(packet: &Packet) -> Option<(PrefixedDenom, u128)> {
+		let (denom, amount) = packet_coin(packet)?;
+		let denom = PrefixedDenom::from_str(&denom).ok()?;
+		let amount = Self::local_amount(&denom, amount, true).ok()?;
+		Some((denom, amount))
+	}
+
+	/// Account the funds received with `packet`.
+	pub(crate) fn record_recv_packet(packet: &Packet) -> Result<(), Error<T>> {
+		if let Some((denom, amount)) = Self::received_coin(packet) {
+			Self::record_recv(&packet.chan_on_b, &denom, amount)?;
+		}
+		Ok(())
+	}
+
+	pub(crate) fn undo_recv_packet(packet: &Packet) {
+		if let Some((denom, amount)) = Self::received_coin(packet) {
+			Self::undo_recv(&packet.chan_on_b, &denom, amount);
+		}
+	}
+
+	/// Settle the accounting of a packet sent by this chain, taking it off the outflow if it
+	/// failed.
+	pub(crate) fn settle_send_packet(packet: &Packet, success: bool) {
+		if success {
+			<RateLimitedSends<T>>::remove(&packet.chan_on_a, packet.sequence);
+		} else if let Some((denom, amount)) = Self::sent_coin(packet) {
+			Self::undo_send(&packet.chan_on_a, packet.sequence, &denom, amount);
+		}
+	}
+}
+
+#[test]
+fn test_rate_limit_window() {
+	let quota = Quota {
+		max_percent_send: Percent::from_percent(10),
+		max_percent_recv: Percent::from_percent(20),
+		window: 10u32,
+	};
+	let mut limit = RateLimit::new(quota);
+	limit.refresh(1, || 1_000);
+	assert_eq!(limit.flow.window_end, 11);
+
+	assert!(limit.add_outflow(100));
+	assert!(!limit.add_outflow(1));
+	// inflow nets out the outflow
+	assert!(limit.add_inflow(50));
+	assert!(limit.add_outflow(50));
+	assert!(!limit.add_inflow(301));
+
+	limit.refresh(5, || 0);
+	assert_eq!(limit.flow.outflow, 150);
+	limit.refresh(11, || 2_000);
+	assert_eq!(limit.flow, Flow { inflow: 0, outflow: 0, channel_value: 2_000, window_end: 21 });
+}
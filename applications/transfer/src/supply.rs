@@ -0,0 +1,203 @@
+//! Accounting of escrowed tokens and minted vouchers.
+//!
+//! The tokens moved in and out of the escrow account of a channel are summed in [`EscrowTotals`],
+//! per channel and denomination, and the vouchers minted and burnt in [`VoucherSupply`], per
+//! `ibc/{hash}` denomination. The escrowed total of a denomination is the supply of its vouchers
+//! on the counterparty chain, and the voucher supply what was received and not sent back.
+//!
+//! Both are updated with checked arithmetic, failing the transfer that would overflow or underflow
+//! them, and were seeded from the balances and issuances of the chain by [`migrations::v3`]. Until
+//! that migration is done, they may underflow to zero, as the seeding overwrites them.
+//!
+//! The `try_state` hook checks that every escrow account holds exactly its escrowed totals beyond
+//! its existential deposit, and that the issuance of every voucher asset is its supply.
+use crate::{assets::LocalAsset, *};
+use alloc::string::ToString;
+use frame_support::traits::fungibles::Inspect;
+use ibc::applications::transfer::{error::TokenTransferError, PrefixedDenom};
+use sp_runtime::traits::UniqueSaturatedInto;
+
+impl<T: Config> Pallet<T> {
+	/// The balance of `account` in `asset`.
+	pub fn asset_balance(account: &T::AccountId, asset: LocalAsset<T::AssetId>) -> u128 {
+		match asset {
+			LocalAsset::Native => T::Currency::free_balance(account).unique_saturated_into(),
+			LocalAsset::Asset(asset_id) =>
+				<T::Fungibles as Inspect<T::AccountId>>::balance(asset_id, account).into(),
+		}
+	}
+
+	/// The balance of `asset` held in escrow by `account`: the whole balance of an asset, the
+	/// balance beyond the existential deposit of the native token.
+	pub fn escrowed_balance(account: &T::AccountId, asset: LocalAsset<T::AssetId>) -> u128 {
+		let balance = Self::asset_balance(account, asset);
+		match asset {
+			LocalAsset::Native =>
+				balance.saturating_sub(T::Currency::minimum_balance().unique_saturated_into()),
+			LocalAsset::Asset(_) => balance,
+		}
+	}
+
+	/// The full paths of the denominations that may be escrowed, with their local asset: the
+	/// native token, the assets of [`NativeAssets`] and the vouchers of [`DenomTrace`].
+	pub fn escrowable_denoms() -> Vec<(Vec<u8>, LocalAsset<T::AssetId>)> {
+		let mut denoms = sp_std::vec![(T::NATIVE_TOKEN_NAME.to_vec(), LocalAsset::Native)];
+		denoms.extend(
+			<NativeAssets<T>>::iter().map(|(denom, asset_id)| (denom, LocalAsset::Asset(asset_id))),
+		);
+		denoms.extend(<DenomTrace<T>>::iter().filter_map(|(hash, trace)| {
+			let asset_id = <AssetIdByName<T>>::try_get(&*hash).ok()?;
+			Some((trace.full_path(), LocalAsset::Asset(asset_id)))
+		}));
+		denoms
+	}
+
+	/// Whether the escrowed totals and voucher supplies are seeded: they are not while the
+	/// migration to version 3, or an earlier one, is in progress.
+	pub(crate) fn supply_seeded() -> bool {
+		Self::on_chain_storage_version() >= 3 || !<MigrationCursor<T>>::exists()
+	}
+
+	/// Account a transfer of `amount` of `denom` from `from` to `to`, if either is an escrow
+	/// account.
+	pub(crate) fn record_escrow_transfer(
+		from: &T::AccountId,
+		to: &T::AccountId,
+		denom: &PrefixedDenom,
+		amount: u128,
+	) -> Result<(), TokenTransferError> {
+		let path = denom.to_string().into_bytes();
+		if let Some((_, channel_id)) = <EscrowAccountChannels<T>>::get(from) {
+			<EscrowTotals<T>>::try_mutate(&channel_id, &path, |total| {
+				*total = total
+					.checked_sub(amount)
+					.or_else(|| (!Self::supply_seeded()).then_some(0))
+					.ok_or_else(|| {
+						log::error!(
+							target: LOG_TARGET,
+							"❌ [record_escrow_transfer] {} of {} leaving {} escrowing {}",
+							amount,
+							denom,
+							channel_id,
+							total
+						);
+						TokenTransferError::InvalidToken
+					})?;
+				Ok::<_, TokenTransferError>(())
+			})?;
+		}
+		if let Some((_, channel_id)) = <EscrowAccountChannels<T>>::get(to) {
+			<EscrowTotals<T>>::try_mutate(&channel_id, &path, |total| {
+				*total = total.checked_add(amount).ok_or_else(|| {
+					log::error!(
+						target: LOG_TARGET,
+						"❌ [record_escrow_transfer] {} of {} overflows the total of {}",
+						amount,
+						denom,
+						channel_id
+					);
+					TokenTransferError::InvalidToken
+				})?;
+				Ok::<_, TokenTransferError>(())
+			})?;
+		}
+		Ok(())
+	}
+
+	/// Account `amount` of vouchers of `denom` minted.
+	pub(crate) fn record_mint(
+		denom: &PrefixedDenom,
+		amount: u128,
+	) -> Result<(), TokenTransferError> {
+		<VoucherSupply<T>>::try_mutate(Self::asset_name(denom), |supply| {
+			*supply = supply.checked_add(amount).ok_or_else(|| {
+				log::error!(target: LOG_TARGET, "❌ [record_mint] {} of {} overflows its supply", amount, denom);
+				TokenTransferError::InvalidToken
+			})?;
+			Ok(())
+		})
+	}
+
+	/// Account `amount` of vouchers of `denom` burnt.
+	pub(crate) fn record_burn(
+		denom: &PrefixedDenom,
+		amount: u128,
+	) -> Result<(), TokenTransferError> {
+		<VoucherSupply<T>>::try_mutate(Self::asset_name(denom), |supply| {
+			*supply = supply
+				.checked_sub(amount)
+				.or_else(|| (!Self::supply_seeded()).then_some(0))
+				.ok_or_else(|| {
+					log::error!(
+						target: LOG_TARGET,
+						"❌ [record_burn] {} of {} exceeds its supply {}",
+						amount,
+						denom,
+						supply
+					);
+					TokenTransferError::InvalidToken
+				})?;
+			Ok(())
+		})
+	}
+
+	/// The escrowed totals, by channel and denomination.
+	pub fn escrow_totals() -> Vec<(ChannelId, Vec<u8>, u128)> {
+		<EscrowTotals<T>>::iter().collect()
+	}
+
+	/// The supplies of vouchers, by `ibc/{hash}` denomination.
+	pub fn voucher_supplies() -> Vec<(Vec<u8>, u128)> {
+		<VoucherSupply<T>>::iter().collect()
+	}
+
+	/// Check that the escrow accounts hold their escrowed totals and the voucher assets were
+	/// issued for their supplies.
+	#[cfg(any(feature = "try-runtime", test))]
+	pub fn do_try_state() -> Result<(), &'static str> {
+		use sp_std::str::FromStr;
+
+		// the accounting is seeded by the migration to version 3, the entries that no longer
+		// decode removed by the migration to version 4
+		if <MigrationCursor<T>>::exists() {
+			return Ok(())
+		}
+		ensure!(
+			migrations::v4::undecodable_entries::<T>() == 0,
+			"storage entry that no longer decodes"
+		);
+		let denoms = Self::escrowable_denoms();
+		for (_, channel_id, account) in <EscrowAccounts<T>>::iter() {
+			for (denom, total) in <EscrowTotals<T>>::iter_prefix(&channel_id) {
+				let asset = PrefixedDenom::from_str(&String::from_utf8_lossy(&denom))
+					.ok()
+					.and_then(|denom| Self::local_asset(&denom))
+					.ok_or("escrowed denomination without local asset")?;
+				ensure!(
+					Self::escrowed_balance(&account, asset) == total,
+					"escrow account does not hold its escrowed total"
+				);
+			}
+			// the denominations without total are not escrowed
+			for (denom, asset) in denoms.iter() {
+				if !<EscrowTotals<T>>::contains_key(&channel_id, denom) {
+					ensure!(
+						Self::escrowed_balance(&account, *asset) == 0,
+						"escrow account holds a denomination without escrowed total"
+					);
+				}
+			}
+		}
+
+		for (denom, supply) in <VoucherSupply<T>>::iter() {
+			let issuance = match T::AssetIdByName::try_get_asset_id(&denom) {
+				Ok(asset_id) =>
+					<T::Fungibles as Inspect<T::AccountId>>::total_issuance(asset_id).into(),
+				Err(_) => 0,
+			};
+			ensure!(issuance == supply, "voucher issuance is not its supply");
+		}
+
+		Ok(())
+	}
+}
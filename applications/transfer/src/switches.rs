@@ -0,0 +1,88 @@
+//! Switches to stop sending or receiving transfers.
+//!
+//! Sends and receives can be disabled by [`Config::AdminOrigin`] for all transfers, for the
+//! transfers over a channel or for the transfers of a denomination, e.g. to stop the flows of a
+//! compromised counterparty during an incident without a runtime upgrade. A transfer is only sent
+//! or received if it is enabled by all three switches.
+use crate::*;
+use alloc::string::ToString;
+use codec::{Decode, Encode, MaxEncodedLen};
+use ibc::{applications::transfer::PrefixedDenom, core::ics04_channel::packet::Packet};
+use scale_info::TypeInfo;
+
+/// Whether transfers may be sent and received
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo, MaxEncodedLen)]
+pub struct Switch {
+	pub send: bool,
+	pub receive: bool,
+}
+
+impl Default for Switch {
+	fn default() -> Self {
+		Self { send: true, receive: true }
+	}
+}
+
+/// The transfers a switch applies to
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum SwitchScope {
+	/// All transfers
+	Global,
+	/// Transfers over a channel, the source channel of sends and the destination one of receives
+	Channel(ChannelId),
+	/// Transfers of a denomination, as it is named on this chain
+	Denom(Vec<u8>),
+}
+
+impl<T: Config> Pallet<T> {
+	/// The switch of `scope`.
+	pub fn switch(scope: &SwitchScope) -> Switch {
+		match scope {
+			SwitchScope::Global => <GlobalSwitch<T>>::get(),
+			SwitchScope::Channel(channel_id) =>
+				<ChannelSwitches<T>>::get(channel_id).unwrap_or_default(),
+			SwitchScope::Denom(denom) => <DenomSwitches<T>>::get(denom).unwrap_or_default(),
+		}
+	}
+
+	/// Set the switch of `scope`, removing channel and denom switches that enable both.
+	pub(crate) fn store_switch(scope: &SwitchScope, switch: Switch) {
+		let stored = (switch != Switch::default()).then_some(switch);
+		match scope {
+			SwitchScope::Global => <GlobalSwitch<T>>::put(switch),
+			SwitchScope::Channel(channel_id) => <ChannelSwitches<T>>::set(channel_id, stored),
+			SwitchScope::Denom(denom) => <DenomSwitches<T>>::set(denom, stored),
+		}
+	}
+
+	/// The switches applying to the transfers of `denom` over `channel_id`.
+	fn switches(channel_id: &ChannelId, denom: &PrefixedDenom) -> [Switch; 3] {
+		[
+			<GlobalSwitch<T>>::get(),
+			Self::switch(&SwitchScope::Channel(channel_id.clone())),
+			Self::switch(&SwitchScope::Denom(denom.to_string().into_bytes())),
+		]
+	}
+
+	/// Whether `denom` may be sent over `channel_id`.
+	pub fn is_send_enabled_for(channel_id: &ChannelId, denom: &PrefixedDenom) -> bool {
+		Self::switches(channel_id, denom).iter().all(|switch| switch.send)
+	}
+
+	/// Whether `denom` may be received over `channel_id`.
+	pub fn is_receive_enabled_for(channel_id: &ChannelId, denom: &PrefixedDenom) -> bool {
+		Self::switches(channel_id, denom).iter().all(|switch| switch.receive)
+	}
+
+	/// Check that the funds of `packet` may be received.
+	pub(crate) fn ensure_receive_enabled(packet: &Packet) -> Result<(), Error<T>> {
+		ensure!(<GlobalSwitch<T>>::get().receive, Error::<T>::ReceiveDisabled);
+		if let Some((denom, _)) = Self::received_coin(packet) {
+			ensure!(
+				Self::is_receive_enabled_for(&packet.chan_on_b, &denom),
+				Error::<T>::ReceiveDisabled
+			);
+		}
+		Ok(())
+	}
+}
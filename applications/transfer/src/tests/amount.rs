@@ -0,0 +1,140 @@
+//! Decimal conversions of ICS20 amounts.
+use crate::{
+	amount::DecimalConversion,
+	callback::IbcTransferModule,
+	mock::{new_test_ext, Balances, RuntimeEvent, RuntimeOrigin, System, Test, DOLLARS},
+	tests::{account, alice, escrow_balance, open_channels, packet, relayer, send},
+	DecimalConversions, Error, Event, Pallet, VoucherSupply,
+};
+use core::marker::PhantomData;
+use frame_support::{assert_noop, assert_ok};
+use ibc::core::ics26_routing::context::{Module, ModuleOutputBuilder};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use sp_runtime::{traits::IdentifyAccount, DispatchError};
+
+/// 18 decimals on this chain, 6 in packets
+const CONVERSION: DecimalConversion = DecimalConversion { local_decimals: 18, remote_decimals: 6 };
+
+#[test]
+fn test_decimal_conversion_is_set_by_admin() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let alice = RuntimeOrigin::signed(account(1).into_account());
+
+		assert_noop!(
+			Pallet::<Test>::set_decimal_conversion(alice, b"DEMO".to_vec(), Some(CONVERSION)),
+			DispatchError::BadOrigin
+		);
+		for invalid in [vec![0xff], b"".to_vec(), b"transfer/channel-0/".to_vec()] {
+			assert_noop!(
+				Pallet::<Test>::set_decimal_conversion(
+					RuntimeOrigin::root(),
+					invalid,
+					Some(CONVERSION)
+				),
+				Error::<Test>::InvalidTokenId
+			);
+		}
+
+		assert_ok!(Pallet::<Test>::set_decimal_conversion(
+			RuntimeOrigin::root(),
+			b"transfer/channel-0/uatom".to_vec(),
+			Some(CONVERSION)
+		));
+		// keyed by the full path, as the escrowed totals, switches and rate limits
+		assert_eq!(
+			<DecimalConversions<Test>>::get(b"transfer/channel-0/uatom".to_vec()),
+			Some(CONVERSION)
+		);
+		System::assert_last_event(RuntimeEvent::Ics20Transfer(Event::DecimalConversionSet {
+			denom: b"transfer/channel-0/uatom".to_vec(),
+			conversion: Some(CONVERSION),
+		}));
+
+		assert_ok!(Pallet::<Test>::set_decimal_conversion(
+			RuntimeOrigin::root(),
+			b"transfer/channel-0/uatom".to_vec(),
+			None
+		));
+		assert!(!<DecimalConversions<Test>>::contains_key(b"transfer/channel-0/uatom".to_vec()));
+	})
+}
+
+#[test]
+fn test_decimal_conversion_of_denom_in_use_is_refused() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		open_channels(&[(0, 5)]);
+		account(1);
+
+		// tokens in flight
+		assert_ok!(send(DOLLARS));
+		assert_noop!(
+			Pallet::<Test>::set_decimal_conversion(
+				RuntimeOrigin::root(),
+				b"DEMO".to_vec(),
+				Some(CONVERSION)
+			),
+			Error::<Test>::DenomInUse
+		);
+
+		// vouchers minted
+		<VoucherSupply<Test>>::insert(
+			Pallet::<Test>::asset_name(&"transfer/channel-0/uatom".parse().unwrap()),
+			1,
+		);
+		assert_noop!(
+			Pallet::<Test>::set_decimal_conversion(
+				RuntimeOrigin::root(),
+				b"transfer/channel-0/uatom".to_vec(),
+				Some(CONVERSION)
+			),
+			Error::<Test>::DenomInUse
+		);
+	})
+}
+
+#[test]
+fn test_transfer_round_trip_at_converted_decimals() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		open_channels(&[(0, 5)]);
+		account(1);
+		assert_ok!(Pallet::<Test>::set_decimal_conversion(
+			RuntimeOrigin::root(),
+			b"DEMO".to_vec(),
+			Some(CONVERSION)
+		));
+		let mut module = IbcTransferModule::<Test>(PhantomData);
+
+		// below one packet unit, the amount cannot be sent
+		assert_noop!(send(DOLLARS / 1_000_000 + 1), Error::<Test>::InvalidAmount);
+
+		assert_ok!(send(5 * DOLLARS));
+		assert_eq!(escrow_balance(0), 5 * DOLLARS);
+		assert_eq!(Balances::free_balance(alice()), 5 * DOLLARS);
+
+		// the packet of 5_000_000 units fails, refunding the 5 DOLLARS escrowed
+		module
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&packet(1, 5_000_000, true),
+				&GenericAcknowledgement::error("failed").into(),
+				&relayer(),
+			)
+			.unwrap();
+		assert_eq!(escrow_balance(0), 0);
+		assert_eq!(Balances::free_balance(alice()), 10 * DOLLARS);
+
+		// sent again and returned as 2_000_000 units, 2 DOLLARS are unescrowed
+		assert_ok!(send(5 * DOLLARS));
+		let ack = module.on_recv_packet(
+			&mut ModuleOutputBuilder::new(),
+			&packet(1, 2_000_000, false),
+			&relayer(),
+		);
+		assert!(GenericAcknowledgement::from_bytes(ack.as_ref()).unwrap().is_success());
+		assert_eq!(escrow_balance(0), 3 * DOLLARS);
+		assert_eq!(Balances::free_balance(alice()), 7 * DOLLARS);
+	})
+}
@@ -0,0 +1,125 @@
+//! Registration of the local assets of received denominations.
+use crate::{
+	callback::IbcTransferModule,
+	mock::{new_test_ext, Assets, Balances, RuntimeOrigin, Test, DOLLARS},
+	tests::{account, coin},
+	AssetIdByName, AssetNameById, Error, NextAssetId, Pallet,
+};
+use core::marker::PhantomData;
+use frame_support::{
+	assert_noop, assert_ok,
+	traits::{fungibles::Inspect, Currency},
+};
+use ibc::applications::transfer::{context::BankKeeper, PrefixedDenom};
+use sp_runtime::traits::IdentifyAccount;
+use std::str::FromStr;
+
+const VOUCHER: &str = "transfer/channel-0/uatom";
+
+fn asset_name() -> Vec<u8> {
+	Pallet::<Test>::asset_name(&PrefixedDenom::from_str(VOUCHER).unwrap())
+}
+
+#[test]
+fn test_received_denom_is_registered() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+
+		ctx.mint_coins(&alice, &coin(VOUCHER, 100)).unwrap();
+		assert_eq!(<AssetIdByName<Test>>::get(asset_name()), 1000);
+		assert_eq!(<NextAssetId<Test>>::get(), Some(1001));
+		assert_eq!(Assets::balance(1000, alice.into_account()), 100);
+	})
+}
+
+#[test]
+fn test_blocked_denom_is_not_received() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		assert_ok!(Pallet::<Test>::set_denom_blocked(RuntimeOrigin::root(), asset_name(), true));
+
+		assert_noop!(
+			Pallet::<Test>::ensure_asset(&PrefixedDenom::from_str(VOUCHER).unwrap()),
+			Error::<Test>::DenomBlocked
+		);
+		assert!(ctx.mint_coins(&alice, &coin(VOUCHER, 100)).is_err());
+		assert!(!<AssetIdByName<Test>>::contains_key(asset_name()));
+
+		assert_ok!(Pallet::<Test>::set_denom_blocked(RuntimeOrigin::root(), asset_name(), false));
+		ctx.mint_coins(&alice, &coin(VOUCHER, 100)).unwrap();
+		assert_eq!(Assets::balance(1000, alice.into_account()), 100);
+	})
+}
+
+#[test]
+fn test_mapped_denom_is_not_registered() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		assert_ok!(Assets::force_create(
+			RuntimeOrigin::root(),
+			7,
+			alice.clone().into_account().into(),
+			false,
+			1
+		));
+		assert_ok!(Pallet::<Test>::map_denom(RuntimeOrigin::root(), asset_name(), 7));
+
+		ctx.mint_coins(&alice, &coin(VOUCHER, 100)).unwrap();
+		assert_eq!(Assets::balance(7, alice.into_account()), 100);
+		assert_eq!(<NextAssetId<Test>>::get(), None);
+		assert!(!<Assets as Inspect<_>>::asset_exists(1000));
+	})
+}
+
+#[test]
+fn test_remapped_denom_unmaps_the_previous_asset_and_denom() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		ctx.mint_coins(&alice, &coin(VOUCHER, 100)).unwrap();
+
+		// neither the denomination nor the asset may be remapped while vouchers are minted
+		assert_noop!(
+			Pallet::<Test>::map_denom(RuntimeOrigin::root(), asset_name(), 7),
+			Error::<Test>::DenomInUse
+		);
+		assert_noop!(
+			Pallet::<Test>::map_denom(RuntimeOrigin::root(), b"OTHER".to_vec(), 1000),
+			Error::<Test>::DenomInUse
+		);
+
+		ctx.burn_coins(&alice, &coin(VOUCHER, 100)).unwrap();
+		assert_ok!(Pallet::<Test>::map_denom(RuntimeOrigin::root(), asset_name(), 7));
+		assert_eq!(<AssetIdByName<Test>>::get(asset_name()), 7);
+		assert_eq!(<AssetNameById<Test>>::get(7), Some(asset_name()));
+		assert!(!<AssetNameById<Test>>::contains_key(1000));
+
+		// the asset takes the new denomination
+		assert_ok!(Pallet::<Test>::map_denom(RuntimeOrigin::root(), b"OTHER".to_vec(), 7));
+		assert_eq!(<AssetIdByName<Test>>::get(b"OTHER".to_vec()), 7);
+		assert_eq!(<AssetNameById<Test>>::get(7), Some(b"OTHER".to_vec()));
+		assert!(!<AssetIdByName<Test>>::contains_key(asset_name()));
+	})
+}
+
+#[test]
+fn test_registration_skips_taken_asset_ids() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let bob = account(2).into_account();
+		Balances::make_free_balance_be(&bob, 1_000 * DOLLARS);
+
+		// anyone may create the asset registration would pick next
+		assert_ok!(Assets::create(RuntimeOrigin::signed(bob.clone()), 1000, bob.into(), 1));
+
+		ctx.mint_coins(&alice, &coin(VOUCHER, 100)).unwrap();
+		assert_eq!(<AssetIdByName<Test>>::get(asset_name()), 1001);
+		assert_eq!(<NextAssetId<Test>>::get(), Some(1002));
+		assert_eq!(Assets::balance(1001, alice.clone().into_account()), 100);
+		assert_eq!(Assets::balance(1000, alice.into_account()), 0);
+	})
+}
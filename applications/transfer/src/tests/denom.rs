@@ -0,0 +1,60 @@
+//! Denomination trace queries.
+use crate::{
+	callback::IbcTransferModule,
+	mock::{new_test_ext, Test},
+	tests::{account, coin},
+	utils::derive_ibc_denom_with_path,
+	AssetIdByName, Pallet,
+};
+use core::marker::PhantomData;
+use ibc::applications::transfer::context::BankKeeper;
+use std::collections::BTreeSet;
+
+const PATHS: [&str; 3] = [
+	"transfer/channel-0/uatom",
+	"transfer/channel-1/uosmo",
+	"transfer/channel-0/transfer/channel-9/uatom",
+];
+
+fn hash(path: &str) -> Vec<u8> {
+	derive_ibc_denom_with_path(path).unwrap().into_bytes()
+}
+
+#[test]
+fn test_denom_trace_of_minted_voucher() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		ctx.mint_coins(&account(1), &coin(PATHS[0], 10)).unwrap();
+
+		let trace = Pallet::<Test>::denom_trace(&hash(PATHS[0])).unwrap();
+		assert_eq!(trace.trace_path.to_vec(), b"transfer/channel-0".to_vec());
+		assert_eq!(trace.base_denom.to_vec(), b"uatom".to_vec());
+		let asset_id = <AssetIdByName<Test>>::try_get(hash(PATHS[0])).unwrap();
+		assert_eq!(Pallet::<Test>::denom_trace_by_asset(asset_id), Some(trace));
+		assert_eq!(Pallet::<Test>::denom_trace(&hash(PATHS[1])), None);
+	})
+}
+
+#[test]
+fn test_denom_traces_pages() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		for path in PATHS {
+			ctx.mint_coins(&account(1), &coin(path, 10)).unwrap();
+		}
+
+		let first = Pallet::<Test>::denom_traces(None, 2);
+		assert_eq!(first.len(), 2);
+		let rest = Pallet::<Test>::denom_traces(Some(first[1].0.clone()), 2);
+		assert_eq!(rest.len(), 1);
+
+		let pages = first.into_iter().chain(rest).collect::<Vec<_>>();
+		let hashes = pages.iter().map(|(hash, _)| hash.clone()).collect::<BTreeSet<_>>();
+		assert_eq!(hashes, PATHS.iter().map(|path| hash(path)).collect());
+		for (hash, trace) in pages {
+			let path = String::from_utf8(trace.full_path()).unwrap();
+			assert_eq!(derive_ibc_denom_with_path(&path).unwrap().into_bytes(), hash);
+		}
+		assert!(Pallet::<Test>::denom_traces(None, 0).is_empty());
+	})
+}
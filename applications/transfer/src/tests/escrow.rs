@@ -0,0 +1,113 @@
+//! Escrow accounts of channels.
+use crate::{
+	callback::IbcTransferModule,
+	mock::{
+		new_test_ext, Assets, Balances, ExistentialDeposit, RuntimeOrigin, System, Test,
+		TransferPalletId, DOLLARS,
+	},
+	tests::{account, coin, escrow, escrow_balance},
+	EscrowAccounts, Pallet,
+};
+use core::marker::PhantomData;
+use frame_support::traits::Get;
+use ibc::{
+	applications::transfer::context::{BankKeeper, TokenTransferReader},
+	core::ics24_host::identifier::{ChannelId, PortId},
+};
+use sp_core::crypto::AccountId32;
+use sp_runtime::traits::{AccountIdConversion, IdentifyAccount};
+
+#[test]
+fn test_escrow_account_is_registered_pallet_sub_account() {
+	new_test_ext().execute_with(|| {
+		let ctx = IbcTransferModule::<Test>(PhantomData);
+		let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+		let hash = crate::utils::get_channel_escrow_address_hash(&port_id, &channel_id);
+		let expected: AccountId32 = TransferPalletId::get().into_sub_account_truncating(hash);
+
+		// reading the escrow address registers nothing
+		let escrow = ctx.get_channel_escrow_address(&port_id, &channel_id).unwrap().into_account();
+		assert_eq!(escrow, expected);
+		assert!(!<EscrowAccounts<Test>>::contains_key(&port_id, &channel_id));
+		assert!(!Pallet::<Test>::is_escrow_account(&escrow));
+		assert_eq!(System::providers(&escrow), 0);
+
+		let pallet_account = Pallet::<Test>::pallet_account();
+		let (funds, issuance) =
+			(Balances::free_balance(&pallet_account), Balances::total_issuance());
+		let escrow = Pallet::<Test>::register_escrow_account(&port_id, &channel_id).unwrap();
+		assert_eq!(escrow, expected);
+		assert_eq!(<EscrowAccounts<Test>>::get(&port_id, &channel_id), Some(escrow.clone()));
+		assert!(Pallet::<Test>::is_escrow_account(&escrow));
+		// a provider reference of its own, and the existential deposit of the balances
+		assert_eq!(System::providers(&escrow), 2);
+		assert_eq!(Balances::free_balance(&escrow), ExistentialDeposit::get());
+		// paid by the pallet account, nothing is minted
+		assert_eq!(Balances::free_balance(&pallet_account), funds - ExistentialDeposit::get());
+		assert_eq!(Balances::total_issuance(), issuance);
+
+		// registering twice changes nothing
+		Pallet::<Test>::register_escrow_account(&port_id, &channel_id).unwrap();
+		assert_eq!(System::providers(&escrow), 2);
+		assert_eq!(Balances::free_balance(&escrow), ExistentialDeposit::get());
+		assert_eq!(Balances::free_balance(&pallet_account), funds - ExistentialDeposit::get());
+		assert_eq!(Pallet::<Test>::escrow_accounts().len(), 1);
+	})
+}
+
+#[test]
+fn test_escrow_dust_is_not_reaped() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let escrow = escrow(0);
+
+		ctx.send_coins(&alice, &escrow, &coin("DEMO", 5 * DOLLARS)).unwrap();
+		// unescrowing all but a dust below the existential deposit keeps the dust in escrow
+		ctx.send_coins(&escrow, &alice, &coin("DEMO", 5 * DOLLARS - 1)).unwrap();
+		assert_eq!(escrow_balance(0), 1);
+		assert_eq!(Balances::free_balance(alice.clone().into_account()), 10 * DOLLARS - 1);
+
+		// so it can be unescrowed too
+		ctx.send_coins(&escrow, &alice, &coin("DEMO", 1)).unwrap();
+		assert_eq!(escrow_balance(0), 0);
+		assert_eq!(Balances::free_balance(alice.into_account()), 10 * DOLLARS);
+		// the existential deposit is not escrowed
+		assert!(ctx.send_coins(&escrow, &account(2), &coin("DEMO", 1)).is_err());
+		assert_eq!(Balances::free_balance(escrow.into_account()), ExistentialDeposit::get());
+	})
+}
+
+#[test]
+fn test_escrow_holds_insufficient_asset() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let escrow = escrow(0);
+
+		Assets::force_create(
+			RuntimeOrigin::root(),
+			7,
+			alice.clone().into_account().into(),
+			false,
+			1,
+		)
+		.unwrap();
+		Assets::mint(
+			RuntimeOrigin::signed(alice.clone().into_account()),
+			7,
+			alice.clone().into_account().into(),
+			100,
+		)
+		.unwrap();
+		Pallet::<Test>::set_native_asset(RuntimeOrigin::root(), b"usdt".to_vec(), Some(7)).unwrap();
+
+		// its provider reference lets the escrow hold an asset that is not sufficient
+		ctx.send_coins(&alice, &escrow, &coin("usdt", 10)).unwrap();
+		assert_eq!(Assets::balance(7, escrow.clone().into_account()), 10);
+
+		ctx.send_coins(&escrow, &alice, &coin("usdt", 10)).unwrap();
+		assert_eq!(Assets::balance(7, escrow.into_account()), 0);
+		assert_eq!(Assets::balance(7, alice.into_account()), 100);
+	})
+}
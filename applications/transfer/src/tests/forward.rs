@@ -0,0 +1,296 @@
+//! Settlement of forwarded transfers.
+use crate::{
+	callback::IbcTransferModule,
+	forward::PacketForwardMiddleware,
+	mock::{new_test_ext, RuntimeEvent, RuntimeOrigin, System, Test, DOLLARS},
+	packet::FungibleTokenPacketData,
+	tests::{account, coin, escrow, escrow_balance, open_channels, relayer},
+	utils::get_forward_intermediate_address,
+	Event, InFlightForwards,
+};
+use core::marker::PhantomData;
+use frame_support::assert_ok;
+use ibc::{
+	applications::transfer::context::BankKeeper,
+	core::{
+		ics02_client::height::Height,
+		ics04_channel::{
+			channel::State,
+			msgs::{
+				acknowledgement::Acknowledgement,
+				recv_packet::{MsgRecvPacket, TYPE_URL as RECV_PACKET_TYPE_URL},
+			},
+			packet::{Packet, Sequence},
+			timeout::TimeoutHeight,
+		},
+		ics23_commitment::commitment::CommitmentProofBytes,
+		ics24_host::{
+			identifier::{ChannelId, PortId},
+			path::{AcksPath, ChannelEndsPath},
+		},
+		ics26_routing::context::{Module, ModuleOutputBuilder},
+	},
+	events::IbcEvent,
+	timestamp::Timestamp,
+};
+use ibc_proto::{google::protobuf::Any, protobuf::Protobuf};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use sp_runtime::traits::IdentifyAccount;
+
+const SENDER: &str = "cosmos1sender";
+const AMOUNT: u128 = 5 * DOLLARS;
+
+/// Open `channel-0` to `channel-5` and `channel-1` to `channel-7`, and escrow `AMOUNT` of the
+/// native token on `channel-0`.
+fn setup() {
+	System::set_block_number(1);
+	open_channels(&[(0, 5), (1, 7)]);
+	let mut ctx = IbcTransferModule::<Test>(PhantomData);
+	ctx.send_coins(&account(1), &escrow(0), &coin("DEMO", AMOUNT)).unwrap();
+}
+
+/// The native token returning over `channel-0`, to be forwarded over `channel-1` with `retries`
+fn received_packet(retries: u8) -> Packet {
+	let memo = format!(
+		r#"{{"forward":{{"receiver":"cosmos1receiver","port":"transfer","channel":"channel-1","retries":{}}}}}"#,
+		retries
+	);
+	let data = FungibleTokenPacketData {
+		denom: "transfer/channel-5/DEMO".to_string(),
+		amount: AMOUNT.to_string(),
+		sender: SENDER.to_string(),
+		receiver: "0x0202020202020202020202020202020202020202020202020202020202020202".to_string(),
+		memo,
+	};
+	Packet {
+		sequence: Sequence::from(1),
+		port_on_a: PortId::transfer(),
+		chan_on_a: ChannelId::new(5),
+		port_on_b: PortId::transfer(),
+		chan_on_b: ChannelId::new(0),
+		data: data.to_bytes(),
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	}
+}
+
+/// The packet forwarding the received transfer over `channel-1` with `sequence`
+fn forwarded_packet(sequence: u64) -> Packet {
+	let intermediate = get_forward_intermediate_address(&ChannelId::new(0), SENDER).unwrap();
+	let data = FungibleTokenPacketData {
+		denom: "DEMO".to_string(),
+		amount: AMOUNT.to_string(),
+		sender: intermediate.to_string(),
+		receiver: "cosmos1receiver".to_string(),
+		memo: String::new(),
+	};
+	Packet {
+		sequence: Sequence::from(sequence),
+		port_on_a: PortId::transfer(),
+		chan_on_a: ChannelId::new(1),
+		port_on_b: PortId::transfer(),
+		chan_on_b: ChannelId::new(7),
+		data: data.to_bytes(),
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	}
+}
+
+/// Receive `packet` and check that it was forwarded with its acknowledgement deferred.
+fn receive_and_forward(middleware: &mut PacketForwardMiddleware<Test>, packet: &Packet) {
+	let ack = middleware.on_recv_packet(&mut ModuleOutputBuilder::new(), packet, &relayer());
+	assert_eq!(
+		GenericAcknowledgement::from_bytes(ack.as_ref()),
+		Some(GenericAcknowledgement::success([1u8]))
+	);
+	assert!(<InFlightForwards<Test>>::contains_key(ChannelId::new(1), Sequence::from(1)));
+	assert!(<pallet_ibc::DeferredAcknowledgements<Test>>::contains_key(original_ack_path()));
+	assert_eq!(escrow_balance(0), 0);
+	assert_eq!(escrow_balance(1), AMOUNT);
+}
+
+/// Relay `packet` to pallet-ibc, proven at the height of the mock client.
+fn deliver(packet: Packet) {
+	let msg = MsgRecvPacket {
+		packet,
+		proof_commitment_on_a: CommitmentProofBytes::try_from(vec![1]).unwrap(),
+		proof_height_on_a: Height::new(0, 5).unwrap(),
+		signer: relayer(),
+	};
+	assert_ok!(pallet_ibc::Pallet::<Test>::deliver(
+		RuntimeOrigin::signed(account(1).into_account()),
+		vec![Any { type_url: RECV_PACKET_TYPE_URL.to_string(), value: msg.encode_vec().unwrap() }],
+	));
+}
+
+/// The `WriteAcknowledgement` events reported by pallet-ibc and by the middleware.
+fn write_acknowledgements() -> usize {
+	System::events()
+		.into_iter()
+		.map(|record| match record.event {
+			RuntimeEvent::Ibc(pallet_ibc::Event::IbcEvents { events }) => events
+				.iter()
+				.filter(|event| matches!(event, IbcEvent::WriteAcknowledgement(_)))
+				.count(),
+			RuntimeEvent::Ics20Transfer(Event::WriteAcknowledgement(_)) => 1,
+			_ => 0,
+		})
+		.sum()
+}
+
+fn original_ack_path() -> AcksPath {
+	AcksPath {
+		port_id: PortId::transfer(),
+		channel_id: ChannelId::new(0),
+		sequence: Sequence::from(1),
+	}
+}
+
+fn assert_settled(success: bool) {
+	assert!(<pallet_ibc::Acknowledgements<Test>>::contains_key(original_ack_path()));
+	System::assert_has_event(RuntimeEvent::Ics20Transfer(Event::ForwardSettled {
+		channel_id: ChannelId::new(0),
+		sequence: Sequence::from(1),
+		success,
+	}));
+}
+
+#[test]
+fn test_forward_acknowledged() {
+	new_test_ext().execute_with(|| {
+		setup();
+		let mut middleware = PacketForwardMiddleware::<Test>(PhantomData);
+		receive_and_forward(&mut middleware, &received_packet(0));
+
+		middleware
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&forwarded_packet(1),
+				&GenericAcknowledgement::success([1u8]).into(),
+				&relayer(),
+			)
+			.unwrap();
+		assert!(!<InFlightForwards<Test>>::contains_key(ChannelId::new(1), Sequence::from(1)));
+		assert_settled(true);
+		assert_eq!(escrow_balance(1), AMOUNT);
+	})
+}
+
+#[test]
+fn test_forward_error_acknowledgement_unwinds() {
+	new_test_ext().execute_with(|| {
+		setup();
+		let mut middleware = PacketForwardMiddleware::<Test>(PhantomData);
+		receive_and_forward(&mut middleware, &received_packet(0));
+
+		middleware
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&forwarded_packet(1),
+				&GenericAcknowledgement::error("receiver rejected").into(),
+				&relayer(),
+			)
+			.unwrap();
+		assert!(!<InFlightForwards<Test>>::contains_key(ChannelId::new(1), Sequence::from(1)));
+		assert_settled(false);
+		// the funds are back in the escrow they were received from
+		assert_eq!(escrow_balance(0), AMOUNT);
+		assert_eq!(escrow_balance(1), 0);
+	})
+}
+
+#[test]
+fn test_forward_timeout_is_retried_then_unwound() {
+	new_test_ext().execute_with(|| {
+		setup();
+		let mut middleware = PacketForwardMiddleware::<Test>(PhantomData);
+		receive_and_forward(&mut middleware, &received_packet(1));
+
+		// the first timeout sends the funds again
+		middleware
+			.on_timeout_packet(&mut ModuleOutputBuilder::new(), &forwarded_packet(1), &relayer())
+			.unwrap();
+		assert!(!<InFlightForwards<Test>>::contains_key(ChannelId::new(1), Sequence::from(1)));
+		let retry = <InFlightForwards<Test>>::get(ChannelId::new(1), Sequence::from(2)).unwrap();
+		assert_eq!(retry.retries_left, 0);
+		assert!(!<pallet_ibc::Acknowledgements<Test>>::contains_key(original_ack_path()));
+		assert_eq!(escrow_balance(1), AMOUNT);
+
+		// the second one gives up
+		middleware
+			.on_timeout_packet(&mut ModuleOutputBuilder::new(), &forwarded_packet(2), &relayer())
+			.unwrap();
+		assert!(!<InFlightForwards<Test>>::contains_key(ChannelId::new(1), Sequence::from(2)));
+		assert_settled(false);
+		assert_eq!(escrow_balance(0), AMOUNT);
+		assert_eq!(escrow_balance(1), 0);
+	})
+}
+
+#[test]
+fn test_failed_settlement_keeps_forward_in_flight() {
+	new_test_ext().execute_with(|| {
+		setup();
+		let mut middleware = PacketForwardMiddleware::<Test>(PhantomData);
+		receive_and_forward(&mut middleware, &received_packet(0));
+
+		// the acknowledgement cannot be written on a closed channel
+		let path = ChannelEndsPath(PortId::transfer(), ChannelId::new(0));
+		<pallet_ibc::Channels<Test>>::mutate(&path, |channel| {
+			channel.as_mut().unwrap().set_state(State::Closed)
+		});
+		let ack: Acknowledgement = GenericAcknowledgement::error("receiver rejected").into();
+		assert!(middleware
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&forwarded_packet(1),
+				&ack,
+				&relayer()
+			)
+			.is_err());
+		// nothing was refunded nor unwound
+		assert!(<InFlightForwards<Test>>::contains_key(ChannelId::new(1), Sequence::from(1)));
+		assert_eq!(escrow_balance(0), 0);
+		assert_eq!(escrow_balance(1), AMOUNT);
+
+		// so the acknowledgement can be relayed again
+		<pallet_ibc::Channels<Test>>::mutate(&path, |channel| {
+			channel.as_mut().unwrap().set_state(State::Open)
+		});
+		middleware
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&forwarded_packet(1),
+				&ack,
+				&relayer(),
+			)
+			.unwrap();
+		assert_settled(false);
+		assert_eq!(escrow_balance(0), AMOUNT);
+		assert_eq!(escrow_balance(1), 0);
+	})
+}
+
+#[test]
+fn test_forwarded_packet_is_acknowledged_once() {
+	new_test_ext().execute_with(|| {
+		setup();
+		deliver(received_packet(0));
+
+		// the acknowledgement is deferred, pallet-ibc neither stores nor reports the placeholder
+		assert!(<InFlightForwards<Test>>::contains_key(ChannelId::new(1), Sequence::from(1)));
+		assert!(!<pallet_ibc::Acknowledgements<Test>>::contains_key(original_ack_path()));
+		assert_eq!(write_acknowledgements(), 0);
+
+		PacketForwardMiddleware::<Test>(PhantomData)
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&forwarded_packet(1),
+				&GenericAcknowledgement::success([1u8]).into(),
+				&relayer(),
+			)
+			.unwrap();
+		assert_settled(true);
+		assert_eq!(write_acknowledgements(), 1);
+	})
+}
@@ -0,0 +1,157 @@
+//! Receive hooks dispatching calls from the hook account.
+use crate::{
+	callback::IbcTransferModule,
+	impls::IbcAccount,
+	mock::{new_test_ext, Balances, RuntimeCall, RuntimeEvent, System, Test, DOLLARS},
+	packet::FungibleTokenPacketData,
+	tests::{account, coin, escrow, escrow_balance},
+	utils::get_hook_account_address,
+	Event,
+};
+use codec::Encode;
+use core::marker::PhantomData;
+use ibc::{
+	applications::transfer::context::BankKeeper,
+	core::{
+		ics04_channel::{
+			packet::{Packet, Sequence},
+			timeout::TimeoutHeight,
+		},
+		ics24_host::identifier::{ChannelId, PortId},
+		ics26_routing::context::{Module, ModuleOutputBuilder},
+	},
+	signer::Signer,
+	timestamp::Timestamp,
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use sp_core::crypto::AccountId32;
+use sp_runtime::traits::IdentifyAccount;
+use std::str::FromStr;
+
+const SENDER: &str = "cosmos1sender";
+
+/// Escrow 5 DOLLARS of the native token on `channel-0`.
+fn setup() {
+	System::set_block_number(1);
+	let mut ctx = IbcTransferModule::<Test>(PhantomData);
+	ctx.send_coins(&account(1), &escrow(0), &coin("DEMO", 5 * DOLLARS)).unwrap();
+}
+
+fn hook_account() -> AccountId32 {
+	let signer = get_hook_account_address(&ChannelId::new(0), SENDER).unwrap();
+	IbcAccount::try_from(signer).unwrap().into_account()
+}
+
+/// 5 DOLLARS of the native token returning over `channel-0` with a hook dispatching `calls`
+fn packet(calls: Vec<RuntimeCall>) -> Packet {
+	let calls = calls
+		.iter()
+		.map(|call| format!(r#""0x{}""#, hex::encode(call.encode())))
+		.collect::<Vec<_>>()
+		.join(",");
+	let data = FungibleTokenPacketData {
+		denom: "transfer/channel-5/DEMO".to_string(),
+		amount: (5 * DOLLARS).to_string(),
+		sender: SENDER.to_string(),
+		receiver: "0x0303030303030303030303030303030303030303030303030303030303030303".to_string(),
+		memo: format!(r#"{{"hooks":{{"calls":[{}]}}}}"#, calls),
+	};
+	Packet {
+		sequence: Sequence::from(1),
+		port_on_a: PortId::transfer(),
+		chan_on_a: ChannelId::new(5),
+		port_on_b: PortId::transfer(),
+		chan_on_b: ChannelId::new(0),
+		data: data.to_bytes(),
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	}
+}
+
+fn transfer_to(dest: &IbcAccount, value: u128) -> RuntimeCall {
+	RuntimeCall::Balances(pallet_balances::Call::transfer {
+		dest: dest.clone().into_account().into(),
+		value,
+	})
+}
+
+fn receive(packet: &Packet) -> GenericAcknowledgement {
+	let mut module = IbcTransferModule::<Test>(PhantomData);
+	let relayer = Signer::from_str("cosmos1relayer").unwrap();
+	let ack = module.on_recv_packet(&mut ModuleOutputBuilder::new(), packet, &relayer);
+	GenericAcknowledgement::from_bytes(ack.as_ref()).unwrap()
+}
+
+#[test]
+fn test_hook_calls_are_dispatched_from_hook_account() {
+	new_test_ext().execute_with(|| {
+		setup();
+		let bob = account(2);
+
+		let ack = receive(&packet(vec![transfer_to(&bob, DOLLARS), transfer_to(&bob, DOLLARS)]));
+		assert!(ack.is_success());
+		assert_eq!(escrow_balance(0), 0);
+		assert_eq!(Balances::free_balance(hook_account()), 3 * DOLLARS);
+		assert_eq!(Balances::free_balance(bob.into_account()), 12 * DOLLARS);
+		System::assert_has_event(RuntimeEvent::Ics20Transfer(Event::HookExecuted {
+			channel_id: ChannelId::new(0),
+			sequence: Sequence::from(1),
+			account: hook_account(),
+			calls: 2,
+		}));
+	})
+}
+
+#[test]
+fn test_failing_hook_call_reverts_receive() {
+	new_test_ext().execute_with(|| {
+		setup();
+		let bob = account(2);
+
+		// the second call spends more than was received
+		let ack =
+			receive(&packet(vec![transfer_to(&bob, DOLLARS), transfer_to(&bob, 5 * DOLLARS)]));
+		assert!(!ack.is_success());
+		// the funds stay in escrow, so the sender is refunded
+		assert_eq!(escrow_balance(0), 5 * DOLLARS);
+		assert_eq!(Balances::free_balance(hook_account()), 0);
+		assert_eq!(Balances::free_balance(bob.into_account()), 10 * DOLLARS);
+		assert!(!System::events().iter().any(|record| matches!(
+			record.event,
+			RuntimeEvent::Ics20Transfer(Event::HookExecuted { .. })
+		)));
+	})
+}
+
+#[test]
+fn test_deeply_nested_hook_call_is_rejected() {
+	new_test_ext().execute_with(|| {
+		setup();
+		let bob = account(2);
+
+		let nested = |depth| {
+			(0..depth).fold(transfer_to(&bob, DOLLARS), |call, _| {
+				RuntimeCall::Utility(pallet_utility::Call::batch_all { calls: vec![call] })
+			})
+		};
+
+		// nested deeper than an extrinsic may be
+		assert!(!receive(&packet(vec![nested(sp_api::MAX_EXTRINSIC_DEPTH)])).is_success());
+		assert_eq!(escrow_balance(0), 5 * DOLLARS);
+		assert_eq!(Balances::free_balance(bob.clone().into_account()), 10 * DOLLARS);
+
+		// a shallow batch is dispatched
+		assert!(receive(&packet(vec![nested(1)])).is_success());
+		assert_eq!(Balances::free_balance(bob.into_account()), 11 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_hook_without_calls_is_rejected() {
+	new_test_ext().execute_with(|| {
+		setup();
+
+		assert!(!receive(&packet(vec![])).is_success());
+		assert_eq!(escrow_balance(0), 5 * DOLLARS);
+	})
+}
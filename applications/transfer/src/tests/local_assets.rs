@@ -0,0 +1,62 @@
+//! Escrow of tokens issued on this chain.
+use crate::{
+	callback::IbcTransferModule,
+	mock::{new_test_ext, Assets, Balances, RuntimeOrigin, Test, DOLLARS},
+	tests::{account, coin, escrow},
+	Pallet,
+};
+use core::marker::PhantomData;
+use frame_support::traits::Currency;
+use ibc::applications::transfer::context::BankKeeper;
+use sp_runtime::traits::IdentifyAccount;
+
+#[test]
+fn test_escrow_mapped_local_asset() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let escrow = escrow(0);
+		Balances::make_free_balance_be(&escrow.clone().into_account(), 10 * DOLLARS);
+
+		Assets::force_create(
+			RuntimeOrigin::root(),
+			7,
+			alice.clone().into_account().into(),
+			true,
+			1,
+		)
+		.unwrap();
+		Assets::mint(
+			RuntimeOrigin::signed(alice.clone().into_account()),
+			7,
+			alice.clone().into_account().into(),
+			100,
+		)
+		.unwrap();
+
+		// not mapped yet, so it can't be sent out
+		assert!(ctx.send_coins(&alice, &escrow, &coin("usdt", 10)).is_err());
+
+		Pallet::<Test>::set_native_asset(RuntimeOrigin::root(), b"usdt".to_vec(), Some(7)).unwrap();
+		ctx.send_coins(&alice, &escrow, &coin("usdt", 10)).unwrap();
+		assert_eq!(Assets::balance(7, alice.clone().into_account()), 90);
+		assert_eq!(Assets::balance(7, escrow.clone().into_account()), 10);
+
+		// and unescrowed when it returns
+		ctx.send_coins(&escrow, &alice, &coin("usdt", 5)).unwrap();
+		assert_eq!(Assets::balance(7, alice.into_account()), 95);
+	})
+}
+
+#[test]
+fn test_unknown_local_denom_is_rejected() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let bob = account(2);
+
+		assert!(ctx.send_coins(&alice, &bob, &coin("unknown", 1)).is_err());
+		// the native token needs no mapping
+		ctx.send_coins(&alice, &bob, &coin("DEMO", 1)).unwrap();
+	})
+}
@@ -0,0 +1,292 @@
+//! Storage migrations.
+use crate::{
+	callback::IbcTransferModule,
+	denom::PrefixedDenom,
+	migrations::{
+		v1::{self, MigrateToV1},
+		v2::{LegacyEscrowChannels, MigrateToV2},
+		v3::MigrateToV3,
+		v4::{self, MigrateToV4},
+		MIGRATION_STEP,
+	},
+	mock::{new_test_ext, Balances, ExistentialDeposit, Test, DOLLARS},
+	tests::{account, coin, escrow, escrow_balance},
+	utils::{derive_ibc_denom_with_path, get_channel_escrow_address_hash},
+	AssetIdByName, AssetNameById, Config, DenomTrace, EscrowTotals, MigrationCursor, Pallet,
+	VoucherSupply,
+};
+use codec::Encode;
+use core::marker::PhantomData;
+use frame_support::{
+	storage::unhashed,
+	traits::{
+		Currency, Get, GetStorageVersion, Hooks, LockableCurrency, OnRuntimeUpgrade,
+		StorageVersion, WithdrawReasons,
+	},
+	weights::Weight,
+};
+use ibc::{
+	applications::transfer::context::BankKeeper,
+	core::ics24_host::identifier::{ChannelId, PortId},
+};
+use pallet_ibc_utils::migration::Cursor;
+use sp_runtime::{traits::IdentifyAccount, AccountId32};
+
+#[test]
+fn test_migrate_to_v1_rekeys_vouchers() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(0).put::<Pallet<Test>>();
+		let trace: PrefixedDenom<<Test as Config>::MaxTraceLength> = PrefixedDenom {
+			trace_path: b"transfer/channel-0".to_vec().try_into().unwrap(),
+			base_denom: b"uatom".to_vec().try_into().unwrap(),
+		};
+		<v1::DenomTrace<Test>>::insert(b"legacy-hash".to_vec(), trace.clone());
+		<AssetIdByName<Test>>::insert(b"uatom".to_vec(), 7);
+
+		MigrateToV1::<Test>::on_runtime_upgrade();
+		Pallet::<Test>::on_idle(1, Weight::MAX);
+
+		let hash = derive_ibc_denom_with_path("transfer/channel-0/uatom").unwrap().into_bytes();
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 1);
+		assert_eq!(<v1::DenomTrace<Test>>::iter().collect::<Vec<_>>(), vec![(hash.clone(), trace)]);
+		assert!(!<AssetIdByName<Test>>::contains_key(b"uatom".to_vec()));
+		assert_eq!(<AssetIdByName<Test>>::get(&hash), 7);
+		assert_eq!(<AssetNameById<Test>>::get(7), Some(hash));
+	})
+}
+
+struct EscrowedChannels;
+
+impl Get<Vec<(PortId, ChannelId)>> for EscrowedChannels {
+	fn get() -> Vec<(PortId, ChannelId)> {
+		vec![(PortId::transfer(), ChannelId::new(0))]
+	}
+}
+
+#[test]
+fn test_migrate_to_v2_moves_escrowed_tokens() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(1).put::<Pallet<Test>>();
+		let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+		let legacy = AccountId32::new(get_channel_escrow_address_hash(&port_id, &channel_id));
+		Balances::make_free_balance_be(&legacy, 5 * DOLLARS);
+
+		MigrateToV2::<Test, EscrowedChannels>::on_runtime_upgrade();
+
+		let escrow = Pallet::<Test>::escrow_account(&port_id, &channel_id);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 2);
+		assert!(Pallet::<Test>::is_escrow_account(&escrow));
+		assert_eq!(Balances::free_balance(&legacy), 0);
+		assert_eq!(escrow_balance(0), 5 * DOLLARS);
+		assert!(!<LegacyEscrowChannels<Test>>::exists());
+
+		// a later upgrade leaves the escrow alone
+		Balances::make_free_balance_be(&legacy, 3 * DOLLARS);
+		MigrateToV2::<Test, EscrowedChannels>::on_runtime_upgrade();
+		assert_eq!(escrow_balance(0), 5 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_migrate_to_v1_over_several_blocks() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(0).put::<Pallet<Test>>();
+		let traces = MIGRATION_STEP as u64 + 1;
+		for channel in 0..traces {
+			let trace: PrefixedDenom<<Test as Config>::MaxTraceLength> = PrefixedDenom {
+				trace_path: format!("transfer/channel-{}", channel)
+					.into_bytes()
+					.try_into()
+					.unwrap(),
+				base_denom: b"uatom".to_vec().try_into().unwrap(),
+			};
+			<v1::DenomTrace<Test>>::insert(format!("legacy-{}", channel).into_bytes(), trace);
+		}
+		<AssetIdByName<Test>>::insert(b"uatom".to_vec(), 7);
+
+		MigrateToV1::<Test>::on_runtime_upgrade();
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 0);
+		assert!(<MigrationCursor<Test>>::exists());
+		// the voucher keeps its base denomination until every trace is re-keyed
+		assert_eq!(<AssetIdByName<Test>>::get(b"uatom".to_vec()), 7);
+
+		Pallet::<Test>::on_idle(1, Weight::MAX);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 1);
+		assert_eq!(<v1::DenomTrace<Test>>::iter().count() as u64, traces);
+		for (hash, trace) in <v1::DenomTrace<Test>>::iter() {
+			let path = String::from_utf8(trace.full_path()).unwrap();
+			assert_eq!(derive_ibc_denom_with_path(&path).unwrap().into_bytes(), hash);
+			assert_eq!(<AssetIdByName<Test>>::get(&hash), 7);
+		}
+		assert!(!<AssetIdByName<Test>>::contains_key(b"uatom".to_vec()));
+		assert!(<AssetNameById<Test>>::get(7).unwrap().starts_with(b"ibc/"));
+	})
+}
+
+#[test]
+fn test_failed_migration_to_v2_is_started_again() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(1).put::<Pallet<Test>>();
+		let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+		let legacy = AccountId32::new(get_channel_escrow_address_hash(&port_id, &channel_id));
+		Balances::make_free_balance_be(&legacy, 5 * DOLLARS);
+		Balances::set_lock(*b"testlock", &legacy, DOLLARS, WithdrawReasons::all());
+
+		// the escrowed tokens cannot be moved, so the version is left as it was
+		MigrateToV2::<Test, EscrowedChannels>::on_runtime_upgrade();
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 1);
+		assert!(!<MigrationCursor<Test>>::exists());
+		assert_eq!(<LegacyEscrowChannels<Test>>::get(), Some(vec![(port_id, channel_id)]));
+		assert_eq!(Balances::free_balance(&legacy), 5 * DOLLARS);
+
+		Balances::remove_lock(*b"testlock", &legacy);
+		MigrateToV2::<Test, EscrowedChannels>::on_runtime_upgrade();
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 2);
+		assert_eq!(Balances::free_balance(&legacy), 0);
+		assert_eq!(escrow_balance(0), 5 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_migrate_to_v1_rekeys_traces_without_asset() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(0).put::<Pallet<Test>>();
+		let trace: PrefixedDenom<<Test as Config>::MaxTraceLength> = PrefixedDenom {
+			trace_path: b"transfer/channel-1".to_vec().try_into().unwrap(),
+			base_denom: b"uosmo".to_vec().try_into().unwrap(),
+		};
+		<v1::DenomTrace<Test>>::insert(b"legacy-hash".to_vec(), trace.clone());
+		// an asset named after another base denomination is left alone
+		<AssetIdByName<Test>>::insert(b"uatom".to_vec(), 7);
+
+		MigrateToV1::<Test>::on_runtime_upgrade();
+		Pallet::<Test>::on_idle(1, Weight::MAX);
+
+		let hash = derive_ibc_denom_with_path("transfer/channel-1/uosmo").unwrap().into_bytes();
+		assert_eq!(<v1::DenomTrace<Test>>::iter().collect::<Vec<_>>(), vec![(hash.clone(), trace)]);
+		assert!(!<AssetIdByName<Test>>::contains_key(&hash));
+		assert_eq!(<AssetIdByName<Test>>::get(b"uatom".to_vec()), 7);
+		assert_eq!(<AssetNameById<Test>>::get(7), Some(b"uatom".to_vec()));
+		assert_eq!(
+			Pallet::<Test>::denom_trace(&hash).map(|trace| trace.full_path()),
+			Some(b"transfer/channel-1/uosmo".to_vec())
+		);
+	})
+}
+
+#[test]
+fn test_migrate_to_v3_seeds_supply_accounting() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let voucher = coin("transfer/channel-1/uatom", 100);
+		ctx.mint_coins(&account(1), &voucher).unwrap();
+		// tokens escrowed before version 3 were not accounted
+		let escrow = escrow(0);
+		Balances::make_free_balance_be(
+			&escrow.into_account(),
+			ExistentialDeposit::get() + 5 * DOLLARS,
+		);
+		let _ = <VoucherSupply<Test>>::clear(u32::MAX, None);
+		StorageVersion::new(2).put::<Pallet<Test>>();
+		assert!(Pallet::<Test>::do_try_state().is_err());
+
+		MigrateToV3::<Test>::on_runtime_upgrade();
+
+		// the escrow accounts are seeded, the vouchers are left to the next step
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 2);
+		assert!(<MigrationCursor<Test>>::exists());
+		assert_eq!(<EscrowTotals<Test>>::get(ChannelId::new(0), b"DEMO".to_vec()), 5 * DOLLARS);
+		Pallet::<Test>::on_idle(1, Weight::MAX);
+		let ibc_denom = Pallet::<Test>::asset_name(&voucher.denom);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 3);
+		// the migration to version 4 follows
+		Pallet::<Test>::on_idle(2, Weight::MAX);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 4);
+		assert!(!<MigrationCursor<Test>>::exists());
+		assert_eq!(<EscrowTotals<Test>>::get(ChannelId::new(0), b"DEMO".to_vec()), 5 * DOLLARS);
+		assert_eq!(escrow_balance(0), 5 * DOLLARS);
+		assert_eq!(<VoucherSupply<Test>>::get(&ibc_denom), 100);
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+
+		// a later upgrade leaves the accounting alone
+		Balances::make_free_balance_be(
+			&Pallet::<Test>::escrow_account(&PortId::transfer(), &ChannelId::new(0)),
+			ExistentialDeposit::get(),
+		);
+		MigrateToV3::<Test>::on_runtime_upgrade();
+		assert_eq!(<EscrowTotals<Test>>::get(ChannelId::new(0), b"DEMO".to_vec()), 5 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_migrate_to_v3_steps_through_escrow_accounts_and_denominations() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let voucher = coin("transfer/channel-1/uatom", 100);
+		ctx.mint_coins(&alice, &voucher).unwrap();
+		for channel in 0..2 {
+			ctx.send_coins(&alice, &escrow(channel), &coin("DEMO", DOLLARS)).unwrap();
+			ctx.send_coins(&alice, &escrow(channel), &coin("transfer/channel-1/uatom", 10))
+				.unwrap();
+		}
+		let _ = <EscrowTotals<Test>>::clear(u32::MAX, None);
+		let _ = <VoucherSupply<Test>>::clear(u32::MAX, None);
+		StorageVersion::new(2).put::<Pallet<Test>>();
+
+		let (result, weight) = MigrateToV3::<Test>::step(None, 1);
+		let mut cursor = result.unwrap();
+		assert!(MigrateToV3::<Test>::max_step_weight(1).all_gte(weight));
+		<MigrationCursor<Test>>::put(Cursor::try_from(cursor.clone().unwrap()).unwrap());
+		// the totals are not checked until they are seeded
+		ctx.send_coins(&escrow(1), &alice, &coin("DEMO", DOLLARS / 2)).unwrap();
+		ctx.burn_coins(&alice, &coin("transfer/channel-1/uatom", 20)).unwrap();
+
+		// a denomination of an escrow account, then a voucher, per step
+		let mut steps = 1;
+		while let Some(next) = cursor {
+			let (result, weight) = MigrateToV3::<Test>::step(Some(next), 1);
+			assert!(MigrateToV3::<Test>::max_step_weight(1).all_gte(weight));
+			cursor = result.unwrap();
+			steps += 1;
+		}
+		<MigrationCursor<Test>>::kill();
+		StorageVersion::new(4).put::<Pallet<Test>>();
+
+		assert!(steps > 5);
+		assert_eq!(<EscrowTotals<Test>>::get(ChannelId::new(0), b"DEMO".to_vec()), DOLLARS);
+		assert_eq!(<EscrowTotals<Test>>::get(ChannelId::new(1), b"DEMO".to_vec()), DOLLARS / 2);
+		assert_eq!(
+			<EscrowTotals<Test>>::get(ChannelId::new(1), b"transfer/channel-1/uatom".to_vec()),
+			10
+		);
+		assert_eq!(<VoucherSupply<Test>>::get(Pallet::<Test>::asset_name(&voucher.denom)), 80);
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+	})
+}
+
+#[test]
+fn test_migrate_to_v4_removes_traces_longer_than_their_bound() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(3).put::<Pallet<Test>>();
+		let trace: PrefixedDenom<<Test as Config>::MaxTraceLength> = PrefixedDenom {
+			trace_path: b"transfer/channel-0".to_vec().try_into().unwrap(),
+			base_denom: b"uatom".to_vec().try_into().unwrap(),
+		};
+		let hash = derive_ibc_denom_with_path("transfer/channel-0/uatom").unwrap().into_bytes();
+		<DenomTrace<Test>>::insert(&hash, trace.clone());
+		// a trace stored under a key longer than an `ibc/{hash}` denomination
+		let long_key = [b"ibc/".to_vec(), vec![b'A'; 100]].concat();
+		unhashed::put_raw(&<DenomTrace<Test>>::hashed_key_for(&long_key), &trace.encode());
+		assert_eq!(v4::undecodable_entries::<Test>(), 1);
+		assert!(Pallet::<Test>::do_try_state().is_err());
+
+		MigrateToV4::<Test>::on_runtime_upgrade();
+
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 4);
+		assert!(!<MigrationCursor<Test>>::exists());
+		assert_eq!(v4::undecodable_entries::<Test>(), 0);
+		assert_eq!(<v1::DenomTrace<Test>>::iter().collect::<Vec<_>>(), vec![(hash, trace)]);
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+	})
+}
@@ -1,2 +1,221 @@
+use crate::{
+	impls::IbcAccount,
+	mock::{Balances, ExistentialDeposit, RuntimeOrigin, Test, DOLLARS},
+	packet::FungibleTokenPacketData,
+	transfer::TransferTimeout,
+	Pallet,
+};
+use frame_support::traits::{Currency, GenesisBuild, Get};
+use ibc::{
+	applications::transfer::{PrefixedCoin, PrefixedDenom},
+	core::{
+		ics02_client::height::Height,
+		ics03_connection::{
+			connection::{
+				ConnectionEnd, Counterparty as ConnectionCounterparty, State as ConnectionState,
+			},
+			version::get_compatible_versions,
+		},
+		ics04_channel::{
+			channel::{ChannelEnd, Counterparty, Order, State},
+			packet::{Packet, Sequence},
+			timeout::TimeoutHeight,
+			Version,
+		},
+		ics23_commitment::commitment::CommitmentPrefix,
+		ics24_host::identifier::{ChannelId, ClientId, ConnectionId, PortId},
+	},
+	mock::{
+		client_state::{client_type as mock_client_type, MockClientState},
+		consensus_state::MockConsensusState,
+		header::MockHeader,
+	},
+	signer::Signer,
+	timestamp::{Timestamp, ZERO_DURATION},
+};
+use ibc_proto::{
+	google::protobuf::Any,
+	ibc::core::{
+		channel::v1::Channel as RawChannel, connection::v1::ConnectionEnd as RawConnectionEnd,
+	},
+	protobuf::Protobuf,
+};
+use primitive_types::U256;
+use sp_runtime::{traits::IdentifyAccount, AccountId32, DispatchError};
+use std::str::FromStr;
+
+mod amount;
 mod applications;
+mod assets;
+mod denom;
+mod escrow;
+mod forward;
+mod hooks;
+mod local_assets;
+mod migrations;
+mod multihop;
+mod pending;
+mod policy;
+mod ratelimit;
 mod routing;
+mod supply;
+mod switches;
+mod timeout;
+mod transfer;
+
+/// Unix time of the mock chain and of the counterparty client, in milliseconds
+pub const NOW: u64 = 1_600_000_000_000;
+
+/// The account of seed `seed`, funded with 10 DOLLARS.
+pub fn account(seed: u8) -> IbcAccount {
+	let signer = Signer::from_str(&format!("0x{}", hex::encode([seed; 32]))).unwrap();
+	let account = IbcAccount::try_from(signer).unwrap();
+	Balances::make_free_balance_be(&account.clone().into_account(), 10 * DOLLARS);
+	account
+}
+
+/// The escrow account of `transfer/channel-{channel}`, registered if it is not.
+pub fn escrow(channel: u64) -> IbcAccount {
+	Pallet::<Test>::register_escrow_account(&PortId::transfer(), &ChannelId::new(channel))
+		.unwrap()
+		.into()
+}
+
+/// The native tokens escrowed on `transfer/channel-{channel}`, beyond the existential deposit of
+/// its escrow account.
+pub fn escrow_balance(channel: u64) -> u128 {
+	let escrow = Pallet::<Test>::escrow_account(&PortId::transfer(), &ChannelId::new(channel));
+	Balances::free_balance(escrow).saturating_sub(ExistentialDeposit::get())
+}
+
+pub fn coin(denom: &str, amount: u128) -> PrefixedCoin {
+	PrefixedCoin {
+		denom: PrefixedDenom::from_str(denom).unwrap(),
+		amount: U256::from(amount).into(),
+	}
+}
+
+/// The account of `account(1)`, without funding it again
+pub fn alice() -> AccountId32 {
+	AccountId32::new([1; 32])
+}
+
+pub fn relayer() -> Signer {
+	Signer::from_str("cosmos1relayer").unwrap()
+}
+
+/// A transfer of `amount` of the native token between `channel-0` and `channel-5` with
+/// `sequence`, sent by `alice()` if `outgoing`, returning to `alice()` otherwise
+pub fn packet(sequence: u64, amount: u128, outgoing: bool) -> Packet {
+	let address = Pallet::<Test>::account_address(&alice());
+	let (denom, sender, receiver) = if outgoing {
+		("DEMO", address, "cosmos1receiver".to_string())
+	} else {
+		("transfer/channel-5/DEMO", "cosmos1sender".to_string(), address)
+	};
+	let data = FungibleTokenPacketData {
+		denom: denom.to_string(),
+		amount: amount.to_string(),
+		sender,
+		receiver,
+		memo: String::new(),
+	};
+	let local = (PortId::transfer(), ChannelId::new(0));
+	let counterparty = (PortId::transfer(), ChannelId::new(5));
+	let ((port_on_a, chan_on_a), (port_on_b, chan_on_b)) =
+		if outgoing { (local, counterparty) } else { (counterparty, local) };
+	Packet {
+		sequence: Sequence::from(sequence),
+		port_on_a,
+		chan_on_a,
+		port_on_b,
+		chan_on_b,
+		data: data.to_bytes(),
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	}
+}
+
+/// Send `amount` of the native token from `alice()` to `cosmos1receiver` over `channel-0`.
+pub fn send(amount: u128) -> Result<(), DispatchError> {
+	Pallet::<Test>::transfer(
+		RuntimeOrigin::signed(alice()),
+		None,
+		amount,
+		b"cosmos1receiver".to_vec(),
+		ChannelId::new(0),
+		TransferTimeout::default(),
+		vec![],
+	)
+}
+
+/// Open the transfer channels `(channel id, counterparty channel id)` over `connection-0` to a
+/// mock client at height 5, registering their escrow accounts, and set the time of the mock chain
+/// and of the client to [`NOW`].
+pub fn open_channels(channels: &[(u64, u64)]) {
+	let client_id = ClientId::new(mock_client_type(), 0).unwrap();
+	let header = MockHeader {
+		height: Height::new(0, 5).unwrap(),
+		timestamp: Timestamp::from_nanoseconds(NOW * 1_000_000).unwrap(),
+	};
+	let connection_end = ConnectionEnd::new(
+		ConnectionState::Open,
+		client_id.clone(),
+		ConnectionCounterparty::new(
+			client_id.clone(),
+			Some(ConnectionId::new(0)),
+			CommitmentPrefix::try_from(b"ibc".to_vec()).unwrap(),
+		),
+		get_compatible_versions(),
+		ZERO_DURATION,
+	);
+	let channel_end = |counterparty: u64| {
+		let channel_end = ChannelEnd::new(
+			State::Open,
+			Order::Unordered,
+			Counterparty::new(PortId::transfer(), Some(ChannelId::new(counterparty))),
+			vec![ConnectionId::new(0)],
+			Version::ics20(),
+		);
+		Protobuf::<RawChannel>::encode_vec(&channel_end).unwrap()
+	};
+
+	let config = pallet_ibc::GenesisConfig {
+		clients: vec![(
+			client_id.to_string(),
+			pallet_ibc::MOCK_CLIENT_TYPE.to_string(),
+			Protobuf::<Any>::encode_vec(&MockClientState::new(header)).unwrap(),
+		)],
+		consensus_states: vec![(
+			client_id.to_string(),
+			0,
+			5,
+			Protobuf::<Any>::encode_vec(&MockConsensusState::new(header)).unwrap(),
+		)],
+		connections: vec![(
+			ConnectionId::new(0).to_string(),
+			Protobuf::<RawConnectionEnd>::encode_vec(&connection_end).unwrap(),
+		)],
+		channels: channels
+			.iter()
+			.map(|(channel, counterparty)| {
+				(
+					PortId::transfer().to_string(),
+					ChannelId::new(*channel).to_string(),
+					channel_end(*counterparty),
+				)
+			})
+			.collect(),
+		sequences: channels
+			.iter()
+			.map(|(channel, _)| {
+				(PortId::transfer().to_string(), ChannelId::new(*channel).to_string(), 1, 1, 1)
+			})
+			.collect(),
+	};
+	<pallet_ibc::GenesisConfig as GenesisBuild<Test>>::build(&config);
+	for (channel, _) in channels {
+		escrow(*channel);
+	}
+	pallet_timestamp::Pallet::<Test>::set_timestamp(NOW);
+}
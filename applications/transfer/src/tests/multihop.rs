@@ -0,0 +1,119 @@
+//! Source and sink handling of vouchers travelling over several hops.
+use crate::{
+	callback::IbcTransferModule,
+	impls::IbcAccount,
+	mock::{new_test_ext, Assets, Test},
+	tests::{account, coin, escrow},
+	utils::get_received_denom,
+	AssetIdByName, Pallet,
+};
+use core::marker::PhantomData;
+use frame_support::traits::fungibles::Inspect;
+use ibc::{
+	applications::transfer::{context::BankKeeper, is_sender_chain_source, PrefixedDenom},
+	core::{
+		ics04_channel::{packet::Packet, timeout::TimeoutHeight},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	timestamp::Timestamp,
+};
+use sp_runtime::traits::IdentifyAccount;
+use std::str::FromStr;
+
+/// A transfer packet from `chan_on_a` of the counterparty to `chan_on_b` of this chain
+fn packet(chan_on_a: &str, chan_on_b: &str) -> Packet {
+	Packet {
+		sequence: 1u64.into(),
+		port_on_a: PortId::transfer(),
+		chan_on_a: ChannelId::from_str(chan_on_a).unwrap(),
+		port_on_b: PortId::transfer(),
+		chan_on_b: ChannelId::from_str(chan_on_b).unwrap(),
+		data: vec![],
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	}
+}
+
+fn balance(denom: &PrefixedDenom, account: &IbcAccount) -> u128 {
+	let asset_id = AssetIdByName::<Test>::get(Pallet::<Test>::asset_name(denom));
+	Assets::balance(asset_id, account.clone().into_account())
+}
+
+#[test]
+fn test_voucher_returning_to_source_is_burnt() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+
+		// uatom arrives from the hub over our channel-0
+		let (denom, returning) =
+			get_received_denom(&packet("channel-5", "channel-0"), "uatom").unwrap();
+		assert!(!returning);
+		assert_eq!(denom.to_string(), "transfer/channel-0/uatom");
+		ctx.mint_coins(&alice, &coin(&denom.to_string(), 100)).unwrap();
+		assert_eq!(balance(&denom, &alice), 100);
+
+		// sent back over channel-0 it returns to its source
+		assert!(!is_sender_chain_source(
+			PortId::transfer(),
+			ChannelId::from_str("channel-0").unwrap(),
+			&denom
+		));
+		ctx.burn_coins(&alice, &coin(&denom.to_string(), 100)).unwrap();
+		assert_eq!(balance(&denom, &alice), 0);
+	})
+}
+
+#[test]
+fn test_voucher_sent_onward_is_escrowed() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let escrow = escrow(1);
+
+		let (denom, _) = get_received_denom(&packet("channel-5", "channel-0"), "uatom").unwrap();
+		ctx.mint_coins(&alice, &coin(&denom.to_string(), 100)).unwrap();
+
+		// sent to a third chain over channel-1, this chain is the source of the voucher
+		assert!(is_sender_chain_source(
+			PortId::transfer(),
+			ChannelId::from_str("channel-1").unwrap(),
+			&denom
+		));
+		ctx.send_coins(&alice, &escrow, &coin(&denom.to_string(), 60)).unwrap();
+		assert_eq!(balance(&denom, &alice), 40);
+		assert_eq!(balance(&denom, &escrow), 60);
+
+		// and comes back from the third chain, which prefixed it with its own channel-9
+		let (returned, returning) = get_received_denom(
+			&packet("channel-9", "channel-1"),
+			"transfer/channel-9/transfer/channel-0/uatom",
+		)
+		.unwrap();
+		assert!(returning);
+		assert_eq!(returned, denom);
+		ctx.send_coins(&escrow, &alice, &coin(&returned.to_string(), 50)).unwrap();
+		assert_eq!(balance(&denom, &alice), 90);
+		assert_eq!(balance(&denom, &escrow), 10);
+	})
+}
+
+#[test]
+fn test_same_base_denom_over_different_paths() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+
+		let (hub, _) = get_received_denom(&packet("channel-5", "channel-0"), "uatom").unwrap();
+		let (other, _) = get_received_denom(&packet("channel-3", "channel-2"), "uatom").unwrap();
+		ctx.mint_coins(&alice, &coin(&hub.to_string(), 100)).unwrap();
+		ctx.mint_coins(&alice, &coin(&other.to_string(), 30)).unwrap();
+
+		assert_ne!(Pallet::<Test>::asset_name(&hub), Pallet::<Test>::asset_name(&other));
+		assert_eq!(balance(&hub, &alice), 100);
+		assert_eq!(balance(&other, &alice), 30);
+
+		// tokens sourced on this chain are never burnt
+		assert!(ctx.burn_coins(&alice, &coin("DEMO", 1)).is_err());
+	})
+}
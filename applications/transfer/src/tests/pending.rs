@@ -0,0 +1,217 @@
+//! Tracking of outgoing transfers until they are settled.
+use crate::{
+	callback::IbcTransferModule,
+	mock::{
+		new_test_ext, Balances, ExistentialDeposit, RuntimeEvent, RuntimeOrigin, System, Test,
+		DOLLARS,
+	},
+	pending::TransferStatus,
+	tests::{account, alice, escrow_balance, open_channels, packet, relayer, send},
+	Error, Event, Pallet, PendingTransfers,
+};
+use core::marker::PhantomData;
+use frame_support::{
+	assert_noop, assert_ok,
+	traits::{Currency, Get},
+};
+use ibc::core::{
+	ics04_channel::packet::Sequence,
+	ics24_host::identifier::{ChannelId, PortId},
+	ics26_routing::context::{Module, ModuleOutputBuilder},
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use sp_core::crypto::AccountId32;
+use sp_runtime::DispatchError;
+use std::str::FromStr;
+
+/// Open `channel-0` to `channel-5` and send `DOLLARS` of the native token from `alice()` over it
+/// `count` times, with packet sequences from 1.
+fn send_transfers(count: u64) {
+	System::set_block_number(1);
+	open_channels(&[(0, 5)]);
+	account(1);
+	for _ in 0..count {
+		assert_ok!(send(DOLLARS));
+	}
+}
+
+fn acknowledge(sequence: u64, success: bool) -> Result<(), String> {
+	let ack = if success {
+		GenericAcknowledgement::success([1u8])
+	} else {
+		GenericAcknowledgement::error("receiver rejected")
+	};
+	IbcTransferModule::<Test>(PhantomData)
+		.on_acknowledgement_packet(
+			&mut ModuleOutputBuilder::new(),
+			&packet(sequence, DOLLARS, true),
+			&ack.into(),
+			&relayer(),
+		)
+		.map_err(|e| e.to_string())
+}
+
+fn time_out(sequence: u64) -> Result<(), String> {
+	IbcTransferModule::<Test>(PhantomData)
+		.on_timeout_packet(
+			&mut ModuleOutputBuilder::new(),
+			&packet(sequence, DOLLARS, true),
+			&relayer(),
+		)
+		.map_err(|e| e.to_string())
+}
+
+/// Leave only the existential deposit on the escrow account of `channel-0`, so refunds fail.
+fn drain_escrow() {
+	Balances::make_free_balance_be(&escrow_account(), ExistentialDeposit::get());
+}
+
+fn escrow_account() -> AccountId32 {
+	Pallet::<Test>::escrow_account(&PortId::transfer(), &ChannelId::new(0))
+}
+
+fn key() -> (PortId, ChannelId) {
+	(PortId::transfer(), ChannelId::new(0))
+}
+
+#[test]
+fn test_settled_transfers_are_no_longer_pending() {
+	new_test_ext().execute_with(|| {
+		send_transfers(3);
+		assert_eq!(escrow_balance(0), 3 * DOLLARS);
+		let pending = Pallet::<Test>::pending_transfers(&alice());
+		assert_eq!(pending.len(), 3);
+		assert!(pending.iter().all(|(port_id, _, _, transfer)| *port_id == PortId::transfer() &&
+			transfer.sender == alice() &&
+			transfer.amount == DOLLARS &&
+			transfer.status == TransferStatus::Pending));
+
+		assert_ok!(acknowledge(1, true));
+		System::assert_has_event(RuntimeEvent::Ics20Transfer(Event::TransferSucceeded {
+			port_id: PortId::transfer(),
+			channel_id: ChannelId::new(0),
+			sequence: Sequence::from(1),
+			sender: alice(),
+		}));
+		assert_ok!(acknowledge(2, false));
+		assert_ok!(time_out(3));
+		System::assert_has_event(RuntimeEvent::Ics20Transfer(Event::TransferRefunded {
+			port_id: PortId::transfer(),
+			channel_id: ChannelId::new(0),
+			sequence: Sequence::from(3),
+			sender: alice(),
+		}));
+
+		assert!(Pallet::<Test>::pending_transfers(&alice()).is_empty());
+		assert_eq!(<PendingTransfers<Test>>::iter().count(), 0);
+		// the failed transfers were refunded
+		assert_eq!(escrow_balance(0), DOLLARS);
+		assert_eq!(Balances::free_balance(alice()), 9 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_failed_refund_is_kept_until_paid_out() {
+	new_test_ext().execute_with(|| {
+		send_transfers(1);
+		drain_escrow();
+
+		// the packet is settled, the failure of its refund recorded on the transfer
+		assert_ok!(acknowledge(1, false));
+		System::assert_has_event(RuntimeEvent::Ics20Transfer(Event::TransferFailed {
+			port_id: PortId::transfer(),
+			channel_id: ChannelId::new(0),
+			sequence: Sequence::from(1),
+			sender: alice(),
+		}));
+		let transfer = <PendingTransfers<Test>>::get(key(), Sequence::from(1)).unwrap();
+		assert_eq!(transfer.status, TransferStatus::RefundFailed);
+		assert_eq!(Balances::free_balance(alice()), 9 * DOLLARS);
+
+		// governance pays out the refund once the escrow holds the funds again
+		assert_noop!(
+			Pallet::<Test>::refund_failed_transfer(
+				RuntimeOrigin::signed(alice()),
+				PortId::transfer(),
+				ChannelId::new(0),
+				Sequence::from(1),
+			),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			Pallet::<Test>::refund_failed_transfer(
+				RuntimeOrigin::root(),
+				PortId::transfer(),
+				ChannelId::new(0),
+				Sequence::from(1),
+			),
+			Error::<Test>::TransferFailed
+		);
+		Balances::make_free_balance_be(&escrow_account(), ExistentialDeposit::get() + DOLLARS);
+		assert_ok!(Pallet::<Test>::refund_failed_transfer(
+			RuntimeOrigin::root(),
+			PortId::transfer(),
+			ChannelId::new(0),
+			Sequence::from(1),
+		));
+		System::assert_last_event(RuntimeEvent::Ics20Transfer(Event::TransferRefunded {
+			port_id: PortId::transfer(),
+			channel_id: ChannelId::new(0),
+			sequence: Sequence::from(1),
+			sender: alice(),
+		}));
+		assert!(Pallet::<Test>::pending_transfers(&alice()).is_empty());
+		assert_eq!(Balances::free_balance(alice()), 10 * DOLLARS);
+		assert_eq!(escrow_balance(0), 0);
+
+		// the refund is paid out once
+		assert_noop!(
+			Pallet::<Test>::refund_failed_transfer(
+				RuntimeOrigin::root(),
+				PortId::transfer(),
+				ChannelId::new(0),
+				Sequence::from(1),
+			),
+			Error::<Test>::PendingTransferNotFound
+		);
+	})
+}
+
+#[test]
+fn test_only_failed_refunds_are_paid_out() {
+	new_test_ext().execute_with(|| {
+		send_transfers(1);
+
+		assert_noop!(
+			Pallet::<Test>::refund_failed_transfer(
+				RuntimeOrigin::root(),
+				PortId::transfer(),
+				ChannelId::new(0),
+				Sequence::from(1),
+			),
+			Error::<Test>::TransferNotRefundable
+		);
+		// the same sequence on another port is another transfer
+		assert_noop!(
+			Pallet::<Test>::refund_failed_transfer(
+				RuntimeOrigin::root(),
+				PortId::from_str("other").unwrap(),
+				ChannelId::new(0),
+				Sequence::from(1),
+			),
+			Error::<Test>::PendingTransferNotFound
+		);
+	})
+}
+
+#[test]
+fn test_failed_refund_of_untracked_packet_fails_it() {
+	new_test_ext().execute_with(|| {
+		send_transfers(0);
+		drain_escrow();
+
+		// the packet was not sent by `Pallet::transfer` nor `Pallet::raw_transfer`
+		assert!(time_out(7).is_err());
+		assert_eq!(<PendingTransfers<Test>>::iter().count(), 0);
+	})
+}
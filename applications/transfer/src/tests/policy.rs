@@ -0,0 +1,290 @@
+//! Policies deciding which transfers are allowed.
+use crate::{
+	callback::IbcTransferModule,
+	mock::{new_test_ext, Balances, RuntimeEvent, RuntimeOrigin, System, Test, DOLLARS},
+	packet::FungibleTokenPacketData,
+	pending::TransferStatus,
+	policy::{
+		Direction, ListPolicy, PolicyDecision, PolicyKind, PolicyRule, PolicyTarget, TransferInfo,
+		TransferPolicy,
+	},
+	tests::{
+		account, alice,
+		applications::transfer::test_util::{get_dummy_msg_transfer, get_dummy_transfer_packet},
+		coin, escrow, escrow_balance, open_channels, relayer,
+	},
+	Error, Event, HeldTransfers, Pallet, PendingTransfers,
+};
+use core::marker::PhantomData;
+use frame_support::{assert_noop, assert_ok};
+use ibc::{
+	applications::transfer::context::BankKeeper,
+	core::{
+		ics04_channel::{
+			packet::{Packet, Sequence},
+			timeout::TimeoutHeight,
+		},
+		ics24_host::identifier::{ChainId, ChannelId, PortId},
+		ics26_routing::context::{Module, ModuleOutputBuilder},
+	},
+	timestamp::Timestamp,
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use sp_core::crypto::AccountId32;
+use sp_runtime::DispatchError;
+
+const SENDER: &str = "cosmos1sender";
+const AMOUNT: u128 = 5 * DOLLARS;
+
+fn send(chain_id: Option<&str>) -> TransferInfo<AccountId32> {
+	TransferInfo {
+		direction: Direction::Send,
+		account: Some(alice()),
+		sender: Pallet::<Test>::account_address(&alice()),
+		receiver: "cosmos1receiver".to_string(),
+		denom: "DEMO".parse().unwrap(),
+		amount: 10,
+		port_id: PortId::transfer(),
+		channel_id: ChannelId::new(0),
+		counterparty_chain_id: chain_id.map(|chain_id| ChainId::new(chain_id.to_string(), 4)),
+	}
+}
+
+fn set_rule(target: PolicyTarget<AccountId32>, rule: Option<PolicyRule>) {
+	assert_ok!(Pallet::<Test>::set_policy_rule(RuntimeOrigin::root(), target, rule));
+}
+
+#[test]
+fn test_policy_is_set_by_admin() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			Pallet::<Test>::set_policy_rule(
+				RuntimeOrigin::signed(alice()),
+				PolicyTarget::Account(alice()),
+				Some(PolicyRule::Deny)
+			),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			Pallet::<Test>::set_policy_strict(
+				RuntimeOrigin::signed(alice()),
+				PolicyKind::Chain,
+				true
+			),
+			DispatchError::BadOrigin
+		);
+	})
+}
+
+#[test]
+fn test_list_policy_denies_then_holds() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(ListPolicy::<Test>::check(&send(None)), PolicyDecision::Allow);
+
+		set_rule(PolicyTarget::Address(b"cosmos1receiver".to_vec()), Some(PolicyRule::Hold));
+		assert_eq!(ListPolicy::<Test>::check(&send(None)), PolicyDecision::Hold);
+
+		// a denied target wins over a held one
+		set_rule(PolicyTarget::Denom(b"DEMO".to_vec()), Some(PolicyRule::Deny));
+		assert!(matches!(ListPolicy::<Test>::check(&send(None)), PolicyDecision::Deny(_)));
+
+		set_rule(PolicyTarget::Denom(b"DEMO".to_vec()), None);
+		set_rule(PolicyTarget::Address(b"cosmos1receiver".to_vec()), None);
+		assert_eq!(ListPolicy::<Test>::check(&send(None)), PolicyDecision::Allow);
+	})
+}
+
+#[test]
+fn test_strict_list_policy_only_allows_allowlisted() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Pallet::<Test>::set_policy_strict(
+			RuntimeOrigin::root(),
+			PolicyKind::Chain,
+			true
+		));
+		// transfers over channels of unknown chains are denied too
+		assert!(matches!(ListPolicy::<Test>::check(&send(None)), PolicyDecision::Deny(_)));
+		assert!(matches!(
+			ListPolicy::<Test>::check(&send(Some("cosmoshub"))),
+			PolicyDecision::Deny(_)
+		));
+
+		let cosmoshub = ChainId::new("cosmoshub".to_string(), 4);
+		set_rule(
+			PolicyTarget::Chain(cosmoshub.as_str().as_bytes().to_vec()),
+			Some(PolicyRule::Allow),
+		);
+		assert_eq!(ListPolicy::<Test>::check(&send(Some("cosmoshub"))), PolicyDecision::Allow);
+		assert!(matches!(
+			ListPolicy::<Test>::check(&send(Some("osmosis"))),
+			PolicyDecision::Deny(_)
+		));
+	})
+}
+
+#[test]
+fn test_received_transfer_is_checked() {
+	new_test_ext().execute_with(|| {
+		let msg = get_dummy_msg_transfer(TimeoutHeight::no_timeout(), None);
+		let packet = get_dummy_transfer_packet(msg.clone(), 1u64.into());
+		assert_eq!(Pallet::<Test>::recv_decision(&packet), PolicyDecision::Allow);
+
+		// the sender of the packet is held on the counterparty chain
+		set_rule(
+			PolicyTarget::Address(msg.sender.as_ref().as_bytes().to_vec()),
+			Some(PolicyRule::Hold),
+		);
+		assert_eq!(Pallet::<Test>::recv_decision(&packet), PolicyDecision::Hold);
+	})
+}
+
+#[test]
+fn test_release_of_unknown_held_transfer_fails() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			Pallet::<Test>::release_held_transfer(
+				RuntimeOrigin::root(),
+				ChannelId::new(0),
+				1u64.into()
+			),
+			Error::<Test>::HeldTransferNotFound
+		);
+	})
+}
+
+/// The account receiving `received_packet`
+fn bob() -> AccountId32 {
+	AccountId32::new([2; 32])
+}
+
+/// The native token returning over `channel-0` to `bob()`, with `amount`
+fn received_packet(amount: &str) -> Packet {
+	let data = FungibleTokenPacketData {
+		denom: "transfer/channel-5/DEMO".to_string(),
+		amount: amount.to_string(),
+		sender: SENDER.to_string(),
+		receiver: Pallet::<Test>::account_address(&bob()),
+		memo: String::new(),
+	};
+	Packet {
+		sequence: Sequence::from(1),
+		port_on_a: PortId::transfer(),
+		chan_on_a: ChannelId::new(5),
+		port_on_b: PortId::transfer(),
+		chan_on_b: ChannelId::new(0),
+		data: data.to_bytes(),
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	}
+}
+
+/// Open `channel-0` to `channel-5` with `AMOUNT` of the native token escrowed, and hold the
+/// transfers of `SENDER`.
+fn setup_hold() {
+	System::set_block_number(1);
+	open_channels(&[(0, 5)]);
+	let mut ctx = IbcTransferModule::<Test>(PhantomData);
+	ctx.send_coins(&account(1), &escrow(0), &coin("DEMO", AMOUNT)).unwrap();
+	set_rule(PolicyTarget::Address(SENDER.as_bytes().to_vec()), Some(PolicyRule::Hold));
+}
+
+/// Receive `received_packet` with `AMOUNT`, and check that it was held.
+fn receive_on_hold() {
+	let ack = IbcTransferModule::<Test>(PhantomData).on_recv_packet(
+		&mut ModuleOutputBuilder::new(),
+		&received_packet(&AMOUNT.to_string()),
+		&relayer(),
+	);
+	assert_eq!(
+		GenericAcknowledgement::from_bytes(ack.as_ref()),
+		Some(GenericAcknowledgement::success([1u8]))
+	);
+	let held = <HeldTransfers<Test>>::get(ChannelId::new(0), Sequence::from(1)).unwrap();
+	assert_eq!(held.denom, b"DEMO".to_vec());
+	assert_eq!(held.amount, AMOUNT);
+	assert_eq!(Balances::free_balance(Pallet::<Test>::hold_account()), AMOUNT);
+	assert_eq!(Balances::free_balance(bob()), 0);
+}
+
+#[test]
+fn test_received_transfer_without_local_amount_is_denied() {
+	new_test_ext().execute_with(|| {
+		// more than a u128 of local units
+		let amount = format!("{}0", u128::MAX);
+		assert!(matches!(
+			Pallet::<Test>::recv_decision(&received_packet(&amount)),
+			PolicyDecision::Deny(_)
+		));
+	})
+}
+
+#[test]
+fn test_held_transfer_is_released_to_receiver() {
+	new_test_ext().execute_with(|| {
+		setup_hold();
+		receive_on_hold();
+
+		assert_noop!(
+			Pallet::<Test>::release_held_transfer(
+				RuntimeOrigin::signed(bob()),
+				ChannelId::new(0),
+				1u64.into()
+			),
+			DispatchError::BadOrigin
+		);
+		assert_ok!(Pallet::<Test>::release_held_transfer(
+			RuntimeOrigin::root(),
+			ChannelId::new(0),
+			1u64.into()
+		));
+		System::assert_last_event(RuntimeEvent::Ics20Transfer(Event::HeldTransferReleased {
+			channel_id: ChannelId::new(0),
+			sequence: Sequence::from(1),
+			receiver: bob(),
+		}));
+		assert!(!<HeldTransfers<Test>>::contains_key(ChannelId::new(0), Sequence::from(1)));
+		assert_eq!(Balances::free_balance(bob()), AMOUNT);
+		assert_eq!(Balances::free_balance(Pallet::<Test>::hold_account()), 0);
+	})
+}
+
+#[test]
+fn test_held_transfer_is_returned_to_sender() {
+	new_test_ext().execute_with(|| {
+		setup_hold();
+		receive_on_hold();
+
+		// the return is not held by the rule on its receiver
+		assert_ok!(Pallet::<Test>::return_held_transfer(
+			RuntimeOrigin::root(),
+			ChannelId::new(0),
+			1u64.into()
+		));
+		System::assert_last_event(RuntimeEvent::Ics20Transfer(Event::HeldTransferReturned {
+			channel_id: ChannelId::new(0),
+			sequence: Sequence::from(1),
+			return_sequence: Sequence::from(1),
+		}));
+		assert!(!<HeldTransfers<Test>>::contains_key(ChannelId::new(0), Sequence::from(1)));
+		assert_eq!(Balances::free_balance(Pallet::<Test>::hold_account()), 0);
+		assert_eq!(escrow_balance(0), AMOUNT);
+		let returned = <PendingTransfers<Test>>::get(
+			(PortId::transfer(), ChannelId::new(0)),
+			Sequence::from(1),
+		)
+		.unwrap();
+		assert_eq!(returned.sender, Pallet::<Test>::hold_account());
+		assert_eq!(returned.receiver, SENDER.as_bytes().to_vec());
+		assert_eq!(returned.amount, AMOUNT);
+		assert_eq!(returned.status, TransferStatus::Pending);
+
+		assert_noop!(
+			Pallet::<Test>::return_held_transfer(
+				RuntimeOrigin::root(),
+				ChannelId::new(0),
+				1u64.into()
+			),
+			Error::<Test>::HeldTransferNotFound
+		);
+	})
+}
@@ -0,0 +1,234 @@
+//! Rate limits of ICS20 flows.
+use crate::{
+	callback::IbcTransferModule,
+	mock::{new_test_ext, Balances, RuntimeEvent, RuntimeOrigin, System, Test, DOLLARS},
+	ratelimit::Quota,
+	tests::{account, alice, coin, escrow, escrow_balance, open_channels, packet, relayer, send},
+	Error, Event, Pallet, RateLimitedSends, RateLimits,
+};
+use core::marker::PhantomData;
+use frame_support::{assert_noop, assert_ok, traits::Currency};
+use ibc::{
+	applications::transfer::context::BankKeeper,
+	core::{
+		ics04_channel::packet::Sequence,
+		ics24_host::identifier::ChannelId,
+		ics26_routing::context::{Module, ModuleOutputBuilder},
+	},
+};
+use pallet_ibc_utils::ack::GenericAcknowledgement;
+use sp_runtime::{traits::IdentifyAccount, DispatchError, Percent};
+
+fn quota(max_percent_send: u8, max_percent_recv: u8) -> Quota<u32> {
+	Quota {
+		max_percent_send: Percent::from_percent(max_percent_send),
+		max_percent_recv: Percent::from_percent(max_percent_recv),
+		window: 10,
+	}
+}
+
+fn set_limit(max_percent_send: u8, max_percent_recv: u8) {
+	assert_ok!(Pallet::<Test>::set_rate_limit(
+		RuntimeOrigin::root(),
+		ChannelId::new(0),
+		b"DEMO".to_vec(),
+		quota(max_percent_send, max_percent_recv),
+	));
+}
+
+fn flow() -> (u128, u128) {
+	let limit = <RateLimits<Test>>::get(ChannelId::new(0), b"DEMO".to_vec()).unwrap();
+	(limit.flow.inflow, limit.flow.outflow)
+}
+
+/// Fund `alice()` so that, with the existential deposit of the escrow account of `channel-0`,
+/// 10 DOLLARS of the native token are issued.
+fn fund_alice() {
+	escrow(0);
+	Balances::make_free_balance_be(&alice(), 9 * DOLLARS);
+}
+
+fn receive(amount: u128) -> bool {
+	let mut module = IbcTransferModule::<Test>(PhantomData);
+	let ack = module.on_recv_packet(
+		&mut ModuleOutputBuilder::new(),
+		&packet(1, amount, false),
+		&relayer(),
+	);
+	GenericAcknowledgement::from_bytes(ack.as_ref()).unwrap().is_success()
+}
+
+#[test]
+fn test_rate_limit_is_set_by_admin() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let alice = RuntimeOrigin::signed(account(1).into_account());
+		let channel_id = ChannelId::new(0);
+
+		assert_noop!(
+			Pallet::<Test>::set_rate_limit(
+				alice,
+				channel_id.clone(),
+				b"DEMO".to_vec(),
+				quota(10, 10)
+			),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			Pallet::<Test>::set_rate_limit(
+				RuntimeOrigin::root(),
+				channel_id.clone(),
+				b"DEMO".to_vec(),
+				Quota { window: 0, ..quota(10, 10) }
+			),
+			Error::<Test>::InvalidQuota
+		);
+		assert_noop!(
+			Pallet::<Test>::reset_rate_limit(
+				RuntimeOrigin::root(),
+				channel_id.clone(),
+				b"DEMO".to_vec()
+			),
+			Error::<Test>::RateLimitNotFound
+		);
+
+		set_limit(10, 10);
+		System::assert_last_event(RuntimeEvent::Ics20Transfer(Event::RateLimitSet {
+			channel_id: channel_id.clone(),
+			denom: b"DEMO".to_vec(),
+			quota: quota(10, 10),
+		}));
+
+		// changing the quota keeps the flows of the window
+		<RateLimits<Test>>::mutate(&channel_id, b"DEMO".to_vec(), |limit| {
+			limit.as_mut().unwrap().flow.outflow = 7
+		});
+		set_limit(20, 20);
+		assert_eq!(flow(), (0, 7));
+
+		assert_ok!(Pallet::<Test>::reset_rate_limit(
+			RuntimeOrigin::root(),
+			channel_id.clone(),
+			b"DEMO".to_vec()
+		));
+		assert_eq!(flow(), (0, 0));
+
+		assert_ok!(Pallet::<Test>::remove_rate_limit(
+			RuntimeOrigin::root(),
+			channel_id.clone(),
+			b"DEMO".to_vec()
+		));
+		assert!(!<RateLimits<Test>>::contains_key(&channel_id, b"DEMO".to_vec()));
+		assert_noop!(
+			Pallet::<Test>::remove_rate_limit(RuntimeOrigin::root(), channel_id, b"DEMO".to_vec()),
+			Error::<Test>::RateLimitNotFound
+		);
+	})
+}
+
+#[test]
+fn test_send_over_quota_is_rejected() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		open_channels(&[(0, 5)]);
+		fund_alice();
+		// 10% of the 10 DOLLARS issued
+		set_limit(10, 10);
+
+		assert_ok!(send(DOLLARS / 2));
+		assert_ok!(send(DOLLARS / 2));
+		assert_noop!(send(1), Error::<Test>::RateLimitExceeded);
+		assert_eq!(flow(), (0, DOLLARS));
+		assert!(<RateLimitedSends<Test>>::contains_key(ChannelId::new(0), Sequence::from(2)));
+
+		// the next window starts afresh
+		System::set_block_number(11);
+		assert_ok!(send(DOLLARS / 2));
+		assert_eq!(flow(), (0, DOLLARS / 2));
+	})
+}
+
+#[test]
+fn test_receive_over_quota_is_rejected() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		fund_alice();
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		ctx.send_coins(&alice().into(), &escrow(0), &coin("DEMO", 5 * DOLLARS)).unwrap();
+		// 20% of the 10 DOLLARS issued
+		set_limit(10, 20);
+
+		assert!(!receive(3 * DOLLARS));
+		assert_eq!(flow(), (0, 0));
+		assert!(receive(2 * DOLLARS));
+		assert_eq!(flow(), (2 * DOLLARS, 0));
+		assert_eq!(escrow_balance(0), 3 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_failed_receive_is_taken_off_inflow() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		fund_alice();
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		ctx.send_coins(&alice().into(), &escrow(0), &coin("DEMO", DOLLARS)).unwrap();
+		set_limit(10, 20);
+
+		// within the quota, but more than the escrow holds
+		assert!(!receive(2 * DOLLARS));
+		assert_eq!(flow(), (0, 0));
+	})
+}
+
+#[test]
+fn test_failed_send_is_taken_off_outflow() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		open_channels(&[(0, 5)]);
+		fund_alice();
+		set_limit(10, 10);
+		let mut module = IbcTransferModule::<Test>(PhantomData);
+
+		assert_ok!(send(DOLLARS / 2));
+		assert_ok!(send(DOLLARS / 4));
+		assert_ok!(send(DOLLARS / 4));
+		assert_eq!(flow(), (0, DOLLARS));
+
+		// an error acknowledgement refunds the sender and frees the quota
+		module
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&packet(1, DOLLARS / 2, true),
+				&GenericAcknowledgement::error("failed").into(),
+				&relayer(),
+			)
+			.unwrap();
+		assert_eq!(flow(), (0, DOLLARS / 2));
+		assert_eq!(Balances::free_balance(alice()), 8 * DOLLARS + DOLLARS / 2);
+
+		// so does a timeout
+		module
+			.on_timeout_packet(
+				&mut ModuleOutputBuilder::new(),
+				&packet(2, DOLLARS / 4, true),
+				&relayer(),
+			)
+			.unwrap();
+		assert_eq!(flow(), (0, DOLLARS / 4));
+
+		// a successful acknowledgement keeps the outflow
+		module
+			.on_acknowledgement_packet(
+				&mut ModuleOutputBuilder::new(),
+				&packet(3, DOLLARS / 4, true),
+				&GenericAcknowledgement::success([1u8]).into(),
+				&relayer(),
+			)
+			.unwrap();
+		assert_eq!(flow(), (0, DOLLARS / 4));
+		assert!(!<RateLimitedSends<Test>>::contains_key(ChannelId::new(0), Sequence::from(3)));
+		assert_ok!(send(DOLLARS * 3 / 4));
+		assert_noop!(send(1), Error::<Test>::RateLimitExceeded);
+	})
+}
@@ -0,0 +1,74 @@
+//! Accounting of escrowed tokens and minted vouchers.
+use crate::{
+	callback::IbcTransferModule,
+	mock::{new_test_ext, Balances, Test, DOLLARS},
+	tests::{account, coin, escrow},
+	EscrowTotals, Pallet, VoucherSupply,
+};
+use core::marker::PhantomData;
+use frame_support::traits::Currency;
+use ibc::{applications::transfer::context::BankKeeper, core::ics24_host::identifier::ChannelId};
+use sp_runtime::traits::IdentifyAccount;
+
+#[test]
+fn test_escrowed_totals_follow_escrow_transfers() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let channel_id = ChannelId::new(0);
+		let escrow = escrow(0);
+
+		ctx.send_coins(&alice, &escrow, &coin("DEMO", 3 * DOLLARS)).unwrap();
+		ctx.send_coins(&escrow, &alice, &coin("DEMO", DOLLARS)).unwrap();
+		assert_eq!(EscrowTotals::<Test>::get(&channel_id, b"DEMO".to_vec()), 2 * DOLLARS);
+		assert_eq!(Pallet::<Test>::escrow_totals().len(), 1);
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+
+		// the escrow account no longer holds what was escrowed
+		EscrowTotals::<Test>::insert(&channel_id, b"DEMO".to_vec(), 3 * DOLLARS);
+		assert!(Pallet::<Test>::do_try_state().is_err());
+
+		// nor only what was escrowed
+		EscrowTotals::<Test>::insert(&channel_id, b"DEMO".to_vec(), DOLLARS);
+		assert!(Pallet::<Test>::do_try_state().is_err());
+	})
+}
+
+#[test]
+fn test_escrowed_total_underflow_fails_the_transfer() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let escrow = escrow(0);
+		ctx.send_coins(&alice, &escrow, &coin("DEMO", DOLLARS)).unwrap();
+		// the tokens sent straight to the escrow account are not escrowed
+		Balances::make_free_balance_be(&escrow.clone().into_account(), 4 * DOLLARS);
+
+		assert!(ctx.send_coins(&escrow, &alice, &coin("DEMO", 2 * DOLLARS)).is_err());
+	})
+}
+
+#[test]
+fn test_voucher_supply_follows_mints_and_burns() {
+	new_test_ext().execute_with(|| {
+		let mut ctx = IbcTransferModule::<Test>(PhantomData);
+		let alice = account(1);
+		let voucher = coin("transfer/channel-0/uatom", 100);
+		let ibc_denom = Pallet::<Test>::asset_name(&voucher.denom);
+
+		ctx.mint_coins(&alice, &voucher).unwrap();
+		ctx.burn_coins(&alice, &coin("transfer/channel-0/uatom", 40)).unwrap();
+		assert_eq!(VoucherSupply::<Test>::get(&ibc_denom), 60);
+		assert_eq!(Pallet::<Test>::voucher_supplies(), vec![(ibc_denom.clone(), 60)]);
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+
+		VoucherSupply::<Test>::insert(&ibc_denom, 61);
+		assert!(Pallet::<Test>::do_try_state().is_err());
+		VoucherSupply::<Test>::insert(&ibc_denom, 59);
+		assert!(Pallet::<Test>::do_try_state().is_err());
+
+		// more vouchers than were minted cannot be burnt
+		VoucherSupply::<Test>::insert(&ibc_denom, 10);
+		assert!(ctx.burn_coins(&alice, &coin("transfer/channel-0/uatom", 20)).is_err());
+	})
+}
@@ -0,0 +1,86 @@
+//! Switches to stop sending or receiving transfers.
+use crate::{
+	mock::{new_test_ext, RuntimeOrigin, Test},
+	switches::{Switch, SwitchScope},
+	tests::applications::transfer::test_util::{get_dummy_msg_transfer, get_dummy_transfer_packet},
+	transfer::TransferTimeout,
+	ChannelSwitches, Error, Pallet,
+};
+use frame_support::{assert_noop, assert_ok};
+use ibc::core::ics04_channel::timeout::TimeoutHeight;
+use sp_core::crypto::AccountId32;
+use sp_runtime::DispatchError;
+
+const SEND_DISABLED: Switch = Switch { send: false, receive: true };
+const RECEIVE_DISABLED: Switch = Switch { send: true, receive: false };
+
+#[test]
+fn test_switches_are_set_by_admin() {
+	new_test_ext().execute_with(|| {
+		let scope = SwitchScope::Channel(Default::default());
+		assert_noop!(
+			Pallet::<Test>::set_switch(
+				RuntimeOrigin::signed(AccountId32::new([1; 32])),
+				scope.clone(),
+				SEND_DISABLED
+			),
+			DispatchError::BadOrigin
+		);
+
+		assert_ok!(Pallet::<Test>::set_switch(RuntimeOrigin::root(), scope.clone(), SEND_DISABLED));
+		assert_eq!(Pallet::<Test>::switch(&scope), SEND_DISABLED);
+
+		// enabling both again clears the switch
+		assert_ok!(Pallet::<Test>::set_switch(RuntimeOrigin::root(), scope, Switch::default()));
+		assert!(!ChannelSwitches::<Test>::contains_key(Default::default()));
+	})
+}
+
+#[test]
+fn test_disabled_denom_is_not_sent() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Pallet::<Test>::set_switch(
+			RuntimeOrigin::root(),
+			SwitchScope::Denom(b"DEMO".to_vec()),
+			SEND_DISABLED
+		));
+
+		assert_eq!(
+			Pallet::<Test>::transfer(
+				RuntimeOrigin::signed(AccountId32::new([1; 32])),
+				None,
+				10,
+				b"0x02".to_vec(),
+				Default::default(),
+				TransferTimeout::default(),
+				vec![],
+			),
+			Err(Error::<Test>::SendDisabled.into())
+		);
+	})
+}
+
+#[test]
+fn test_disabled_channel_does_not_receive() {
+	new_test_ext().execute_with(|| {
+		let msg = get_dummy_msg_transfer(TimeoutHeight::no_timeout(), None);
+		let packet = get_dummy_transfer_packet(msg, 1u64.into());
+		assert_ok!(Pallet::<Test>::ensure_receive_enabled(&packet));
+
+		// the packet is received over channel-0 of this chain
+		let scope = SwitchScope::Channel(packet.chan_on_b.clone());
+		Pallet::<Test>::set_switch(RuntimeOrigin::root(), scope.clone(), RECEIVE_DISABLED).unwrap();
+		assert_eq!(
+			Pallet::<Test>::ensure_receive_enabled(&packet),
+			Err(Error::<Test>::ReceiveDisabled)
+		);
+
+		Pallet::<Test>::set_switch(RuntimeOrigin::root(), scope, Switch::default()).unwrap();
+		Pallet::<Test>::set_switch(RuntimeOrigin::root(), SwitchScope::Global, RECEIVE_DISABLED)
+			.unwrap();
+		assert_eq!(
+			Pallet::<Test>::ensure_receive_enabled(&packet),
+			Err(Error::<Test>::ReceiveDisabled)
+		);
+	})
+}
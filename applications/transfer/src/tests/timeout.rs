@@ -0,0 +1,136 @@
+//! Timeouts of outgoing transfers.
+use crate::{
+	mock::{new_test_ext, RuntimeOrigin, Test, DOLLARS},
+	tests::{account, escrow_balance, open_channels, NOW},
+	transfer::TransferTimeout,
+	Error, Pallet,
+};
+use frame_support::assert_noop;
+use ibc::{
+	core::{
+		ics04_channel::timeout::TimeoutHeight,
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	timestamp::Timestamp,
+	Height,
+};
+use sp_core::crypto::AccountId32;
+use sp_runtime::DispatchError;
+
+/// Unix time of the counterparty client, in nanoseconds
+const CLIENT_NOW: u64 = NOW * 1_000_000;
+
+fn height(revision_height: u64) -> TimeoutHeight {
+	TimeoutHeight::At(Height::new(0, revision_height).unwrap())
+}
+
+fn timestamp(nanoseconds: u64) -> Timestamp {
+	Timestamp::from_nanoseconds(nanoseconds).unwrap()
+}
+
+fn resolve(
+	height: TimeoutHeight,
+	timestamp: Timestamp,
+) -> Result<(TimeoutHeight, Timestamp), DispatchError> {
+	Pallet::<Test>::resolve_timeout(&PortId::transfer(), &ChannelId::new(0), height, timestamp)
+		.map_err(Into::into)
+}
+
+fn send(timeout: TransferTimeout) -> Result<(), DispatchError> {
+	Pallet::<Test>::transfer(
+		RuntimeOrigin::signed(AccountId32::new([1; 32])),
+		None,
+		DOLLARS,
+		b"cosmos1receiver".to_vec(),
+		ChannelId::new(0),
+		timeout,
+		vec![],
+	)
+}
+
+#[test]
+fn test_default_timeout_follows_counterparty_client() {
+	new_test_ext().execute_with(|| {
+		open_channels(&[(0, 5)]);
+
+		// the mock offsets are 1000 blocks and 10 minutes
+		assert_eq!(
+			Pallet::<Test>::default_timeout(&PortId::transfer(), &ChannelId::new(0))
+				.map_err(DispatchError::from),
+			Ok((height(1_005), timestamp(CLIENT_NOW + 600_000_000_000)))
+		);
+		assert_eq!(
+			Pallet::<Test>::counterparty_latest(&PortId::transfer(), &ChannelId::new(0))
+				.map_err(DispatchError::from),
+			Ok((Height::new(0, 5).unwrap(), timestamp(CLIENT_NOW)))
+		);
+		// an unknown channel has no client to default from
+		assert_eq!(
+			Pallet::<Test>::default_timeout(&PortId::transfer(), &ChannelId::new(1))
+				.map_err(DispatchError::from),
+			Err(Error::<Test>::InvalidChannel.into())
+		);
+	})
+}
+
+#[test]
+fn test_resolve_timeout() {
+	new_test_ext().execute_with(|| {
+		open_channels(&[(0, 5)]);
+
+		// a transfer setting no timeout gets the default one
+		assert_eq!(
+			resolve(TimeoutHeight::no_timeout(), Timestamp::none()),
+			Ok((height(1_005), timestamp(CLIENT_NOW + 600_000_000_000)))
+		);
+		// a timeout set in the future of the client is kept
+		assert_eq!(resolve(height(6), Timestamp::none()), Ok((height(6), Timestamp::none())));
+		assert_eq!(
+			resolve(TimeoutHeight::no_timeout(), timestamp(CLIENT_NOW + 1)),
+			Ok((TimeoutHeight::no_timeout(), timestamp(CLIENT_NOW + 1)))
+		);
+		assert_eq!(
+			resolve(height(6), timestamp(CLIENT_NOW + 1)),
+			Ok((height(6), timestamp(CLIENT_NOW + 1)))
+		);
+	})
+}
+
+#[test]
+fn test_expired_timeout_is_rejected() {
+	new_test_ext().execute_with(|| {
+		open_channels(&[(0, 5)]);
+		account(1);
+
+		// the client is at height 5
+		assert_eq!(
+			resolve(height(5), Timestamp::none()),
+			Err(Error::<Test>::TimeoutExpired.into())
+		);
+		assert_eq!(
+			resolve(height(4), Timestamp::none()),
+			Err(Error::<Test>::TimeoutExpired.into())
+		);
+		// and at CLIENT_NOW
+		assert_eq!(
+			resolve(TimeoutHeight::no_timeout(), timestamp(CLIENT_NOW - 1)),
+			Err(Error::<Test>::TimeoutExpired.into())
+		);
+		// either timeout having passed is enough
+		assert_eq!(
+			resolve(height(6), timestamp(CLIENT_NOW - 1)),
+			Err(Error::<Test>::TimeoutExpired.into())
+		);
+
+		// the funds of an expired transfer are not escrowed
+		assert_noop!(
+			send(TransferTimeout { height: Some((0, 5)), timestamp: None }),
+			Error::<Test>::TimeoutExpired
+		);
+		assert_noop!(
+			send(TransferTimeout { height: None, timestamp: Some(CLIENT_NOW - 1) }),
+			Error::<Test>::TimeoutExpired
+		);
+		assert_eq!(escrow_balance(0), 0);
+	})
+}
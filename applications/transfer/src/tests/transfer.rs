@@ -0,0 +1,181 @@
+//! Transfers sent by accounts of this chain.
+use crate::{
+	mock::{new_test_ext, Balances, RuntimeOrigin, System, Test, DOLLARS},
+	pending::TransferStatus,
+	tests::{
+		account, alice, applications::transfer::test_util::get_dummy_msg_transfer, coin,
+		escrow_balance, open_channels, send,
+	},
+	transfer::TransferTimeout,
+	Error, EscrowAccounts, Pallet, PendingTransfers,
+};
+use frame_support::assert_ok;
+use ibc::{
+	applications::transfer::msgs::transfer::{MsgTransfer, TYPE_URL},
+	core::{
+		ics04_channel::{packet::Sequence, timeout::TimeoutHeight},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	timestamp::Timestamp,
+};
+use ibc_proto::{google::protobuf::Any, protobuf::Protobuf};
+use pallet_ibc_utils::SignedRouter;
+use sp_core::crypto::AccountId32;
+
+/// A `MsgTransfer` of `DOLLARS` of the native token over `channel-0` sent by `alice()`, setting no
+/// timeout
+fn raw_message() -> Any {
+	let msg = MsgTransfer {
+		port_on_a: PortId::transfer(),
+		chan_on_a: ChannelId::new(0),
+		token: coin("DEMO", DOLLARS),
+		sender: Pallet::<Test>::account_address(&alice()).parse().unwrap(),
+		receiver: "cosmos1receiver".parse().unwrap(),
+		timeout_height_on_b: TimeoutHeight::no_timeout(),
+		timeout_timestamp_on_b: Timestamp::none(),
+	};
+	Any { type_url: TYPE_URL.to_string(), value: msg.encode_vec().unwrap() }
+}
+
+/// Check that `DOLLARS` sent by `alice()` are escrowed and pending with packet sequence 1.
+fn assert_sent() {
+	assert_eq!(escrow_balance(0), DOLLARS);
+	assert_eq!(Balances::free_balance(alice()), 9 * DOLLARS);
+	let transfer =
+		<PendingTransfers<Test>>::get((PortId::transfer(), ChannelId::new(0)), Sequence::from(1))
+			.unwrap();
+	assert_eq!(transfer.sender, alice());
+	assert_eq!(transfer.receiver, b"cosmos1receiver".to_vec());
+	assert_eq!(transfer.denom, b"DEMO".to_vec());
+	assert_eq!(transfer.amount, DOLLARS);
+	assert_eq!(transfer.status, TransferStatus::Pending);
+	assert_eq!(Pallet::<Test>::pending_transfers(&alice()).len(), 1);
+}
+
+#[test]
+fn test_raw_transfer_must_be_sent_by_signer() {
+	new_test_ext().execute_with(|| {
+		// the message is sent by the dummy account, not the signer
+		let msg = get_dummy_msg_transfer(TimeoutHeight::no_timeout(), None);
+		let message = Any { type_url: TYPE_URL.to_string(), value: msg.encode_vec().unwrap() };
+		assert_eq!(
+			Pallet::<Test>::raw_transfer(RuntimeOrigin::signed(alice()), vec![message])
+				.map_err(|e| e.error),
+			Err(Error::<Test>::InvalidSender.into())
+		);
+	})
+}
+
+#[test]
+fn test_failed_raw_transfer_registers_no_escrow_account() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		account(1);
+
+		// channel-0 is not open
+		assert!(Pallet::<Test>::raw_transfer(RuntimeOrigin::signed(alice()), vec![raw_message()])
+			.is_err());
+		assert!(!<EscrowAccounts<Test>>::contains_key(PortId::transfer(), ChannelId::new(0)));
+		assert_eq!(Balances::free_balance(alice()), 10 * DOLLARS);
+	})
+}
+
+#[test]
+fn test_typed_transfer_checks_its_arguments() {
+	new_test_ext().execute_with(|| {
+		let alice = RuntimeOrigin::signed(alice());
+		let channel_id = ChannelId::new(0);
+		let timeout = TransferTimeout { height: None, timestamp: Some(u64::MAX) };
+
+		// no timeout is set, and channel-0 has no counterparty client to default it from
+		assert_eq!(
+			Pallet::<Test>::transfer(
+				alice.clone(),
+				None,
+				10,
+				b"0x02".to_vec(),
+				channel_id.clone(),
+				TransferTimeout::default(),
+				vec![],
+			),
+			Err(Error::<Test>::InvalidChannel.into())
+		);
+		// a timeout height of revision height zero is invalid
+		assert_eq!(
+			Pallet::<Test>::transfer(
+				alice.clone(),
+				None,
+				10,
+				b"0x02".to_vec(),
+				channel_id.clone(),
+				TransferTimeout { height: Some((1, 0)), timestamp: None },
+				vec![],
+			),
+			Err(Error::<Test>::InvalidTimeout.into())
+		);
+		// no denomination is sent out as asset 7
+		assert_eq!(
+			Pallet::<Test>::transfer(
+				alice,
+				Some(7),
+				10,
+				b"0x02".to_vec(),
+				channel_id,
+				timeout,
+				vec![],
+			),
+			Err(Error::<Test>::InvalidTokenId.into())
+		);
+
+		assert_eq!(Pallet::<Test>::asset_denom(None).unwrap().to_string(), "DEMO");
+		assert_eq!(Pallet::<Test>::asset_denom(Some(7)), None);
+	})
+}
+
+#[test]
+fn test_raw_transfer_sent_by_signer() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		open_channels(&[(0, 5)]);
+		account(1);
+
+		assert_ok!(Pallet::<Test>::raw_transfer(
+			RuntimeOrigin::signed(alice()),
+			vec![raw_message()]
+		));
+		assert_sent();
+	})
+}
+
+#[test]
+fn test_routed_transfer_must_be_sent_by_signer() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		open_channels(&[(0, 5)]);
+		account(1);
+
+		assert_eq!(
+			<Pallet<Test> as SignedRouter<_>>::dispatch(
+				&AccountId32::new([2; 32]),
+				vec![raw_message()]
+			),
+			Err(Error::<Test>::InvalidSender.into())
+		);
+		assert_eq!(escrow_balance(0), 0);
+
+		assert_ok!(<Pallet<Test> as SignedRouter<_>>::dispatch(&alice(), vec![raw_message()]));
+		assert_sent();
+	})
+}
+
+#[test]
+fn test_typed_transfer() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		open_channels(&[(0, 5)]);
+		account(1);
+
+		assert_ok!(send(DOLLARS));
+		assert_sent();
+	})
+}
@@ -0,0 +1,89 @@
+//! Timeouts of outgoing transfers.
+//!
+//! A transfer that sets neither a timeout height nor a timeout timestamp times out
+//! [`Config::DefaultTimeoutHeightOffset`] blocks and [`Config::DefaultTimeoutTimestampOffset`]
+//! nanoseconds after the latest height and consensus timestamp of the counterparty client of its
+//! channel, a zero offset leaving that timeout unset. Transfers whose timeout has already passed on
+//! the client are rejected before their funds are escrowed or burnt.
+use crate::*;
+use ibc::{
+	core::ics04_channel::{error::ChannelError, timeout::TimeoutHeight},
+	timestamp::{Expiry, Timestamp},
+	Height,
+};
+
+impl<T: Config> Pallet<T> {
+	/// The latest height and consensus timestamp of the counterparty client of `channel_id`.
+	pub fn counterparty_latest(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<(Height, Timestamp), Error<T>> {
+		let client = || -> Result<(Height, Timestamp), ChannelError> {
+			let channel_end = T::IbcContext::channel_end(port_id, channel_id)?;
+			let connection_id = channel_end
+				.connection_hops()
+				.first()
+				.cloned()
+				.ok_or(ChannelError::InvalidConnectionHopsLength { expected: 1, actual: 0 })?;
+			let connection_end = T::IbcContext::connection_end(&connection_id)?;
+			let client_id = connection_end.client_id();
+			let latest_height = T::IbcContext::client_state(client_id)?.latest_height();
+			let consensus_state = T::IbcContext::client_consensus_state(client_id, &latest_height)?;
+			Ok((latest_height, consensus_state.timestamp()))
+		};
+		client().map_err(|e| {
+			log::error!(target: LOG_TARGET, "❌ [counterparty_latest] {}: {}", channel_id, e);
+			Error::<T>::InvalidChannel
+		})
+	}
+
+	/// The timeout of a transfer over `channel_id` that sets none.
+	pub fn default_timeout(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+	) -> Result<(TimeoutHeight, Timestamp), Error<T>> {
+		let (latest_height, latest_timestamp) = Self::counterparty_latest(port_id, channel_id)?;
+
+		let height = match T::DefaultTimeoutHeightOffset::get() {
+			0 => TimeoutHeight::no_timeout(),
+			offset => TimeoutHeight::At(latest_height.add(offset)),
+		};
+		let timestamp = match T::DefaultTimeoutTimestampOffset::get() {
+			0 => Timestamp::none(),
+			offset => (latest_timestamp + core::time::Duration::from_nanos(offset))
+				.map_err(|_| Error::<T>::InvalidTimeout)?,
+		};
+		ensure!(
+			height != TimeoutHeight::no_timeout() || is_set(&timestamp),
+			Error::<T>::InvalidTimeout
+		);
+		Ok((height, timestamp))
+	}
+
+	/// The timeout of a transfer over `channel_id` with the given timeout, the default one if it
+	/// sets none. Fails if the timeout has passed on the counterparty client.
+	pub fn resolve_timeout(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		height: TimeoutHeight,
+		timestamp: Timestamp,
+	) -> Result<(TimeoutHeight, Timestamp), Error<T>> {
+		if height == TimeoutHeight::no_timeout() && !is_set(&timestamp) {
+			return Self::default_timeout(port_id, channel_id)
+		}
+
+		let (latest_height, latest_timestamp) = Self::counterparty_latest(port_id, channel_id)?;
+		ensure!(!height.has_expired(latest_height), Error::<T>::TimeoutExpired);
+		if is_set(&timestamp) {
+			ensure!(
+				!matches!(latest_timestamp.check_expiry(&timestamp), Expiry::Expired),
+				Error::<T>::TimeoutExpired
+			);
+		}
+		Ok((height, timestamp))
+	}
+}
+
+fn is_set(timestamp: &Timestamp) -> bool {
+	timestamp.nanoseconds() != 0
+}
@@ -0,0 +1,192 @@
+//! Transfers sent by accounts of this chain.
+//!
+//! [`Pallet::transfer`] builds the transfer from a local asset and amount, so wallets need not
+//! encode a `MsgTransfer`, and sends it from the signer. Like [`Pallet::raw_transfer`], whose
+//! messages must be sent by the signer, it escrows tokens sourced on this chain and burns vouchers
+//! before sending the packet, which also carries the memo of the transfer.
+use crate::{address::AddressValidator, packet::FungibleTokenPacketData, *};
+use alloc::{format, string::ToString};
+use codec::{Decode, Encode, MaxEncodedLen};
+use ibc::{
+	applications::transfer::{
+		context::BankKeeper, is_sender_chain_source, PrefixedCoin, PrefixedDenom,
+	},
+	core::{ics02_client::height::Height, ics04_channel::timeout::TimeoutHeight},
+	timestamp::Timestamp,
+};
+use pallet_ibc_utils::packet::{build_packet, send_packet};
+use scale_info::TypeInfo;
+use sp_std::str::FromStr;
+
+/// Timeout of a transfer on the counterparty chain, the default one if neither is set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Encode, Decode, TypeInfo, MaxEncodedLen)]
+pub struct TransferTimeout {
+	/// (revision number, revision height) of the counterparty chain the transfer times out at
+	pub height: Option<(u64, u64)>,
+	/// Unix time on the counterparty chain the transfer times out at, in nanoseconds
+	pub timestamp: Option<u64>,
+}
+
+impl TransferTimeout {
+	/// The timeout height and timestamp of the packet, unset if they are `None`, `None` if they
+	/// are invalid.
+	pub fn to_packet_timeout(&self) -> Option<(TimeoutHeight, Timestamp)> {
+		let height = match self.height {
+			Some((revision_number, revision_height)) =>
+				TimeoutHeight::At(Height::new(revision_number, revision_height).ok()?),
+			None => TimeoutHeight::no_timeout(),
+		};
+		let timestamp = match self.timestamp {
+			Some(timestamp) => Timestamp::from_nanoseconds(timestamp).ok()?,
+			None => Timestamp::none(),
+		};
+		Some((height, timestamp))
+	}
+}
+
+/// A transfer of fungible tokens from an account of this chain
+pub struct OutgoingTransfer<AccountId> {
+	pub sender: AccountId,
+	pub port_id: PortId,
+	pub channel_id: ChannelId,
+	pub coin: PrefixedCoin,
+	/// Receiver on the counterparty chain
+	pub receiver: String,
+	pub memo: String,
+	pub timeout_height: TimeoutHeight,
+	pub timeout_timestamp: Timestamp,
+}
+
+impl<T: Config> Pallet<T> {
+	/// The denomination the local asset `asset_id`, the native token for `None`, is sent under.
+	pub fn asset_denom(asset_id: Option<T::AssetId>) -> Option<PrefixedDenom> {
+		let asset_id = match asset_id {
+			Some(asset_id) => asset_id,
+			None =>
+				return PrefixedDenom::from_str(sp_std::str::from_utf8(T::NATIVE_TOKEN_NAME).ok()?)
+					.ok(),
+		};
+		if let Some(trace) = Self::denom_trace_by_asset(asset_id) {
+			return PrefixedDenom::from_str(&String::from_utf8_lossy(&trace.full_path())).ok()
+		}
+		<NativeAssets<T>>::iter()
+			.find(|(_, id)| *id == asset_id)
+			.and_then(|(denom, _)| PrefixedDenom::from_str(&String::from_utf8_lossy(&denom)).ok())
+	}
+
+	/// The ICS20 address of `account`, the hex of its encoding.
+	pub fn account_address(account: &T::AccountId) -> String {
+		format!("0x{}", hex::encode(account.encode()))
+	}
+
+	/// Escrow or burn the funds of `transfer`, then send its packet, returning its sequence.
+	pub fn send_outgoing(transfer: OutgoingTransfer<T::AccountId>) -> Result<Sequence, Error<T>> {
+		Self::do_send_outgoing(transfer, true)
+	}
+
+	/// [`Self::send_outgoing`] without consulting the transfer policy, for the transfers decided
+	/// by [`Config::AdminOrigin`].
+	pub(crate) fn send_outgoing_unchecked(
+		transfer: OutgoingTransfer<T::AccountId>,
+	) -> Result<Sequence, Error<T>> {
+		Self::do_send_outgoing(transfer, false)
+	}
+
+	fn do_send_outgoing(
+		transfer: OutgoingTransfer<T::AccountId>,
+		check_policy: bool,
+	) -> Result<Sequence, Error<T>> {
+		let OutgoingTransfer {
+			sender,
+			port_id,
+			channel_id,
+			coin,
+			receiver,
+			memo,
+			timeout_height,
+			timeout_timestamp,
+		} = transfer;
+		ensure!(Self::is_send_enabled_for(&channel_id, &coin.denom), Error::<T>::SendDisabled);
+		ensure!(T::ReceiverValidator::is_valid(&receiver), Error::<T>::InvalidReceiver);
+		let (timeout_height, timeout_timestamp) =
+			Self::resolve_timeout(&port_id, &channel_id, timeout_height, timeout_timestamp)?;
+		let amount = Self::local_amount(&coin.denom, coin.amount, true)
+			.map_err(|_| Error::<T>::InvalidAmount)?;
+		if check_policy {
+			Self::ensure_send_allowed(
+				&sender,
+				&receiver,
+				&port_id,
+				&channel_id,
+				&coin.denom,
+				amount,
+			)?;
+		}
+
+		let mut ctx = IbcTransferModule(PhantomData::<T>);
+		let sender_address = Self::account_address(&sender);
+		let sender = T::AccountIdConversion::from(sender);
+		let escrowed = is_sender_chain_source(port_id.clone(), channel_id.clone(), &coin.denom);
+		let result = if escrowed {
+			let escrow = Pallet::<T>::escrow_account(&port_id, &channel_id);
+			ctx.send_coins(&sender, &T::AccountIdConversion::from(escrow), &coin)
+		} else {
+			ctx.burn_coins(&sender, &coin)
+		};
+		result.map_err(|e| {
+			log::error!(target: LOG_TARGET, "❌ [send_outgoing] escrow or burn {}: {}", coin.denom, e);
+			Error::<T>::TransferFailed
+		})?;
+
+		let data = FungibleTokenPacketData {
+			denom: coin.denom.to_string(),
+			amount: coin.amount.to_string(),
+			sender: sender_address,
+			receiver,
+			memo,
+		};
+		let packet = build_packet::<T::IbcContext>(
+			&port_id,
+			&channel_id,
+			data.to_bytes(),
+			timeout_height,
+			timeout_timestamp,
+		)
+		.map_err(|e| {
+			log::error!(target: LOG_TARGET, "❌ [send_outgoing] build packet: {}", e);
+			Error::<T>::InvalidChannel
+		})?;
+		let sequence = packet.sequence;
+		Self::record_send(&channel_id, sequence, &coin.denom, amount)?;
+		let event = send_packet::<T::IbcContext>(packet).map_err(|e| {
+			log::error!(target: LOG_TARGET, "❌ [send_outgoing] send packet: {}", e);
+			Error::<T>::TransferFailed
+		})?;
+		if escrowed {
+			Pallet::<T>::register_escrow_account(&port_id, &channel_id).map_err(|e| {
+				log::error!(target: LOG_TARGET, "❌ [send_outgoing] register escrow: {:?}", e);
+				Error::<T>::TransferFailed
+			})?;
+		}
+		Self::deposit_event(Event::<T>::SendPacket(event));
+
+		Ok(sequence)
+	}
+}
+
+#[test]
+fn test_transfer_timeout() {
+	assert_eq!(
+		TransferTimeout::default().to_packet_timeout(),
+		Some((TimeoutHeight::no_timeout(), Timestamp::none()))
+	);
+
+	let (height, timestamp) = TransferTimeout { height: Some((1, 100)), timestamp: None }
+		.to_packet_timeout()
+		.unwrap();
+	assert_eq!(height, TimeoutHeight::At(Height::new(1, 100).unwrap()));
+	assert_eq!(timestamp, Timestamp::none());
+
+	// height zero is not a valid timeout height
+	assert_eq!(TransferTimeout { height: Some((1, 0)), timestamp: None }.to_packet_timeout(), None);
+}
@@ -1,40 +1,119 @@
 use alloc::string::String;
+use core::str::FromStr;
+use frame_support::storage::{with_transaction, TransactionOutcome};
 use ibc::{
-	applications::transfer::{error::TokenTransferError, VERSION},
-	core::ics24_host::identifier::{ChannelId as IbcChannelId, PortId},
+	applications::transfer::{
+		error::TokenTransferError, is_receiver_chain_source, PrefixedDenom, TracePrefix, VERSION,
+	},
+	core::{
+		ics04_channel::packet::Packet,
+		ics24_host::identifier::{ChannelId as IbcChannelId, PortId},
+	},
 	signer::Signer,
 };
 use scale_info::prelude::format;
 use sha2::{Digest, Sha256};
+use sp_runtime::DispatchError;
 
-/// In ICS20 fungible token transfer, get the escrow address by channel ID and port ID
+/// In ICS20 fungible token transfer, get the hash the escrow address of a channel is derived from,
+/// `sha256("{VERSION}\0{port_id}/{channel_id}")`, whose first 20 bytes are the escrow address.
 ///
 /// Parameters:
 /// - `port_id`: The ID of the port corresponding to the escrow.
 /// - `channel_id`: The ID of the channel corresponding to the escrow.
-pub fn get_channel_escrow_address(
-	port_id: &PortId,
-	channel_id: &IbcChannelId,
-) -> Result<Signer, TokenTransferError> {
+pub fn get_channel_escrow_address_hash(port_id: &PortId, channel_id: &IbcChannelId) -> [u8; 32] {
 	let contents = format!("{}/{}", port_id, channel_id);
 	let mut data = VERSION.as_bytes().to_vec();
 	data.extend_from_slice(&[0]);
 	data.extend_from_slice(contents.as_bytes());
 
+	sp_io::hashing::sha2_256(&data)
+}
+
+/// Get the account receiving a forwarded transfer on this chain, before it is sent over the next
+/// hop, by the channel the transfer arrived on and its original sender.
+///
+/// Parameters:
+/// - `channel_id`: The ID of the channel the transfer was received on.
+/// - `sender`: The sender of the transfer on the previous hop.
+pub fn get_forward_intermediate_address(
+	channel_id: &IbcChannelId,
+	sender: &str,
+) -> Result<Signer, TokenTransferError> {
+	derive_address(b"ibc-packet-forward", channel_id, sender)
+}
+
+/// Get the account receiving a transfer and dispatching the calls of its receive hook, by the
+/// channel the transfer arrived on and its original sender.
+///
+/// Parameters:
+/// - `channel_id`: The ID of the channel the transfer was received on.
+/// - `sender`: The sender of the transfer on the counterparty chain.
+pub fn get_hook_account_address(
+	channel_id: &IbcChannelId,
+	sender: &str,
+) -> Result<Signer, TokenTransferError> {
+	derive_address(b"ibc-hooks", channel_id, sender)
+}
+
+fn derive_address(
+	domain: &[u8],
+	channel_id: &IbcChannelId,
+	sender: &str,
+) -> Result<Signer, TokenTransferError> {
+	let contents = format!("{}/{}", channel_id, sender);
+	let mut data = domain.to_vec();
+	data.extend_from_slice(&[0]);
+	data.extend_from_slice(contents.as_bytes());
+
 	let hash = sp_io::hashing::sha2_256(&data).to_vec();
 	let mut hex_string = hex::encode_upper(hash);
 	hex_string.insert_str(0, "0x");
 	hex_string.parse::<Signer>().map_err(TokenTransferError::Signer)
 }
 
-// pub fn derive_ibc_denom(
-// 	port_id: &PortId,
-// 	channel_id: &IbcChannelId,
-// 	denom: &str,
-// ) -> Result<String, TokenTransferError> {
-// 	let transfer_path = format!("{}/{}/{}", port_id, channel_id, denom);
-// 	derive_ibc_denom_with_path(&transfer_path)
-// }
+/// Get the denomination on this chain of the funds received with `packet`, and whether they are
+/// returning to this chain, i.e. are unescrowed rather than minted.
+///
+/// Parameters:
+/// - `packet`: The packet the funds are received with.
+/// - `denom`: The denomination of the funds in the packet data.
+pub fn get_received_denom(
+	packet: &Packet,
+	denom: &str,
+) -> Result<(PrefixedDenom, bool), TokenTransferError> {
+	let mut denom = PrefixedDenom::from_str(denom).map_err(|_| TokenTransferError::InvalidToken)?;
+	let returning =
+		is_receiver_chain_source(packet.port_on_a.clone(), packet.chan_on_a.clone(), &denom);
+	if returning {
+		denom.remove_trace_prefix(&TracePrefix::new(
+			packet.port_on_a.clone(),
+			packet.chan_on_a.clone(),
+		));
+	} else {
+		denom
+			.add_trace_prefix(TracePrefix::new(packet.port_on_b.clone(), packet.chan_on_b.clone()));
+	}
+	Ok((denom, returning))
+}
+
+/// Run `f` in a storage transaction, reverting it if `f` fails.
+pub(crate) fn transactional<R>(f: impl FnOnce() -> Result<R, String>) -> Result<R, String> {
+	with_transaction(|| {
+		let result = f();
+		if result.is_ok() {
+			TransactionOutcome::Commit(Ok(result))
+		} else {
+			TransactionOutcome::Rollback(Ok(result))
+		}
+	})
+	.unwrap_or_else(|e: DispatchError| Err(format!("{:?}", e)))
+}
+
+/// Derive the `ibc/{hash}` denomination of a voucher from its full path, trace included.
+pub fn derive_ibc_denom(denom: &PrefixedDenom) -> Result<String, TokenTransferError> {
+	derive_ibc_denom_with_path(&format!("{}", denom))
+}
 
 /// Derive the transferred token denomination using
 /// <https://github.com/cosmos/ibc-go/blob/main/docs/architecture/adr-001-coin-source-tracing.md>
@@ -51,7 +130,22 @@ pub fn derive_ibc_denom_with_path(transfer_path: &str) -> Result<String, TokenTr
 }
 
 #[test]
-fn test_get_channel_escrow_address() {
-	let signer = get_channel_escrow_address(&PortId::default(), &IbcChannelId::default()).unwrap();
-	println!("{:?}", signer)
+fn test_derive_ibc_denom() {
+	// ibc-go derives the denom from the full path, base denom included
+	let denom = PrefixedDenom::from_str("transfer/channel-0/uatom").unwrap();
+	assert_eq!(
+		derive_ibc_denom(&denom).unwrap(),
+		"ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"
+	);
+}
+
+#[test]
+fn test_get_channel_escrow_address_hash() {
+	// cosmos1x54ltnyg88k0ejmk8ytwrhd3ltm84xehrnlslf, the escrow of transfer/channel-141 on the
+	// Cosmos Hub
+	let hash = get_channel_escrow_address_hash(
+		&PortId::transfer(),
+		&IbcChannelId::from_str("channel-141").unwrap(),
+	);
+	assert_eq!(hex::encode(&hash[..20]), "352bf5cc8839ecfccb763916e1ddb1faf67a9b37");
 }
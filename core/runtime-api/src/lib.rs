@@ -0,0 +1,36 @@
+//! Runtime API of the IBC core pallet.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+	/// Queries of the IBC core pallet for relayers, with port and channel ids as their string
+	/// bytes
+	pub trait IbcApi {
+		/// At most `limit` sequences of the packets sent on `channel_id` of `port_id` whose
+		/// commitment is stored, following `start_after`, in ascending order.
+		fn packet_commitments(
+			port_id: Vec<u8>,
+			channel_id: Vec<u8>,
+			start_after: Option<u64>,
+			limit: u32,
+		) -> Vec<u64>;
+
+		/// At most `limit` sequences of the packets received on `channel_id` of `port_id` whose
+		/// acknowledgement is stored, following `start_after`, in ascending order.
+		fn packet_acknowledgements(
+			port_id: Vec<u8>,
+			channel_id: Vec<u8>,
+			start_after: Option<u64>,
+			limit: u32,
+		) -> Vec<u64>;
+
+		/// The `sequences` sent by the counterparty of `channel_id` of `port_id` that are not
+		/// received yet.
+		fn unreceived_packets(port_id: Vec<u8>, channel_id: Vec<u8>, sequences: Vec<u64>) -> Vec<u64>;
+
+		/// The `sequences` sent on `channel_id` of `port_id` whose acknowledgement is not received
+		/// yet.
+		fn unreceived_acks(port_id: Vec<u8>, channel_id: Vec<u8>, sequences: Vec<u64>) -> Vec<u64>;
+	}
+}
@@ -0,0 +1,144 @@
+//! Storage wrappers bounding the encoding of ibc-rs types, which have no `MaxEncodedLen`.
+//!
+//! A [`Bounded`] value is encoded as the value it wraps, so a storage item can move to it without
+//! migrating its entries; the entries longer than the bound no longer decode.
+use codec::{Decode, Encode, EncodeLike, Input, MaxEncodedLen, Output};
+use core::{fmt, marker::PhantomData, ops::Deref};
+use frame_support::traits::{ConstU32, Get};
+use ibc::core::ics24_host::{identifier::ConnectionId, path::ClientStatePath};
+use scale_info::{Type, TypeInfo};
+
+/// The longest encoding of a client, connection or channel identifier: at most 64 characters by
+/// ICS-24, and their length.
+pub type MaxIdentifierLen = ConstU32<66>;
+
+/// The longest encoding of a port identifier and a channel identifier: at most 128 and 64
+/// characters by ICS-24, and their lengths.
+pub type MaxPortChannelLen = ConstU32<196>;
+
+/// `V`, whose encoding is at most `S` bytes long.
+pub struct Bounded<V, S>(V, PhantomData<S>);
+
+impl<V: Encode, S: Get<u32>> Bounded<V, S> {
+	/// `value`, unless its encoding is longer than `S`.
+	pub fn try_new(value: V) -> Result<Self, V> {
+		if value.encoded_size() > S::get() as usize {
+			return Err(value)
+		}
+		Ok(Self(value, PhantomData))
+	}
+}
+
+impl<V, S> Bounded<V, S> {
+	pub fn into_inner(self) -> V {
+		self.0
+	}
+}
+
+impl<V, S> Deref for Bounded<V, S> {
+	type Target = V;
+
+	fn deref(&self) -> &V {
+		&self.0
+	}
+}
+
+impl<V: Clone, S> Clone for Bounded<V, S> {
+	fn clone(&self) -> Self {
+		Self(self.0.clone(), PhantomData)
+	}
+}
+
+impl<V: PartialEq, S> PartialEq for Bounded<V, S> {
+	fn eq(&self, other: &Self) -> bool {
+		self.0 == other.0
+	}
+}
+
+impl<V: Eq, S> Eq for Bounded<V, S> {}
+
+impl<V: fmt::Debug, S> fmt::Debug for Bounded<V, S> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.0.fmt(f)
+	}
+}
+
+impl<V: Encode, S> Encode for Bounded<V, S> {
+	fn size_hint(&self) -> usize {
+		self.0.size_hint()
+	}
+
+	fn encode_to<O: Output + ?Sized>(&self, dest: &mut O) {
+		self.0.encode_to(dest)
+	}
+
+	fn encoded_size(&self) -> usize {
+		self.0.encoded_size()
+	}
+}
+
+impl<V: Encode, S> EncodeLike for Bounded<V, S> {}
+
+/// An input counting the bytes read from it
+struct CountedInput<'a, I> {
+	input: &'a mut I,
+	read: usize,
+}
+
+impl<I: Input> Input for CountedInput<'_, I> {
+	fn remaining_len(&mut self) -> Result<Option<usize>, codec::Error> {
+		self.input.remaining_len()
+	}
+
+	fn read(&mut self, into: &mut [u8]) -> Result<(), codec::Error> {
+		self.input.read(into)?;
+		self.read = self.read.saturating_add(into.len());
+		Ok(())
+	}
+
+	fn read_byte(&mut self) -> Result<u8, codec::Error> {
+		let byte = self.input.read_byte()?;
+		self.read = self.read.saturating_add(1);
+		Ok(byte)
+	}
+
+	fn descend_ref(&mut self) -> Result<(), codec::Error> {
+		self.input.descend_ref()
+	}
+
+	fn ascend_ref(&mut self) {
+		self.input.ascend_ref()
+	}
+}
+
+impl<V: Decode, S: Get<u32>> Decode for Bounded<V, S> {
+	/// Fails if the encoding of the value is longer than `S`.
+	fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+		let mut input = CountedInput { input, read: 0 };
+		let value = V::decode(&mut input)?;
+		if input.read > S::get() as usize {
+			return Err("value longer than its bound".into())
+		}
+		Ok(Self(value, PhantomData))
+	}
+}
+
+impl<V: Encode, S: Get<u32>> MaxEncodedLen for Bounded<V, S> {
+	fn max_encoded_len() -> usize {
+		S::get() as usize
+	}
+}
+
+impl<V: TypeInfo + 'static, S: 'static> TypeInfo for Bounded<V, S> {
+	type Identity = V;
+
+	fn type_info() -> Type {
+		V::type_info()
+	}
+}
+
+// the keys of the bounded storage items are passed as the values they wrap
+impl<S> EncodeLike<Bounded<ClientStatePath, S>> for ClientStatePath {}
+impl<S> EncodeLike<Bounded<ClientStatePath, S>> for &ClientStatePath {}
+impl<S> EncodeLike<Bounded<ConnectionId, S>> for ConnectionId {}
+impl<S> EncodeLike<Bounded<ConnectionId, S>> for &ConnectionId {}
@@ -1,9 +1,11 @@
 use crate::{
-	context::Context, Acknowledgements, ChannelCounter, Channels, ChannelsConnection,
-	ClientProcessedHeights, ClientProcessedTimes, Config, NextSequenceAck, NextSequenceRecv,
-	NextSequenceSend, PacketCommitment as PacketCommitStore, PacketReceipt,
+	bounded::Bounded, context::Context, packets::SequenceKey, Acknowledgements,
+	ChannelAcknowledgements, ChannelCommitments, ChannelCounter, Channels, ChannelsConnection,
+	ClientProcessedHeights, ClientProcessedTimes, Config, DeferredAcknowledgements,
+	NextSequenceAck, NextSequenceRecv, NextSequenceSend, PacketCommitment as PacketCommitStore,
+	PacketReceipt,
 };
-use alloc::{format, string::ToString, vec};
+use alloc::{format, string::ToString};
 use core::time::Duration;
 use ibc::{
 	core::{
@@ -57,7 +59,8 @@ where
 	}
 
 	fn connection_channels(cid: &ConnectionId) -> Result<Vec<(PortId, ChannelId)>, ChannelError> {
-		<ChannelsConnection<T>>::get(&cid)
+		<ChannelsConnection<T>>::get(cid)
+			.map(|channels| channels.into_iter().map(Bounded::into_inner).collect())
 			.ok_or(ChannelError::ConnectionNotOpen { connection_id: cid.clone() })
 	}
 
@@ -222,6 +225,11 @@ impl<T: Config> ChannelKeeperInterface for Context<T> {
 		sequence: Sequence,
 		commitment: PacketCommitment,
 	) -> Result<(), PacketError> {
+		<ChannelCommitments<T>>::insert(
+			(port_id.clone(), channel_id.clone()),
+			SequenceKey::from(sequence),
+			(),
+		);
 		<PacketCommitStore<T>>::insert(
 			CommitmentsPath { port_id, channel_id, sequence },
 			commitment,
@@ -240,6 +248,10 @@ impl<T: Config> ChannelKeeperInterface for Context<T> {
 			channel_id: channel_id.clone(),
 			sequence: seq.clone(),
 		});
+		<ChannelCommitments<T>>::remove(
+			(port_id.clone(), channel_id.clone()),
+			SequenceKey::from(*seq),
+		);
 
 		Ok(())
 	}
@@ -261,7 +273,17 @@ impl<T: Config> ChannelKeeperInterface for Context<T> {
 		sequence: Sequence,
 		ack_commitment: AcknowledgementCommitment,
 	) -> Result<(), PacketError> {
-		<Acknowledgements<T>>::insert(AcksPath { port_id, channel_id, sequence }, ack_commitment);
+		let acks_path = AcksPath { port_id, channel_id, sequence };
+		// the application acknowledges the packet later on
+		if <DeferredAcknowledgements<T>>::take(&acks_path).is_some() {
+			return Ok(())
+		}
+		<ChannelAcknowledgements<T>>::insert(
+			(acks_path.port_id.clone(), acks_path.channel_id.clone()),
+			SequenceKey::from(acks_path.sequence),
+			(),
+		);
+		<Acknowledgements<T>>::insert(acks_path, ack_commitment);
 
 		Ok(())
 	}
@@ -276,6 +298,27 @@ impl<T: Config> ChannelKeeperInterface for Context<T> {
 			channel_id: channel_id.clone(),
 			sequence: sequence.clone(),
 		});
+		<ChannelAcknowledgements<T>>::remove(
+			(port_id.clone(), channel_id.clone()),
+			SequenceKey::from(*sequence),
+		);
+
+		Ok(())
+	}
+
+	fn defer_packet_acknowledgement(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		sequence: &Sequence,
+	) -> Result<(), PacketError> {
+		<DeferredAcknowledgements<T>>::insert(
+			AcksPath {
+				port_id: port_id.clone(),
+				channel_id: channel_id.clone(),
+				sequence: sequence.clone(),
+			},
+			(),
+		);
 
 		Ok(())
 	}
@@ -285,22 +328,16 @@ impl<T: Config> ChannelKeeperInterface for Context<T> {
 		port_id: PortId,
 		channel_id: ChannelId,
 	) -> Result<(), ChannelError> {
-		if <ChannelsConnection<T>>::contains_key(&conn_id) {
-			let _ =
-				<ChannelsConnection<T>>::try_mutate(&conn_id, |val| -> Result<(), ChannelError> {
-					if let Some(value) = val {
-						value.push((port_id, channel_id));
-					}
-					Ok(())
-				})
-				.map_err(|e| ChannelError::Other {
-					description: format!("store connection channels failed: {:?}", e),
-				});
-		} else {
-			<ChannelsConnection<T>>::insert(conn_id, vec![(port_id, channel_id)]);
-		}
-
-		Ok(())
+		let channel = Bounded::try_new((port_id, channel_id)).map_err(|_| ChannelError::Other {
+			description: format!("channel identifiers too long on connection {}", conn_id),
+		})?;
+		<ChannelsConnection<T>>::try_mutate(&conn_id, |channels| {
+			channels.get_or_insert_with(Default::default).try_push(channel).map_err(|_| {
+				ChannelError::Other {
+					description: format!("too many channels on connection {}", conn_id),
+				}
+			})
+		})
 	}
 
 	/// Stores the given channel_end at a path associated with the port_id and channel_id.
@@ -6,6 +6,7 @@ pub use alloc::{
 	format,
 	string::{String, ToString},
 };
+use frame_support::BoundedVec;
 use ibc::{
 	clients::ics07_tendermint::{
 		client_state::ClientState as Ics07ClientState,
@@ -298,6 +299,9 @@ impl<T: Config> ClientKeeper for Context<T> {
 		let data = client_state.encode_vec().map_err(|e| ClientError::Other {
 			description: format!("Encode ClientState Failed: {:?}", e),
 		})?;
+		let data = BoundedVec::<u8, T::MaxClientStateSize>::try_from(data).map_err(|_| {
+			ClientError::Other { description: format!("ClientState of {} too large", client_id) }
+		})?;
 
 		<ClientStates<T>>::insert(ClientStatePath(client_id), data);
 		Ok(())
@@ -312,6 +316,10 @@ impl<T: Config> ClientKeeper for Context<T> {
 		let consensus_state = consensus_state.encode_vec().map_err(|e| ClientError::Other {
 			description: format!("Encode ConsensusStates failed: {:?}", e),
 		})?;
+		let consensus_state = BoundedVec::<u8, T::MaxClientStateSize>::try_from(consensus_state)
+			.map_err(|_| ClientError::Other {
+				description: format!("ConsensusState of {} too large", client_id),
+			})?;
 
 		<ConsensusStates<T>>::insert(
 			ClientConsensusStatePath {
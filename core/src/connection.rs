@@ -1,5 +1,5 @@
 use crate::{
-	context::Context, Config, ConnectionClient, ConnectionCounter, Connections, OldHeight,
+	context::Context, ClientConnections, Config, ConnectionCounter, Connections, OldHeight,
 };
 pub use alloc::{
 	format,
@@ -104,7 +104,7 @@ impl<T: Config> ConnectionKeeper for Context<T> {
 		connection_id: ConnectionId,
 		client_id: ClientId,
 	) -> Result<(), ConnectionError> {
-		<ConnectionClient<T>>::insert(client_id, connection_id);
+		<ClientConnections<T>>::insert(client_id, connection_id, ());
 
 		Ok(())
 	}
@@ -0,0 +1,238 @@
+//! Genesis IBC state.
+//!
+//! [`GenesisConfig`] seeds the store with clients, connections and channels established
+//! elsewhere, e.g. to start a test network or a fork with open channels. States and ends are
+//! given protobuf-encoded, as relayers and `ibc-go` exchange them, and are checked for consistency
+//! before they are stored: every consensus state and connection must belong to a client, every
+//! channel to a connection, and every sequence to a channel. The identifier counters are set past
+//! the identifiers in use, and the consensus states count as processed at the genesis block, so
+//! proofs against them have passed the delay period of their connections.
+//!
+//! [`Pallet::export_genesis`] dumps the live clients, connections and channels in the same form,
+//! to be put into a chain spec. Packets in flight are not exported.
+use crate::*;
+use ibc::{
+	clients::ics07_tendermint::{
+		client_state::ClientState as Ics07ClientState,
+		consensus_state::ConsensusState as Ics07ConsensusState,
+	},
+	mock::{client_state::MockClientState, consensus_state::MockConsensusState},
+};
+use ibc_proto::{
+	ibc::core::{
+		channel::v1::Channel as RawChannel, connection::v1::ConnectionEnd as RawConnectionEnd,
+	},
+	protobuf::Protobuf,
+};
+use sp_runtime::traits::UniqueSaturatedInto;
+use sp_std::str::FromStr;
+
+/// Check that the client state or consensus state `data` decodes for `client_type`.
+fn check_client_data(client_type: &str, data: &[u8], consensus: bool) -> Result<(), String> {
+	let result = match (client_type, consensus) {
+		(TENDERMINT_CLIENT_TYPE, false) =>
+			<Ics07ClientState as Protobuf<Any>>::decode_vec(data).map(|_| ()),
+		(TENDERMINT_CLIENT_TYPE, true) =>
+			<Ics07ConsensusState as Protobuf<Any>>::decode_vec(data).map(|_| ()),
+		(MOCK_CLIENT_TYPE, false) =>
+			<MockClientState as Protobuf<Any>>::decode_vec(data).map(|_| ()),
+		(MOCK_CLIENT_TYPE, true) =>
+			<MockConsensusState as Protobuf<Any>>::decode_vec(data).map(|_| ()),
+		_ => return Err(format!("unknown client type {}", client_type)),
+	};
+	result.map_err(|e| format!("invalid {} state: {}", client_type, e))
+}
+
+/// The counter value past the identifier `id`, e.g. `4` for `channel-3`.
+fn next_index(id: &str) -> Result<u64, String> {
+	id.rsplit('-')
+		.next()
+		.and_then(|index| index.parse::<u64>().ok())
+		.and_then(|index| index.checked_add(1))
+		.ok_or(format!("identifier {} has no index", id))
+}
+
+/// Check `config` and store it.
+pub(crate) fn build<T: Config>(config: &GenesisConfig) -> Result<(), String> {
+	let mut client_types = Vec::new();
+	let mut client_counter = 0;
+	for (client_id, client_type, client_state) in &config.clients {
+		let client_id = ClientId::from_str(client_id).map_err(|e| e.to_string())?;
+		check_client_data(client_type, client_state, false)?;
+		let client_state = BoundedVec::<u8, T::MaxClientStateSize>::try_from(client_state.clone())
+			.map_err(|_| format!("client state of {} too large", client_id))?;
+		ensure!(
+			!client_types.iter().any(|(id, _)| id == &client_id),
+			format!("duplicate client {}", client_id)
+		);
+		client_counter = client_counter.max(next_index(client_id.as_str())?);
+		<Clients<T>>::insert(
+			ClientTypePath(client_id.clone()),
+			ClientType::new(client_type.clone()),
+		);
+		<ClientStates<T>>::insert(ClientStatePath(client_id.clone()), client_state);
+		client_types.push((client_id, client_type.as_str()));
+	}
+	<ClientCounter<T>>::put(client_counter);
+
+	// the host height of the genesis block, which is not a valid height
+	let block_number: u64 = <frame_system::Pallet<T>>::block_number().unique_saturated_into();
+	let processed_height =
+		Height::new(T::ChainVersion::get(), block_number.max(1)).map_err(|e| e.to_string())?;
+	let processed_time = T::TimeProvider::now().as_nanos() as u64;
+	for (client_id, revision_number, revision_height, consensus_state) in &config.consensus_states {
+		let client_id = ClientId::from_str(client_id).map_err(|e| e.to_string())?;
+		let (_, client_type) = client_types
+			.iter()
+			.find(|(id, _)| id == &client_id)
+			.ok_or(format!("consensus state of unknown client {}", client_id))?;
+		check_client_data(client_type, consensus_state, true)?;
+		let consensus_state =
+			BoundedVec::<u8, T::MaxClientStateSize>::try_from(consensus_state.clone())
+				.map_err(|_| format!("consensus state of {} too large", client_id))?;
+		let height = Height::new(*revision_number, *revision_height).map_err(|e| e.to_string())?;
+		<ClientProcessedTimes<T>>::insert(&client_id, height, processed_time);
+		<ClientProcessedHeights<T>>::insert(&client_id, height, processed_height);
+		<ConsensusStates<T>>::insert(
+			ClientConsensusStatePath {
+				client_id,
+				epoch: *revision_number,
+				height: *revision_height,
+			},
+			consensus_state,
+		);
+	}
+
+	let mut connection_counter = 0;
+	for (connection_id, connection_end) in &config.connections {
+		let connection_id = ConnectionId::from_str(connection_id).map_err(|e| e.to_string())?;
+		let connection_end =
+			<ConnectionEnd as Protobuf<RawConnectionEnd>>::decode_vec(connection_end)
+				.map_err(|e| format!("invalid connection {}: {}", connection_id, e))?;
+		let client_id = connection_end.client_id().clone();
+		ensure!(
+			client_types.iter().any(|(id, _)| id == &client_id),
+			format!("connection {} of unknown client {}", connection_id, client_id)
+		);
+		ensure!(
+			!<Connections<T>>::contains_key(ConnectionsPath(connection_id.clone())),
+			format!("duplicate connection {}", connection_id)
+		);
+		connection_counter = connection_counter.max(next_index(connection_id.as_str())?);
+		<Connections<T>>::insert(ConnectionsPath(connection_id.clone()), connection_end);
+		<ClientConnections<T>>::insert(client_id, connection_id, ());
+	}
+	<ConnectionCounter<T>>::put(connection_counter);
+
+	let mut channel_counter = 0;
+	for (port_id, channel_id, channel_end) in &config.channels {
+		let port_id = PortId::from_str(port_id).map_err(|e| e.to_string())?;
+		let channel_id = ChannelId::from_str(channel_id).map_err(|e| e.to_string())?;
+		let channel_end = <ChannelEnd as Protobuf<RawChannel>>::decode_vec(channel_end)
+			.map_err(|e| format!("invalid channel {}: {}", channel_id, e))?;
+		let connection_id = channel_end
+			.connection_hops()
+			.first()
+			.cloned()
+			.ok_or(format!("channel {} without connection", channel_id))?;
+		ensure!(
+			<Connections<T>>::contains_key(ConnectionsPath(connection_id.clone())),
+			format!("channel {} of unknown connection {}", channel_id, connection_id)
+		);
+		let path = ChannelEndsPath(port_id.clone(), channel_id.clone());
+		ensure!(!<Channels<T>>::contains_key(&path), format!("duplicate channel {}", channel_id));
+		channel_counter = channel_counter.max(next_index(channel_id.as_str())?);
+		<Channels<T>>::insert(path, channel_end);
+		let channel = Bounded::try_new((port_id, channel_id))
+			.map_err(|(_, channel_id)| format!("identifiers of {} too long", channel_id))?;
+		<ChannelsConnection<T>>::try_mutate(&connection_id, |channels| {
+			channels
+				.get_or_insert_with(Default::default)
+				.try_push(channel)
+				.map_err(|_| format!("too many channels on connection {}", connection_id))
+		})?;
+	}
+	<ChannelCounter<T>>::put(channel_counter);
+
+	for (port_id, channel_id, next_send, next_recv, next_ack) in &config.sequences {
+		let port_id = PortId::from_str(port_id).map_err(|e| e.to_string())?;
+		let channel_id = ChannelId::from_str(channel_id).map_err(|e| e.to_string())?;
+		ensure!(
+			<Channels<T>>::contains_key(ChannelEndsPath(port_id.clone(), channel_id.clone())),
+			format!("sequences of unknown channel {}", channel_id)
+		);
+		ensure!(
+			next_ack <= next_send,
+			format!("next ack sequence of {} beyond its next send sequence", channel_id)
+		);
+		<NextSequenceSend<T>>::insert(
+			SeqSendsPath(port_id.clone(), channel_id.clone()),
+			Sequence::from(*next_send),
+		);
+		<NextSequenceRecv<T>>::insert(
+			SeqRecvsPath(port_id.clone(), channel_id.clone()),
+			Sequence::from(*next_recv),
+		);
+		<NextSequenceAck<T>>::insert(SeqAcksPath(port_id, channel_id), Sequence::from(*next_ack));
+	}
+
+	Ok(())
+}
+
+impl<T: Config> Pallet<T> {
+	/// The clients, connections and channels in the store, as a [`GenesisConfig`].
+	pub fn export_genesis() -> Result<GenesisConfig, String> {
+		let mut config = GenesisConfig::default();
+
+		for (ClientTypePath(client_id), client_type) in <Clients<T>>::iter() {
+			let client_state = <ClientStates<T>>::get(ClientStatePath(client_id.clone()))
+				.ok_or(format!("client {} without state", client_id))?;
+			config.clients.push((
+				client_id.to_string(),
+				client_type.as_str().to_string(),
+				client_state.into_inner(),
+			));
+		}
+		for (path, consensus_state) in <ConsensusStates<T>>::iter() {
+			config.consensus_states.push((
+				path.client_id.to_string(),
+				path.epoch,
+				path.height,
+				consensus_state.into_inner(),
+			));
+		}
+
+		for (ConnectionsPath(connection_id), connection_end) in <Connections<T>>::iter() {
+			let connection_end =
+				<ConnectionEnd as Protobuf<RawConnectionEnd>>::encode_vec(&connection_end)
+					.map_err(|e| e.to_string())?;
+			config.connections.push((connection_id.to_string(), connection_end));
+		}
+
+		for (ChannelEndsPath(port_id, channel_id), channel_end) in <Channels<T>>::iter() {
+			let encoded = <ChannelEnd as Protobuf<RawChannel>>::encode_vec(&channel_end)
+				.map_err(|e| e.to_string())?;
+			config.channels.push((port_id.to_string(), channel_id.to_string(), encoded));
+
+			let next_send =
+				<NextSequenceSend<T>>::get(SeqSendsPath(port_id.clone(), channel_id.clone()));
+			let next_recv =
+				<NextSequenceRecv<T>>::get(SeqRecvsPath(port_id.clone(), channel_id.clone()));
+			let next_ack =
+				<NextSequenceAck<T>>::get(SeqAcksPath(port_id.clone(), channel_id.clone()));
+			if let (Some(next_send), Some(next_recv), Some(next_ack)) =
+				(next_send, next_recv, next_ack)
+			{
+				config.sequences.push((
+					port_id.to_string(),
+					channel_id.to_string(),
+					next_send.into(),
+					next_recv.into(),
+					next_ack.into(),
+				));
+			}
+		}
+
+		Ok(config)
+	}
+}
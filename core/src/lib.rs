@@ -39,18 +39,26 @@ use ibc::{
 	},
 	events::IbcEvent,
 };
-use pallet_ibc_utils::module::AddModule;
+use pallet_ibc_utils::{migration::Cursor, module::AddModule};
 use sp_std::{fmt::Debug, vec, vec::Vec};
 
+pub mod bounded;
 pub mod channel;
 pub mod client;
 pub mod connection;
 pub mod context;
 pub mod errors;
+pub mod genesis;
+pub mod migrations;
+pub mod packets;
 pub mod port;
 pub mod routing;
 
 pub use crate::context::Context;
+use crate::{
+	bounded::{Bounded, MaxIdentifierLen, MaxPortChannelLen},
+	packets::SequenceKey,
+};
 pub use alloc::{
 	format,
 	string::{String, ToString},
@@ -98,20 +106,44 @@ pub mod pallet {
 
 		type IbcModule: AddModule;
 
+		/// The maximum size of an encoded client state or consensus state
+		#[pallet::constant]
+		type MaxClientStateSize: Get<u32>;
+
+		/// The maximum number of channels of a connection
+		#[pallet::constant]
+		type MaxChannelsPerConnection: Get<u32>;
+
+		/// The maximum size of an encoded IBC event kept in `IbcEvents`
+		#[pallet::constant]
+		type MaxEventSize: Get<u32>;
+
 		/// benchmarking weight info
 		type WeightInfo: WeightInfo<Self>;
 	}
 
+	/// The current storage version.
+	const STORAGE_VERSION: StorageVersion = StorageVersion::new(4);
+
+	/// The storage items keyed or valued by ibc-rs types, which have no `MaxEncodedLen`, are
+	/// `unbounded` unless the types are wrapped in [`Bounded`]; their identifiers are bounded by
+	/// ibc-rs validation.
 	#[pallet::pallet]
-	#[pallet::without_storage_info]
+	#[pallet::storage_version(STORAGE_VERSION)]
 	pub struct Pallet<T>(_);
 
 	#[pallet::storage]
 	/// Key: ClientStatePath
 	/// value: ClientState
-	pub type ClientStates<T: Config> = StorageMap<_, Blake2_128Concat, ClientStatePath, Vec<u8>>;
+	pub type ClientStates<T: Config> = StorageMap<
+		_,
+		Blake2_128Concat,
+		Bounded<ClientStatePath, MaxIdentifierLen>,
+		BoundedVec<u8, T::MaxClientStateSize>,
+	>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key1: client_id
 	/// key2: height
 	/// value: timestamp
@@ -119,6 +151,7 @@ pub mod pallet {
 		StorageDoubleMap<_, Blake2_128Concat, ClientId, Blake2_128Concat, Height, u64>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key1: client_id
 	/// key2: height
 	/// value: host_height
@@ -126,18 +159,25 @@ pub mod pallet {
 		StorageDoubleMap<_, Blake2_128Concat, ClientId, Blake2_128Concat, Height, Height>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: ClientConsensusStatePath
 	/// value: ConsensusState
-	pub type ConsensusStates<T: Config> =
-		StorageMap<_, Blake2_128Concat, ClientConsensusStatePath, Vec<u8>>;
+	pub type ConsensusStates<T: Config> = StorageMap<
+		_,
+		Blake2_128Concat,
+		ClientConsensusStatePath,
+		BoundedVec<u8, T::MaxClientStateSize>,
+	>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: ConnectionsPath
 	/// value: ConnectionEnd
 	pub type Connections<T: Config> =
 		StorageMap<_, Blake2_128Concat, ConnectionsPath, ConnectionEnd>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: CHannelEndsPath
 	/// value: ChannelEnd
 	pub type Channels<T: Config> = StorageMap<_, Blake2_128Concat, ChannelEndsPath, ChannelEnd>;
@@ -145,31 +185,54 @@ pub mod pallet {
 	#[pallet::storage]
 	/// key: connection_id
 	/// value: Vec<(port_id, channel_id)>
-	pub type ChannelsConnection<T: Config> =
-		StorageMap<_, Blake2_128Concat, ConnectionId, Vec<(PortId, ChannelId)>>;
+	pub type ChannelsConnection<T: Config> = StorageMap<
+		_,
+		Blake2_128Concat,
+		Bounded<ConnectionId, MaxIdentifierLen>,
+		BoundedVec<Bounded<(PortId, ChannelId), MaxPortChannelLen>, T::MaxChannelsPerConnection>,
+	>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// Key: SeqSendsPath
 	/// value: sequence
 	pub type NextSequenceSend<T: Config> = StorageMap<_, Blake2_128Concat, SeqSendsPath, Sequence>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: SeqRecvsPath
 	/// value: sequence
 	pub type NextSequenceRecv<T: Config> = StorageMap<_, Blake2_128Concat, SeqRecvsPath, Sequence>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: SeqAcksPath
 	/// value: sequence
 	pub type NextSequenceAck<T: Config> = StorageMap<_, Blake2_128Concat, SeqAcksPath, Sequence>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: AcksPath
 	/// value: hash of acknowledgement
 	pub type Acknowledgements<T: Config> =
 		StorageMap<_, Blake2_128Concat, AcksPath, IbcAcknowledgementCommitment>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
+	/// key1: (port_id, channel_id)
+	/// key2: sequence, big-endian so that the sequences of a channel are iterated in order
+	/// value: unit, index of the acknowledgements written for the packets received on a channel
+	pub type ChannelAcknowledgements<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, (PortId, ChannelId), Identity, SequenceKey, ()>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
+	/// key: AcksPath
+	/// value: unit, the application writes the acknowledgement of the packet asynchronously
+	pub type DeferredAcknowledgements<T: Config> = StorageMap<_, Blake2_128Concat, AcksPath, ()>;
+
+	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: ClientTypePath
 	/// value: ClientType
 	pub type Clients<T: Config> = StorageMap<_, Blake2_128Concat, ClientTypePath, ClientType>;
@@ -187,30 +250,58 @@ pub mod pallet {
 	pub type ChannelCounter<T: Config> = StorageValue<_, u64, ValueQuery>;
 
 	#[pallet::storage]
-	/// key: ClientId
-	/// value: ConnectionId
-	pub type ConnectionClient<T: Config> = StorageMap<_, Blake2_128Concat, ClientId, ConnectionId>;
+	#[pallet::unbounded]
+	/// key1: ClientId
+	/// key2: ConnectionId
+	/// value: unit, index of the connections of a client
+	pub type ClientConnections<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, ClientId, Blake2_128Concat, ConnectionId, ()>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: ReceiptsPath
 	/// value: receipt
 	pub type PacketReceipt<T: Config> = StorageMap<_, Blake2_128Concat, ReceiptsPath, Receipt>;
 
 	#[pallet::storage]
+	#[pallet::unbounded]
 	/// key: CommitmentsPath
 	/// value: hash of (timestamp, height, packet)
 	pub type PacketCommitment<T: Config> =
 		StorageMap<_, Blake2_128Concat, CommitmentsPath, IbcPacketCommitment>;
 
 	#[pallet::storage]
-	/// key: height
-	/// value: Ibc event height
-	pub type IbcEventStore<T: Config> = StorageMap<_, Blake2_128Concat, u64, IbcEvent>;
+	#[pallet::unbounded]
+	/// key1: (port_id, channel_id)
+	/// key2: sequence, big-endian so that the sequences of a channel are iterated in order
+	/// value: unit, index of the packet commitments of the packets sent over a channel
+	pub type ChannelCommitments<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, (PortId, ChannelId), Identity, SequenceKey, ()>;
+
+	#[pallet::storage]
+	/// key: (height, index of the event in the block)
+	/// value: Ibc event
+	pub type IbcEvents<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		u64,
+		Blake2_128Concat,
+		u32,
+		Bounded<IbcEvent, T::MaxEventSize>,
+	>;
+
+	#[pallet::storage]
+	/// Number of IBC events of the current block kept in `IbcEvents`, the index of the next one
+	pub type IbcEventCount<T: Config> = StorageValue<_, u32, ValueQuery>;
 
 	#[pallet::storage]
 	/// Previous host block height
 	pub type OldHeight<T: Config> = StorageValue<_, u64, ValueQuery>;
 
+	#[pallet::storage]
+	/// Cursor of the stepped migration in progress, see [`migrations`]
+	pub type MigrationCursor<T: Config> = StorageValue<_, Cursor>;
+
 	/// Substrate IBC event list
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
@@ -248,6 +339,48 @@ pub mod pallet {
 		Other,
 	}
 
+	/// The IBC state to start the chain with, see [`genesis`].
+	#[pallet::genesis_config]
+	#[derive(Default)]
+	pub struct GenesisConfig {
+		/// (client id, client type, protobuf `Any` of the client state)
+		pub clients: Vec<(String, String, Vec<u8>)>,
+		/// (client id, revision number, revision height, protobuf `Any` of the consensus state)
+		pub consensus_states: Vec<(String, u64, u64, Vec<u8>)>,
+		/// (connection id, protobuf connection end)
+		pub connections: Vec<(String, Vec<u8>)>,
+		/// (port id, channel id, protobuf channel end)
+		pub channels: Vec<(String, String, Vec<u8>)>,
+		/// (port id, channel id, next send sequence, next receive sequence, next ack sequence)
+		pub sequences: Vec<(String, String, u64, u64, u64)>,
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig {
+		fn build(&self) {
+			if let Err(e) = genesis::build::<T>(self) {
+				panic!("invalid IBC genesis: {}", e);
+			}
+		}
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(_n: BlockNumberFor<T>) -> Weight {
+			<IbcEventCount<T>>::kill();
+			T::DbWeight::get().writes(1)
+		}
+
+		fn on_idle(_n: BlockNumberFor<T>, remaining_weight: Weight) -> Weight {
+			Self::resume_migration(remaining_weight)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn try_state(_n: BlockNumberFor<T>) -> Result<(), &'static str> {
+			Self::do_try_state()
+		}
+	}
+
 	/// Dispatchable functions allows users to interact with the pallet and invoke state changes.
 	/// These functions materialize as "extrinsic", which are often compared to transactions.
 	/// Dispatch able functions must be annotated with a weight and must return a DispatchResult.
@@ -280,6 +413,216 @@ pub mod pallet {
 	}
 }
 
+/// The counter value the identifier `id`, e.g. `channel-3`, was created at.
+#[cfg(any(feature = "try-runtime", test))]
+fn id_index(id: &str) -> Option<u64> {
+	id.rsplit('-').next()?.parse().ok()
+}
+
+#[cfg(any(feature = "try-runtime", test))]
+impl<T: Config> Pallet<T> {
+	/// Check the consistency of the IBC storage: the references between clients, connections,
+	/// channels and packets, the sequences of channels and the identifier counters.
+	pub fn do_try_state() -> Result<(), &'static str> {
+		// the entries that no longer decode are removed by the migration to version 4
+		if !<MigrationCursor<T>>::exists() {
+			ensure!(
+				migrations::v4::undecodable_entries::<T>() == 0,
+				"storage entry that no longer decodes"
+			);
+		}
+		Self::try_state_clients()?;
+		Self::try_state_connections()?;
+		Self::try_state_channels()?;
+		Self::try_state_packets()
+	}
+
+	fn try_state_clients() -> Result<(), &'static str> {
+		let counter = <ClientCounter<T>>::get();
+		ensure!(<Clients<T>>::iter_keys().count() as u64 <= counter, "more clients than counted");
+		for ClientTypePath(client_id) in <Clients<T>>::iter_keys() {
+			ensure!(
+				id_index(client_id.as_str()).map_or(false, |index| index < counter),
+				"client id beyond the client counter"
+			);
+		}
+		for ClientStatePath(client_id) in <ClientStates<T>>::iter_keys().map(Bounded::into_inner) {
+			ensure!(
+				<Clients<T>>::contains_key(ClientTypePath(client_id)),
+				"client state of a client without type"
+			);
+		}
+		for path in <ConsensusStates<T>>::iter_keys() {
+			ensure!(
+				<ClientStates<T>>::contains_key(ClientStatePath(path.client_id)),
+				"consensus state of a missing client"
+			);
+		}
+		Ok(())
+	}
+
+	fn try_state_connections() -> Result<(), &'static str> {
+		let counter = <ConnectionCounter<T>>::get();
+		ensure!(
+			<Connections<T>>::iter_keys().count() as u64 <= counter,
+			"more connections than counted"
+		);
+		for (ConnectionsPath(connection_id), connection_end) in <Connections<T>>::iter() {
+			ensure!(
+				id_index(connection_id.as_str()).map_or(false, |index| index < counter),
+				"connection id beyond the connection counter"
+			);
+			ensure!(
+				<ClientStates<T>>::contains_key(ClientStatePath(
+					connection_end.client_id().clone()
+				)),
+				"connection of a missing client"
+			);
+		}
+		for (client_id, connection_id) in <ClientConnections<T>>::iter_keys() {
+			let connection_end = <Connections<T>>::get(ConnectionsPath(connection_id))
+				.ok_or("client of a missing connection")?;
+			ensure!(connection_end.client_id() == &client_id, "connection of another client");
+		}
+		// the client index is filled by the migration to version 3
+		if !<MigrationCursor<T>>::exists() {
+			for (ConnectionsPath(connection_id), connection_end) in <Connections<T>>::iter() {
+				ensure!(
+					<ClientConnections<T>>::contains_key(connection_end.client_id(), connection_id),
+					"connection missing from the index of its client"
+				);
+			}
+		}
+		for (connection_id, channels) in <ChannelsConnection<T>>::iter() {
+			let connection_id = connection_id.into_inner();
+			ensure!(
+				<Connections<T>>::contains_key(ConnectionsPath(connection_id.clone())),
+				"channels of a missing connection"
+			);
+			for (port_id, channel_id) in channels.into_iter().map(Bounded::into_inner) {
+				let channel_end = <Channels<T>>::get(ChannelEndsPath(port_id, channel_id))
+					.ok_or("connection lists a missing channel")?;
+				ensure!(
+					channel_end.connection_hops().first() == Some(&connection_id),
+					"connection lists a channel of another connection"
+				);
+			}
+		}
+		Ok(())
+	}
+
+	fn try_state_channels() -> Result<(), &'static str> {
+		let counter = <ChannelCounter<T>>::get();
+		ensure!(<Channels<T>>::iter_keys().count() as u64 <= counter, "more channels than counted");
+		for (ChannelEndsPath(port_id, channel_id), channel_end) in <Channels<T>>::iter() {
+			ensure!(
+				id_index(channel_id.as_str()).map_or(false, |index| index < counter),
+				"channel id beyond the channel counter"
+			);
+			let connection_id =
+				channel_end.connection_hops().first().ok_or("channel without connection")?;
+			ensure!(
+				<Connections<T>>::contains_key(ConnectionsPath(connection_id.clone())),
+				"channel of a missing connection"
+			);
+			let next_send =
+				<NextSequenceSend<T>>::get(SeqSendsPath(port_id.clone(), channel_id.clone()));
+			let next_ack = <NextSequenceAck<T>>::get(SeqAcksPath(port_id, channel_id));
+			if let (Some(next_send), Some(next_ack)) = (next_send, next_ack) {
+				ensure!(next_ack <= next_send, "next ack sequence beyond the next send sequence");
+			}
+		}
+		Ok(())
+	}
+
+	fn try_state_packets() -> Result<(), &'static str> {
+		for CommitmentsPath { port_id, channel_id, sequence } in <PacketCommitment<T>>::iter_keys()
+		{
+			let channel_end =
+				<Channels<T>>::get(ChannelEndsPath(port_id.clone(), channel_id.clone()))
+					.ok_or("packet commitment of a missing channel")?;
+			let next_send =
+				<NextSequenceSend<T>>::get(SeqSendsPath(port_id.clone(), channel_id.clone()))
+					.ok_or("packet commitment of a channel without send sequence")?;
+			ensure!(sequence < next_send, "packet commitment of a sequence not sent");
+			if channel_end.ordering() == &ibc::core::ics04_channel::channel::Order::Ordered {
+				if let Some(next_ack) = <NextSequenceAck<T>>::get(SeqAcksPath(port_id, channel_id))
+				{
+					ensure!(
+						sequence >= next_ack,
+						"packet commitment of an acknowledged sequence on an ordered channel"
+					);
+				}
+			}
+		}
+
+		for ReceiptsPath { port_id, channel_id, .. } in <PacketReceipt<T>>::iter_keys() {
+			let channel_end = <Channels<T>>::get(ChannelEndsPath(port_id, channel_id))
+				.ok_or("packet receipt of a missing channel")?;
+			// ordered channels track the received packets with their next receive sequence
+			ensure!(
+				channel_end.ordering() == &ibc::core::ics04_channel::channel::Order::Unordered,
+				"packet receipt on an ordered channel"
+			);
+		}
+		for AcksPath { port_id, channel_id, sequence } in <Acknowledgements<T>>::iter_keys() {
+			let channel_end =
+				<Channels<T>>::get(ChannelEndsPath(port_id.clone(), channel_id.clone()))
+					.ok_or("acknowledgement of a missing channel")?;
+			if channel_end.ordering() == &ibc::core::ics04_channel::channel::Order::Ordered {
+				let next_recv = <NextSequenceRecv<T>>::get(SeqRecvsPath(port_id, channel_id))
+					.ok_or("acknowledgement of a channel without receive sequence")?;
+				ensure!(sequence < next_recv, "acknowledgement of a sequence not received");
+			} else {
+				ensure!(
+					<PacketReceipt<T>>::contains_key(ReceiptsPath {
+						port_id,
+						channel_id,
+						sequence
+					}),
+					"acknowledgement of a packet without receipt"
+				);
+			}
+		}
+
+		// the channel indexes are filled by the migration to version 2
+		if <MigrationCursor<T>>::exists() {
+			return Ok(())
+		}
+		ensure!(
+			<ChannelCommitments<T>>::iter_keys().count() ==
+				<PacketCommitment<T>>::iter_keys().count(),
+			"packet commitments missing from the channel index"
+		);
+		for ((port_id, channel_id), sequence) in <ChannelCommitments<T>>::iter_keys() {
+			ensure!(
+				<PacketCommitment<T>>::contains_key(CommitmentsPath {
+					port_id,
+					channel_id,
+					sequence: sequence.into()
+				}),
+				"channel index of a missing packet commitment"
+			);
+		}
+		ensure!(
+			<ChannelAcknowledgements<T>>::iter_keys().count() ==
+				<Acknowledgements<T>>::iter_keys().count(),
+			"acknowledgements missing from the channel index"
+		);
+		for ((port_id, channel_id), sequence) in <ChannelAcknowledgements<T>>::iter_keys() {
+			ensure!(
+				<Acknowledgements<T>>::contains_key(AcksPath {
+					port_id,
+					channel_id,
+					sequence: sequence.into()
+				}),
+				"channel index of a missing acknowledgement"
+			);
+		}
+		Ok(())
+	}
+}
+
 impl<T: Config> pallet_ibc_utils::Router for Pallet<T>
 where
 	u64: From<<T as pallet_timestamp::Config>::Moment>
@@ -305,6 +648,20 @@ where
 				(events, logs, errors)
 			},
 		);
+		// the acknowledgement of a packet deferred by its application is neither stored nor
+		// reported until the application writes it
+		let events: Vec<_> = events
+			.into_iter()
+			.filter(|event| match event {
+				IbcEvent::WriteAcknowledgement(event) =>
+					<Acknowledgements<T>>::contains_key(AcksPath {
+						port_id: event.dst_port_id().clone(),
+						channel_id: event.dst_channel_id().clone(),
+						sequence: *event.sequence(),
+					}),
+				_ => true,
+			})
+			.collect();
 		log::info!("🙅🙅 deliver ----> events: {:?}", events);
 		log::info!("🙅🙅 🔥 🔥deliver ----> logs: {:?}", logs);
 		log::info!("🙅🙅 ❌❌ deliver ----> errors: {:?}", errors);
@@ -314,9 +671,22 @@ where
 
 		let block_height = <frame_system::Pallet<T>>::block_number();
 
-		for event in events.clone() {
-			<IbcEventStore<T>>::insert(u64::from(block_height), event);
+		// the events are indexed from the last one kept in the block, by an earlier delivery
+		let mut index = <IbcEventCount<T>>::get();
+		for event in events.iter() {
+			match Bounded::try_new(event.clone()) {
+				Ok(event) => {
+					<IbcEvents<T>>::insert(u64::from(block_height), index, event);
+					index = index.saturating_add(1);
+				},
+				Err(event) => log::error!(
+					target: LOG_TARGET,
+					"❌ [deliver] event too large to be kept: {:?}",
+					event
+				),
+			}
 		}
+		<IbcEventCount<T>>::put(index);
 		Self::deposit_event(Event::IbcEvents { events });
 		if !errors.is_empty() {
 			Self::deposit_event(errors.into());
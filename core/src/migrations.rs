@@ -0,0 +1,547 @@
+//! Storage migrations of pallet-ibc.
+//!
+//! The migrations are [`SteppedMigration`]s: the runtime upgrade runs their first step and
+//! `on_idle` the following ones, as the weight left in the block allows, keeping the cursor in
+//! [`MigrationCursor`]. The storage version is set once the last step is done, so a migration
+//! still in progress is resumed rather than started again by a later upgrade, and the next
+//! migration, if any, is started from `on_idle` as well.
+use crate::*;
+use frame_support::traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion};
+use pallet_ibc_utils::migration::{run_steps, Cursor, StepFailed, StepResult, SteppedMigration};
+
+/// The items migrated per step.
+pub const MIGRATION_STEP: u32 = 64;
+
+impl<T: Config> Pallet<T> {
+	/// Record the outcome of a step of `M`: its cursor, or its storage version once it is done,
+	/// with an empty cursor to start the next migration. A failed migration, or one whose cursor
+	/// is too long to be kept, is stopped, to be started again by a later upgrade.
+	fn end_step<M: SteppedMigration>(result: StepResult) {
+		let result = result
+			.and_then(|cursor| cursor.map(Cursor::try_from).transpose().map_err(|_| StepFailed));
+		match result {
+			Ok(Some(cursor)) => <MigrationCursor<T>>::put(cursor),
+			Err(_) => {
+				log::error!(
+					target: LOG_TARGET,
+					"❌ [migration] migration to version {} failed",
+					M::TO_VERSION
+				);
+				<MigrationCursor<T>>::kill();
+			},
+			Ok(None) => {
+				StorageVersion::new(M::TO_VERSION).put::<Pallet<T>>();
+				log::info!(target: LOG_TARGET, "migrated storage to version {}", M::TO_VERSION);
+				if Self::current_storage_version() > M::TO_VERSION {
+					<MigrationCursor<T>>::put(Cursor::default());
+				} else {
+					<MigrationCursor<T>>::kill();
+				}
+			},
+		}
+	}
+
+	/// Run the steps of the migration in progress, if any, that fit in `remaining_weight`.
+	pub(crate) fn resume_migration(remaining_weight: Weight) -> Weight {
+		let cursor = match <MigrationCursor<T>>::get() {
+			Some(cursor) => cursor.into_inner(),
+			None => return T::DbWeight::get().reads(1),
+		};
+		let weight = match Self::on_chain_storage_version() {
+			version if version < 1 => {
+				let (cursor, weight) =
+					run_steps::<v1::MigrateToV1<T>>(cursor, MIGRATION_STEP, remaining_weight);
+				Self::end_step::<v1::MigrateToV1<T>>(cursor);
+				weight
+			},
+			version if version < 2 => {
+				let (cursor, weight) =
+					run_steps::<v2::MigrateToV2<T>>(cursor, MIGRATION_STEP, remaining_weight);
+				Self::end_step::<v2::MigrateToV2<T>>(cursor);
+				weight
+			},
+			version if version < 3 => {
+				let (cursor, weight) =
+					run_steps::<v3::MigrateToV3<T>>(cursor, MIGRATION_STEP, remaining_weight);
+				Self::end_step::<v3::MigrateToV3<T>>(cursor);
+				weight
+			},
+			version if version < 4 => {
+				let (cursor, weight) =
+					run_steps::<v4::MigrateToV4<T>>(cursor, MIGRATION_STEP, remaining_weight);
+				Self::end_step::<v4::MigrateToV4<T>>(cursor);
+				weight
+			},
+			_ => {
+				log::error!(target: LOG_TARGET, "❌ [migration] cursor without migration");
+				<MigrationCursor<T>>::kill();
+				Weight::zero()
+			},
+		};
+		weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+	}
+}
+
+/// Version 1 keeps every IBC event of a block in [`IbcEvents`], keyed by height and index.
+pub mod v1 {
+	use super::*;
+
+	/// The events by height before version 1, only the last event of each block.
+	#[frame_support::storage_alias]
+	pub(crate) type IbcEventStore<T: Config> =
+		StorageMap<Pallet<T>, Blake2_128Concat, u64, IbcEvent>;
+
+	/// Moves the events of the former `IbcEventStore` to [`IbcEvents`], as the first event of
+	/// their block.
+	pub struct MigrateToV1<T>(PhantomData<T>);
+
+	impl<T: Config> SteppedMigration for MigrateToV1<T> {
+		const TO_VERSION: u16 = 1;
+
+		/// Drains the former store from its start, so the cursor carries nothing.
+		fn step(_cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight) {
+			let events = <IbcEventStore<T>>::drain().take(limit as usize).collect::<Vec<_>>();
+			let migrated = events.len() as u64;
+			for (height, event) in events {
+				match Bounded::try_new(event) {
+					Ok(event) => <IbcEvents<T>>::insert(height, 0, event),
+					Err(event) => log::error!(
+						target: LOG_TARGET,
+						"❌ [migration] event of block {} too large to be kept: {:?}",
+						height,
+						event
+					),
+				}
+			}
+			let done = <IbcEventStore<T>>::iter_keys().next().is_none();
+			let weight = T::DbWeight::get().reads_writes(migrated + 1, migrated * 2);
+			(Ok(if done { None } else { Some(Vec::new()) }), weight)
+		}
+
+		fn max_step_weight(limit: u32) -> Weight {
+			T::DbWeight::get().reads_writes(limit as u64 + 1, limit as u64 * 2)
+		}
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV1<T> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T>::on_chain_storage_version() >= 1 || <MigrationCursor<T>>::exists() {
+				return T::DbWeight::get().reads(2)
+			}
+
+			let (cursor, weight) = Self::step(None, MIGRATION_STEP);
+			Pallet::<T>::end_step::<Self>(cursor);
+			weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+			use codec::Encode;
+
+			let events = (<IbcEventStore<T>>::iter_keys().count() +
+				<IbcEvents<T>>::iter_keys().count()) as u64;
+			Ok(events.encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(state: Vec<u8>) -> Result<(), &'static str> {
+			use codec::Decode;
+
+			let events = u64::decode(&mut &state[..]).map_err(|_| "invalid pre-upgrade state")?;
+			let legacy = <IbcEventStore<T>>::iter_keys().count() as u64;
+			ensure!(
+				legacy + <IbcEvents<T>>::iter_keys().count() as u64 == events,
+				"events lost by the migration"
+			);
+			ensure!(
+				legacy == 0 || <MigrationCursor<T>>::exists(),
+				"events left without migration in progress"
+			);
+			ensure!(
+				legacy > 0 || Pallet::<T>::on_chain_storage_version() >= 1,
+				"storage version not set"
+			);
+			Pallet::<T>::do_try_state()
+		}
+	}
+}
+
+/// Version 2 indexes the packet commitments and acknowledgements by channel, in
+/// [`ChannelCommitments`] and [`ChannelAcknowledgements`].
+pub mod v2 {
+	use super::*;
+	use frame_support::storage::KeyPrefixIterator;
+
+	/// The phases of the migration, leading its cursor.
+	const COMMITMENTS: u8 = 0;
+	const ACKNOWLEDGEMENTS: u8 = 1;
+
+	/// Fills [`ChannelCommitments`] from [`PacketCommitment`], then [`ChannelAcknowledgements`]
+	/// from [`Acknowledgements`].
+	pub struct MigrateToV2<T>(PhantomData<T>);
+
+	/// Index at most `limit` of `keys` with `index`. Returns the raw key of the last one indexed,
+	/// `None` once all are, and how many were.
+	fn index_keys<K>(
+		mut keys: KeyPrefixIterator<K>,
+		limit: u32,
+		index: impl Fn(K),
+	) -> (Option<Vec<u8>>, u64) {
+		let mut indexed = 0;
+		while indexed < limit as u64 {
+			match keys.next() {
+				Some(key) => index(key),
+				None => return (None, indexed),
+			}
+			indexed += 1;
+		}
+		(Some(keys.last_raw_key().to_vec()), indexed)
+	}
+
+	impl<T: Config> SteppedMigration for MigrateToV2<T> {
+		const TO_VERSION: u16 = 2;
+
+		/// The cursor is the phase followed by the raw key of the last item indexed, empty to
+		/// start from the first commitment.
+		fn step(cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight) {
+			let cursor = cursor.unwrap_or_default();
+			let (phase, last_key) = match cursor.split_first() {
+				Some((phase, last_key)) => (*phase, last_key.to_vec()),
+				None => (COMMITMENTS, Vec::new()),
+			};
+			let (cursor, indexed) = if phase == COMMITMENTS {
+				let keys = if last_key.is_empty() {
+					<PacketCommitment<T>>::iter_keys()
+				} else {
+					<PacketCommitment<T>>::iter_keys_from(last_key)
+				};
+				let (last_key, indexed) =
+					index_keys(keys, limit, |CommitmentsPath { port_id, channel_id, sequence }| {
+						<ChannelCommitments<T>>::insert(
+							(port_id, channel_id),
+							SequenceKey::from(sequence),
+							(),
+						)
+					});
+				let cursor = match last_key {
+					Some(last_key) => [&[COMMITMENTS][..], &last_key].concat(),
+					None => [ACKNOWLEDGEMENTS].to_vec(),
+				};
+				(Some(cursor), indexed)
+			} else {
+				let keys = if last_key.is_empty() {
+					<Acknowledgements<T>>::iter_keys()
+				} else {
+					<Acknowledgements<T>>::iter_keys_from(last_key)
+				};
+				let (last_key, indexed) =
+					index_keys(keys, limit, |AcksPath { port_id, channel_id, sequence }| {
+						<ChannelAcknowledgements<T>>::insert(
+							(port_id, channel_id),
+							SequenceKey::from(sequence),
+							(),
+						)
+					});
+				(last_key.map(|last_key| [&[ACKNOWLEDGEMENTS][..], &last_key].concat()), indexed)
+			};
+			(Ok(cursor), T::DbWeight::get().reads_writes(indexed + 1, indexed))
+		}
+
+		fn max_step_weight(limit: u32) -> Weight {
+			T::DbWeight::get().reads_writes(limit as u64 + 1, limit as u64)
+		}
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV2<T> {
+		/// Started from `on_idle` instead if the migration to version 1 is still in progress.
+		fn on_runtime_upgrade() -> Weight {
+			let version = Pallet::<T>::on_chain_storage_version();
+			if version != 1 || <MigrationCursor<T>>::exists() {
+				if version < 1 && !<MigrationCursor<T>>::exists() {
+					log::error!(target: LOG_TARGET, "❌ [migration] version 2 before version 1");
+				}
+				return T::DbWeight::get().reads(2)
+			}
+
+			let (cursor, weight) = Self::step(None, MIGRATION_STEP);
+			Pallet::<T>::end_step::<Self>(cursor);
+			weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+			use codec::Encode;
+
+			let packets = (
+				<PacketCommitment<T>>::iter_keys().count() as u64,
+				<Acknowledgements<T>>::iter_keys().count() as u64,
+			);
+			Ok(packets.encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(state: Vec<u8>) -> Result<(), &'static str> {
+			use codec::Decode;
+
+			let (commitments, acks) =
+				<(u64, u64)>::decode(&mut &state[..]).map_err(|_| "invalid pre-upgrade state")?;
+			if !<MigrationCursor<T>>::exists() {
+				ensure!(Pallet::<T>::on_chain_storage_version() >= 2, "storage version not set");
+				ensure!(
+					<ChannelCommitments<T>>::iter_keys().count() as u64 == commitments &&
+						<ChannelAcknowledgements<T>>::iter_keys().count() as u64 == acks,
+					"packets left out of the channel indexes"
+				);
+			}
+			Pallet::<T>::do_try_state()
+		}
+	}
+}
+
+/// Version 3 indexes every connection of a client in [`ClientConnections`].
+pub mod v3 {
+	use super::*;
+
+	/// The phases of the migration, leading its cursor.
+	const CONNECTIONS: u8 = 0;
+	const LEGACY: u8 = 1;
+
+	/// The connection of each client before version 3, only the last one opened.
+	#[frame_support::storage_alias]
+	pub(crate) type ConnectionClient<T: Config> =
+		StorageMap<Pallet<T>, Blake2_128Concat, ClientId, ConnectionId>;
+
+	/// Fills [`ClientConnections`] from [`Connections`], then removes the former
+	/// `ConnectionClient`.
+	pub struct MigrateToV3<T>(PhantomData<T>);
+
+	impl<T: Config> SteppedMigration for MigrateToV3<T> {
+		const TO_VERSION: u16 = 3;
+
+		/// The cursor is the phase followed, while indexing, by the raw key of the last connection
+		/// indexed, empty to start from the first connection. The former store is drained from
+		/// its start.
+		fn step(cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight) {
+			let cursor = cursor.unwrap_or_default();
+			let (phase, last_key) = match cursor.split_first() {
+				Some((phase, last_key)) => (*phase, last_key.to_vec()),
+				None => (CONNECTIONS, Vec::new()),
+			};
+			if phase == CONNECTIONS {
+				let mut connections = if last_key.is_empty() {
+					<Connections<T>>::iter()
+				} else {
+					<Connections<T>>::iter_from(last_key)
+				};
+				let mut indexed = 0;
+				while indexed < limit as u64 {
+					match connections.next() {
+						Some((ConnectionsPath(connection_id), connection_end)) =>
+							<ClientConnections<T>>::insert(
+								connection_end.client_id(),
+								connection_id,
+								(),
+							),
+						None => {
+							let weight = T::DbWeight::get().reads_writes(indexed + 1, indexed);
+							return (Ok(Some([LEGACY].to_vec())), weight)
+						},
+					}
+					indexed += 1;
+				}
+				let cursor = [&[CONNECTIONS][..], connections.last_raw_key()].concat();
+				(Ok(Some(cursor)), T::DbWeight::get().reads_writes(indexed + 1, indexed))
+			} else {
+				let removed = <ConnectionClient<T>>::drain().take(limit as usize).count() as u64;
+				let done = <ConnectionClient<T>>::iter_keys().next().is_none();
+				let weight = T::DbWeight::get().reads_writes(removed + 1, removed);
+				(Ok(if done { None } else { Some([LEGACY].to_vec()) }), weight)
+			}
+		}
+
+		fn max_step_weight(limit: u32) -> Weight {
+			T::DbWeight::get().reads_writes(limit as u64 + 1, limit as u64)
+		}
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV3<T> {
+		/// Started from `on_idle` instead if an earlier migration is still in progress.
+		fn on_runtime_upgrade() -> Weight {
+			let version = Pallet::<T>::on_chain_storage_version();
+			if version != 2 || <MigrationCursor<T>>::exists() {
+				if version < 2 && !<MigrationCursor<T>>::exists() {
+					log::error!(target: LOG_TARGET, "❌ [migration] version 3 before version 2");
+				}
+				return T::DbWeight::get().reads(2)
+			}
+
+			let (cursor, weight) = Self::step(None, MIGRATION_STEP);
+			Pallet::<T>::end_step::<Self>(cursor);
+			weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(_state: Vec<u8>) -> Result<(), &'static str> {
+			if !<MigrationCursor<T>>::exists() {
+				ensure!(Pallet::<T>::on_chain_storage_version() >= 3, "storage version not set");
+				ensure!(
+					<ConnectionClient<T>>::iter_keys().next().is_none(),
+					"former connections of clients left"
+				);
+			}
+			Pallet::<T>::do_try_state()
+		}
+	}
+}
+
+/// Version 4 bounds the encodings of [`ClientStates`], [`ChannelsConnection`] and [`IbcEvents`],
+/// see [`crate::bounded`].
+pub mod v4 {
+	use super::*;
+	use codec::DecodeAll;
+	use frame_support::storage::StoragePrefixedMap;
+	use pallet_ibc_utils::migration::remove_undecodable;
+
+	/// The storage items looked at in turn, leading the cursor.
+	const CLIENT_STATES: u8 = 0;
+	const CHANNELS_CONNECTION: u8 = 1;
+	const EVENTS: u8 = 2;
+
+	/// The length of a `Blake2_128Concat` hash, before the key it hashes
+	const HASH_LEN: usize = 16;
+
+	/// Whether `bytes` are the encoding of a `V`
+	fn decodes<V: Decode>(bytes: &[u8]) -> bool {
+		V::decode_all(&mut &bytes[..]).is_ok()
+	}
+
+	/// Whether the raw key of a map, without its prefix, and its value decode as `K` and `V`
+	fn entry_decodes<K: Decode, V: Decode>(key: &[u8], value: &[u8]) -> bool {
+		key.get(HASH_LEN..).map_or(false, decodes::<K>) && decodes::<V>(value)
+	}
+
+	/// The prefix of the storage item of `phase`, and whether its raw entries decode.
+	fn storage_item<T: Config>(phase: u8) -> Option<([u8; 32], fn(&[u8], &[u8]) -> bool)> {
+		match phase {
+			CLIENT_STATES => Some((
+				<ClientStates<T>>::final_prefix(),
+				entry_decodes::<
+					Bounded<ClientStatePath, MaxIdentifierLen>,
+					BoundedVec<u8, T::MaxClientStateSize>,
+				>,
+			)),
+			CHANNELS_CONNECTION => Some((
+				<ChannelsConnection<T>>::final_prefix(),
+				entry_decodes::<
+					Bounded<ConnectionId, MaxIdentifierLen>,
+					BoundedVec<
+						Bounded<(PortId, ChannelId), MaxPortChannelLen>,
+						T::MaxChannelsPerConnection,
+					>,
+				>,
+			)),
+			// the keys of the events are integers
+			EVENTS => Some((<IbcEvents<T>>::final_prefix(), |_: &[u8], value: &[u8]| {
+				decodes::<Bounded<IbcEvent, T::MaxEventSize>>(value)
+			})),
+			_ => None,
+		}
+	}
+
+	/// The number of entries of the bounded storage items that no longer decode.
+	#[cfg(any(feature = "try-runtime", test))]
+	pub fn undecodable_entries<T: Config>() -> u64 {
+		[CLIENT_STATES, CHANNELS_CONNECTION, EVENTS]
+			.into_iter()
+			.filter_map(storage_item::<T>)
+			.map(|(prefix, decodes)| {
+				pallet_ibc_utils::migration::count_undecodable(&prefix, decodes)
+			})
+			.sum()
+	}
+
+	/// Removes the entries of the bounded storage items that no longer decode, those longer than
+	/// their bound.
+	pub struct MigrateToV4<T>(PhantomData<T>);
+
+	impl<T: Config> SteppedMigration for MigrateToV4<T> {
+		const TO_VERSION: u16 = 4;
+
+		/// The cursor is the storage item looked at followed by the raw key of the last entry
+		/// looked at, empty to start from its first entry.
+		fn step(cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight) {
+			let cursor = cursor.unwrap_or_default();
+			let (phase, last_key) = match cursor.split_first() {
+				Some((phase, last_key)) => (*phase, last_key.to_vec()),
+				None => (CLIENT_STATES, Vec::new()),
+			};
+			let (prefix, decodes) = match storage_item::<T>(phase) {
+				Some(item) => item,
+				None => {
+					log::error!(target: LOG_TARGET, "❌ [migration] unknown storage item {}", phase);
+					return (Err(StepFailed), T::DbWeight::get().reads(1))
+				},
+			};
+			let (last_key, seen, removed) = remove_undecodable(&prefix, last_key, limit, decodes);
+			if removed > 0 {
+				log::warn!(
+					target: LOG_TARGET,
+					"removed {} entries of storage item {} that no longer decode",
+					removed,
+					phase
+				);
+			}
+			let cursor = match last_key {
+				Some(last_key) => Some([&[phase][..], &last_key].concat()),
+				None if phase < EVENTS => Some([phase + 1].to_vec()),
+				None => None,
+			};
+			(Ok(cursor), T::DbWeight::get().reads_writes(seen + 1, removed))
+		}
+
+		fn max_step_weight(limit: u32) -> Weight {
+			T::DbWeight::get().reads_writes(limit as u64 + 1, limit as u64)
+		}
+	}
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV4<T> {
+		/// Started from `on_idle` instead if an earlier migration is still in progress.
+		fn on_runtime_upgrade() -> Weight {
+			let version = Pallet::<T>::on_chain_storage_version();
+			if version != 3 || <MigrationCursor<T>>::exists() {
+				if version < 3 && !<MigrationCursor<T>>::exists() {
+					log::error!(target: LOG_TARGET, "❌ [migration] version 4 before version 3");
+				}
+				return T::DbWeight::get().reads(2)
+			}
+
+			let (cursor, weight) = Self::step(None, MIGRATION_STEP);
+			Pallet::<T>::end_step::<Self>(cursor);
+			weight.saturating_add(T::DbWeight::get().reads_writes(2, 2))
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+			use codec::Encode;
+
+			let undecodable = undecodable_entries::<T>();
+			if undecodable > 0 {
+				log::warn!(
+					target: LOG_TARGET,
+					"{} entries no longer decode and will be removed",
+					undecodable
+				);
+			}
+			Ok(undecodable.encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(_state: Vec<u8>) -> Result<(), &'static str> {
+			if !<MigrationCursor<T>>::exists() {
+				ensure!(Pallet::<T>::on_chain_storage_version() >= 4, "storage version not set");
+				ensure!(undecodable_entries::<T>() == 0, "entries that no longer decode left");
+			}
+			Pallet::<T>::do_try_state()
+		}
+	}
+}
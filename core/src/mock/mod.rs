@@ -81,8 +81,9 @@ impl frame_system::Config for Test {
 	type RuntimeOrigin = RuntimeOrigin;
 	/// Maximum number of block number to block hash mappings to keep (oldest pruned first).
 	type BlockHashCount = ();
-	/// The weight of database operations that the runtime can invoke.
-	type DbWeight = ();
+	/// The weight of database operations that the runtime can invoke, not zero so that the
+	/// stepped migrations can be tested against the weight left in a block.
+	type DbWeight = RocksDbWeight;
 	/// Version of the runtime.
 	type Version = ();
 	/// Converts a module to the index of the module in `construct_runtime!`.
@@ -151,6 +152,9 @@ impl pallet::Config for Test {
 	const IBC_COMMITMENT_PREFIX: &'static [u8] = b"Ibc";
 	type ChainVersion = ChainVersion;
 	type IbcModule = DefaultRouter;
+	type MaxClientStateSize = ConstU32<{ 64 * 1024 }>;
+	type MaxChannelsPerConnection = ConstU32<64>;
+	type MaxEventSize = ConstU32<{ 8 * 1024 }>;
 	type WeightInfo = ();
 }
 
@@ -0,0 +1,142 @@
+//! Queries of the packets of a channel, for relayers.
+//!
+//! The outstanding packet commitments and acknowledgements are listed from the
+//! [`ChannelCommitments`] and [`ChannelAcknowledgements`] indexes, so a query only reads the
+//! channel it is about. The indexes are keyed by [`SequenceKey`], so their pages come in ascending
+//! order of sequence.
+use crate::*;
+use ibc::core::{
+	ics04_channel::{channel::Order, packet::Sequence},
+	ics24_host::{
+		identifier::{ChannelId, PortId},
+		path::{ChannelEndsPath, CommitmentsPath, ReceiptsPath, SeqRecvsPath},
+	},
+};
+
+/// The sequence of a packet keying the indexes of a channel, encoded big-endian so that the
+/// `Identity`-hashed keys are iterated in ascending order of sequence.
+#[derive(
+	Clone,
+	Copy,
+	PartialEq,
+	Eq,
+	PartialOrd,
+	Ord,
+	RuntimeDebug,
+	Encode,
+	Decode,
+	MaxEncodedLen,
+	TypeInfo,
+)]
+pub struct SequenceKey([u8; 8]);
+
+impl From<Sequence> for SequenceKey {
+	fn from(sequence: Sequence) -> Self {
+		Self(u64::from(sequence).to_be_bytes())
+	}
+}
+
+impl From<SequenceKey> for Sequence {
+	fn from(key: SequenceKey) -> Self {
+		Sequence::from(u64::from_be_bytes(key.0))
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// At most `limit` sequences of the packets sent on `channel_id` of `port_id` whose commitment
+	/// is stored, following `start_after`, in ascending order.
+	pub fn packet_commitments(
+		port_id: PortId,
+		channel_id: ChannelId,
+		start_after: Option<Sequence>,
+		limit: u32,
+	) -> Vec<Sequence> {
+		let channel = (port_id, channel_id);
+		match start_after {
+			Some(sequence) => <ChannelCommitments<T>>::iter_key_prefix_from(
+				channel.clone(),
+				<ChannelCommitments<T>>::hashed_key_for(channel, SequenceKey::from(sequence)),
+			)
+			.take(limit as usize)
+			.map(Sequence::from)
+			.collect(),
+			None => <ChannelCommitments<T>>::iter_key_prefix(channel)
+				.take(limit as usize)
+				.map(Sequence::from)
+				.collect(),
+		}
+	}
+
+	/// At most `limit` sequences of the packets received on `channel_id` of `port_id` whose
+	/// acknowledgement is stored, following `start_after`, in ascending order.
+	pub fn packet_acknowledgements(
+		port_id: PortId,
+		channel_id: ChannelId,
+		start_after: Option<Sequence>,
+		limit: u32,
+	) -> Vec<Sequence> {
+		let channel = (port_id, channel_id);
+		match start_after {
+			Some(sequence) => <ChannelAcknowledgements<T>>::iter_key_prefix_from(
+				channel.clone(),
+				<ChannelAcknowledgements<T>>::hashed_key_for(channel, SequenceKey::from(sequence)),
+			)
+			.take(limit as usize)
+			.map(Sequence::from)
+			.collect(),
+			None => <ChannelAcknowledgements<T>>::iter_key_prefix(channel)
+				.take(limit as usize)
+				.map(Sequence::from)
+				.collect(),
+		}
+	}
+
+	/// The `sequences` of packets sent by the counterparty of `channel_id` of `port_id` that this
+	/// chain has not received yet.
+	pub fn unreceived_packets(
+		port_id: PortId,
+		channel_id: ChannelId,
+		sequences: Vec<Sequence>,
+	) -> Vec<Sequence> {
+		let ordered = <Channels<T>>::get(ChannelEndsPath(port_id.clone(), channel_id.clone()))
+			.map_or(false, |channel| channel.ordering() == &Order::Ordered);
+		if ordered {
+			let next_sequence_recv =
+				<NextSequenceRecv<T>>::get(SeqRecvsPath(port_id, channel_id)).unwrap_or_default();
+			sequences
+				.into_iter()
+				.filter(|sequence| *sequence >= next_sequence_recv)
+				.collect()
+		} else {
+			sequences
+				.into_iter()
+				.filter(|sequence| {
+					!<PacketReceipt<T>>::contains_key(ReceiptsPath {
+						port_id: port_id.clone(),
+						channel_id: channel_id.clone(),
+						sequence: *sequence,
+					})
+				})
+				.collect()
+		}
+	}
+
+	/// The `sequences` of packets sent on `channel_id` of `port_id` whose acknowledgement this
+	/// chain has not received yet, that is whose commitment is still stored.
+	pub fn unreceived_acks(
+		port_id: PortId,
+		channel_id: ChannelId,
+		sequences: Vec<Sequence>,
+	) -> Vec<Sequence> {
+		sequences
+			.into_iter()
+			.filter(|sequence| {
+				<PacketCommitment<T>>::contains_key(CommitmentsPath {
+					port_id: port_id.clone(),
+					channel_id: channel_id.clone(),
+					sequence: *sequence,
+				})
+			})
+			.collect()
+	}
+}
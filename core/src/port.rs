@@ -9,6 +9,7 @@ use ibc::{
 		ics26_routing::context::ModuleId,
 	},
 };
+use pallet_ibc_utils::module::AddModule;
 use sp_std::str::FromStr;
 
 impl<T: Config> PortReader for Context<T> {
@@ -16,7 +17,8 @@ impl<T: Config> PortReader for Context<T> {
 		match port_id.as_str() {
 			TRANSFER_PORT_ID => Ok(ModuleId::from_str(TRANSFER_MODULE_ID)
 				.map_err(|_| PortError::ImplementationSpecific)?),
-			_ => Err(PortError::ImplementationSpecific),
+			_ => T::IbcModule::lookup_module_by_port(port_id)
+				.ok_or(PortError::ImplementationSpecific),
 		}
 	}
 }
@@ -0,0 +1,61 @@
+use crate::{
+	mock::{new_test_ext, AccountId, RuntimeOrigin, System, Test},
+	tests::common::get_dummy_account_id,
+	IbcEventCount, IbcEvents, Pallet,
+};
+use frame_support::{assert_ok, traits::Hooks};
+use ibc::{
+	core::ics02_client::msgs::create_client::{MsgCreateClient, TYPE_URL},
+	events::IbcEvent,
+	mock::{
+		client_state::MockClientState, consensus_state::MockConsensusState, header::MockHeader,
+	},
+	Height,
+};
+use ibc_proto::{google::protobuf::Any, protobuf::Protobuf};
+
+/// Deliver a message creating a mock client, emitting a single event.
+fn create_client() {
+	let height = Height::new(0, 42).unwrap();
+	let msg = MsgCreateClient::new(
+		MockClientState::new(MockHeader::new(height)).into(),
+		MockConsensusState::new(MockHeader::new(height)).into(),
+		get_dummy_account_id(),
+	);
+	assert_ok!(Pallet::<Test>::deliver(
+		RuntimeOrigin::signed(AccountId::new([1; 32])),
+		vec![Any { type_url: TYPE_URL.to_string(), value: msg.encode_vec().unwrap() }],
+	));
+}
+
+/// The events kept for `height`, by index.
+fn events(height: u64) -> Vec<(u32, IbcEvent)> {
+	let mut events: Vec<_> = <IbcEvents<Test>>::iter_prefix(height)
+		.map(|(index, event)| (index, event.into_inner()))
+		.collect();
+	events.sort_by_key(|(index, _)| *index);
+	events
+}
+
+#[test]
+fn test_events_of_a_block_are_indexed_across_deliveries() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		create_client();
+		create_client();
+
+		// the second delivery does not overwrite the event of the first
+		let kept = events(1);
+		assert_eq!(kept.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![0, 1]);
+		assert!(kept.iter().all(|(_, event)| matches!(event, IbcEvent::CreateClient(_))));
+		assert_eq!(<IbcEventCount<Test>>::get(), 2);
+
+		// the index restarts in the next block
+		System::set_block_number(2);
+		Pallet::<Test>::on_initialize(2);
+		assert_eq!(<IbcEventCount<Test>>::get(), 0);
+		create_client();
+		assert_eq!(events(2).iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![0]);
+		assert_eq!(events(1).len(), 2);
+	})
+}
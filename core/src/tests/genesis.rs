@@ -0,0 +1,178 @@
+use crate::{
+	bounded::Bounded,
+	genesis,
+	mock::{new_test_ext, Test},
+	tests::connection::common::test_util::get_dummy_raw_counterparty,
+	ChannelCounter, Channels, ChannelsConnection, ClientConnections, ClientCounter,
+	ClientProcessedHeights, ClientProcessedTimes, Clients, ConnectionCounter, GenesisConfig,
+	NextSequenceAck, Pallet, MOCK_CLIENT_TYPE,
+};
+use frame_support::traits::GenesisBuild;
+use ibc::{
+	core::{
+		ics02_client::height::Height,
+		ics03_connection::{
+			connection::{
+				ConnectionEnd, Counterparty as ConnectionCounterparty, State as ConnectionState,
+			},
+			version::get_compatible_versions,
+		},
+		ics04_channel::{
+			channel::{ChannelEnd, Counterparty, Order, State},
+			packet::Sequence,
+			Version,
+		},
+		ics24_host::{
+			identifier::{ChannelId, ClientId, ConnectionId, PortId},
+			path::{ChannelEndsPath, SeqAcksPath},
+		},
+	},
+	mock::{
+		client_state::{client_type as mock_client_type, MockClientState},
+		consensus_state::MockConsensusState,
+		header::MockHeader,
+	},
+	timestamp::ZERO_DURATION,
+};
+use ibc_proto::{
+	google::protobuf::Any,
+	ibc::core::{
+		channel::v1::Channel as RawChannel, connection::v1::ConnectionEnd as RawConnectionEnd,
+	},
+	protobuf::Protobuf,
+};
+
+/// A mock client `9999-mock-3` with a connection `connection-1` and a transfer channel
+/// `channel-2` that sent four packets and received two.
+fn dummy_genesis() -> GenesisConfig {
+	let client_id = ClientId::new(mock_client_type(), 3).unwrap();
+	let header = MockHeader::new(Height::new(0, 5).unwrap());
+	let client_state = Protobuf::<Any>::encode_vec(&MockClientState::new(header)).unwrap();
+	let consensus_state = Protobuf::<Any>::encode_vec(&MockConsensusState::new(header)).unwrap();
+
+	let connection_end = ConnectionEnd::new(
+		ConnectionState::Open,
+		client_id.clone(),
+		ConnectionCounterparty::try_from(get_dummy_raw_counterparty()).unwrap(),
+		get_compatible_versions(),
+		ZERO_DURATION,
+	);
+	let channel_end = ChannelEnd::new(
+		State::Open,
+		Order::Unordered,
+		Counterparty::new(PortId::transfer(), Some(ChannelId::new(5))),
+		vec![ConnectionId::new(1)],
+		Version::ics20(),
+	);
+
+	GenesisConfig {
+		clients: vec![(client_id.to_string(), MOCK_CLIENT_TYPE.to_string(), client_state)],
+		consensus_states: vec![(client_id.to_string(), 0, 5, consensus_state)],
+		connections: vec![(
+			ConnectionId::new(1).to_string(),
+			Protobuf::<RawConnectionEnd>::encode_vec(&connection_end).unwrap(),
+		)],
+		channels: vec![(
+			PortId::transfer().to_string(),
+			ChannelId::new(2).to_string(),
+			Protobuf::<RawChannel>::encode_vec(&channel_end).unwrap(),
+		)],
+		sequences: vec![(PortId::transfer().to_string(), ChannelId::new(2).to_string(), 5, 3, 4)],
+	}
+}
+
+#[test]
+fn test_genesis_build_stores_state() {
+	new_test_ext().execute_with(|| {
+		pallet_timestamp::Pallet::<Test>::set_timestamp(1_000);
+		// a second connection of the same client
+		let mut config = dummy_genesis();
+		let mut second_connection = config.connections[0].clone();
+		second_connection.0 = ConnectionId::new(4).to_string();
+		config.connections.push(second_connection);
+		<GenesisConfig as GenesisBuild<Test>>::build(&config);
+
+		let client_id = ClientId::new(mock_client_type(), 3).unwrap();
+		assert_eq!(<Clients<Test>>::iter().count(), 1);
+		assert_eq!(<ClientCounter<Test>>::get(), 4);
+		assert_eq!(<ConnectionCounter<Test>>::get(), 5);
+		assert_eq!(<ChannelCounter<Test>>::get(), 3);
+		let mut connections =
+			<ClientConnections<Test>>::iter_key_prefix(&client_id).collect::<Vec<_>>();
+		connections.sort();
+		assert_eq!(connections, vec![ConnectionId::new(1), ConnectionId::new(4)]);
+		// the consensus state counts as processed at the genesis block
+		let height = Height::new(0, 5).unwrap();
+		assert_eq!(<ClientProcessedTimes<Test>>::get(&client_id, height), Some(1_000_000_000));
+		assert_eq!(
+			<ClientProcessedHeights<Test>>::get(&client_id, height),
+			Some(Height::new(0, 1).unwrap())
+		);
+		assert_eq!(
+			<ChannelsConnection<Test>>::get(ConnectionId::new(1))
+				.map(|c| c.into_iter().map(Bounded::into_inner).collect::<Vec<_>>()),
+			Some(vec![(PortId::transfer(), ChannelId::new(2))])
+		);
+		assert!(<Channels<Test>>::contains_key(ChannelEndsPath(
+			PortId::transfer(),
+			ChannelId::new(2)
+		)));
+		assert_eq!(
+			<NextSequenceAck<Test>>::get(SeqAcksPath(PortId::transfer(), ChannelId::new(2))),
+			Some(Sequence::from(4))
+		);
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+	})
+}
+
+#[test]
+fn test_genesis_export_round_trips() {
+	new_test_ext().execute_with(|| {
+		let config = dummy_genesis();
+		<GenesisConfig as GenesisBuild<Test>>::build(&config);
+
+		let exported = Pallet::<Test>::export_genesis().unwrap();
+		assert_eq!(exported.clients, config.clients);
+		assert_eq!(exported.consensus_states, config.consensus_states);
+		assert_eq!(exported.connections, config.connections);
+		assert_eq!(exported.channels, config.channels);
+		assert_eq!(exported.sequences, config.sequences);
+	})
+}
+
+#[test]
+fn test_genesis_rejects_inconsistent_state() {
+	new_test_ext().execute_with(|| {
+		let mut config = dummy_genesis();
+		config.clients[0].1 = "07-unknown".to_string();
+		assert_eq!(genesis::build::<Test>(&config), Err("unknown client type 07-unknown".into()));
+	});
+
+	new_test_ext().execute_with(|| {
+		let mut config = dummy_genesis();
+		config.connections.clear();
+		assert_eq!(
+			genesis::build::<Test>(&config),
+			Err("channel channel-2 of unknown connection connection-1".into())
+		);
+	});
+
+	new_test_ext().execute_with(|| {
+		let mut config = dummy_genesis();
+		config.sequences[0].4 = 6;
+		assert_eq!(
+			genesis::build::<Test>(&config),
+			Err("next ack sequence of channel-2 beyond its next send sequence".into())
+		);
+	});
+}
+
+#[test]
+#[should_panic(expected = "invalid IBC genesis")]
+fn test_genesis_build_panics_on_inconsistent_state() {
+	new_test_ext().execute_with(|| {
+		let mut config = dummy_genesis();
+		config.consensus_states[0].0 = "9999-mock-7".to_string();
+		<GenesisConfig as GenesisBuild<Test>>::build(&config);
+	})
+}
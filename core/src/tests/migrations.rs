@@ -0,0 +1,221 @@
+use crate::{
+	bounded::Bounded,
+	migrations::{v1, v2, v3, v4, MIGRATION_STEP},
+	mock::{new_test_ext, Test},
+	packets::SequenceKey,
+	Acknowledgements, ChannelAcknowledgements, ChannelCommitments, ChannelsConnection,
+	ClientConnections, Connections, IbcEvents, MigrationCursor, PacketCommitment, Pallet,
+};
+use codec::Encode;
+use frame_support::{
+	storage::unhashed,
+	traits::{Get, GetStorageVersion, Hooks, OnRuntimeUpgrade, StorageVersion},
+	weights::Weight,
+};
+use ibc::{
+	core::{
+		ics02_client::{events::CreateClient, height::Height},
+		ics03_connection::connection::ConnectionEnd,
+		ics04_channel::{
+			commitment::{AcknowledgementCommitment, PacketCommitment as IbcPacketCommitment},
+			packet::Sequence,
+		},
+		ics24_host::{
+			identifier::{ChannelId, ClientId, ConnectionId, PortId},
+			path::{AcksPath, CommitmentsPath, ConnectionsPath},
+		},
+	},
+	events::IbcEvent,
+	mock::client_state::client_type as mock_client_type,
+};
+use pallet_ibc_utils::migration::{Cursor, SteppedMigration};
+
+/// Store `count` events in the layout before version 1, one per height.
+fn store_legacy_events(count: u64) {
+	StorageVersion::new(0).put::<Pallet<Test>>();
+	for height in 1..=count {
+		let event = CreateClient::new(
+			ClientId::new(mock_client_type(), height).unwrap(),
+			mock_client_type(),
+			Height::new(0, height).unwrap(),
+		);
+		<v1::IbcEventStore<Test>>::insert(height, IbcEvent::CreateClient(event));
+	}
+}
+
+#[test]
+fn test_migrate_to_v1_in_the_upgrade_block() {
+	new_test_ext().execute_with(|| {
+		store_legacy_events(3);
+
+		v1::MigrateToV1::<Test>::on_runtime_upgrade();
+
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 1);
+		// the migration to version 2 follows
+		assert_eq!(<MigrationCursor<Test>>::get(), Some(Cursor::default()));
+		assert_eq!(<v1::IbcEventStore<Test>>::iter().count(), 0);
+		assert_eq!(<IbcEvents<Test>>::iter().count(), 3);
+		assert!(matches!(
+			<IbcEvents<Test>>::get(2, 0).map(Bounded::into_inner),
+			Some(IbcEvent::CreateClient(_))
+		));
+
+		// a later upgrade leaves the migrated storage alone
+		v1::MigrateToV1::<Test>::on_runtime_upgrade();
+		assert_eq!(<IbcEvents<Test>>::iter().count(), 3);
+	})
+}
+
+#[test]
+fn test_migrate_to_v1_over_several_blocks() {
+	new_test_ext().execute_with(|| {
+		let events = MIGRATION_STEP as u64 * 2 + 1;
+		store_legacy_events(events);
+
+		v1::MigrateToV1::<Test>::on_runtime_upgrade();
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 0);
+		assert!(<MigrationCursor<Test>>::exists());
+		assert_eq!(<IbcEvents<Test>>::iter().count() as u64, MIGRATION_STEP as u64);
+
+		// a block without room for a step migrates nothing
+		Pallet::<Test>::on_idle(1, Weight::zero());
+		assert_eq!(<IbcEvents<Test>>::iter().count() as u64, MIGRATION_STEP as u64);
+
+		// a block with room for a single step
+		let step_weight = v1::MigrateToV1::<Test>::max_step_weight(MIGRATION_STEP);
+		Pallet::<Test>::on_idle(2, step_weight);
+		assert_eq!(<IbcEvents<Test>>::iter().count() as u64, MIGRATION_STEP as u64 * 2);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 0);
+
+		// a new upgrade before the end resumes the migration rather than restarting it
+		v1::MigrateToV1::<Test>::on_runtime_upgrade();
+		assert_eq!(<IbcEvents<Test>>::iter().count() as u64, MIGRATION_STEP as u64 * 2);
+
+		Pallet::<Test>::on_idle(3, Weight::MAX);
+		assert_eq!(<IbcEvents<Test>>::iter().count() as u64, events);
+		assert_eq!(<v1::IbcEventStore<Test>>::iter().count(), 0);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 1);
+
+		Pallet::<Test>::on_idle(4, Weight::MAX);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 2);
+
+		Pallet::<Test>::on_idle(5, Weight::MAX);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 3);
+
+		Pallet::<Test>::on_idle(6, Weight::MAX);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 4);
+		assert!(!<MigrationCursor<Test>>::exists());
+	})
+}
+
+#[test]
+fn test_migrate_to_v2_indexes_packets_by_channel() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(1).put::<Pallet<Test>>();
+		let commitments = MIGRATION_STEP as u64 + 1;
+		for (channel, sequence) in (0..commitments).map(|i| (i % 2, i + 1)) {
+			<PacketCommitment<Test>>::insert(
+				CommitmentsPath {
+					port_id: PortId::transfer(),
+					channel_id: ChannelId::new(channel),
+					sequence: Sequence::from(sequence),
+				},
+				IbcPacketCommitment::from(vec![1]),
+			);
+		}
+		<Acknowledgements<Test>>::insert(
+			AcksPath {
+				port_id: PortId::transfer(),
+				channel_id: ChannelId::new(0),
+				sequence: Sequence::from(1),
+			},
+			AcknowledgementCommitment::from(vec![1]),
+		);
+
+		v2::MigrateToV2::<Test>::on_runtime_upgrade();
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 1);
+		assert_eq!(<ChannelCommitments<Test>>::iter().count() as u64, MIGRATION_STEP as u64);
+
+		Pallet::<Test>::on_idle(1, Weight::MAX);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 2);
+		// the migration to version 3 follows
+		assert_eq!(<MigrationCursor<Test>>::get(), Some(Cursor::default()));
+		assert_eq!(<ChannelCommitments<Test>>::iter().count() as u64, commitments);
+		assert!(<ChannelCommitments<Test>>::contains_key(
+			(PortId::transfer(), ChannelId::new(1)),
+			SequenceKey::from(Sequence::from(2))
+		));
+		assert_eq!(
+			<ChannelAcknowledgements<Test>>::iter_keys().collect::<Vec<_>>(),
+			vec![((PortId::transfer(), ChannelId::new(0)), SequenceKey::from(Sequence::from(1)))]
+		);
+	})
+}
+
+#[test]
+fn test_migrate_to_v3_indexes_every_connection_of_a_client() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(2).put::<Pallet<Test>>();
+		let connections = MIGRATION_STEP as u64 + 1;
+		for index in 0..connections {
+			<Connections<Test>>::insert(
+				ConnectionsPath(ConnectionId::new(index)),
+				ConnectionEnd::default(),
+			);
+		}
+		// only the last connection of the client was kept before version 3
+		let client_id = ClientId::default();
+		<v3::ConnectionClient<Test>>::insert(&client_id, ConnectionId::new(connections - 1));
+
+		v3::MigrateToV3::<Test>::on_runtime_upgrade();
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 2);
+		assert_eq!(<ClientConnections<Test>>::iter().count() as u64, MIGRATION_STEP as u64);
+
+		Pallet::<Test>::on_idle(1, Weight::MAX);
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 3);
+		// the migration to version 4 follows
+		assert_eq!(<MigrationCursor<Test>>::get(), Some(Cursor::default()));
+		assert_eq!(
+			<ClientConnections<Test>>::iter_key_prefix(&client_id).count() as u64,
+			connections
+		);
+		assert_eq!(<v3::ConnectionClient<Test>>::iter().count(), 0);
+	})
+}
+
+#[test]
+fn test_migrate_to_v4_removes_entries_longer_than_their_bound() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(3).put::<Pallet<Test>>();
+		let event = IbcEvent::CreateClient(CreateClient::new(
+			ClientId::default(),
+			mock_client_type(),
+			Height::new(0, 1).unwrap(),
+		));
+		<IbcEvents<Test>>::insert(1, 0, Bounded::try_new(event).unwrap());
+		// a connection with more channels than allowed, stored before the bound
+		let max_channels: u32 = <Test as crate::Config>::MaxChannelsPerConnection::get();
+		let channels: Vec<_> = (0..=max_channels as u64)
+			.map(|index| (PortId::transfer(), ChannelId::new(index)))
+			.collect();
+		unhashed::put_raw(
+			&<ChannelsConnection<Test>>::hashed_key_for(ConnectionId::new(0)),
+			&channels.encode(),
+		);
+		<ChannelsConnection<Test>>::insert(
+			ConnectionId::new(1),
+			frame_support::bounded_vec![Bounded::try_new(channels[0].clone()).unwrap()],
+		);
+		assert_eq!(v4::undecodable_entries::<Test>(), 1);
+
+		v4::MigrateToV4::<Test>::on_runtime_upgrade();
+		Pallet::<Test>::on_idle(1, Weight::MAX);
+
+		assert_eq!(Pallet::<Test>::on_chain_storage_version(), 4);
+		assert!(!<MigrationCursor<Test>>::exists());
+		assert_eq!(v4::undecodable_entries::<Test>(), 0);
+		assert!(!<ChannelsConnection<Test>>::contains_key(ConnectionId::new(0)));
+		assert!(<ChannelsConnection<Test>>::contains_key(ConnectionId::new(1)));
+		assert_eq!(<IbcEvents<Test>>::iter().count(), 1);
+	})
+}
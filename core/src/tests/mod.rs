@@ -3,6 +3,16 @@ pub mod client;
 pub mod commitment;
 pub mod common;
 pub mod connection;
+#[cfg(test)]
+pub mod events;
+#[cfg(test)]
+pub mod genesis;
+#[cfg(test)]
+pub mod migrations;
+#[cfg(test)]
+pub mod packets;
+#[cfg(test)]
+pub mod try_state;
 
 #[cfg(test)]
 mod tests {
@@ -268,6 +278,36 @@ mod tests {
 		})
 	}
 
+	#[test]
+	fn test_store_connection_channels_over_limit() {
+		use frame_support::traits::Get;
+
+		let connection_id = ConnectionId::new(0);
+		let max_channels: u32 = <Test as crate::Config>::MaxChannelsPerConnection::get();
+
+		let mut context: Context<Test> = Context::new();
+		new_test_ext().execute_with(|| {
+			for index in 0..max_channels {
+				assert!(context
+					.store_connection_channels(
+						connection_id.clone(),
+						PortId::default(),
+						ChannelId::new(index as u64)
+					)
+					.is_ok());
+			}
+			assert!(context
+				.store_connection_channels(
+					connection_id.clone(),
+					PortId::default(),
+					ChannelId::new(max_channels as u64)
+				)
+				.is_err());
+			let result = context.connection_channels(&connection_id).unwrap();
+			assert_eq!(result.len(), max_channels as usize);
+		})
+	}
+
 	#[test]
 	fn test_next_sequence_send_ok() {
 		let sequence_id = Sequence::from(0);
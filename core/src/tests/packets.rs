@@ -0,0 +1,191 @@
+use crate::{
+	mock::{new_test_ext, Test},
+	Channels, Context, Pallet,
+};
+use ibc::core::{
+	ics04_channel::{
+		channel::{ChannelEnd, Counterparty, Order, State},
+		commitment::{AcknowledgementCommitment, PacketCommitment},
+		context::ChannelKeeper,
+		packet::{Receipt, Sequence},
+		Version,
+	},
+	ics24_host::{
+		identifier::{ChannelId, ConnectionId, PortId},
+		path::ChannelEndsPath,
+	},
+};
+
+fn sequences(sequences: &[u64]) -> Vec<Sequence> {
+	sequences.iter().map(|sequence| Sequence::from(*sequence)).collect()
+}
+
+/// Store an open transfer channel with the ordering `order`.
+fn store_channel(channel_id: ChannelId, order: Order) {
+	let channel_end = ChannelEnd::new(
+		State::Open,
+		order,
+		Counterparty::new(PortId::transfer(), Some(ChannelId::new(5))),
+		vec![ConnectionId::new(0)],
+		Version::ics20(),
+	);
+	<Channels<Test>>::insert(ChannelEndsPath(PortId::transfer(), channel_id), channel_end);
+}
+
+#[test]
+fn test_packet_commitments_of_a_channel() {
+	new_test_ext().execute_with(|| {
+		let mut context: Context<Test> = Context::new();
+		let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+		for sequence in 1..=5 {
+			context
+				.store_packet_commitment(
+					port_id.clone(),
+					channel_id.clone(),
+					Sequence::from(sequence),
+					PacketCommitment::from(vec![1]),
+				)
+				.unwrap();
+		}
+		// a commitment of another channel
+		context
+			.store_packet_commitment(
+				port_id.clone(),
+				ChannelId::new(1),
+				Sequence::from(1),
+				PacketCommitment::from(vec![1]),
+			)
+			.unwrap();
+		context
+			.delete_packet_commitment(&port_id, &channel_id, &Sequence::from(3))
+			.unwrap();
+
+		let all =
+			Pallet::<Test>::packet_commitments(port_id.clone(), channel_id.clone(), None, u32::MAX);
+		assert_eq!(all, sequences(&[1, 2, 4, 5]));
+
+		// pages follow each other without overlap
+		let first =
+			Pallet::<Test>::packet_commitments(port_id.clone(), channel_id.clone(), None, 2);
+		assert_eq!(first.len(), 2);
+		let second = Pallet::<Test>::packet_commitments(
+			port_id.clone(),
+			channel_id.clone(),
+			first.last().copied(),
+			2,
+		);
+		assert_eq!([first, second].concat(), all);
+
+		assert_eq!(
+			Pallet::<Test>::unreceived_acks(port_id, channel_id, sequences(&[1, 3, 5, 6])),
+			sequences(&[1, 5])
+		);
+	})
+}
+
+#[test]
+fn test_packet_acknowledgements_of_a_channel() {
+	new_test_ext().execute_with(|| {
+		let mut context: Context<Test> = Context::new();
+		let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+		for sequence in [2, 4] {
+			context
+				.store_packet_acknowledgement(
+					port_id.clone(),
+					channel_id.clone(),
+					Sequence::from(sequence),
+					AcknowledgementCommitment::from(vec![1]),
+				)
+				.unwrap();
+		}
+
+		let acks = Pallet::<Test>::packet_acknowledgements(port_id, channel_id, None, 10);
+		assert_eq!(acks, sequences(&[2, 4]));
+	})
+}
+
+#[test]
+fn test_pages_of_packets_are_in_ascending_order() {
+	new_test_ext().execute_with(|| {
+		let mut context: Context<Test> = Context::new();
+		let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+		// sequences past 255 and 65535 sort differently by their little-endian encoding
+		let stored = [1, 2, 255, 256, 257, 1_000, 65_535, 65_536, 70_000];
+		for sequence in stored.iter().rev() {
+			context
+				.store_packet_commitment(
+					port_id.clone(),
+					channel_id.clone(),
+					Sequence::from(*sequence),
+					PacketCommitment::from(vec![1]),
+				)
+				.unwrap();
+			context
+				.store_packet_acknowledgement(
+					port_id.clone(),
+					channel_id.clone(),
+					Sequence::from(*sequence),
+					AcknowledgementCommitment::from(vec![1]),
+				)
+				.unwrap();
+		}
+
+		let pages = |query: fn(PortId, ChannelId, Option<Sequence>, u32) -> Vec<Sequence>| {
+			let mut pages = Vec::new();
+			let mut start_after = None;
+			loop {
+				let page = query(port_id.clone(), channel_id.clone(), start_after, 2);
+				if page.is_empty() {
+					return pages
+				}
+				start_after = page.last().copied();
+				pages.extend(page);
+			}
+		};
+		assert_eq!(pages(Pallet::<Test>::packet_commitments), sequences(&stored));
+		assert_eq!(pages(Pallet::<Test>::packet_acknowledgements), sequences(&stored));
+	})
+}
+
+#[test]
+fn test_unreceived_packets() {
+	new_test_ext().execute_with(|| {
+		let mut context: Context<Test> = Context::new();
+		let port_id = PortId::transfer();
+
+		// an ordered channel receives the packets in sequence
+		store_channel(ChannelId::new(0), Order::Ordered);
+		context
+			.store_next_sequence_recv(port_id.clone(), ChannelId::new(0), Sequence::from(3))
+			.unwrap();
+		assert_eq!(
+			Pallet::<Test>::unreceived_packets(
+				port_id.clone(),
+				ChannelId::new(0),
+				sequences(&[1, 2, 3, 4])
+			),
+			sequences(&[3, 4])
+		);
+
+		// an unordered channel keeps a receipt of each packet
+		store_channel(ChannelId::new(1), Order::Unordered);
+		for sequence in [1, 3] {
+			context
+				.store_packet_receipt(
+					port_id.clone(),
+					ChannelId::new(1),
+					Sequence::from(sequence),
+					Receipt::Ok,
+				)
+				.unwrap();
+		}
+		assert_eq!(
+			Pallet::<Test>::unreceived_packets(
+				port_id,
+				ChannelId::new(1),
+				sequences(&[1, 2, 3, 4])
+			),
+			sequences(&[2, 4])
+		);
+	})
+}
@@ -0,0 +1,259 @@
+use crate::{
+	bounded::Bounded,
+	mock::{new_test_ext, Test},
+	packets::SequenceKey,
+	Acknowledgements, ChannelAcknowledgements, ChannelCommitments, ChannelCounter, Channels,
+	ChannelsConnection, ClientCounter, ClientStates, Clients, ConnectionCounter, Connections,
+	NextSequenceAck, NextSequenceRecv, NextSequenceSend, PacketCommitment, PacketReceipt, Pallet,
+	TENDERMINT_CLIENT_TYPE,
+};
+use frame_support::bounded_vec;
+use ibc::core::{
+	ics02_client::client_type::ClientType,
+	ics03_connection::connection::ConnectionEnd,
+	ics04_channel::{
+		channel::{ChannelEnd, Counterparty, Order, State},
+		commitment::{AcknowledgementCommitment, PacketCommitment as IbcPacketCommitment},
+		packet::{Receipt, Sequence},
+		Version,
+	},
+	ics24_host::{
+		identifier::{ChannelId, ClientId, ConnectionId, PortId},
+		path::{
+			AcksPath, ChannelEndsPath, ClientStatePath, ClientTypePath, CommitmentsPath,
+			ConnectionsPath, ReceiptsPath, SeqAcksPath, SeqRecvsPath, SeqSendsPath,
+		},
+	},
+};
+
+/// Store a client, a connection and an ordered transfer channel that sent two packets, of which
+/// the first was acknowledged.
+fn store_channel() {
+	let client_id = ClientId::default();
+	<Clients<Test>>::insert(
+		ClientTypePath(client_id.clone()),
+		ClientType::new(TENDERMINT_CLIENT_TYPE.into()),
+	);
+	<ClientStates<Test>>::insert(ClientStatePath(client_id), bounded_vec![1, 2, 3]);
+	<ClientCounter<Test>>::put(1);
+
+	<Connections<Test>>::insert(ConnectionsPath(ConnectionId::new(0)), ConnectionEnd::default());
+	<ConnectionCounter<Test>>::put(1);
+
+	let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+	let channel_end = ChannelEnd::new(
+		State::Open,
+		Order::Ordered,
+		Counterparty::new(PortId::transfer(), Some(ChannelId::new(5))),
+		vec![ConnectionId::new(0)],
+		Version::ics20(),
+	);
+	<Channels<Test>>::insert(ChannelEndsPath(port_id.clone(), channel_id.clone()), channel_end);
+	<ChannelCounter<Test>>::put(1);
+	<ChannelsConnection<Test>>::insert(
+		ConnectionId::new(0),
+		bounded_vec![Bounded::try_new((port_id.clone(), channel_id.clone())).unwrap()],
+	);
+	<NextSequenceSend<Test>>::insert(
+		SeqSendsPath(port_id.clone(), channel_id.clone()),
+		Sequence::from(3),
+	);
+	<NextSequenceAck<Test>>::insert(
+		SeqAcksPath(port_id.clone(), channel_id.clone()),
+		Sequence::from(2),
+	);
+	<ChannelCommitments<Test>>::insert(
+		(port_id.clone(), channel_id.clone()),
+		SequenceKey::from(Sequence::from(2)),
+		(),
+	);
+	<PacketCommitment<Test>>::insert(
+		CommitmentsPath { port_id, channel_id, sequence: Sequence::from(2) },
+		IbcPacketCommitment::from(vec![1]),
+	);
+}
+
+#[test]
+fn test_try_state_of_consistent_storage() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+		store_channel();
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+	})
+}
+
+#[test]
+fn test_try_state_detects_missing_channel_of_connection() {
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<ChannelsConnection<Test>>::mutate(ConnectionId::new(0), |channels| {
+			channels
+				.as_mut()
+				.unwrap()
+				.try_push(Bounded::try_new((PortId::transfer(), ChannelId::new(1))).unwrap())
+				.unwrap()
+		});
+		assert_eq!(Pallet::<Test>::do_try_state(), Err("connection lists a missing channel"));
+	})
+}
+
+#[test]
+fn test_try_state_detects_inconsistent_sequences() {
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<NextSequenceAck<Test>>::insert(
+			SeqAcksPath(PortId::transfer(), ChannelId::new(0)),
+			Sequence::from(4),
+		);
+		assert_eq!(
+			Pallet::<Test>::do_try_state(),
+			Err("next ack sequence beyond the next send sequence")
+		);
+	});
+
+	new_test_ext().execute_with(|| {
+		store_channel();
+		// the commitment of an acknowledged packet is deleted on ordered channels
+		<PacketCommitment<Test>>::insert(
+			CommitmentsPath {
+				port_id: PortId::transfer(),
+				channel_id: ChannelId::new(0),
+				sequence: Sequence::from(1),
+			},
+			IbcPacketCommitment::from(vec![1]),
+		);
+		assert_eq!(
+			Pallet::<Test>::do_try_state(),
+			Err("packet commitment of an acknowledged sequence on an ordered channel")
+		);
+	});
+
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<ChannelCommitments<Test>>::remove(
+			(PortId::transfer(), ChannelId::new(0)),
+			SequenceKey::from(Sequence::from(2)),
+		);
+		assert_eq!(
+			Pallet::<Test>::do_try_state(),
+			Err("packet commitments missing from the channel index")
+		);
+	})
+}
+
+#[test]
+fn test_try_state_detects_stale_counters() {
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<ChannelCounter<Test>>::put(0);
+		assert_eq!(Pallet::<Test>::do_try_state(), Err("more channels than counted"));
+	});
+
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<ConnectionCounter<Test>>::put(0);
+		assert_eq!(Pallet::<Test>::do_try_state(), Err("more connections than counted"));
+	});
+
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<ClientCounter<Test>>::put(0);
+		assert_eq!(Pallet::<Test>::do_try_state(), Err("more clients than counted"));
+	})
+}
+
+#[test]
+fn test_try_state_detects_missing_connection_of_channels() {
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<Connections<Test>>::remove(ConnectionsPath(ConnectionId::new(0)));
+		assert_eq!(Pallet::<Test>::do_try_state(), Err("channels of a missing connection"));
+	})
+}
+
+/// Store the acknowledgement of the packet received with `sequence` on the channel of
+/// `store_channel`, and its channel index.
+fn store_acknowledgement(sequence: u64) {
+	let (port_id, channel_id) = (PortId::transfer(), ChannelId::new(0));
+	<Acknowledgements<Test>>::insert(
+		AcksPath {
+			port_id: port_id.clone(),
+			channel_id: channel_id.clone(),
+			sequence: sequence.into(),
+		},
+		AcknowledgementCommitment::from(vec![1]),
+	);
+	<ChannelAcknowledgements<Test>>::insert(
+		(port_id, channel_id),
+		SequenceKey::from(Sequence::from(sequence)),
+		(),
+	);
+}
+
+#[test]
+fn test_try_state_detects_inconsistent_receipts_and_acknowledgements() {
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<NextSequenceRecv<Test>>::insert(
+			SeqRecvsPath(PortId::transfer(), ChannelId::new(0)),
+			Sequence::from(2),
+		);
+		store_acknowledgement(1);
+		assert_eq!(Pallet::<Test>::do_try_state(), Ok(()));
+
+		// the packet of sequence 2 was not received yet
+		store_acknowledgement(2);
+		assert_eq!(
+			Pallet::<Test>::do_try_state(),
+			Err("acknowledgement of a sequence not received")
+		);
+	});
+
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<PacketReceipt<Test>>::insert(
+			ReceiptsPath {
+				port_id: PortId::transfer(),
+				channel_id: ChannelId::new(0),
+				sequence: Sequence::from(1),
+			},
+			Receipt::Ok,
+		);
+		assert_eq!(Pallet::<Test>::do_try_state(), Err("packet receipt on an ordered channel"));
+	});
+
+	new_test_ext().execute_with(|| {
+		store_channel();
+		// the acknowledgement of a packet received on an unordered channel needs its receipt
+		let path = ChannelEndsPath(PortId::transfer(), ChannelId::new(0));
+		let channel_end = <Channels<Test>>::get(&path).unwrap();
+		<Channels<Test>>::insert(
+			&path,
+			ChannelEnd::new(
+				State::Open,
+				Order::Unordered,
+				channel_end.counterparty().clone(),
+				channel_end.connection_hops().clone(),
+				channel_end.version().clone(),
+			),
+		);
+		store_acknowledgement(1);
+		assert_eq!(
+			Pallet::<Test>::do_try_state(),
+			Err("acknowledgement of a packet without receipt")
+		);
+	});
+
+	new_test_ext().execute_with(|| {
+		store_channel();
+		<Channels<Test>>::remove(ChannelEndsPath(PortId::transfer(), ChannelId::new(0)));
+		<ChannelsConnection<Test>>::remove(ConnectionId::new(0));
+		<PacketCommitment<Test>>::remove(CommitmentsPath {
+			port_id: PortId::transfer(),
+			channel_id: ChannelId::new(0),
+			sequence: Sequence::from(2),
+		});
+		store_acknowledgement(1);
+		assert_eq!(Pallet::<Test>::do_try_state(), Err("acknowledgement of a missing channel"));
+	})
+}
@@ -74,7 +74,7 @@ impl<T: frame_system::Config> MockClientWeightInfo<T> {
 	// Storage: Ibc Clients (r:1 w:0)
 	// Storage: Ibc ConnectionCounter (r:1 w:1)
 	// Storage: Ibc Connections (r:0 w:1)
-	// Storage: Ibc ConnectionClient (r:0 w:1)
+	// Storage: Ibc ClientConnections (r:0 w:1)
 	pub fn conn_open_init_mock(&self) -> Weight {
 		// Minimum execution time: 26_000 nanoseconds.
 		Weight::from_parts(27_000_000, 0)
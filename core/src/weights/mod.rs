@@ -467,11 +467,10 @@ pub fn channel_client<T: Config>(
 	port_id: &PortId,
 ) -> Result<ClientId, Error<T>> {
 	for (connection_id, channels) in ChannelsConnection::<T>::iter() {
-		if channels.contains(&(port_id.clone(), channel_id.clone())) {
-			if let Some((client_id, ..)) = ConnectionClient::<T>::iter()
-				.find(|(.., connection_ids)| connection_ids == &connection_id)
-			{
-				return Ok(client_id)
+		if channels.iter().any(|channel| channel.0 == *port_id && channel.1 == *channel_id) {
+			let path = ConnectionsPath(connection_id.into_inner());
+			if let Some(connection_end) = Connections::<T>::get(path) {
+				return Ok(connection_end.client_id().clone())
 			}
 		}
 	}
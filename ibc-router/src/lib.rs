@@ -30,6 +30,10 @@ pub mod pallet {
 			+ From<Event<Self>>
 			+ Debug
 			+ IsType<<Self as frame_system::Config>::RuntimeEvent>;
+
+		/// Router of the ICS20 `MsgTransfer`s, which must be sent by the signer, e.g. the
+		/// transfer pallet, `()` to route none
+		type TransferRouter: pallet_ibc_utils::SignedRouter<Self::AccountId>;
 	}
 
 	// Pallets use events to inform users when important changes are made.
@@ -53,17 +57,15 @@ pub mod pallet {
 		#[pallet::call_index(0)]
 		#[pallet::weight(0)]
 		pub fn dispatch(origin: OriginFor<T>, messages: Vec<Any>) -> DispatchResultWithPostInfo {
-			let _ = ensure_signed(origin)?;
+			let signer = ensure_signed(origin)?;
 
 			for message in messages {
 				match message.type_url.as_str() {
 					"/ibc.applications.transfer.v1.MsgTransfer" => {
-						#[cfg(feature = "ics20")]
-						{
-							<pallet_ics20::Pallet<T> as pallet_ibc_utils::Router>::dispatch(vec![
-								message,
-							])?;
-						}
+						<T::TransferRouter as pallet_ibc_utils::SignedRouter<_>>::dispatch(
+							&signer,
+							vec![message],
+						)?;
 					},
 					_ => {
 						<pallet_ibc::Pallet<T> as pallet_ibc_utils::Router>::dispatch(vec![
@@ -0,0 +1,67 @@
+use ibc::core::ics04_channel::msgs::acknowledgement::Acknowledgement;
+use scale_info::prelude::string::{String, ToString};
+use serde::{Deserialize, Serialize};
+use sp_std::vec::Vec;
+
+/// The ICS-04 generic acknowledgement envelope used by ICS-27, ICS-721 and ICS-31:
+/// `{"result": "<base64>"}` on success or `{"error": "<reason>"}` on failure.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenericAcknowledgement {
+	/// Base64 encoded application result
+	Result(String),
+	/// Error reason
+	Error(String),
+}
+
+impl GenericAcknowledgement {
+	/// A successful acknowledgement carrying `data` as its result.
+	pub fn success(data: impl AsRef<[u8]>) -> Self {
+		use base64::Engine;
+		Self::Result(base64::engine::general_purpose::STANDARD.encode(data))
+	}
+
+	/// An error acknowledgement with the given reason.
+	pub fn error(reason: impl ToString) -> Self {
+		Self::Error(reason.to_string())
+	}
+
+	pub fn is_success(&self) -> bool {
+		matches!(self, Self::Result(_))
+	}
+
+	/// Decode the base64 result of a successful acknowledgement.
+	pub fn result(&self) -> Option<Vec<u8>> {
+		use base64::Engine;
+		match self {
+			Self::Result(data) => base64::engine::general_purpose::STANDARD.decode(data).ok(),
+			Self::Error(_) => None,
+		}
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+		serde_json::from_slice(bytes).ok()
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		serde_json::to_vec(self).expect("GenericAcknowledgement's Serialize impl never fails")
+	}
+}
+
+impl From<GenericAcknowledgement> for Acknowledgement {
+	fn from(ack: GenericAcknowledgement) -> Self {
+		ack.to_bytes().into()
+	}
+}
+
+#[test]
+fn test_generic_acknowledgement_json() {
+	let ack = GenericAcknowledgement::success(b"ok");
+	assert_eq!(ack.to_bytes(), br#"{"result":"b2s="}"#.to_vec());
+	assert_eq!(GenericAcknowledgement::from_bytes(&ack.to_bytes()), Some(ack.clone()));
+	assert_eq!(ack.result(), Some(b"ok".to_vec()));
+
+	let ack = GenericAcknowledgement::error("failed");
+	assert_eq!(ack.to_bytes(), br#"{"error":"failed"}"#.to_vec());
+	assert!(!ack.is_success());
+}
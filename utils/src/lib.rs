@@ -5,9 +5,12 @@ use sp_std::vec::Vec;
 pub mod traits;
 pub mod weights;
 pub use weights::*;
+pub mod ack;
+pub mod migration;
 pub mod module;
+pub mod packet;
 
-use frame_support::dispatch::DispatchResult;
+use frame_support::dispatch::{DispatchError, DispatchResult};
 use ibc_proto::google::protobuf::Any;
 
 /// A trait handling asset ID and name
@@ -23,3 +26,15 @@ pub trait AssetIdAndNameProvider<AssetId> {
 pub trait Router {
 	fn dispatch(messages: Vec<Any>) -> DispatchResult;
 }
+
+/// for ibc router, routing messages that must be sent by their signer
+pub trait SignedRouter<AccountId> {
+	fn dispatch(signer: &AccountId, messages: Vec<Any>) -> DispatchResult;
+}
+
+/// Routes no message
+impl<AccountId> SignedRouter<AccountId> for () {
+	fn dispatch(_signer: &AccountId, _messages: Vec<Any>) -> DispatchResult {
+		Err(DispatchError::Other("no router for the messages"))
+	}
+}
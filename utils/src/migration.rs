@@ -0,0 +1,99 @@
+//! Storage migrations spread over several blocks.
+//!
+//! A migration too large for the block of a runtime upgrade implements [`SteppedMigration`]. Its
+//! pallet runs the first step on upgrade and the following ones with [`run_steps`] from `on_idle`,
+//! keeping the cursor in between, and only sets its new storage version once the last step is done.
+//! A step that fails stops its migration, leaving the storage version as it was.
+use frame_support::{sp_io, storage::unhashed, traits::ConstU32, weights::Weight, BoundedVec};
+use sp_std::vec::Vec;
+
+/// The longest cursor kept between two steps: a phase and a raw storage key.
+pub type MaxCursorLen = ConstU32<1024>;
+
+/// A cursor as kept in storage
+pub type Cursor = BoundedVec<u8, MaxCursorLen>;
+
+/// A step that failed, stopping its migration
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepFailed;
+
+/// The cursor to resume a migration from, `None` once all items are migrated
+pub type StepResult = Result<Option<Vec<u8>>, StepFailed>;
+
+/// A storage migration run a bounded number of items at a time
+pub trait SteppedMigration {
+	/// The storage version the migration upgrades to
+	const TO_VERSION: u16;
+
+	/// Migrate at most `limit` items after `cursor`, `None` for the first step. Returns the cursor
+	/// to resume from, `None` once all items are migrated, and the weight consumed.
+	fn step(cursor: Option<Vec<u8>>, limit: u32) -> (StepResult, Weight);
+
+	/// The most weight a step of `limit` items consumes.
+	fn max_step_weight(limit: u32) -> Weight;
+}
+
+/// Run the steps of `M` of `limit` items from `cursor` that fit in `max_weight`, until one fails.
+/// Returns the cursor to resume from, `None` once the migration is done, and the weight consumed.
+pub fn run_steps<M: SteppedMigration>(
+	cursor: Vec<u8>,
+	limit: u32,
+	max_weight: Weight,
+) -> (StepResult, Weight) {
+	let mut cursor = cursor;
+	let mut consumed = Weight::zero();
+	loop {
+		let remaining = max_weight.saturating_sub(consumed);
+		if !remaining.all_gte(M::max_step_weight(limit)) {
+			return (Ok(Some(cursor)), consumed)
+		}
+		let (next, weight) = M::step(Some(cursor), limit);
+		consumed = consumed.saturating_add(weight);
+		match next {
+			Ok(Some(next)) => cursor = next,
+			done_or_failed => return (done_or_failed, consumed),
+		}
+	}
+}
+
+/// Look at the entries under the storage `prefix` following the raw key `last_key`, empty to start
+/// from the first one, at most `limit`, and remove those whose raw key, without the prefix, and
+/// value `decodes` rejects. Returns the raw key to resume from, `None` once all entries were looked
+/// at, and the numbers of entries looked at and removed.
+pub fn remove_undecodable(
+	prefix: &[u8],
+	last_key: Vec<u8>,
+	limit: u32,
+	decodes: impl Fn(&[u8], &[u8]) -> bool,
+) -> (Option<Vec<u8>>, u64, u64) {
+	let mut key = if last_key.is_empty() { prefix.to_vec() } else { last_key };
+	let (mut seen, mut removed) = (0, 0);
+	while seen < limit as u64 {
+		key = match sp_io::storage::next_key(&key).filter(|next| next.starts_with(prefix)) {
+			Some(next) => next,
+			None => return (None, seen, removed),
+		};
+		seen += 1;
+		let value = unhashed::get_raw(&key).unwrap_or_default();
+		if !decodes(&key[prefix.len()..], &value) {
+			unhashed::kill(&key);
+			removed += 1;
+		}
+	}
+	(Some(key), seen, removed)
+}
+
+/// The number of entries under the storage `prefix` whose raw key, without the prefix, and value
+/// `decodes` rejects.
+pub fn count_undecodable(prefix: &[u8], decodes: impl Fn(&[u8], &[u8]) -> bool) -> u64 {
+	let mut key = prefix.to_vec();
+	let mut undecodable = 0;
+	while let Some(next) = sp_io::storage::next_key(&key).filter(|next| next.starts_with(prefix)) {
+		let value = unhashed::get_raw(&next).unwrap_or_default();
+		if !decodes(&next[prefix.len()..], &value) {
+			undecodable += 1;
+		}
+		key = next;
+	}
+	undecodable
+}
@@ -1,4 +1,7 @@
-use ibc::core::ics26_routing::context::{Module, ModuleId};
+use ibc::core::{
+	ics24_host::identifier::PortId,
+	ics26_routing::context::{Module, ModuleId},
+};
 use scale_info::prelude::{format, string::String};
 use sp_std::{
 	borrow::{Borrow, ToOwned},
@@ -10,6 +13,12 @@ use sp_std::{
 
 pub trait AddModule {
 	fn add_module(router: Router) -> Router;
+
+	/// Returns the module bound to `port_id` for ports that are not known to pallet-ibc itself,
+	/// e.g. the per-owner `icacontroller-*` ports of ICS-27.
+	fn lookup_module_by_port(_port_id: &PortId) -> Option<ModuleId> {
+		None
+	}
 }
 
 pub struct DefaultRouter;
@@ -0,0 +1,178 @@
+use crate::traits::{ChannelKeeperInterface, ChannelReaderInterface};
+use ibc::{
+	core::{
+		ics04_channel::{
+			channel::State,
+			commitment::PacketCommitment,
+			error::{ChannelError, PacketError},
+			events::{SendPacket, WriteAcknowledgement},
+			msgs::acknowledgement::Acknowledgement,
+			packet::Packet,
+			timeout::TimeoutHeight,
+		},
+		ics24_host::identifier::{ChannelId, PortId},
+	},
+	timestamp::{Expiry, Timestamp},
+};
+use sp_std::vec::Vec;
+
+/// Compute the commitment of a packet as defined by ICS-04:
+/// `hash(timeout_timestamp || timeout_revision_number || timeout_revision_height || hash(data))`.
+pub fn compute_packet_commitment<Ctx: ChannelReaderInterface>(
+	packet_data: &[u8],
+	timeout_height: &TimeoutHeight,
+	timeout_timestamp: &Timestamp,
+) -> PacketCommitment {
+	let mut hash_input = timeout_timestamp.nanoseconds().to_be_bytes().to_vec();
+	hash_input.extend_from_slice(&timeout_height.commitment_revision_number().to_be_bytes());
+	hash_input.extend_from_slice(&timeout_height.commitment_revision_height().to_be_bytes());
+	hash_input.extend(Ctx::hash(packet_data));
+
+	Ctx::hash(&hash_input).into()
+}
+
+/// Send a packet from an application module that is not driven by an ibc-rs relay handler.
+///
+/// Performs the ICS-04 `sendPacket` checks against the channel, connection and counterparty
+/// client, stores the packet commitment and bumps the next send sequence. Returns the
+/// `SendPacket` event to be deposited by the caller.
+pub fn send_packet<Ctx>(packet: Packet) -> Result<SendPacket, PacketError>
+where
+	Ctx: ChannelReaderInterface + ChannelKeeperInterface,
+{
+	let channel_end =
+		Ctx::channel_end(&packet.port_on_a, &packet.chan_on_a).map_err(PacketError::Channel)?;
+
+	if channel_end.state_matches(&State::Closed) {
+		return Err(PacketError::ChannelClosed { channel_id: packet.chan_on_a.clone() })
+	}
+
+	let counterparty = channel_end.counterparty();
+	if !counterparty.port_id().eq(&packet.port_on_b) ||
+		counterparty.channel_id() != Some(&packet.chan_on_b)
+	{
+		return Err(PacketError::InvalidPacketCounterparty {
+			port_id: packet.port_on_b.clone(),
+			channel_id: packet.chan_on_b.clone(),
+		})
+	}
+
+	let connection_id = channel_end.connection_hops().first().cloned().ok_or(
+		PacketError::Channel(ChannelError::InvalidConnectionHopsLength { expected: 1, actual: 0 }),
+	)?;
+	let connection_end = Ctx::connection_end(&connection_id).map_err(PacketError::Channel)?;
+
+	let client_id = connection_end.client_id();
+	let client_state = Ctx::client_state(client_id).map_err(PacketError::Channel)?;
+	if client_state.is_frozen() {
+		return Err(PacketError::FrozenClient { client_id: client_id.clone() })
+	}
+
+	let latest_height = client_state.latest_height();
+	if packet.timeout_height_on_b.has_expired(latest_height) {
+		return Err(PacketError::LowPacketHeight {
+			chain_height: latest_height,
+			timeout_height: packet.timeout_height_on_b,
+		})
+	}
+
+	let consensus_state =
+		Ctx::client_consensus_state(client_id, &latest_height).map_err(PacketError::Channel)?;
+	let latest_timestamp = consensus_state.timestamp();
+	if let Expiry::Expired = latest_timestamp.check_expiry(&packet.timeout_timestamp_on_b) {
+		return Err(PacketError::LowPacketTimestamp)
+	}
+
+	let next_seq_send = Ctx::get_next_sequence_send(&packet.port_on_a, &packet.chan_on_a)?;
+	if packet.sequence != next_seq_send {
+		return Err(PacketError::InvalidPacketSequence {
+			given_sequence: packet.sequence,
+			next_sequence: next_seq_send,
+		})
+	}
+
+	let commitment = compute_packet_commitment::<Ctx>(
+		&packet.data,
+		&packet.timeout_height_on_b,
+		&packet.timeout_timestamp_on_b,
+	);
+	Ctx::store_packet_commitment(
+		packet.port_on_a.clone(),
+		packet.chan_on_a.clone(),
+		packet.sequence,
+		commitment,
+	)?;
+	Ctx::store_next_sequence_send(
+		packet.port_on_a.clone(),
+		packet.chan_on_a.clone(),
+		next_seq_send.increment(),
+	)?;
+
+	Ok(SendPacket::new(packet, *channel_end.ordering(), connection_id))
+}
+
+/// Write the acknowledgement of a received packet whose acknowledgement was deferred with
+/// [`ChannelKeeperInterface::defer_packet_acknowledgement`].
+///
+/// Returns the `WriteAcknowledgement` event to be deposited by the caller.
+pub fn write_acknowledgement<Ctx>(
+	packet: Packet,
+	acknowledgement: Acknowledgement,
+) -> Result<WriteAcknowledgement, PacketError>
+where
+	Ctx: ChannelReaderInterface + ChannelKeeperInterface,
+{
+	let channel_end =
+		Ctx::channel_end(&packet.port_on_b, &packet.chan_on_b).map_err(PacketError::Channel)?;
+	if !channel_end.state_matches(&State::Open) {
+		return Err(PacketError::ChannelClosed { channel_id: packet.chan_on_b.clone() })
+	}
+
+	if Ctx::get_packet_acknowledgement(&packet.port_on_b, &packet.chan_on_b, &packet.sequence)
+		.is_ok()
+	{
+		return Err(PacketError::AcknowledgementExists { sequence: packet.sequence })
+	}
+	if acknowledgement.as_ref().is_empty() {
+		return Err(PacketError::InvalidAcknowledgement)
+	}
+
+	let connection_id = channel_end.connection_hops().first().cloned().ok_or(
+		PacketError::Channel(ChannelError::InvalidConnectionHopsLength { expected: 1, actual: 0 }),
+	)?;
+	Ctx::store_packet_acknowledgement(
+		packet.port_on_b.clone(),
+		packet.chan_on_b.clone(),
+		packet.sequence,
+		Ctx::hash(acknowledgement.as_ref()).into(),
+	)?;
+
+	Ok(WriteAcknowledgement::new(packet, acknowledgement, connection_id))
+}
+
+/// Build the next outgoing packet on `(port_id, channel_id)` towards its counterparty.
+pub fn build_packet<Ctx: ChannelReaderInterface>(
+	port_id: &PortId,
+	channel_id: &ChannelId,
+	data: Vec<u8>,
+	timeout_height: TimeoutHeight,
+	timeout_timestamp: Timestamp,
+) -> Result<Packet, PacketError> {
+	let channel_end = Ctx::channel_end(port_id, channel_id).map_err(PacketError::Channel)?;
+	let counterparty = channel_end.counterparty();
+	let chan_on_b = counterparty
+		.channel_id()
+		.cloned()
+		.ok_or(PacketError::Channel(ChannelError::MissingCounterparty))?;
+
+	Ok(Packet {
+		sequence: Ctx::get_next_sequence_send(port_id, channel_id)?,
+		port_on_a: port_id.clone(),
+		chan_on_a: channel_id.clone(),
+		port_on_b: counterparty.port_id().clone(),
+		chan_on_b,
+		data,
+		timeout_height_on_b: timeout_height,
+		timeout_timestamp_on_b: timeout_timestamp,
+	})
+}
@@ -134,6 +134,17 @@ pub trait ChannelKeeperInterface {
 		sequence: &Sequence,
 	) -> Result<(), PacketError>;
 
+	/// Skip the next write of the acknowledgement of a packet being received.
+	///
+	/// Lets an application acknowledge the packet asynchronously, with
+	/// [`crate::packet::write_acknowledgement`], instead of with the acknowledgement returned by
+	/// its `on_recv_packet` callback.
+	fn defer_packet_acknowledgement(
+		port_id: &PortId,
+		channel_id: &ChannelId,
+		sequence: &Sequence,
+	) -> Result<(), PacketError>;
+
 	fn store_connection_channels(
 		conn_id: ConnectionId,
 		port_id: PortId,